@@ -103,6 +103,26 @@ impl FlutterApp {
     pub fn depfile(&self) -> &Path {
         &self.depfile
     }
+
+    /// Tells `volito::app_paths!` where the bundle is, through `VOLITO_ASSETS`, `VOLITO_ICUDTL_DAT`
+    /// and (for release builds) `VOLITO_APP_LIBRARY`.
+    fn export_paths(self) -> Self {
+        println!(
+            "cargo::rustc-env=VOLITO_ASSETS={}",
+            self.asset_dir.display()
+        );
+        println!(
+            "cargo::rustc-env=VOLITO_ICUDTL_DAT={}",
+            env("DEP_FLUTTER_ENGINE_ICUDTL_DAT").unwrap()
+        );
+        if let Some(app_library) = &self.app_library {
+            println!(
+                "cargo::rustc-env=VOLITO_APP_LIBRARY={}",
+                app_library.display()
+            );
+        }
+        self
+    }
 }
 
 pub struct FlutterAppBuilder {
@@ -203,33 +223,7 @@ impl FlutterAppBuilder {
 
         if self.mode == Mode::Release {
             let dart_sdk = flutter_engine.join("flutter_patched_sdk");
-
-            let regular_dart_runtime = flutter_engine.join("dart-sdk").join("bin").join("dart");
-            let dart_aot_runtime = flutter_engine
-                .join("dart-sdk")
-                .join("bin")
-                .join("dartaotruntime");
-
-            let frontend_server_jit = flutter_engine
-                .join("gen")
-                .join("frontend_server.dart.snapshot");
-            let frontend_server_aot = flutter_engine
-                .join("gen")
-                .join("frontend_server_aot.dart.snapshot");
-
-            let (dart, frontend_server) = if frontend_server_jit.exists() {
-                if !regular_dart_runtime.exists() {
-                    return Err(BuildError::DartNotFound { wanted_aot: false });
-                }
-                (regular_dart_runtime, frontend_server_jit)
-            } else if frontend_server_aot.exists() {
-                if !dart_aot_runtime.exists() {
-                    return Err(BuildError::DartNotFound { wanted_aot: true });
-                }
-                (dart_aot_runtime, frontend_server_aot)
-            } else {
-                return Err(BuildError::FrontendServerNotFound);
-            };
+            let (dart, frontend_server) = find_frontend_server(&flutter_engine)?;
 
             let kernel_snapshot = out_dir.join("app.dill");
 
@@ -270,14 +264,81 @@ impl FlutterAppBuilder {
                 asset_dir,
                 depfile,
                 app_library: Some(app_library),
-            })
+            }
+            .export_paths())
         } else {
+            if self.mode == Mode::Debug {
+                self.write_hot_reload_config(&out_dir, &flutter_engine, &asset_dir)?;
+            }
+
             Ok(FlutterApp {
                 asset_dir,
                 depfile,
                 app_library: None,
-            })
+            }
+            .export_paths())
+        }
+    }
+
+    /// Tells `volito::hot_reload` where everything is, through a file at `VOLITO_HOT_RELOAD`.
+    fn write_hot_reload_config(
+        &self,
+        out_dir: &Path,
+        flutter_engine: &Path,
+        asset_dir: &Path,
+    ) -> Result<(), BuildError> {
+        let (dart, frontend_server) = find_frontend_server(flutter_engine)?;
+        let entrypoint = self.project_root.join(&self.entrypoint);
+
+        let config = [
+            ("dart", dart.as_path()),
+            ("frontend_server", frontend_server.as_path()),
+            ("sdk_root", &flutter_engine.join("flutter_patched_sdk")),
+            ("project_root", &self.project_root),
+            ("entrypoint", &entrypoint),
+            ("assets", asset_dir),
+        ]
+        .iter()
+        .map(|(key, path)| format!("{key}={}\n", path.display()))
+        .collect::<String>();
+
+        let config_path = out_dir.join("hot_reload");
+        std::fs::write(&config_path, config).unwrap();
+        println!(
+            "cargo::rustc-env=VOLITO_HOT_RELOAD={}",
+            config_path.display()
+        );
+        Ok(())
+    }
+}
+
+/// The Dart runtime and frontend server snapshot to compile kernel with.
+fn find_frontend_server(flutter_engine: &Path) -> Result<(PathBuf, PathBuf), BuildError> {
+    let regular_dart_runtime = flutter_engine.join("dart-sdk").join("bin").join("dart");
+    let dart_aot_runtime = flutter_engine
+        .join("dart-sdk")
+        .join("bin")
+        .join("dartaotruntime");
+
+    let frontend_server_jit = flutter_engine
+        .join("gen")
+        .join("frontend_server.dart.snapshot");
+    let frontend_server_aot = flutter_engine
+        .join("gen")
+        .join("frontend_server_aot.dart.snapshot");
+
+    if frontend_server_jit.exists() {
+        if !regular_dart_runtime.exists() {
+            return Err(BuildError::DartNotFound { wanted_aot: false });
+        }
+        Ok((regular_dart_runtime, frontend_server_jit))
+    } else if frontend_server_aot.exists() {
+        if !dart_aot_runtime.exists() {
+            return Err(BuildError::DartNotFound { wanted_aot: true });
         }
+        Ok((dart_aot_runtime, frontend_server_aot))
+    } else {
+        Err(BuildError::FrontendServerNotFound)
     }
 }
 
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use bindgen::callbacks::{IntKind, ItemKind, MacroParsingBehavior};
 
@@ -101,7 +101,9 @@ impl bindgen::callbacks::ParseCallbacks for ParseCallbacks {
                 | "FlutterMetalExternalTexturePixelFormat"
                 | "FlutterMetalExternalTextureYUVColorSpace"
                 | "FlutterPointerPhase"
-                | "FlutterStringAttributeType" => variant.to_string(),
+                | "FlutterStringAttributeType"
+                | "FlutterViewFocusState"
+                | "FlutterViewFocusDirection" => variant.to_string(),
                 _ => {
                     println!(
                         "cargo:warning=enum variant {original_variant_name} has unpredictable name with parent {enum_name}"
@@ -113,9 +115,98 @@ impl bindgen::callbacks::ParseCallbacks for ParseCallbacks {
     }
 }
 
-fn generate_bindings(embedder_h: &str) {
+// Checked-in bindings for a pinned `embedder.h`, relative to the crate root.
+// Used with the `pregenerated-bindings` feature, so that bindgen (and libclang) isn't needed at build time.
+const PREGENERATED_BINDINGS: &str = "bindings/embedder.rs";
+// The engine commit that the checked-in bindings were generated from.
+const PREGENERATED_BINDINGS_COMMIT: &str = "bindings/engine_commit";
+const UPDATE_PREGENERATED_BINDINGS: &str = "VOLITO_UPDATE_PREGENERATED_BINDINGS";
+
+fn pregenerated_bindings_path() -> PathBuf {
+    PathBuf::from(env("CARGO_MANIFEST_DIR").unwrap()).join(PREGENERATED_BINDINGS)
+}
+
+fn pregenerated_bindings_commit_path() -> PathBuf {
+    PathBuf::from(env("CARGO_MANIFEST_DIR").unwrap()).join(PREGENERATED_BINDINGS_COMMIT)
+}
+
+/// Copies the checked-in bindings into place, and returns the engine commit they're pinned to.
+fn use_pregenerated_bindings() -> Option<String> {
+    let pregenerated = pregenerated_bindings_path();
+    let commit = pregenerated_bindings_commit_path();
+    println!("cargo::rerun-if-changed={}", pregenerated.display());
+    println!("cargo::rerun-if-changed={}", commit.display());
+    let pinned = std::fs::read_to_string(&commit)
+        .ok()
+        .map(|commit| commit.trim().to_owned());
+    let Some(pinned) = pinned.filter(|_| pregenerated.is_file()) else {
+        println!(
+            "cargo::error=the `pregenerated-bindings` feature is enabled, but there are no bindings at {} pinned by {}",
+            pregenerated.display(),
+            commit.display()
+        );
+        println!("cargo::error=generate them by building once without that feature, with {UPDATE_PREGENERATED_BINDINGS}=1 and {ENGINE_HASH} set to the engine commit to pin");
+        return None;
+    };
+
+    std::fs::copy(
+        pregenerated,
+        PathBuf::from(env("OUT_DIR").unwrap()).join("embedder.rs"),
+    )
+    .expect("Couldn't copy pregenerated bindings");
+    detect_embedder_capabilities();
+    Some(pinned)
+}
+
+/// The checked-in bindings describe one `embedder.h`; linking them against any other engine is unsound.
+fn check_pregenerated_bindings_commit(pinned: &str, commit: Option<&str>) -> bool {
+    match commit {
+        Some(commit) if commit == pinned => true,
+        Some(commit) => {
+            println!("cargo::error=the pregenerated bindings are for engine {pinned}, but the engine being linked is {commit}");
+            println!("cargo::error=link against the pinned engine, or regenerate the bindings with {UPDATE_PREGENERATED_BINDINGS}=1");
+            false
+        }
+        None => {
+            println!("cargo::warning=couldn't determine the engine commit, so it can't be checked against the pregenerated bindings (pinned to {pinned})");
+            true
+        }
+    }
+}
+
+// Parts of the embedder API that aren't in every `embedder.h` we can build against.
+// Each is enabled as a `cfg` when its marker appears in the generated bindings.
+const EMBEDDER_CAPABILITIES: &[(&str, &str)] = &[
+    // `FlutterEngineAddView` and `FlutterEngineRemoveView`
+    ("embedder_add_view", "pub fn AddView("),
+    // `FlutterProjectArgs::channel_update_callback`
+    ("embedder_channel_update", "pub channel_update_callback:"),
+    // `FlutterEngineSendViewFocusEvent` and `FlutterProjectArgs::view_focus_change_request_callback`
+    ("embedder_view_focus", "pub fn SendViewFocusEvent("),
+];
+
+fn declare_embedder_capabilities() {
+    for (cfg, _) in EMBEDDER_CAPABILITIES {
+        println!("cargo::rustc-check-cfg=cfg({cfg})");
+    }
+}
+
+fn detect_embedder_capabilities() {
+    let bindings =
+        std::fs::read_to_string(PathBuf::from(env("OUT_DIR").unwrap()).join("embedder.rs"))
+            .expect("Couldn't read bindings");
+    for (cfg, marker) in EMBEDDER_CAPABILITIES {
+        if bindings.contains(marker) {
+            println!("cargo::rustc-cfg={cfg}");
+        } else {
+            println!("cargo::warning=this embedder.h predates `{cfg}`; the corresponding APIs are unavailable");
+        }
+    }
+}
+
+fn generate_bindings(embedder_h: &str, commit: Option<&str>) {
     println!("cargo::rerun-if-changed={embedder_h}");
-    bindgen::builder()
+    let bindings = bindgen::builder()
         .header(embedder_h)
         .parse_callbacks(Box::new(ParseCallbacks))
         // various non-flutter-related things that generate warnings idc about
@@ -142,9 +233,32 @@ fn generate_bindings(embedder_h: &str) {
         .merge_extern_blocks(true)
         .sort_semantically(true)
         .generate()
-        .expect("Unable to generate bindings")
+        .expect("Unable to generate bindings");
+
+    bindings
         .write_to_file(PathBuf::from(env("OUT_DIR").unwrap()).join("embedder.rs"))
         .expect("Couldn't write bindings");
+
+    if env(UPDATE_PREGENERATED_BINDINGS).is_some_and(|s| !s.is_empty()) {
+        let Some(commit) = commit else {
+            println!("cargo::error=can't update the pregenerated bindings without knowing which engine commit they're from; set {ENGINE_HASH}");
+            return;
+        };
+        let pregenerated = pregenerated_bindings_path();
+        std::fs::create_dir_all(pregenerated.parent().unwrap())
+            .expect("Couldn't create the pregenerated bindings directory");
+        bindings
+            .write_to_file(&pregenerated)
+            .expect("Couldn't write pregenerated bindings");
+        std::fs::write(pregenerated_bindings_commit_path(), format!("{commit}\n"))
+            .expect("Couldn't write the pregenerated bindings commit");
+        println!(
+            "cargo::warning=updated pregenerated bindings at {} for engine {commit}",
+            pregenerated.display()
+        );
+    }
+
+    detect_embedder_capabilities();
 }
 
 fn env(name: &str) -> Option<String> {
@@ -152,17 +266,244 @@ fn env(name: &str) -> Option<String> {
     std::env::var(name).ok()
 }
 
+const ENGINE: &str = "FLUTTER_ENGINE";
+const ENGINE_HASH: &str = "VOLITO_ENGINE_HASH";
+const ENGINE_CACHE: &str = "VOLITO_ENGINE_CACHE";
+
+// The directory (under `out`) that downloaded artifacts are placed in.
+// Only the debug embedder is published for desktop linux, so that's all we ever download.
+const DOWNLOADED_LINK_HOST: &str = "host_debug";
+
+fn engine_cache_dir() -> Option<PathBuf> {
+    env(ENGINE_CACHE)
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env("XDG_CACHE_HOME")
+                .filter(|s| !s.is_empty())
+                .map(|cache| PathBuf::from(cache).join("volito"))
+        })
+        .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".cache").join("volito")))
+        .map(|cache| cache.join("engine"))
+}
+
+fn run(command: &mut std::process::Command) -> bool {
+    match command.status() {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            println!("cargo::error={command:?} failed with {status}");
+            false
+        }
+        Err(err) => {
+            println!("cargo::error=couldn't run {command:?}: {err}");
+            false
+        }
+    }
+}
+
+/// Downloads the prebuilt embedder artifacts for `engine_hash` into the cache directory,
+/// laid out like an engine checkout (`<root>/out/<link host>`), and returns the root.
+///
+/// These are the same artifacts the `flutter` tool downloads; they are fetched with `curl` and extracted with `unzip`.
+fn download_engine(engine_hash: &str) -> Option<PathBuf> {
+    if engine_hash.len() != 40 || !engine_hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        println!("cargo::error={ENGINE_HASH} must be a full engine commit hash (40 hex digits), got {engine_hash:?}");
+        return None;
+    }
+
+    let platform = match (
+        env("CARGO_CFG_TARGET_OS").as_deref(),
+        env("CARGO_CFG_TARGET_ARCH").as_deref(),
+    ) {
+        (Some("linux"), Some("x86_64")) => "linux-x64",
+        (Some("linux"), Some("aarch64")) => "linux-arm64",
+        (os, arch) => {
+            println!("cargo::error=there are no prebuilt embedder artifacts for {os:?} {arch:?}; set {ENGINE} to a local engine build instead");
+            return None;
+        }
+    };
+
+    let Some(cache) = engine_cache_dir() else {
+        println!("cargo::error=couldn't find a cache directory for the downloaded engine; set {ENGINE_CACHE}");
+        return None;
+    };
+    let root = cache.join(engine_hash).join(platform);
+    let host = root.join("out").join(DOWNLOADED_LINK_HOST);
+
+    if ["libflutter_engine.so", "flutter_embedder.h", "icudtl.dat"]
+        .iter()
+        .all(|file| host.join(file).is_file())
+    {
+        return Some(root);
+    }
+
+    println!(
+        "cargo::warning=downloading flutter engine {engine_hash} for {platform} into {}",
+        root.display()
+    );
+
+    // extract into a scratch directory first, so that an interrupted download is never mistaken for a complete one
+    let partial = root.join("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial).expect("Couldn't clear partial engine download");
+    }
+    std::fs::create_dir_all(&partial).expect("Couldn't create engine cache directory");
+
+    let base = format!(
+        "https://storage.googleapis.com/flutter_infra_release/flutter/{engine_hash}/{platform}"
+    );
+    for (url, archive) in [
+        // libflutter_engine.so, flutter_embedder.h
+        (format!("{base}/{platform}-embedder"), "embedder.zip"),
+        // icudtl.dat (and the rest of the host tools, which we don't need)
+        (format!("{base}/artifacts.zip"), "artifacts.zip"),
+    ] {
+        let archive = partial.join(archive);
+        let downloaded = run(std::process::Command::new("curl")
+            .args([
+                "--fail",
+                "--location",
+                "--silent",
+                "--show-error",
+                "--output",
+            ])
+            .arg(&archive)
+            .arg(&url));
+        if !downloaded
+            || !run(std::process::Command::new("unzip")
+                .args(["-o", "-q"])
+                .arg(&archive)
+                .arg("-d")
+                .arg(&partial))
+        {
+            println!("cargo::error=couldn't fetch {url}");
+            return None;
+        }
+        std::fs::remove_file(&archive).expect("Couldn't remove downloaded archive");
+    }
+
+    std::fs::create_dir_all(host.parent().unwrap())
+        .expect("Couldn't create engine cache directory");
+    if host.exists() {
+        std::fs::remove_dir_all(&host).expect("Couldn't clear stale engine download");
+    }
+    std::fs::rename(&partial, &host).expect("Couldn't move downloaded engine into place");
+
+    Some(root)
+}
+
+// Read by `engine_build_info()` at compile time.
+const ENGINE_COMMIT: &str = "VOLITO_ENGINE_COMMIT";
+const ENGINE_LINK_HOST: &str = "VOLITO_ENGINE_LINK_HOST";
+
+/// The commit of the engine checkout at `root`, if it is one.
+///
+/// In a gclient checkout, `root` is the buildroot and the engine itself is at `root/flutter`.
+/// In the monorepo, that's a link back into the flutter repository, so the same path works.
+fn engine_commit(root: &str) -> Option<String> {
+    let flutter = Path::new(root).join("flutter");
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(&flutter)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?;
+    Some(commit.trim().to_owned())
+}
+
+const STATIC_ENGINE_LIBS: &str = "VOLITO_STATIC_ENGINE_LIBS";
+
+// what libflutter_engine.so itself links against on linux.
+// the engine bundles its own libc++, and loads GL/Vulkan through the embedder's proc resolvers, so that's all of it.
+const STATIC_ENGINE_SYSTEM_LIBS: &[&str] = &["dl", "pthread", "m", "rt"];
+
+/// Links `libflutter_engine.a` into the final binary instead of depending on `libflutter_engine.so` at runtime.
+///
+/// The whole archive is linked, because nothing in the engine is referenced by name except the embedder API,
+/// and the linker would otherwise discard the static initializers that register the engine's internals.
+fn link_static_engine(flutter_engine: &Path) -> bool {
+    if !flutter_engine.join("libflutter_engine.a").is_file() {
+        println!(
+            "cargo::error=the static-engine feature needs libflutter_engine.a in {}",
+            flutter_engine.display()
+        );
+        println!("cargo::error=this needs an engine build that produces the embedder as a static library; otherwise, disable static-engine");
+        return false;
+    }
+
+    println!("cargo::rustc-link-lib=static:+whole-archive,-bundle=flutter_engine");
+    for lib in STATIC_ENGINE_SYSTEM_LIBS {
+        println!("cargo::rustc-link-lib=dylib={lib}");
+    }
+    // for engine builds configured with extra dependencies (e.g. system freetype or a sysroot libstdc++)
+    if let Some(libs) = env(STATIC_ENGINE_LIBS) {
+        for lib in libs.split_whitespace() {
+            println!("cargo::rustc-link-lib={lib}");
+        }
+    }
+    true
+}
+
 fn main() {
-    const ENGINE: &str = "FLUTTER_ENGINE";
-    let flutter_engine = match env(ENGINE) {
-        Some(s) if !s.is_empty() => s,
+    declare_embedder_capabilities();
+
+    // not read through `env()`; cargo already reruns the build script when features change
+    let pregenerated = std::env::var_os("CARGO_FEATURE_PREGENERATED_BINDINGS").is_some();
+    let pinned = if pregenerated {
+        let Some(pinned) = use_pregenerated_bindings() else {
+            return;
+        };
+        Some(pinned)
+    } else {
+        None
+    };
+
+    let static_engine = std::env::var_os("CARGO_FEATURE_STATIC_ENGINE").is_some();
+
+    let mut downloaded = None;
+    let flutter_engine = match (env(ENGINE), env(ENGINE_HASH)) {
+        (Some(s), _) if !s.is_empty() => s,
+        // opt-in: fetch prebuilt artifacts instead of requiring a local engine build
+        (_, Some(engine_hash)) if !engine_hash.is_empty() => {
+            let Some(root) = download_engine(&engine_hash) else {
+                return;
+            };
+            if !cfg!(debug_assertions) {
+                println!("cargo::warning=the downloaded engine is a debug build; use a local engine build for release builds");
+            }
+            let root = root.to_str().unwrap().to_owned();
+            downloaded = Some(engine_hash);
+            root
+        }
+        // the bindings don't need the engine, so we can still typecheck (e.g. on docs.rs). we just can't link.
+        _ if pregenerated => {
+            println!("cargo::warning=The {ENGINE} environment variable is missing, so libflutter_engine.so will not be linked");
+            return;
+        }
         _ => {
             println!("cargo::error=The {ENGINE} environment variable is missing. It must point to a library path with libflutter_engine.so");
+            println!("cargo::error=alternatively, set {ENGINE_HASH} to an engine commit hash to download prebuilt artifacts");
             return;
         }
     };
     println!("cargo::metadata=root={flutter_engine}");
 
+    let commit = downloaded
+        .clone()
+        .or_else(|| engine_commit(&flutter_engine));
+    if let Some(commit) = &commit {
+        println!("cargo::rustc-env={ENGINE_COMMIT}={commit}");
+    }
+    if let Some(pinned) = &pinned {
+        if !check_pregenerated_bindings_commit(pinned, commit.as_deref()) {
+            return;
+        }
+    }
+
     let flutter_engine = {
         let mut path = PathBuf::from(&flutter_engine);
         path.push("out");
@@ -232,7 +573,9 @@ fn main() {
             }
         }
 
-        if let Some(link_host) = env(LINK_HOST) {
+        if downloaded.is_some() {
+            path.push(DOWNLOADED_LINK_HOST);
+        } else if let Some(link_host) = env(LINK_HOST) {
             let (was_split, link_host) = link_host.split_once('/').map_or(
                 (false, link_host.as_str()),
                 |(release, debug)| {
@@ -271,7 +614,18 @@ fn main() {
         "cargo::rustc-link-search=native={}",
         flutter_engine.to_str().unwrap()
     );
-    println!("cargo::rustc-link-lib=flutter_engine");
+    if static_engine {
+        if !link_static_engine(&flutter_engine) {
+            return;
+        }
+    } else {
+        println!("cargo::rustc-link-lib=flutter_engine");
+    }
+    // a rebuilt engine may be from a different commit, which `engine_build_info()` should reflect
+    println!(
+        "cargo::rerun-if-changed={}",
+        flutter_engine.join("libflutter_engine.so").display()
+    );
 
     let embedder_h = flutter_engine.join("flutter_embedder.h");
     let link_host = flutter_engine.file_name().unwrap();
@@ -282,6 +636,9 @@ fn main() {
     let icudtl_dat = icudtl_dat.to_str().unwrap();
 
     println!("cargo::metadata=link_host={link_host}");
+    println!("cargo::rustc-env={ENGINE_LINK_HOST}={link_host}");
     println!("cargo::metadata=icudtl_dat={icudtl_dat}");
-    generate_bindings(embedder_h);
+    if !pregenerated {
+        generate_bindings(embedder_h, commit.as_deref());
+    }
 }
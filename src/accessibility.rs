@@ -0,0 +1,233 @@
+//! Keeps the engine's [`AccessibilityFeature`]s in sync with the desktop's accessibility settings.
+//!
+//! GNOME (and anything else configured through `GSettings`) is read with `gsettings`, and KDE from `kdeglobals`.
+//! Whether a screen reader is running is asked of the accessibility bus with `gdbus`, on any desktop.
+//! Desktops have no setting for bold text or inverted colors, so those are never reported.
+//!
+//! ```ignore
+//! let handle = Arc::new(Engine::spawn(start)?);
+//! let engine = handle.clone();
+//! let watcher = AccessibilityWatcher::spawn(Duration::from_secs(2), move |features| {
+//!     engine.post(move |engine| {
+//!         let _ = engine.update_accessibility_features(features);
+//!     });
+//! });
+//! ```
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use tracing::debug;
+
+use crate::AccessibilityFeature;
+
+/// The accessibility features the desktop currently asks for.
+///
+/// Settings that can't be read (e.g. because `gsettings` isn't installed) count as disabled.
+#[must_use]
+pub fn system_accessibility_features() -> AccessibilityFeature {
+    let mut features = AccessibilityFeature::empty();
+    let desktop = DesktopSettings::read();
+    if desktop.high_contrast {
+        features |= AccessibilityFeature::HighContrast;
+    }
+    if desktop.disable_animations {
+        features |= AccessibilityFeature::DisableAnimations;
+    }
+    if desktop.screen_reader || screen_reader_running() == Some(true) {
+        features |= AccessibilityFeature::AccessibleNavigation;
+    }
+    features
+}
+
+#[derive(Debug, Default)]
+struct DesktopSettings {
+    high_contrast: bool,
+    disable_animations: bool,
+    screen_reader: bool,
+}
+
+impl DesktopSettings {
+    fn read() -> Self {
+        let is_kde = std::env::var("XDG_CURRENT_DESKTOP")
+            .is_ok_and(|desktops| desktops.split(':').any(|desktop| desktop == "KDE"));
+        if is_kde {
+            Self::read_kde()
+        } else {
+            Self::read_gnome()
+        }
+    }
+
+    fn read_gnome() -> Self {
+        Self {
+            high_contrast: gsettings_bool("org.gnome.desktop.a11y.interface", "high-contrast")
+                .unwrap_or(false),
+            disable_animations: gsettings_bool("org.gnome.desktop.interface", "enable-animations")
+                .is_some_and(|enabled| !enabled),
+            screen_reader: gsettings_bool(
+                "org.gnome.desktop.a11y.applications",
+                "screen-reader-enabled",
+            )
+            .unwrap_or(false),
+        }
+    }
+
+    fn read_kde() -> Self {
+        let Some(kdeglobals) = config_home().map(|config| config.join("kdeglobals")) else {
+            return Self::default();
+        };
+        let Ok(contents) = fs::read_to_string(&kdeglobals) else {
+            debug!("couldn't read {}", kdeglobals.display());
+            return Self::default();
+        };
+        Self {
+            high_contrast: ini_value(&contents, "General", "ColorScheme")
+                .is_some_and(|scheme| scheme.contains("HighContrast")),
+            disable_animations: ini_value(&contents, "KDE", "AnimationDurationFactor")
+                .and_then(|factor| factor.parse::<f64>().ok())
+                .is_some_and(|factor| factor == 0.0),
+            // KDE only has this on the accessibility bus
+            screen_reader: false,
+        }
+    }
+}
+
+fn config_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|config| !config.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+/// The value of `key` in `[section]` of an INI-style file, as KDE writes them.
+fn ini_value<'a>(contents: &'a str, section: &str, key: &str) -> Option<&'a str> {
+    let mut in_section = false;
+    for line in contents.lines().map(str::trim) {
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            in_section = name == section;
+        } else if in_section {
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            // keys may be followed by flags like `[$i]`
+            let name = name.split('[').next().unwrap_or(name).trim();
+            if name == key {
+                return Some(value.trim());
+            }
+        }
+    }
+    None
+}
+
+fn command_output(command: &mut Command) -> Option<String> {
+    let output = command.stdin(Stdio::null()).stderr(Stdio::null()).output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8(output.stdout).ok(),
+        Ok(_) => None,
+        Err(err) => {
+            debug!("couldn't run {:?}: {err}", command.get_program());
+            None
+        }
+    }
+}
+
+fn gsettings_bool(schema: &str, key: &str) -> Option<bool> {
+    let value = command_output(Command::new("gsettings").args(["get", schema, key]))?;
+    match value.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether the accessibility bus says a screen reader is running, if there is one.
+fn screen_reader_running() -> Option<bool> {
+    let value = command_output(Command::new("gdbus").args([
+        "call",
+        "--session",
+        "--dest",
+        "org.a11y.Bus",
+        "--object-path",
+        "/org/a11y/bus",
+        "--method",
+        "org.freedesktop.DBus.Properties.Get",
+        "org.a11y.Status",
+        "ScreenReaderEnabled",
+    ]))?;
+    // e.g. `(<true>,)`
+    match value.trim() {
+        "(<true>,)" => Some(true),
+        "(<false>,)" => Some(false),
+        _ => None,
+    }
+}
+
+/// Reads the desktop's accessibility settings every so often, on a thread of its own, and reports when they change.
+///
+/// Dropping it stops the thread, without waiting for it.
+#[derive(Debug)]
+pub struct AccessibilityWatcher {
+    stop: Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AccessibilityWatcher {
+    /// Starts watching, reading the settings every `interval`.
+    ///
+    /// `changed` is called on the watcher's thread with the current features right away, and again whenever they change.
+    /// It should pass them on to [`crate::Engine::update_accessibility_features`] on the platform thread.
+    ///
+    /// # Panics
+    ///
+    /// If the thread can't be spawned.
+    pub fn spawn(
+        interval: Duration,
+        mut changed: impl FnMut(AccessibilityFeature) + Send + 'static,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("volito-accessibility".to_owned())
+            .spawn(move || {
+                let mut current = None;
+                loop {
+                    let features = system_accessibility_features();
+                    if current != Some(features) {
+                        current = Some(features);
+                        changed(features);
+                    }
+                    match stopped.recv_timeout(interval) {
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+            })
+            .expect("failed to spawn the accessibility watcher thread");
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops watching, and waits for the thread to finish.
+    pub fn stop(mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for AccessibilityWatcher {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+    }
+}
@@ -1,4 +1,8 @@
-use std::{ffi::CString, path::PathBuf};
+use std::{
+    ffi::CString,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+};
 
 use crate::sys;
 
@@ -11,10 +15,15 @@ pub enum AOTDataSource {
 
 /// An opaque object that describes the AOT data that can be used to launch a Flutter [`crate::Engine`] instance in AOT mode.
 #[must_use]
+#[derive(Debug)]
 pub struct AOTData {
     pub(crate) data: sys::FlutterEngineAOTData,
 }
 
+// SAFETY: the AOT data is never changed once it's created, and engines on any thread may read it.
+unsafe impl Send for AOTData {}
+unsafe impl Sync for AOTData {}
+
 impl AOTData {
     /// Indicates whether the Dart VM requires AOT data or JIT data to run.
     /// If this returns true, the Dart VM requires AOT data to run.
@@ -59,3 +68,59 @@ impl Drop for AOTData {
         unsafe { sys::CollectAOTData(self.data) };
     }
 }
+
+/// The Dart VM is shared by every engine in the process, and may refer to the AOT data of any engine that ran in it
+/// until it shuts down. So AOT data is kept here until then, and collected exactly once when the last `Arc` is dropped.
+///
+/// The VM starts with the first engine. It shuts down with the last engine only if the engine that started it had
+/// `shutdown_dart_vm_when_done` set; otherwise it lives until the process exits, and so does the AOT data it used.
+static DART_VM: Mutex<DartVm> = Mutex::new(DartVm {
+    engines: 0,
+    persistent: false,
+    aot_data: Vec::new(),
+});
+
+struct DartVm {
+    /// Engines that have been launched and not yet shut down.
+    engines: usize,
+    /// The VM was started by an engine without `shutdown_dart_vm_when_done`, so it never shuts down.
+    persistent: bool,
+    aot_data: Vec<Arc<AOTData>>,
+}
+
+/// Called before an engine is launched. Returns whether this engine starts the VM, for [`dart_vm_engine_running`].
+pub(crate) fn dart_vm_engine_launched(aot_data: Option<&Arc<AOTData>>) -> bool {
+    let mut vm = DART_VM.lock().unwrap_or_else(PoisonError::into_inner);
+    let starts_vm = vm.engines == 0 && !vm.persistent;
+    vm.engines += 1;
+    if let Some(aot_data) = aot_data {
+        if !vm.aot_data.iter().any(|kept| Arc::ptr_eq(kept, aot_data)) {
+            vm.aot_data.push(aot_data.clone());
+        }
+    }
+    starts_vm
+}
+
+/// Called once an engine has launched successfully.
+///
+/// Only then is the VM known to be running, so an engine that fails to launch can't make it persistent.
+pub(crate) fn dart_vm_engine_running(starts_vm: bool, shutdown_dart_vm_when_done: bool) {
+    if starts_vm && !shutdown_dart_vm_when_done {
+        DART_VM
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .persistent = true;
+    }
+}
+
+/// Called once an engine has shut down, or failed to launch.
+pub(crate) fn dart_vm_engine_shut_down() {
+    let mut vm = DART_VM.lock().unwrap_or_else(PoisonError::into_inner);
+    vm.engines -= 1;
+    if vm.engines == 0 && !vm.persistent {
+        // the VM shut down with the last engine
+        let aot_data = std::mem::take(&mut vm.aot_data);
+        drop(vm);
+        drop(aot_data);
+    }
+}
@@ -0,0 +1,726 @@
+//! A batteries-included runner: one winit window, rendered with OpenGL (EGL), running the app that
+//! `volito-build-support` built, with the [`crate::desktop`] channels already handled.
+//!
+//! ```ignore
+//! fn main() -> Result<(), volito::app::AppError> {
+//!     volito::app::FlusterApp::run(AppArgs::new("My App", volito::app_paths!()))
+//! }
+//! ```
+//!
+//! It's opinionated on purpose; embedders that need more control can use the pieces it's made of directly.
+
+use std::{
+    ffi::{CStr, CString, OsStr},
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    thread::ThreadId,
+    time::{Duration, Instant},
+};
+
+use tracing::{error, info, warn};
+use winit::{
+    application::ApplicationHandler as WinitHandler,
+    dpi::LogicalSize,
+    error::{EventLoopError, OsError},
+    event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy},
+    keyboard::{Key, NamedKey},
+    platform::{
+        modifier_supplement::KeyEventExtModifierSupplement, scancode::PhysicalKeyExtScancode,
+    },
+    raw_window_handle::{HandleError, HasDisplayHandle, HasWindowHandle},
+    window::{CursorIcon, Theme, Window, WindowId},
+};
+
+use crate::{
+    desktop::{
+        self, AppLifecycleState, ApplicationHandler, Brightness, Clipboard, CursorHandler,
+        LocalClipboard, PlatformSettings, Shell, ShellHandlers,
+    },
+    task_runners::TaskQueue,
+    window_handle::{opengl_renderer_config, WindowHandleError},
+    AOTData, AOTDataSource, CursorChange, CustomTaskRunners, Engine, EngineClock, EngineContext,
+    EngineHandler, KeyEvent, KeyboardState, PlatformMessageResponse, PointerButtons,
+    PointerDeviceKind, PointerState, ProjectArgs, ScrollConfig, SemanticsUpdate, Task,
+    TaskRunnerDescription, TaskRunnerHandler, ViewFocusEvent, ViewId, VsyncBaton, VsyncQueue,
+    WindowMetricsEvent,
+};
+
+/// Where the app's bundle is. See [`crate::app_paths!`].
+#[derive(Debug, Clone)]
+pub struct AppPaths {
+    /// The `flutter_assets` directory.
+    pub assets: PathBuf,
+    /// The engine's `icudtl.dat`.
+    pub icu_data: PathBuf,
+    /// The AOT-compiled app, for release builds. Debug builds run the kernel snapshot in the assets.
+    pub app_library: Option<PathBuf>,
+}
+
+/// The [`app::AppPaths`](crate::app::AppPaths) of the app built by `volito-build-support` in the calling crate's build script.
+#[macro_export]
+macro_rules! app_paths {
+    () => {
+        $crate::app::AppPaths {
+            assets: ::std::path::PathBuf::from(env!("VOLITO_ASSETS")),
+            icu_data: ::std::path::PathBuf::from(env!("VOLITO_ICUDTL_DAT")),
+            app_library: option_env!("VOLITO_APP_LIBRARY").map(::std::path::PathBuf::from),
+        }
+    };
+}
+
+/// What to run, and in what window.
+pub struct AppArgs {
+    pub title: String,
+    /// The initial size of the window, in logical pixels.
+    pub size: (u32, u32),
+    pub paths: AppPaths,
+    /// Passed to the Dart `main`.
+    pub dart_entrypoint_argv: Vec<String>,
+    /// Winit doesn't have a clipboard, so this is a [`LocalClipboard`] unless replaced.
+    pub clipboard: Box<dyn Clipboard>,
+    /// How far the mouse wheel scrolls. Touchpads scroll by pixels, which this doesn't change, except for natural scrolling.
+    pub scroll: ScrollConfig,
+}
+
+impl fmt::Debug for AppArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppArgs")
+            .field("title", &self.title)
+            .field("size", &self.size)
+            .field("paths", &self.paths)
+            .field("dart_entrypoint_argv", &self.dart_entrypoint_argv)
+            .field("scroll", &self.scroll)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AppArgs {
+    #[must_use]
+    pub fn new(title: impl Into<String>, paths: AppPaths) -> Self {
+        Self {
+            title: title.into(),
+            size: (800, 600),
+            paths,
+            dart_entrypoint_argv: Vec::new(),
+            clipboard: Box::new(LocalClipboard::default()),
+            scroll: ScrollConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AppError {
+    EventLoop(EventLoopError),
+    Window(OsError),
+    Handle(HandleError),
+    Renderer(WindowHandleError),
+    Engine(crate::Error),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::EventLoop(err) => write!(f, "event loop failed: {err}"),
+            AppError::Window(err) => write!(f, "failed to create the window: {err}"),
+            AppError::Handle(err) => write!(f, "the window has no usable handle: {err}"),
+            AppError::Renderer(err) => write!(f, "failed to create the renderer: {err}"),
+            AppError::Engine(err) => write!(f, "engine call failed: {err:?}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<EventLoopError> for AppError {
+    fn from(err: EventLoopError) -> Self {
+        Self::EventLoop(err)
+    }
+}
+
+impl From<OsError> for AppError {
+    fn from(err: OsError) -> Self {
+        Self::Window(err)
+    }
+}
+
+impl From<HandleError> for AppError {
+    fn from(err: HandleError) -> Self {
+        Self::Handle(err)
+    }
+}
+
+impl From<WindowHandleError> for AppError {
+    fn from(err: WindowHandleError) -> Self {
+        Self::Renderer(err)
+    }
+}
+
+impl From<crate::Error> for AppError {
+    fn from(err: crate::Error) -> Self {
+        Self::Engine(err)
+    }
+}
+
+/// Runs a Flutter app in a window until it's closed.
+///
+/// The window's event loop is the engine's platform thread. Vsync is paced by redraw requests (and the
+/// swap interval of the EGL surface), and mouse, scroll and keyboard input go to the implicit view.
+/// The [`crate::desktop`] channels are handled, with the window's cursor and title, and [`AppArgs::clipboard`].
+pub struct FlusterApp {
+    // dropped before the window, which the renderer draws to
+    engine: Option<Engine>,
+    window: Option<Arc<Window>>,
+
+    args: AppArgs,
+    proxy: EventLoopProxy<UserEvent>,
+    vsync: VsyncQueue,
+    tasks: TaskQueue,
+    aot_data: Option<Arc<AOTData>>,
+    error: Option<AppError>,
+
+    refresh_interval: Duration,
+    focused: bool,
+    pointer: PointerState,
+    keyboard: KeyboardState,
+}
+
+impl fmt::Debug for FlusterApp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlusterApp")
+            .field("engine", &self.engine)
+            .field("window", &self.window)
+            .field("args", &self.args)
+            .field("refresh_interval", &self.refresh_interval)
+            .field("focused", &self.focused)
+            .finish_non_exhaustive()
+    }
+}
+
+enum UserEvent {
+    Task(Duration, Task),
+    Vsync,
+    Exit,
+}
+
+const MOUSE_DEVICE: i32 = 0;
+
+impl FlusterApp {
+    /// Opens the window and runs the app, returning once the window is closed or the app exits.
+    pub fn run(args: AppArgs) -> Result<(), AppError> {
+        let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+        #[allow(clippy::arc_with_non_send_sync)] // it's what `ProjectArgs` takes
+        let aot_data = match &args.paths.app_library {
+            Some(path) if AOTData::is_aot() => Some(Arc::new(AOTData::new(
+                &AOTDataSource::ElfPath(path.clone()),
+            )?)),
+            _ => None,
+        };
+        let mut pointer =
+            PointerState::new(ViewId::IMPLICIT, MOUSE_DEVICE, PointerDeviceKind::Mouse);
+        pointer.set_scroll_config(&args.scroll);
+        let mut app = Self {
+            engine: None,
+            window: None,
+
+            args,
+            proxy: event_loop.create_proxy(),
+            vsync: VsyncQueue::new(),
+            tasks: TaskQueue::new(Arc::new(EngineClock)),
+            aot_data,
+            error: None,
+
+            refresh_interval: Duration::from_nanos(1_000_000_000 / 60),
+            focused: false,
+            pointer,
+            keyboard: KeyboardState::new(),
+        };
+        event_loop.run_app(&mut app)?;
+        app.error.map_or(Ok(()), Err)
+    }
+
+    fn start(&mut self, event_loop: &ActiveEventLoop) -> Result<(), AppError> {
+        let (width, height) = self.args.size;
+        let window = Arc::new(
+            event_loop.create_window(
+                Window::default_attributes()
+                    .with_title(&self.args.title)
+                    .with_inner_size(LogicalSize::new(width, height)),
+            )?,
+        );
+        if let Some(refresh_rate) = window
+            .current_monitor()
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+        {
+            self.refresh_interval = Duration::from_secs_f64(1000.0 / f64::from(refresh_rate));
+        }
+
+        // the window outlives the engine, see the field order
+        let renderer = unsafe {
+            opengl_renderer_config(
+                window.display_handle()?.as_raw(),
+                window.window_handle()?.as_raw(),
+            )
+        }?;
+
+        let shell = Shell::new(ShellHandlers {
+            clipboard: std::mem::replace(
+                &mut self.args.clipboard,
+                Box::new(LocalClipboard::default()),
+            ),
+            cursor: Box::new(WindowCursor(window.clone())),
+            application: Box::new(WindowApplication {
+                window: window.clone(),
+                proxy: self.proxy.clone(),
+            }),
+            virtual_keyboard: None,
+            text_input: None,
+        });
+
+        let dart_entrypoint_argv = self
+            .args
+            .dart_entrypoint_argv
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let executable = std::env::args_os().next().unwrap_or_default();
+        let command_line_argv: &[&OsStr] = &[&executable];
+
+        let engine = Engine::run(
+            renderer,
+            ProjectArgs {
+                assets_path: &self.args.paths.assets,
+                icu_data_path: &self.args.paths.icu_data,
+                command_line_argv,
+                persistent_cache_path: None,
+                is_persistent_cache_read_only: false,
+                custom_dart_entrypoint: None,
+                custom_task_runners: Some(CustomTaskRunners {
+                    platform_task_runner: Some(TaskRunnerDescription {
+                        identifier: 1,
+                        handler: Box::new(AppTaskRunner {
+                            thread: std::thread::current().id(),
+                            proxy: self.proxy.clone(),
+                        }),
+                    }),
+                    render_task_runner: None,
+                    set_thread_priority: None,
+                }),
+                shutdown_dart_vm_when_done: true,
+                compositor: None,
+                dart_entrypoint_argv: &dart_entrypoint_argv,
+                log_tag: CString::from(c"flutter"),
+                dart_old_gen_heap_size: -1,
+                aot_data: self.aot_data.clone(),
+                handler: Box::new(AppEngineHandler {
+                    shell,
+                    vsync: self.vsync.clone(),
+                    proxy: self.proxy.clone(),
+                }),
+                compute_platform_resolved_locale: None,
+            },
+        )?;
+        let engine = self.engine.insert(engine);
+
+        Self::send_metrics(engine, &window)?;
+        desktop::send_settings(engine, &Self::settings(window.theme()))?;
+        self.window = Some(window);
+        Ok(())
+    }
+
+    fn send_metrics(engine: &mut Engine, window: &Window) -> crate::Result<()> {
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            // e.g. while minimized; the engine keeps the last size
+            return Ok(());
+        }
+        engine.send_window_metrics_event(WindowMetricsEvent {
+            view_id: ViewId::IMPLICIT,
+            width: size.width as usize,
+            height: size.height as usize,
+            pixel_ratio: window.scale_factor(),
+            left: 0,
+            top: 0,
+            physical_view_inset_top: 0.0,
+            physical_view_inset_right: 0.0,
+            physical_view_inset_bottom: 0.0,
+            physical_view_inset_left: 0.0,
+            display_id: 0,
+        })
+    }
+
+    fn settings(theme: Option<Theme>) -> PlatformSettings {
+        PlatformSettings {
+            platform_brightness: match theme {
+                Some(Theme::Dark) => Brightness::Dark,
+                Some(Theme::Light) | None => Brightness::Light,
+            },
+            ..PlatformSettings::default()
+        }
+    }
+
+    fn exit(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(engine) = &mut self.engine {
+            if let Err(err) = desktop::set_lifecycle_state(engine, AppLifecycleState::Detached) {
+                warn!("failed to send the lifecycle state: {err:?}");
+            }
+        }
+        self.engine = None;
+        self.window = None;
+        event_loop.exit();
+    }
+
+    fn fail(&mut self, event_loop: &ActiveEventLoop, err: AppError) {
+        error!("{err}");
+        self.error.get_or_insert(err);
+        self.exit(event_loop);
+    }
+
+    fn handle_window_event(&mut self, event: WindowEvent) -> Result<(), AppError> {
+        let (Some(engine), Some(window)) = (&mut self.engine, &self.window) else {
+            return Ok(());
+        };
+        let timestamp = Engine::get_current_time();
+        let mut pointer_events = Vec::new();
+
+        match event {
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                Self::send_metrics(engine, window)?;
+            }
+            WindowEvent::ThemeChanged(theme) => {
+                desktop::send_settings(engine, &Self::settings(Some(theme)))?;
+            }
+            WindowEvent::Focused(focused) => {
+                self.focused = focused;
+                let state = if focused {
+                    AppLifecycleState::Resumed
+                } else {
+                    AppLifecycleState::Inactive
+                };
+                desktop::set_lifecycle_state(engine, state)?;
+                if !focused {
+                    // the keys held now will be released in another window
+                    for key_event in self.keyboard.release_all(timestamp) {
+                        Self::send_key_event(engine, key_event);
+                    }
+                }
+            }
+            WindowEvent::Occluded(occluded) => {
+                let state = match (occluded, self.focused) {
+                    (true, _) => AppLifecycleState::Hidden,
+                    (false, true) => AppLifecycleState::Resumed,
+                    (false, false) => AppLifecycleState::Inactive,
+                };
+                desktop::set_lifecycle_state(engine, state)?;
+            }
+            WindowEvent::RedrawRequested => {
+                self.vsync.frame(engine, timestamp, self.refresh_interval)?;
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                if self.pointer.is_added() {
+                    pointer_events.push(self.pointer.motion(timestamp, position.x, position.y));
+                } else {
+                    pointer_events.extend(self.pointer.enter(
+                        ViewId::IMPLICIT,
+                        timestamp,
+                        position.x,
+                        position.y,
+                    ));
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                pointer_events.extend(self.pointer.leave(timestamp));
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => PointerButtons::MousePrimary,
+                    MouseButton::Right => PointerButtons::MouseSecondary,
+                    MouseButton::Middle => PointerButtons::MouseMiddle,
+                    MouseButton::Back => PointerButtons::MouseBack,
+                    MouseButton::Forward => PointerButtons::MouseForward,
+                    MouseButton::Other(_) => return Ok(()),
+                };
+                pointer_events.push(self.pointer.button(
+                    timestamp,
+                    button,
+                    state == ElementState::Pressed,
+                ));
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                // winit scrolls content down for positive deltas, Flutter scrolls it up
+                pointer_events.push(match delta {
+                    // winit's lines are wheel clicks
+                    MouseScrollDelta::LineDelta(x, y) => self.pointer.scroll_clicks(
+                        timestamp,
+                        -f64::from(x),
+                        -f64::from(y),
+                        window.scale_factor(),
+                    ),
+                    MouseScrollDelta::PixelDelta(position) => {
+                        self.pointer.scroll(timestamp, -position.x, -position.y)
+                    }
+                });
+            }
+
+            WindowEvent::KeyboardInput { event, .. } => {
+                let Some(evdev_code) = event.physical_key.to_scancode() else {
+                    return Ok(());
+                };
+                let unmodified = event.key_without_modifiers();
+                let character = match &unmodified {
+                    Key::Character(text) => single_char(text),
+                    Key::Named(NamedKey::Space) => Some(' '),
+                    _ => None,
+                };
+                let keysym = match &unmodified {
+                    Key::Named(key) => named_keysym(*key),
+                    _ => 0,
+                };
+                let text = event.text.as_deref().and_then(single_char);
+                let key_event = self.keyboard.key(
+                    timestamp,
+                    evdev_code,
+                    keysym,
+                    character,
+                    text,
+                    event.state == ElementState::Pressed,
+                );
+                Self::send_key_event(engine, key_event);
+            }
+            _ => {}
+        }
+
+        if !pointer_events.is_empty() {
+            engine.send_pointer_event(&pointer_events)?;
+        }
+        Ok(())
+    }
+
+    fn send_key_event(engine: &mut Engine, event: KeyEvent) {
+        if let Err(err) = engine.send_key_event(event, |_handled| {}) {
+            error!("failed to send key event: {err:?}");
+        }
+    }
+
+    /// Runs the platform tasks and times out the platform messages that are due, and returns how long until the next one.
+    fn run_tasks(&mut self) -> Option<Duration> {
+        let engine = self.engine.as_mut()?;
+        loop {
+            match self.tasks.run_due(engine) {
+                Ok(next) => return next,
+                Err(err) => error!("failed to run platform task: {err:?}"),
+            }
+        }
+    }
+}
+
+impl WinitHandler<UserEvent> for FlusterApp {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        if let Err(err) = self.start(event_loop) {
+            self.fail(event_loop, err);
+        }
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::Task(target_time, task) => self.tasks.push(target_time, task),
+            UserEvent::Vsync => {
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            }
+            UserEvent::Exit => self.exit(event_loop),
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        if event == WindowEvent::CloseRequested {
+            self.exit(event_loop);
+            return;
+        }
+        if let Err(err) = self.handle_window_event(event) {
+            self.fail(event_loop, err);
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let control_flow = match self.run_tasks() {
+            Some(wait) => ControlFlow::WaitUntil(Instant::now() + wait),
+            None => ControlFlow::Wait,
+        };
+        event_loop.set_control_flow(control_flow);
+    }
+
+    fn exiting(&mut self, _: &ActiveEventLoop) {
+        self.engine = None;
+    }
+}
+
+struct AppTaskRunner {
+    thread: ThreadId,
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+impl TaskRunnerHandler for AppTaskRunner {
+    fn runs_task_on_current_thread(&self) -> bool {
+        std::thread::current().id() == self.thread
+    }
+
+    fn post_task(&self, target_time: Duration, task: Task) {
+        if self
+            .proxy
+            .send_event(UserEvent::Task(target_time, task))
+            .is_err()
+        {
+            warn!("a platform task was posted after the event loop exited");
+        }
+    }
+}
+
+struct AppEngineHandler {
+    shell: Shell,
+    vsync: VsyncQueue,
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+impl EngineHandler for AppEngineHandler {
+    fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        channel: &CStr,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        self.shell.platform_message(cx, channel, message, response);
+    }
+
+    fn vsync(&mut self, baton: VsyncBaton) {
+        self.vsync.request(baton);
+        let _ = self.proxy.send_event(UserEvent::Vsync);
+    }
+
+    fn update_semantics(&mut self, _: &mut EngineContext, _: SemanticsUpdate) {}
+
+    fn log_message(&mut self, tag: &CStr, message: &CStr) {
+        info!("{}: {}", tag.to_string_lossy(), message.to_string_lossy());
+    }
+
+    fn on_pre_engine_restart(&mut self, _: &mut EngineContext) {}
+
+    fn channel_update(&mut self, _: &mut EngineContext, _: &CStr, _: bool) {}
+
+    fn view_focus_change_request(&mut self, _: &mut EngineContext, _: ViewFocusEvent) {}
+
+    fn root_isolate_created(&mut self) {}
+}
+
+struct WindowCursor(Arc<Window>);
+
+impl CursorHandler for WindowCursor {
+    fn set_cursor(&mut self, change: CursorChange) {
+        // the cursor names are the ones from the freedesktop cursor spec, which CSS (and so winit) uses too
+        match change.cursor.x_cursor_name() {
+            Some(name) => {
+                self.0
+                    .set_cursor(CursorIcon::from_str(name).unwrap_or_default());
+                self.0.set_cursor_visible(true);
+            }
+            None => self.0.set_cursor_visible(false),
+        }
+    }
+}
+
+struct WindowApplication {
+    window: Arc<Window>,
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+impl ApplicationHandler for WindowApplication {
+    fn exit_requested(&mut self) {
+        let _ = self.proxy.send_event(UserEvent::Exit);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    chars.next().filter(|_| chars.next().is_none())
+}
+
+/// The XKB keysym for the keys that [`crate::logical_key_from_keysym`] knows, since winit doesn't report keysyms.
+fn named_keysym(key: NamedKey) -> u32 {
+    match key {
+        NamedKey::Backspace => 0xff08,
+        NamedKey::Tab => 0xff09,
+        NamedKey::Enter => 0xff0d,
+        NamedKey::Escape => 0xff1b,
+        NamedKey::Delete => 0xffff,
+        NamedKey::CapsLock => 0xffe5,
+        NamedKey::NumLock => 0xff7f,
+        NamedKey::ScrollLock => 0xff14,
+        NamedKey::ArrowDown => 0xff54,
+        NamedKey::ArrowLeft => 0xff51,
+        NamedKey::ArrowRight => 0xff53,
+        NamedKey::ArrowUp => 0xff52,
+        NamedKey::End => 0xff57,
+        NamedKey::Home => 0xff50,
+        NamedKey::PageDown => 0xff56,
+        NamedKey::PageUp => 0xff55,
+        NamedKey::Insert => 0xff63,
+        NamedKey::ContextMenu => 0xff67,
+        NamedKey::Pause => 0xff13,
+        NamedKey::PrintScreen => 0xff61,
+        NamedKey::Control => 0xffe3,
+        NamedKey::Shift => 0xffe1,
+        NamedKey::Alt => 0xffe9,
+        NamedKey::AltGraph => 0xfe03,
+        NamedKey::Super | NamedKey::Meta => 0xffeb,
+        NamedKey::Space => 0x0020,
+        _ => match function_key(key) {
+            Some(n) => 0xffbe + n,
+            None => 0,
+        },
+    }
+}
+
+/// 0 for F1, up to 23 for F24.
+fn function_key(key: NamedKey) -> Option<u32> {
+    const KEYS: [NamedKey; 24] = [
+        NamedKey::F1,
+        NamedKey::F2,
+        NamedKey::F3,
+        NamedKey::F4,
+        NamedKey::F5,
+        NamedKey::F6,
+        NamedKey::F7,
+        NamedKey::F8,
+        NamedKey::F9,
+        NamedKey::F10,
+        NamedKey::F11,
+        NamedKey::F12,
+        NamedKey::F13,
+        NamedKey::F14,
+        NamedKey::F15,
+        NamedKey::F16,
+        NamedKey::F17,
+        NamedKey::F18,
+        NamedKey::F19,
+        NamedKey::F20,
+        NamedKey::F21,
+        NamedKey::F22,
+        NamedKey::F23,
+        NamedKey::F24,
+    ];
+    KEYS.iter()
+        .position(|&f| f == key)
+        .and_then(|n| u32::try_from(n).ok())
+}
@@ -0,0 +1,34 @@
+use crate::sys;
+
+/// Identifies the Flutter engine that this crate was built against.
+///
+/// This is captured at build time, from the engine build that `FLUTTER_ENGINE` (or `VOLITO_ENGINE_HASH`) pointed to.
+/// The engine doesn't expose any of this at runtime, so if a different `libflutter_engine.so` is loaded,
+/// this will not reflect that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EngineBuildInfo {
+    /// The embedder API version, i.e. `FLUTTER_ENGINE_VERSION` from `embedder.h`.
+    ///
+    /// This only changes on breaking changes to the embedder API, which have so far never happened.
+    pub embedder_api_version: usize,
+    /// The commit hash of the engine.
+    ///
+    /// This is [`None`] if the engine build directory was not in a git checkout.
+    pub commit: Option<&'static str>,
+    /// The engine build that was linked against, such as `host_debug_unopt` or `host_release`.
+    ///
+    /// This is [`None`] if no engine was linked at all (e.g. on docs.rs).
+    pub link_host: Option<&'static str>,
+}
+
+/// Returns information about the Flutter engine that this crate was built against.
+///
+/// Useful for logging, and for refusing to run against engine versions that are known to be broken.
+#[must_use]
+pub const fn engine_build_info() -> EngineBuildInfo {
+    EngineBuildInfo {
+        embedder_api_version: sys::FLUTTER_ENGINE_VERSION,
+        commit: option_env!("VOLITO_ENGINE_COMMIT"),
+        link_host: option_env!("VOLITO_ENGINE_LINK_HOST"),
+    }
+}
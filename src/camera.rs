@@ -0,0 +1,755 @@
+//! Shows PipeWire cameras in a `Texture` widget.
+//!
+//! [`Cameras`] keeps track of the cameras that PipeWire knows about, and captures from them into external textures.
+//! [`CameraChannel`] lets Dart list the cameras and start and stop capturing, on [`CAMERA_CHANNEL`]:
+//!
+//! ```ignore
+//! let cameras = Cameras::new(move |texture_id| {
+//!     // wake the platform thread, which calls `cameras.mark_frame_available(engine)`
+//! })?;
+//! router.register(CAMERA_CHANNEL, CameraChannel::new(cameras.clone(), 1000));
+//!
+//! // in OpenGLRendererHandler::gl_external_texture_frame, on the raster thread
+//! let frame = cameras.latest_frame(texture_id)?;
+//! // upload frame.pixels() with glTexImage2D(.., GL_RGBA, ..)
+//! ```
+//!
+//! On the Dart side, `availableCameras` returns a list of `{"id", "name", "description"}`,
+//! `create` takes a `{"cameraId"}` and returns a `{"textureId"}` to show in a `Texture` widget,
+//! and `dispose` takes that `{"textureId"}` to stop capturing.
+//!
+//! Cameras are asked for RGBA, BGRA, RGBx, BGRx or YUY2 in shared memory, and every frame is converted to RGBA.
+//! Cameras that only produce compressed formats (e.g. MJPG) need a converter in the PipeWire graph.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::CStr,
+    fmt,
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::JoinHandle,
+};
+
+use pipewire as pw;
+use pw::{
+    properties::properties,
+    spa::{
+        self,
+        param::{
+            format::{FormatProperties, MediaSubtype, MediaType},
+            video::{VideoFormat, VideoInfoRaw},
+            ParamType,
+        },
+        pod::{serialize::PodSerializer, Pod},
+    },
+    types::ObjectType,
+};
+use tracing::warn;
+
+use crate::{ChannelHandler, Engine, EngineContext, JsonValue, PlatformMessageResponse};
+
+/// The channel that [`CameraChannel`] handles. Messages use the `JSONMethodCodec`.
+pub const CAMERA_CHANNEL: &CStr = c"volito/camera";
+
+/// A camera, i.e. a PipeWire node of the `Video/Source` media class.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CameraDevice {
+    /// The node's ID, which is only valid for as long as the camera is plugged in.
+    pub id: u32,
+    /// The node's name, e.g. `v4l2_input.pci-0000_00_14.0-usb-0_6_1.0`.
+    pub name: String,
+    /// The node's human-readable description, e.g. `Integrated Camera (V4L2)`.
+    pub description: String,
+}
+
+#[derive(Debug)]
+pub enum CameraError {
+    PipeWire(pw::Error),
+    /// No camera with this node ID is plugged in.
+    UnknownDevice(u32),
+    /// The PipeWire thread has stopped.
+    Stopped,
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraError::PipeWire(err) => err.fmt(f),
+            CameraError::UnknownDevice(id) => write!(f, "there is no camera with the node ID {id}"),
+            CameraError::Stopped => write!(f, "the PipeWire thread has stopped"),
+        }
+    }
+}
+
+impl std::error::Error for CameraError {}
+
+impl From<pw::Error> for CameraError {
+    fn from(err: pw::Error) -> Self {
+        Self::PipeWire(err)
+    }
+}
+
+/// A captured frame, as tightly packed RGBA rows.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CameraFrame {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl fmt::Debug for CameraFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CameraFrame")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CameraFrame {
+    #[must_use]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[must_use]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// R, G, B, A bytes, `width * 4` bytes per row, ready to upload as a `width` by `height` texture.
+    #[must_use]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+/// The formats that cameras are asked for, in order of preference.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PixelFormat {
+    Rgba,
+    Bgra,
+    Rgbx,
+    Bgrx,
+    Yuy2,
+}
+
+impl PixelFormat {
+    const ALL: [Self; 5] = [Self::Rgba, Self::Bgra, Self::Rgbx, Self::Bgrx, Self::Yuy2];
+
+    fn spa(self) -> VideoFormat {
+        match self {
+            Self::Rgba => VideoFormat::RGBA,
+            Self::Bgra => VideoFormat::BGRA,
+            Self::Rgbx => VideoFormat::RGBx,
+            Self::Bgrx => VideoFormat::BGRx,
+            Self::Yuy2 => VideoFormat::YUY2,
+        }
+    }
+
+    fn from_spa(format: VideoFormat) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.spa() == format)
+    }
+
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Yuy2 => 2,
+            _ => 4,
+        }
+    }
+
+    /// Appends one row of `width` pixels in this format to `rgba`.
+    fn convert_row(self, row: &[u8], rgba: &mut Vec<u8>) {
+        match self {
+            Self::Rgba => rgba.extend_from_slice(row),
+            Self::Bgra => rgba.extend(row.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]])),
+            Self::Rgbx => rgba.extend(row.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2], 0xFF])),
+            Self::Bgrx => rgba.extend(row.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], 0xFF])),
+            // Y0 U Y1 V, two pixels sharing their chroma
+            Self::Yuy2 => {
+                for p in row.chunks_exact(4) {
+                    rgba.extend_from_slice(&yuv_to_rgba(p[0], p[1], p[3]));
+                    rgba.extend_from_slice(&yuv_to_rgba(p[2], p[1], p[3]));
+                }
+            }
+        }
+    }
+}
+
+/// BT.601, limited range, which is what webcams use.
+fn yuv_to_rgba(y: u8, u: u8, v: u8) -> [u8; 4] {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn clamp(x: i32) -> u8 {
+        (x >> 8).clamp(0, 255) as u8
+    }
+    let c = 298 * (i32::from(y) - 16) + 128;
+    let d = i32::from(u) - 128;
+    let e = i32::from(v) - 128;
+    [
+        clamp(c + 409 * e),
+        clamp(c - 100 * d - 208 * e),
+        clamp(c + 516 * d),
+        0xFF,
+    ]
+}
+
+/// The latest frame of one external texture.
+#[derive(Debug, Default)]
+struct Slot {
+    frame: Mutex<Option<Arc<CameraFrame>>>,
+    /// A frame arrived that the engine hasn't been told about yet.
+    pending: AtomicBool,
+}
+
+impl Slot {
+    fn frame(&self) -> MutexGuard<'_, Option<Arc<CameraFrame>>> {
+        self.frame.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+enum Command {
+    Start {
+        node: u32,
+        texture_id: i64,
+        slot: Arc<Slot>,
+    },
+    Stop(i64),
+    Quit,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    devices: Mutex<Vec<CameraDevice>>,
+    textures: Mutex<HashMap<i64, Arc<Slot>>>,
+}
+
+impl Shared {
+    fn devices(&self) -> MutexGuard<'_, Vec<CameraDevice>> {
+        self.devices.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn textures(&self) -> MutexGuard<'_, HashMap<i64, Arc<Slot>>> {
+        self.textures.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+struct Thread {
+    commands: pw::channel::Sender<Command>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Thread {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Quit);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Captures from PipeWire cameras into external textures, on a thread running a PipeWire main loop.
+///
+/// Cloning this gives another handle to the same cameras, e.g. for the renderer. The thread stops once every handle is dropped.
+#[derive(Clone)]
+pub struct Cameras {
+    shared: Arc<Shared>,
+    thread: Arc<Thread>,
+}
+
+impl fmt::Debug for Cameras {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cameras")
+            .field("devices", &*self.shared.devices())
+            .field("textures", &self.shared.textures().keys())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Cameras {
+    /// Connects to PipeWire, on a new thread.
+    ///
+    /// `frame_available` is called on that thread when a frame arrives and the engine hasn't been told yet.
+    /// It should get the platform thread to call [`Cameras::mark_frame_available`].
+    ///
+    /// # Panics
+    ///
+    /// If the thread can't be spawned.
+    pub fn new(frame_available: impl Fn(i64) + Send + Sync + 'static) -> Result<Self, CameraError> {
+        let shared = Arc::new(Shared::default());
+        let (commands, receiver) = pw::channel::channel();
+        let (started_sender, started) = mpsc::sync_channel(1);
+
+        let thread_shared = shared.clone();
+        let frame_available: Arc<dyn Fn(i64) + Send + Sync> = Arc::new(frame_available);
+        let thread = std::thread::Builder::new()
+            .name("pipewire-camera".to_owned())
+            .spawn(move || run(thread_shared, receiver, frame_available, &started_sender))
+            .expect("failed to spawn the PipeWire thread");
+
+        // the thread always reports back before running the main loop
+        started.recv().unwrap_or(Err(CameraError::Stopped))?;
+        Ok(Self {
+            shared,
+            thread: Arc::new(Thread {
+                commands,
+                thread: Some(thread),
+            }),
+        })
+    }
+
+    /// The cameras that are plugged in right now.
+    ///
+    /// PipeWire announces them asynchronously, so this is empty for a moment after [`Cameras::new`].
+    #[must_use]
+    pub fn devices(&self) -> Vec<CameraDevice> {
+        self.shared.devices().clone()
+    }
+
+    /// Captures from the camera with the node ID `device` into the external texture `texture_id`,
+    /// which must be registered with the engine. A texture that was already capturing is stopped first.
+    pub fn start(&self, device: u32, texture_id: i64) -> Result<(), CameraError> {
+        if !self.shared.devices().iter().any(|d| d.id == device) {
+            return Err(CameraError::UnknownDevice(device));
+        }
+        self.stop(texture_id)?;
+        let slot = Arc::new(Slot::default());
+        self.shared.textures().insert(texture_id, slot.clone());
+        self.send(Command::Start {
+            node: device,
+            texture_id,
+            slot,
+        })
+    }
+
+    /// Stops capturing into the external texture `texture_id`. Its last frame is forgotten.
+    pub fn stop(&self, texture_id: i64) -> Result<(), CameraError> {
+        if self.shared.textures().remove(&texture_id).is_none() {
+            return Ok(());
+        }
+        self.send(Command::Stop(texture_id))
+    }
+
+    fn send(&self, command: Command) -> Result<(), CameraError> {
+        self.thread
+            .commands
+            .send(command)
+            .map_err(|_| CameraError::Stopped)
+    }
+
+    /// Tells the engine about every texture with a frame it doesn't know about.
+    ///
+    /// This must be called on the platform thread.
+    pub fn mark_frame_available(&self, engine: &mut Engine) -> crate::Result<()> {
+        let pending = self
+            .shared
+            .textures()
+            .iter()
+            .filter(|(_, slot)| slot.pending.swap(false, Ordering::AcqRel))
+            .map(|(&texture_id, _)| texture_id)
+            .collect::<Vec<_>>();
+        for texture_id in pending {
+            engine.mark_external_texture_frame_available(texture_id)?;
+        }
+        Ok(())
+    }
+
+    /// The most recent frame of the external texture `texture_id`, for the renderer's external texture callback.
+    #[must_use]
+    pub fn latest_frame(&self, texture_id: i64) -> Option<Arc<CameraFrame>> {
+        self.shared.textures().get(&texture_id)?.frame().clone()
+    }
+}
+
+/// Handles [`CAMERA_CHANNEL`], registering an external texture for every camera that Dart captures from.
+/// Register it in a [`crate::ChannelRouter`].
+#[derive(Debug)]
+pub struct CameraChannel {
+    cameras: Cameras,
+    next_texture_id: i64,
+}
+
+impl CameraChannel {
+    /// Textures are registered with IDs counting up from `first_texture_id`,
+    /// which must not clash with the application's other external textures.
+    #[must_use]
+    pub fn new(cameras: Cameras, first_texture_id: i64) -> Self {
+        Self {
+            cameras,
+            next_texture_id: first_texture_id,
+        }
+    }
+
+    #[must_use]
+    pub fn cameras(&self) -> &Cameras {
+        &self.cameras
+    }
+
+    /// The response to a method call, in the `JSONMethodCodec`.
+    fn handle(&mut self, cx: &mut EngineContext, message: &[u8]) -> String {
+        let Some(call) = std::str::from_utf8(message).ok().and_then(JsonValue::parse) else {
+            return r#"["malformed","malformed message",null]"#.to_owned();
+        };
+        let args = call.get("args");
+        let arg = |key| {
+            args.and_then(|args| args.get(key))
+                .and_then(JsonValue::as_i64)
+        };
+
+        match call.get("method").and_then(JsonValue::as_str) {
+            Some("availableCameras") => {
+                let devices = self
+                    .cameras
+                    .devices()
+                    .into_iter()
+                    .map(|device| {
+                        JsonValue::Object(vec![
+                            ("id".into(), i64::from(device.id).into()),
+                            ("name".into(), device.name.into()),
+                            ("description".into(), device.description.into()),
+                        ])
+                    })
+                    .collect();
+                JsonValue::Array(vec![JsonValue::Array(devices)]).to_string()
+            }
+            Some("create") => {
+                let Some(device) = arg("cameraId").and_then(|id| u32::try_from(id).ok()) else {
+                    return r#"["malformed","missing cameraId",null]"#.to_owned();
+                };
+                let texture_id = self.next_texture_id;
+                if let Err(err) = cx.register_external_texture(texture_id) {
+                    return error_reply(&err);
+                }
+                if let Err(err) = self.cameras.start(device, texture_id) {
+                    if let Err(err) = cx.unregister_external_texture(texture_id) {
+                        warn!("failed to unregister camera texture {texture_id}: {err}");
+                    }
+                    return error_reply(&err);
+                }
+                self.next_texture_id += 1;
+                JsonValue::Array(vec![JsonValue::Object(vec![(
+                    "textureId".into(),
+                    texture_id.into(),
+                )])])
+                .to_string()
+            }
+            Some("dispose") => {
+                let Some(texture_id) = arg("textureId") else {
+                    return r#"["malformed","missing textureId",null]"#.to_owned();
+                };
+                if let Err(err) = self.cameras.stop(texture_id) {
+                    warn!("failed to stop camera texture {texture_id}: {err}");
+                }
+                if let Err(err) = cx.unregister_external_texture(texture_id) {
+                    warn!("failed to unregister camera texture {texture_id}: {err}");
+                }
+                "[null]".to_owned()
+            }
+            // not implemented
+            _ => String::new(),
+        }
+    }
+}
+
+fn error_reply(err: &dyn fmt::Display) -> String {
+    JsonValue::Array(vec![
+        "error".into(),
+        err.to_string().into(),
+        JsonValue::Null,
+    ])
+    .to_string()
+}
+
+impl ChannelHandler for CameraChannel {
+    fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        let reply = self.handle(cx, message);
+        if let Err(err) = response.send(reply.as_bytes()) {
+            warn!("failed to respond to a camera message: {err}");
+        }
+    }
+}
+
+/// The state of one stream, on the PipeWire thread.
+struct StreamData {
+    texture_id: i64,
+    slot: Arc<Slot>,
+    format: Option<(PixelFormat, u32, u32)>,
+    frame_available: Arc<dyn Fn(i64) + Send + Sync>,
+}
+
+struct ActiveStream {
+    // the listener must be removed before the stream is destroyed
+    _listener: pw::stream::StreamListener<StreamData>,
+    stream: pw::stream::Stream,
+}
+
+fn run(
+    shared: Arc<Shared>,
+    commands: pw::channel::Receiver<Command>,
+    frame_available: Arc<dyn Fn(i64) + Send + Sync>,
+    started: &mpsc::SyncSender<Result<(), CameraError>>,
+) {
+    pw::init();
+    let (mainloop, _context, core, registry) = match connect() {
+        Ok(connected) => connected,
+        Err(err) => {
+            let _ = started.send(Err(err.into()));
+            return;
+        }
+    };
+
+    let added = shared.clone();
+    let _registry_listener = registry
+        .add_listener_local()
+        .global(move |global| {
+            if let Some(device) = camera_device(global) {
+                added.devices().push(device);
+            }
+        })
+        .global_remove(move |id| shared.devices().retain(|device| device.id != id))
+        .register();
+
+    let streams = RefCell::new(HashMap::new());
+    let weak_mainloop = mainloop.downgrade();
+    let _commands = commands.attach(mainloop.loop_(), move |command| match command {
+        Command::Start {
+            node,
+            texture_id,
+            slot,
+        } => {
+            let data = StreamData {
+                texture_id,
+                slot,
+                format: None,
+                frame_available: frame_available.clone(),
+            };
+            match start_stream(&core, node, data) {
+                Ok(stream) => {
+                    streams.borrow_mut().insert(texture_id, stream);
+                }
+                Err(err) => warn!("failed to start capturing from camera {node}: {err}"),
+            }
+        }
+        Command::Stop(texture_id) => {
+            if let Some(active) = streams.borrow_mut().remove(&texture_id) {
+                if let Err(err) = active.stream.disconnect() {
+                    warn!("failed to stop capturing into texture {texture_id}: {err}");
+                }
+            }
+        }
+        Command::Quit => {
+            if let Some(mainloop) = weak_mainloop.upgrade() {
+                mainloop.quit();
+            }
+        }
+    });
+
+    let _ = started.send(Ok(()));
+    mainloop.run();
+}
+
+#[allow(clippy::type_complexity)]
+fn connect() -> Result<
+    (
+        pw::main_loop::MainLoop,
+        pw::context::Context,
+        pw::core::Core,
+        pw::registry::Registry,
+    ),
+    pw::Error,
+> {
+    let mainloop = pw::main_loop::MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+    let registry = core.get_registry()?;
+    Ok((mainloop, context, core, registry))
+}
+
+fn camera_device(
+    global: &pw::registry::GlobalObject<&spa::utils::dict::DictRef>,
+) -> Option<CameraDevice> {
+    if !matches!(global.type_, ObjectType::Node) {
+        return None;
+    }
+    let props = global.props?;
+    if props.get(*pw::keys::MEDIA_CLASS) != Some("Video/Source") {
+        return None;
+    }
+    let name = props
+        .get(*pw::keys::NODE_NAME)
+        .unwrap_or_default()
+        .to_owned();
+    let description = props
+        .get(*pw::keys::NODE_DESCRIPTION)
+        .or_else(|| props.get(*pw::keys::NODE_NICK))
+        .unwrap_or(name.as_str())
+        .to_owned();
+    Some(CameraDevice {
+        id: global.id,
+        name,
+        description,
+    })
+}
+
+fn start_stream(
+    core: &pw::core::Core,
+    node: u32,
+    data: StreamData,
+) -> Result<ActiveStream, pw::Error> {
+    let stream = pw::stream::Stream::new(
+        core,
+        "volito-camera",
+        properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Camera",
+        },
+    )?;
+
+    let listener = stream
+        .add_local_listener_with_user_data(data)
+        .param_changed(|_, data, id, param| {
+            if id == ParamType::Format.as_raw() {
+                data.format = param.and_then(negotiated_format);
+            }
+        })
+        .process(|stream, data| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let Some(format) = data.format else {
+                return;
+            };
+            let Some(frame) = buffer
+                .datas_mut()
+                .first_mut()
+                .and_then(|buffer_data| read_frame(buffer_data, format))
+            else {
+                return;
+            };
+            *data.slot.frame() = Some(Arc::new(frame));
+            if !data.slot.pending.swap(true, Ordering::AcqRel) {
+                (data.frame_available)(data.texture_id);
+            }
+        })
+        .register()?;
+
+    let format = format_pod();
+    let mut params = [Pod::from_bytes(&format).expect("serialized as a pod")];
+    stream.connect(
+        spa::utils::Direction::Input,
+        Some(node),
+        pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+    Ok(ActiveStream {
+        _listener: listener,
+        stream,
+    })
+}
+
+/// The `EnumFormat` param that streams connect with.
+fn format_pod() -> Vec<u8> {
+    let object = spa::pod::object!(
+        spa::utils::SpaTypes::ObjectParamFormat,
+        ParamType::EnumFormat,
+        spa::pod::property!(FormatProperties::MediaType, Id, MediaType::Video),
+        spa::pod::property!(FormatProperties::MediaSubtype, Id, MediaSubtype::Raw),
+        spa::pod::property!(
+            FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            VideoFormat::RGBA,
+            VideoFormat::RGBA,
+            VideoFormat::BGRA,
+            VideoFormat::RGBx,
+            VideoFormat::BGRx,
+            VideoFormat::YUY2,
+        ),
+        spa::pod::property!(
+            FormatProperties::VideoSize,
+            Choice,
+            Range,
+            Rectangle,
+            spa::utils::Rectangle {
+                width: 1280,
+                height: 720
+            },
+            spa::utils::Rectangle {
+                width: 1,
+                height: 1
+            },
+            spa::utils::Rectangle {
+                width: 8192,
+                height: 8192
+            }
+        ),
+        spa::pod::property!(
+            FormatProperties::VideoFramerate,
+            Choice,
+            Range,
+            Fraction,
+            spa::utils::Fraction { num: 30, denom: 1 },
+            spa::utils::Fraction { num: 0, denom: 1 },
+            spa::utils::Fraction {
+                num: 1000,
+                denom: 1
+            }
+        ),
+    );
+    PodSerializer::serialize(Cursor::new(Vec::new()), &spa::pod::Value::Object(object))
+        .expect("serializing into memory can't fail")
+        .0
+        .into_inner()
+}
+
+fn negotiated_format(param: &Pod) -> Option<(PixelFormat, u32, u32)> {
+    let (media_type, media_subtype) = spa::param::format_utils::parse_format(param).ok()?;
+    if media_type != MediaType::Video || media_subtype != MediaSubtype::Raw {
+        warn!("camera negotiated a format that isn't raw video");
+        return None;
+    }
+    let mut info = VideoInfoRaw::default();
+    info.parse(param).ok()?;
+    let Some(format) = PixelFormat::from_spa(info.format()) else {
+        warn!("camera negotiated unexpected format {:?}", info.format());
+        return None;
+    };
+    Some((format, info.size().width, info.size().height))
+}
+
+fn read_frame(
+    data: &mut spa::buffer::Data,
+    (format, width, height): (PixelFormat, u32, u32),
+) -> Option<CameraFrame> {
+    let chunk = data.chunk();
+    let offset = chunk.offset() as usize;
+    let size = chunk.size() as usize;
+    let row = width as usize * format.bytes_per_pixel();
+    // some drivers leave the stride unset for tightly packed frames
+    let stride = usize::try_from(chunk.stride())
+        .ok()
+        .filter(|&stride| stride >= row)
+        .unwrap_or(row);
+
+    let bytes = data.data()?.get(offset..offset + size)?;
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height as usize {
+        let start = y * stride;
+        format.convert_row(bytes.get(start..start + row)?, &mut pixels);
+    }
+    Some(CameraFrame {
+        width,
+        height,
+        pixels,
+    })
+}
@@ -0,0 +1,93 @@
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    fmt,
+};
+
+use tracing::warn;
+
+use crate::{EngineContext, PlatformMessageResponse};
+
+/// Handles the messages on one platform channel, for a [`ChannelRouter`].
+pub trait ChannelHandler {
+    /// Like [`crate::EngineHandler::platform_message`], the response must always be sent.
+    fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    );
+}
+
+impl<F: FnMut(&mut EngineContext, &[u8], PlatformMessageResponse)> ChannelHandler for F {
+    fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        self(cx, message, response);
+    }
+}
+
+/// Sends each platform message to the handler registered for its channel.
+///
+/// Call [`ChannelRouter::platform_message`] from [`crate::EngineHandler::platform_message`].
+#[derive(Default)]
+pub struct ChannelRouter {
+    handlers: HashMap<CString, Box<dyn ChannelHandler>>,
+}
+
+impl fmt::Debug for ChannelRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChannelRouter")
+            .field("channels", &self.handlers.keys())
+            .finish()
+    }
+}
+
+impl ChannelRouter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles `channel` with `handler`, returning the handler it had before, if any.
+    pub fn register(
+        &mut self,
+        channel: &CStr,
+        handler: impl ChannelHandler + 'static,
+    ) -> Option<Box<dyn ChannelHandler>> {
+        self.handlers.insert(channel.to_owned(), Box::new(handler))
+    }
+
+    /// Stops handling `channel`, returning its handler.
+    pub fn unregister(&mut self, channel: &CStr) -> Option<Box<dyn ChannelHandler>> {
+        self.handlers.remove(channel)
+    }
+
+    #[must_use]
+    pub fn handles(&self, channel: &CStr) -> bool {
+        self.handlers.contains_key(channel)
+    }
+
+    /// Sends a message to the handler for its channel.
+    ///
+    /// Messages on channels without a handler get an empty response, which the framework takes to mean "not implemented".
+    pub fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        channel: &CStr,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        match self.handlers.get_mut(channel) {
+            Some(handler) => handler.platform_message(cx, message, response),
+            None => {
+                if let Err(err) = response.send(&[]) {
+                    warn!("failed to respond to a message on {channel:?}: {err}");
+                }
+            }
+        }
+    }
+}
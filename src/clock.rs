@@ -0,0 +1,270 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::Engine;
+
+/// A source of timestamps in the engine's time base, like [`Engine::get_current_time`].
+///
+/// This is what frame timestamps are taken from, which is what the framework uses to drive animations.
+/// The event loops of [`Engine::spawn_with_clock`] and [`crate::HeadlessEngine`] also measure platform task delays with it,
+/// so that timers in the app fire when the clock says so.
+/// Swap it for a [`VirtualClock`] to control time in tests.
+///
+/// A platform task still never runs before its target time by [`Engine::get_current_time`],
+/// because running it early is undefined behavior; a virtual clock can only hold tasks back, not hurry them.
+/// Platform message timeouts (see [`Engine::expire_platform_messages`]) always use the engine's clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Duration;
+}
+
+/// The engine's own clock, i.e. the system monotonic clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EngineClock;
+
+impl Clock for EngineClock {
+    fn now(&self) -> Duration {
+        Engine::get_current_time()
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Cloning this gives another handle to the same clock.
+#[derive(Debug, Clone)]
+pub struct VirtualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)] // 584 years of nanoseconds
+    pub fn new(start: Duration) -> Self {
+        Self {
+            nanos: Arc::new(AtomicU64::new(start.as_nanos() as u64)),
+        }
+    }
+
+    /// A clock that starts at the current time of the [`EngineClock`].
+    #[must_use]
+    pub fn starting_now() -> Self {
+        Self::new(Engine::get_current_time())
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn advance(&self, by: Duration) {
+        self.nanos.fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn set(&self, now: Duration) {
+        self.nanos.store(now.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+}
+
+/// Converts the timestamps of input events into the engine's time base, for [`crate::PointerEvent::timestamp`]
+/// and [`crate::KeyEvent::timestamp`].
+///
+/// The framework computes velocities (e.g. of a fling) from the time between events, so events should carry the time
+/// they actually happened at, rather than when they were handled. But every platform has its own clock:
+/// evdev uses `CLOCK_REALTIME` by default, Wayland uses milliseconds with an unspecified base, and so on.
+///
+/// The offset between a platform clock and the engine's is estimated from the events themselves, since an event
+/// can't be handled before it happened: the smallest `now - timestamp` seen so far is the best estimate.
+/// To follow a platform clock that drifts, the estimate is allowed to grow by up to 1ms per second,
+/// and a platform clock that jumps backwards by more than a second (e.g. `CLOCK_REALTIME` being set) starts over.
+///
+/// Converted timestamps never go backwards, and are never later than the time they're converted at.
+/// Use one of these per platform clock.
+pub struct Timestamps {
+    clock: Box<dyn Clock>,
+    /// Engine time minus platform time, in nanoseconds, and when it was last estimated.
+    offset: Option<(i128, Duration)>,
+    last: Duration,
+    /// The last Wayland timestamp, and how many times it has wrapped around.
+    wayland: Option<(u32, u64)>,
+}
+
+impl fmt::Debug for Timestamps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timestamps")
+            .field("offset", &self.offset)
+            .field("last", &self.last)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Timestamps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timestamps {
+    /// How much the offset between the clocks may grow per second, in parts per million.
+    const MAX_DRIFT_PPM: i128 = 1000;
+    /// An event that seems to have been handled this much later than the others means that the platform clock jumped.
+    const MAX_LATENCY: i128 = 1_000_000_000;
+
+    /// Converts into the time base of the [`EngineClock`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_clock(EngineClock)
+    }
+
+    /// Converts into the time base of `clock`, e.g. a [`VirtualClock`] in tests.
+    #[must_use]
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: Box::new(clock),
+            offset: None,
+            last: Duration::ZERO,
+            wayland: None,
+        }
+    }
+
+    /// Converts a timestamp from any platform clock, as the time since its (arbitrary) epoch.
+    pub fn convert(&mut self, timestamp: Duration) -> Duration {
+        let now = self.clock.now();
+        let sample = now.as_nanos().cast_signed() - timestamp.as_nanos().cast_signed();
+        let offset = match self.offset {
+            Some((offset, _)) if sample - offset > Self::MAX_LATENCY => sample,
+            Some((offset, estimated_at)) => {
+                let elapsed = now.saturating_sub(estimated_at).as_nanos().cast_signed();
+                sample.min(offset + elapsed * Self::MAX_DRIFT_PPM / 1_000_000)
+            }
+            None => sample,
+        };
+        self.offset = Some((offset, now));
+
+        let converted = timestamp.as_nanos().cast_signed() + offset;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // clamped
+        let converted = Duration::from_nanos(converted.clamp(0, i128::from(u64::MAX)) as u64);
+        self.last = converted.max(self.last);
+        self.last
+    }
+
+    /// Converts the `time` of a Wayland input event, in milliseconds, which wraps around every 49 days.
+    pub fn wayland(&mut self, time: u32) -> Duration {
+        let (last, wraps) = self.wayland.unwrap_or((time, 0));
+        let wraps = if time < last && last - time > u32::MAX / 2 {
+            wraps + 1
+        } else {
+            wraps
+        };
+        self.wayland = Some((time, wraps));
+        self.convert(Duration::from_millis(wraps << 32 | u64::from(time)))
+    }
+
+    /// Converts the `time` of an evdev `input_event`, i.e. `tv_sec` and `tv_usec`. It's in `CLOCK_REALTIME`
+    /// unless the device was switched to another clock with `EVIOCSCLOCKID`.
+    pub fn evdev(&mut self, seconds: i64, micros: i64) -> Duration {
+        let timestamp = Duration::from_secs(u64::try_from(seconds).unwrap_or(0))
+            + Duration::from_micros(u64::try_from(micros).unwrap_or(0));
+        self.convert(timestamp)
+    }
+
+    /// Converts an [`Instant`], e.g. from winit's `StartCause` or a timestamp taken when the event was read.
+    ///
+    /// This doesn't need an estimate, since the current time is known in both clocks.
+    pub fn instant(&mut self, instant: Instant) -> Duration {
+        let age = Instant::now().saturating_duration_since(instant);
+        let converted = self.clock.now().saturating_sub(age);
+        self.last = converted.max(self.last);
+        self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn keeps_the_time_between_events() {
+        let clock = VirtualClock::new(secs(100.0));
+        let mut timestamps = Timestamps::with_clock(clock.clone());
+        assert_eq!(timestamps.convert(secs(5.0)), secs(100.0));
+
+        // handled 100ms after it happened, which is slower than the first one was;
+        // only the 1.1ms the clocks may have drifted apart by in the meantime is added
+        clock.advance(secs(1.1));
+        assert_eq!(timestamps.convert(secs(6.0)), secs(101.0011));
+    }
+
+    #[test]
+    fn follows_a_drifting_clock_slowly() {
+        let clock = VirtualClock::new(secs(100.0));
+        let mut timestamps = Timestamps::with_clock(clock.clone());
+        timestamps.convert(secs(5.0));
+
+        // the platform clock lost 10ms in a second, but the offset may only grow by 1ms per second
+        clock.advance(secs(1.0));
+        assert_eq!(timestamps.convert(secs(5.99)), secs(100.991));
+
+        // an event that was handled sooner lowers the estimate right away
+        assert_eq!(timestamps.convert(secs(6.0)), secs(101.0));
+    }
+
+    #[test]
+    fn starts_over_when_the_clock_jumps_back() {
+        let clock = VirtualClock::new(secs(100.0));
+        let mut timestamps = Timestamps::with_clock(clock.clone());
+        timestamps.convert(secs(5.0));
+
+        clock.advance(secs(1.0));
+        assert_eq!(timestamps.convert(secs(1.0)), secs(101.0));
+        clock.advance(secs(0.5));
+        assert_eq!(timestamps.convert(secs(1.5)), secs(101.5));
+    }
+
+    #[test]
+    fn never_goes_backwards_or_ahead() {
+        let clock = VirtualClock::new(secs(100.0));
+        let mut timestamps = Timestamps::with_clock(clock.clone());
+        timestamps.convert(secs(5.0));
+        clock.advance(secs(1.0));
+        assert_eq!(timestamps.convert(secs(6.0)), secs(101.0));
+
+        // out of order
+        assert_eq!(timestamps.convert(secs(5.5)), secs(101.0));
+        // from the future
+        assert_eq!(timestamps.convert(secs(7.0)), secs(101.0));
+    }
+
+    #[test]
+    fn wayland_wraps_around() {
+        let clock = VirtualClock::new(secs(100.0));
+        let mut timestamps = Timestamps::with_clock(clock.clone());
+        assert_eq!(timestamps.wayland(u32::MAX - 9), secs(100.0));
+
+        clock.advance(secs(0.02));
+        assert_eq!(timestamps.wayland(10), secs(100.02));
+        clock.advance(secs(0.01));
+        assert_eq!(timestamps.wayland(20), secs(100.03));
+    }
+
+    #[test]
+    fn evdev_timestamps() {
+        let clock = VirtualClock::new(secs(100.0));
+        let mut timestamps = Timestamps::with_clock(clock.clone());
+        assert_eq!(timestamps.evdev(1_700_000_000, 0), secs(100.0));
+
+        clock.advance(secs(0.25));
+        assert_eq!(timestamps.evdev(1_700_000_000, 250_000), secs(100.25));
+    }
+}
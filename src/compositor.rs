@@ -1,10 +1,21 @@
+use std::{
+    fmt,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use tracing::warn;
+
 use crate::{
-    sys, BackingStore, BackingStoreConfig, PlatformViewMutation, Point, Region, Size, ViewId,
+    sys, BackingStore, BackingStoreConfig, PlatformViewMutation, Point, Rect, Region, Size, ViewId,
 };
 
 pub trait CompositorHandler: Send + Sync {
     /// A callback invoked by the engine to obtain a backing store for a specific
     /// `FlutterLayer`.
+    ///
+    /// For antialiased output, an OpenGL backing store can be a framebuffer with a multisampled color attachment.
+    /// The engine renders into it like any other; the handler resolves it (e.g. with `glBlitFramebuffer`) in
+    /// [`CompositorHandler::present_view`]. Vulkan images must have one sample, as the engine wraps them as such.
     fn create_backing_store(&mut self, config: BackingStoreConfig) -> Option<BackingStore>;
 
     /// A callback invoked by the engine to release the backing store. The
@@ -18,6 +29,21 @@ pub trait CompositorHandler: Send + Sync {
     ///
     /// The callback should return true if the operation was successful.
     fn present_view(&mut self, view_id: ViewId, layers: &[Layer]) -> bool;
+
+    /// The engine confirmed adding a view with [`crate::Engine::add_view`].
+    /// Per-view resources (e.g. a swapchain) can be created here,
+    /// though the first [`CompositorHandler::present_view`] for the view may race with this.
+    ///
+    /// Only views added after the engine started are reported; the implicit view never is.
+    fn view_added(&mut self, view_id: ViewId) {
+        let _ = view_id;
+    }
+
+    /// The engine confirmed removing a view with [`crate::Engine::remove_view`], and won't present anything to it again.
+    /// Per-view resources can be destroyed here.
+    fn view_removed(&mut self, view_id: ViewId) {
+        let _ = view_id;
+    }
 }
 
 pub struct Compositor {
@@ -27,10 +53,99 @@ pub struct Compositor {
     pub handler: Box<dyn CompositorHandler>,
 }
 
+impl fmt::Debug for Compositor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Compositor")
+            .field("avoid_backing_store_cache", &self.avoid_backing_store_cache)
+            .finish_non_exhaustive()
+    }
+}
+
 pub(crate) struct CompositorUserData {
+    // the view callbacks may come from another thread than the compositor callbacks
+    state: Mutex<CompositorState>,
+}
+
+struct CompositorState {
     handler: Box<dyn CompositorHandler>,
+    buffers: LayerBuffers,
+}
+
+impl CompositorUserData {
+    fn state(&self) -> MutexGuard<'_, CompositorState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The compositor of an engine, for the callbacks of [`crate::Engine::add_view`] and [`crate::Engine::remove_view`].
+#[cfg(embedder_add_view)]
+#[derive(Clone, Copy)]
+pub(crate) struct CompositorRef(*const CompositorUserData);
+
+// SAFETY: the user data is only used through its mutex
+#[cfg(embedder_add_view)]
+unsafe impl Send for CompositorRef {}
+
+#[cfg(embedder_add_view)]
+impl CompositorRef {
+    pub(crate) fn new(user_data: *const CompositorUserData) -> Self {
+        Self(user_data)
+    }
+
+    /// # Safety
+    ///
+    /// The engine that the compositor belongs to must not have been dropped.
+    pub(crate) unsafe fn view_added(self, view_id: ViewId) {
+        unsafe { &*self.0 }.state().handler.view_added(view_id);
+    }
+
+    /// # Safety
+    ///
+    /// The engine that the compositor belongs to must not have been dropped.
+    pub(crate) unsafe fn view_removed(self, view_id: ViewId) {
+        unsafe { &*self.0 }.state().handler.view_removed(view_id);
+    }
+}
+
+/// Allocations kept around between frames, so that once they've grown large enough,
+/// converting the layers of a frame doesn't allocate.
+#[derive(Default)]
+struct LayerBuffers {
+    layers: Vec<Layer>,
+    paint_regions: Vec<Vec<Rect<f64>>>,
+    mutations: Vec<Vec<PlatformViewMutation>>,
+}
+
+impl LayerBuffers {
+    fn fill(&mut self, raw: &[*const sys::FlutterLayer]) {
+        for &raw in raw {
+            // layers this crate can't make sense of are left out of the frame, rather than failing all of it
+            if let Some(layer) = Layer::from_raw_in(unsafe { &*raw }, self) {
+                self.layers.push(layer);
+            }
+        }
+    }
+
+    /// Takes apart the layers of a frame, keeping their allocations for the next one.
+    fn recycle(&mut self) {
+        for layer in self.layers.drain(..) {
+            match layer.content {
+                LayerContent::BackingStore(_, present_info) => {
+                    let mut rects = present_info.paint_region.regions;
+                    rects.clear();
+                    self.paint_regions.push(rects);
+                }
+                LayerContent::PlatformView(platform_view) => {
+                    let mut mutations = platform_view.mutations;
+                    mutations.clear();
+                    self.mutations.push(mutations);
+                }
+            }
+        }
+    }
 }
 
+#[derive(Debug)]
 pub struct Layer {
     /// The offset of this layer (in physical pixels) relative to the top left of
     /// the root surface used by the engine.
@@ -45,6 +160,7 @@ pub struct Layer {
     /// See [`crate::Engine::get_current_time`].
     pub presentation_time: u64,
 }
+#[derive(Debug)]
 pub enum LayerContent {
     /// Indicates that the contents of this layer are rendered by Flutter into a backing store.
     BackingStore(BackingStore, BackingStorePresentInfo),
@@ -52,9 +168,25 @@ pub enum LayerContent {
     PlatformView(PlatformView),
 }
 impl Layer {
+    /// `None` (with a warning) for a layer whose content isn't known to this crate.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be valid like when the engine passes it to a compositor:
+    /// every pointer in it must be valid, and its union must hold the variant its type says.
+    ///
+    /// # Panics
+    ///
+    /// Like [`BackingStore::from_raw`].
+    #[cfg(feature = "sys")]
     #[must_use]
-    pub(crate) fn from_raw(raw: &sys::FlutterLayer) -> Self {
-        Self {
+    pub unsafe fn from_raw(raw: &sys::FlutterLayer) -> Option<Self> {
+        Self::from_raw_in(raw, &mut LayerBuffers::default())
+    }
+
+    /// `None` (with a warning) for a layer whose content isn't known to this crate.
+    fn from_raw_in(raw: &sys::FlutterLayer, buffers: &mut LayerBuffers) -> Option<Self> {
+        Some(Self {
             offset: raw.offset.into(),
             size: raw.size.into(),
             content: match raw.type_ {
@@ -65,22 +197,32 @@ impl Layer {
                     // and it's null when not. So, this is safe.
                     let backing_store_present_info = unsafe { &*raw.backing_store_present_info };
                     LayerContent::BackingStore(
-                        BackingStore::from_raw(backing_store),
-                        BackingStorePresentInfo::from_raw(backing_store_present_info),
+                        unsafe { BackingStore::from_raw(backing_store) }?,
+                        BackingStorePresentInfo::from_raw_in(
+                            backing_store_present_info,
+                            buffers.paint_regions.pop().unwrap_or_default(),
+                        ),
                     )
                 }
                 sys::FlutterLayerContentType::PlatformView => {
                     // SAFETY: checked the discriminant above
                     let platform_view = unsafe { &*raw.__bindgen_anon_1.platform_view };
-                    LayerContent::PlatformView(PlatformView::from_raw(platform_view))
+                    LayerContent::PlatformView(PlatformView::from_raw_in(
+                        platform_view,
+                        buffers.mutations.pop().unwrap_or_default(),
+                    ))
+                }
+                unknown => {
+                    warn!("unsupported {unknown:?}; leaving the layer out of the frame");
+                    return None;
                 }
-                _ => unreachable!("Unknown FlutterLayerContentType; cannot construct a Layer. That enum shouldn't ever be extended; this is probably a bug in the Flutter engine."),
             },
             presentation_time: raw.presentation_time,
-        }
+        })
     }
 }
 
+#[derive(Debug)]
 pub struct BackingStorePresentInfo {
     // The area of the backing store that contains Flutter contents.
     // Pixels outside of this area are transparent and the embedder may choose not to render them.
@@ -89,13 +231,24 @@ pub struct BackingStorePresentInfo {
 }
 
 impl BackingStorePresentInfo {
-    pub(crate) fn from_raw(raw: &sys::FlutterBackingStorePresentInfo) -> Self {
+    /// # Safety
+    ///
+    /// `raw` must be valid like when the engine passes it: every pointer in it must be valid.
+    #[cfg(feature = "sys")]
+    #[must_use]
+    pub unsafe fn from_raw(raw: &sys::FlutterBackingStorePresentInfo) -> Self {
+        Self::from_raw_in(raw, Vec::new())
+    }
+
+    /// `rects` is reused for the paint region; it must be empty.
+    fn from_raw_in(raw: &sys::FlutterBackingStorePresentInfo, rects: Vec<Rect<f64>>) -> Self {
         Self {
-            paint_region: Region::from_raw(unsafe { &*raw.paint_region }),
+            paint_region: Region::from_raw_in(unsafe { &*raw.paint_region }, rects),
         }
     }
 }
 
+#[derive(Debug)]
 pub struct PlatformView {
     /// The identifier of this platform view. This identifier is specified by the
     /// application when a platform view is added to the scene via the
@@ -117,10 +270,25 @@ pub struct PlatformView {
 }
 
 impl PlatformView {
-    pub(crate) fn from_raw(raw: &sys::FlutterPlatformView) -> Self {
-        Self {
-            identifier: raw.identifier,
-            mutations: unsafe {
+    /// Mutations that aren't known to this crate are left out, with a warning.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+    /// and the union of every mutation must hold the variant its type says.
+    #[cfg(feature = "sys")]
+    #[must_use]
+    pub unsafe fn from_raw(raw: &sys::FlutterPlatformView) -> Self {
+        Self::from_raw_in(raw, Vec::new())
+    }
+
+    /// `mutations` is reused for the mutations; it must be empty.
+    fn from_raw_in(
+        raw: &sys::FlutterPlatformView,
+        mut mutations: Vec<PlatformViewMutation>,
+    ) -> Self {
+        mutations.extend(
+            unsafe {
                 crate::util::slice_from_raw_parts_with_invalid_empty(
                     raw.mutations,
                     raw.mutations_count,
@@ -129,8 +297,17 @@ impl PlatformView {
             .iter()
             .copied()
             .map(|raw| unsafe { *raw })
-            .map(PlatformViewMutation::from)
-            .collect(),
+            .filter_map(|raw| match PlatformViewMutation::try_from(raw) {
+                Ok(mutation) => Some(mutation),
+                Err(err) => {
+                    warn!("{err}; ignoring the mutation");
+                    None
+                }
+            }),
+        );
+        Self {
+            identifier: raw.identifier,
+            mutations,
         }
     }
 }
@@ -139,6 +316,7 @@ mod callbacks {
 
     use super::*;
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn create_backing_store(
         backing_store_config: *const sys::FlutterBackingStoreConfig,
         backing_store_out: *mut sys::FlutterBackingStore,
@@ -146,48 +324,65 @@ mod callbacks {
     ) -> bool {
         let user_data = user_data.cast::<CompositorUserData>();
 
-        let user_data = unsafe { &mut *user_data };
+        let user_data = unsafe { &*user_data };
 
         let backing_store_config = BackingStoreConfig::from(unsafe { *backing_store_config });
 
-        let backing_store = user_data.handler.create_backing_store(backing_store_config);
+        let backing_store = user_data
+            .state()
+            .handler
+            .create_backing_store(backing_store_config);
 
         unsafe { crate::util::return_out_param(backing_store_out, backing_store) }
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn collect_backing_store(
         backing_store: *const sys::FlutterBackingStore,
         user_data: *mut std::ffi::c_void,
     ) -> bool {
         let user_data = user_data.cast::<CompositorUserData>();
-        let user_data = unsafe { &mut *user_data };
+        let user_data = unsafe { &*user_data };
 
-        let backing_store = BackingStore::from_raw(unsafe { &*backing_store });
+        let Some(backing_store) = (unsafe { BackingStore::from_raw(&*backing_store) }) else {
+            return false;
+        };
 
-        user_data.handler.collect_backing_store(backing_store)
+        user_data
+            .state()
+            .handler
+            .collect_backing_store(backing_store)
     }
 
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "trace", skip_all, fields(view_id, layers))
+    )]
     pub extern "C" fn present_view(present_view_info: *const sys::FlutterPresentViewInfo) -> bool {
         let present_view_info = unsafe { &*present_view_info };
+        #[cfg(feature = "instrument")]
+        tracing::Span::current()
+            .record("view_id", present_view_info.view_id)
+            .record("layers", present_view_info.layers_count);
 
         let user_data = present_view_info.user_data.cast::<CompositorUserData>();
-        let user_data = unsafe { &mut *user_data };
+        let user_data = unsafe { &*user_data };
+        let mut state = user_data.state();
+        let state = &mut *state;
 
-        let layers: Box<[Layer]> = unsafe {
+        state.buffers.fill(unsafe {
             crate::util::slice_from_raw_parts_with_invalid_empty(
                 present_view_info.layers,
                 present_view_info.layers_count,
             )
-        }
-        .iter()
-        .copied()
-        .map(|raw| unsafe { &*raw })
-        .map(Layer::from_raw)
-        .collect();
+        });
 
-        user_data
+        let presented = state
             .handler
-            .present_view(ViewId(present_view_info.view_id), &layers)
+            .present_view(ViewId(present_view_info.view_id), &state.buffers.layers);
+        // the layers refer to the engine's backing stores, which are only valid during this call
+        state.buffers.recycle();
+        presented
     }
     const _: sys::FlutterBackingStoreCreateCallback = Some(create_backing_store);
     const _: sys::FlutterBackingStoreCollectCallback = Some(collect_backing_store);
@@ -197,7 +392,10 @@ mod callbacks {
 impl From<Compositor> for (*mut CompositorUserData, sys::FlutterCompositor) {
     fn from(compositor: Compositor) -> Self {
         let user_data = Box::new(CompositorUserData {
-            handler: compositor.handler,
+            state: Mutex::new(CompositorState {
+                handler: compositor.handler,
+                buffers: LayerBuffers::default(),
+            }),
         });
         let user_data = Box::into_raw(user_data);
 
@@ -0,0 +1,237 @@
+use std::{
+    cell::RefCell,
+    ffi::CStr,
+    mem::ManuallyDrop,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    mock::{MockCall, MockRecorder},
+    sys, Engine, ResponseTimedOut, SemanticsAction, WindowMetricsEvent,
+};
+
+/// The part of the [`Engine`] that an [`crate::EngineHandler`] can use while it handles a callback.
+///
+/// A handler can't hold on to the `Engine`, because the engine owns the handler.
+/// Instead, the callbacks that the engine makes on the platform thread get one of these,
+/// with the operations that are legal to make from inside them.
+///
+/// Until [`Engine::run`] has returned, and once the engine has started shutting down, every call fails
+/// with [`crate::Error::InvalidArguments`]. The context that [`crate::mock::MockEngine`] passes
+/// never reaches an engine; it records every call for the test to look at instead.
+#[derive(Debug)]
+pub struct EngineContext {
+    backend: Backend,
+    semantics_enabled: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+enum Backend {
+    // never dropped; the engine belongs to the real `Engine`
+    Engine(ManuallyDrop<Engine>),
+    Mock(Rc<RefCell<MockRecorder>>),
+}
+
+impl EngineContext {
+    /// # Safety
+    ///
+    /// `engine` must be null, or a running engine that isn't shut down while this is alive.
+    pub(crate) unsafe fn new(
+        engine: sys::FlutterEngine,
+        semantics_enabled: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            backend: Backend::Engine(unsafe { Engine::borrowed(engine) }),
+            semantics_enabled,
+        }
+    }
+
+    /// A context that records its calls in `recorder` instead of making them.
+    pub(crate) fn mock(
+        recorder: Rc<RefCell<MockRecorder>>,
+        semantics_enabled: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            backend: Backend::Mock(recorder),
+            semantics_enabled,
+        }
+    }
+
+    /// Makes the call on the engine, or records `call`.
+    fn call(
+        &mut self,
+        call: MockCall,
+        f: impl FnOnce(&mut Engine) -> crate::Result<()>,
+    ) -> crate::Result<()> {
+        match &mut self.backend {
+            Backend::Engine(engine) => f(engine),
+            Backend::Mock(recorder) => {
+                recorder.borrow_mut().calls.push(call);
+                Ok(())
+            }
+        }
+    }
+
+    /// See [`Engine::send_platform_message`].
+    pub fn send_platform_message(
+        &mut self,
+        channel: &CStr,
+        message: &[u8],
+        response: impl FnOnce(&[u8]) + 'static,
+    ) -> crate::Result<()> {
+        match &mut self.backend {
+            Backend::Engine(engine) => engine.send_platform_message(channel, message, response),
+            Backend::Mock(recorder) => {
+                // without a timeout, the response is never an error
+                recorder.borrow_mut().record_message(
+                    channel,
+                    message,
+                    None,
+                    Box::new(move |result| response(result.unwrap_or_default())),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// See [`Engine::send_platform_message_with_timeout`].
+    pub fn send_platform_message_with_timeout(
+        &mut self,
+        channel: &CStr,
+        message: &[u8],
+        timeout: Duration,
+        response: impl FnOnce(Result<&[u8], ResponseTimedOut>) + 'static,
+    ) -> crate::Result<()> {
+        match &mut self.backend {
+            Backend::Engine(engine) => {
+                engine.send_platform_message_with_timeout(channel, message, timeout, response)
+            }
+            Backend::Mock(recorder) => {
+                recorder.borrow_mut().record_message(
+                    channel,
+                    message,
+                    Some(timeout),
+                    Box::new(response),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// See [`Engine::send_window_metrics_event`].
+    ///
+    /// The metrics are sent, but unlike the engine's own, they aren't kept to be sent again after a restart.
+    pub fn send_window_metrics_event(&mut self, metrics: WindowMetricsEvent) -> crate::Result<()> {
+        match &mut self.backend {
+            Backend::Engine(engine) => engine.send_window_metrics_event(metrics),
+            Backend::Mock(recorder) => {
+                metrics.validate()?;
+                recorder.borrow_mut().record_window_metrics(metrics);
+                Ok(())
+            }
+        }
+    }
+
+    /// See [`Engine::schedule_frame`].
+    pub fn schedule_frame(&mut self) -> crate::Result<()> {
+        self.call(MockCall::ScheduleFrame, Engine::schedule_frame)
+    }
+
+    /// See [`Engine::set_next_frame_callback`].
+    pub fn set_next_frame_callback(
+        &mut self,
+        callback: impl FnOnce() + 'static,
+    ) -> crate::Result<()> {
+        match &mut self.backend {
+            Backend::Engine(engine) => engine.set_next_frame_callback(callback),
+            Backend::Mock(recorder) => {
+                recorder
+                    .borrow_mut()
+                    .next_frame_callbacks
+                    .push(Box::new(callback));
+                Ok(())
+            }
+        }
+    }
+
+    /// See [`Engine::post_render_thread_task`].
+    pub fn post_render_thread_task(
+        &mut self,
+        callback: impl FnOnce() + 'static,
+    ) -> crate::Result<()> {
+        match &mut self.backend {
+            Backend::Engine(engine) => engine.post_render_thread_task(callback),
+            Backend::Mock(recorder) => {
+                recorder
+                    .borrow_mut()
+                    .render_thread_tasks
+                    .push(Box::new(callback));
+                Ok(())
+            }
+        }
+    }
+
+    /// See [`Engine::update_semantics_enabled`].
+    pub fn update_semantics_enabled(&mut self, enabled: bool) -> crate::Result<()> {
+        self.call(MockCall::UpdateSemanticsEnabled(enabled), |engine| {
+            engine.update_semantics_enabled(enabled)
+        })?;
+        self.semantics_enabled.store(enabled, Ordering::Release);
+        Ok(())
+    }
+
+    /// See [`Engine::semantics_enabled`].
+    #[must_use]
+    pub fn semantics_enabled(&self) -> bool {
+        self.semantics_enabled.load(Ordering::Acquire)
+    }
+
+    /// See [`Engine::dispatch_semantics_action`].
+    pub fn dispatch_semantics_action(
+        &mut self,
+        node_id: u64,
+        action: SemanticsAction,
+        data: &[u8],
+    ) -> crate::Result<()> {
+        let call = MockCall::DispatchSemanticsAction {
+            node_id,
+            action,
+            data: data.to_vec(),
+        };
+        self.call(call, |engine| {
+            engine.dispatch_semantics_action(node_id, action, data)
+        })
+    }
+
+    /// See [`Engine::register_external_texture`].
+    pub fn register_external_texture(&mut self, texture_identifier: i64) -> crate::Result<()> {
+        self.call(
+            MockCall::RegisterExternalTexture(texture_identifier),
+            |engine| engine.register_external_texture(texture_identifier),
+        )
+    }
+
+    /// See [`Engine::unregister_external_texture`].
+    pub fn unregister_external_texture(&mut self, texture_identifier: i64) -> crate::Result<()> {
+        self.call(
+            MockCall::UnregisterExternalTexture(texture_identifier),
+            |engine| engine.unregister_external_texture(texture_identifier),
+        )
+    }
+
+    /// See [`Engine::mark_external_texture_frame_available`].
+    pub fn mark_external_texture_frame_available(
+        &mut self,
+        texture_identifier: i64,
+    ) -> crate::Result<()> {
+        self.call(
+            MockCall::MarkExternalTextureFrameAvailable(texture_identifier),
+            |engine| engine.mark_external_texture_frame_available(texture_identifier),
+        )
+    }
+}
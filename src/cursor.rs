@@ -0,0 +1,222 @@
+use std::{collections::HashMap, ffi::CStr};
+
+#[cfg(feature = "wayland")]
+use smithay_client_toolkit::reexports::protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
+use tracing::warn;
+
+use crate::{PlatformMessageResponse, StandardMethodCall, StandardValue};
+
+/// The channel the framework asks for cursor changes on. Messages use the `StandardMethodCodec`.
+pub const MOUSE_CURSOR_CHANNEL: &CStr = c"flutter/mousecursor";
+
+macro_rules! system_mouse_cursors {
+    ($($variant:ident = $kind:literal, $x_name:literal;)*) => {
+        /// The cursors that the framework can ask for, i.e. `SystemMouseCursors` in Dart.
+        #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Default)]
+        pub enum SystemMouseCursor {
+            /// No cursor should be visible.
+            None,
+            #[default]
+            $($variant,)*
+        }
+
+        impl SystemMouseCursor {
+            /// Parses the `kind` that the framework sends, e.g. `"click"`.
+            #[must_use]
+            pub fn from_kind(kind: &str) -> Option<Self> {
+                match kind {
+                    "none" => Some(Self::None),
+                    $($kind => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+
+            /// The `kind` that the framework uses for this cursor.
+            #[must_use]
+            pub fn kind(self) -> &'static str {
+                match self {
+                    Self::None => "none",
+                    $(Self::$variant => $kind,)*
+                }
+            }
+
+            /// The name of this cursor in X cursor themes, as in the freedesktop cursor spec, or `None` if the cursor should be hidden.
+            #[must_use]
+            pub fn x_cursor_name(self) -> Option<&'static str> {
+                match self {
+                    Self::None => None,
+                    $(Self::$variant => Some($x_name),)*
+                }
+            }
+        }
+    };
+}
+
+// the first one is the default
+system_mouse_cursors! {
+    Basic = "basic", "default";
+    Click = "click", "pointer";
+    Forbidden = "forbidden", "not-allowed";
+    Wait = "wait", "wait";
+    Progress = "progress", "progress";
+    ContextMenu = "contextMenu", "context-menu";
+    Help = "help", "help";
+    Text = "text", "text";
+    VerticalText = "verticalText", "vertical-text";
+    Cell = "cell", "cell";
+    Precise = "precise", "crosshair";
+    Move = "move", "move";
+    Grab = "grab", "grab";
+    Grabbing = "grabbing", "grabbing";
+    NoDrop = "noDrop", "no-drop";
+    Alias = "alias", "alias";
+    Copy = "copy", "copy";
+    // only macOS has a cursor for this
+    Disappearing = "disappearing", "default";
+    AllScroll = "allScroll", "all-scroll";
+    ResizeLeftRight = "resizeLeftRight", "ew-resize";
+    ResizeUpDown = "resizeUpDown", "ns-resize";
+    ResizeUpLeftDownRight = "resizeUpLeftDownRight", "nwse-resize";
+    ResizeUpRightDownLeft = "resizeUpRightDownLeft", "nesw-resize";
+    ResizeUp = "resizeUp", "n-resize";
+    ResizeDown = "resizeDown", "s-resize";
+    ResizeLeft = "resizeLeft", "w-resize";
+    ResizeRight = "resizeRight", "e-resize";
+    ResizeUpLeft = "resizeUpLeft", "nw-resize";
+    ResizeUpRight = "resizeUpRight", "ne-resize";
+    ResizeDownLeft = "resizeDownLeft", "sw-resize";
+    ResizeDownRight = "resizeDownRight", "se-resize";
+    ResizeColumn = "resizeColumn", "col-resize";
+    ResizeRow = "resizeRow", "row-resize";
+    ZoomIn = "zoomIn", "zoom-in";
+    ZoomOut = "zoomOut", "zoom-out";
+}
+
+#[cfg(feature = "wayland")]
+impl SystemMouseCursor {
+    /// The `wp_cursor_shape_v1` shape for this cursor, or `None` if the cursor should be hidden.
+    #[must_use]
+    pub fn wp_cursor_shape(self) -> Option<Shape> {
+        Some(match self {
+            Self::None => return None,
+            Self::Basic | Self::Disappearing => Shape::Default,
+            Self::Click => Shape::Pointer,
+            Self::Forbidden => Shape::NotAllowed,
+            Self::Wait => Shape::Wait,
+            Self::Progress => Shape::Progress,
+            Self::ContextMenu => Shape::ContextMenu,
+            Self::Help => Shape::Help,
+            Self::Text => Shape::Text,
+            Self::VerticalText => Shape::VerticalText,
+            Self::Cell => Shape::Cell,
+            Self::Precise => Shape::Crosshair,
+            Self::Move => Shape::Move,
+            Self::Grab => Shape::Grab,
+            Self::Grabbing => Shape::Grabbing,
+            Self::NoDrop => Shape::NoDrop,
+            Self::Alias => Shape::Alias,
+            Self::Copy => Shape::Copy,
+            Self::AllScroll => Shape::AllScroll,
+            Self::ResizeLeftRight => Shape::EwResize,
+            Self::ResizeUpDown => Shape::NsResize,
+            Self::ResizeUpLeftDownRight => Shape::NwseResize,
+            Self::ResizeUpRightDownLeft => Shape::NeswResize,
+            Self::ResizeUp => Shape::NResize,
+            Self::ResizeDown => Shape::SResize,
+            Self::ResizeLeft => Shape::WResize,
+            Self::ResizeRight => Shape::EResize,
+            Self::ResizeUpLeft => Shape::NwResize,
+            Self::ResizeUpRight => Shape::NeResize,
+            Self::ResizeDownLeft => Shape::SwResize,
+            Self::ResizeDownRight => Shape::SeResize,
+            Self::ResizeColumn => Shape::ColResize,
+            Self::ResizeRow => Shape::RowResize,
+            Self::ZoomIn => Shape::ZoomIn,
+            Self::ZoomOut => Shape::ZoomOut,
+        })
+    }
+}
+
+/// A cursor that the framework asked for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CursorChange {
+    /// The pointer device the cursor is for, as in [`crate::PointerEvent::device`].
+    pub device: i32,
+    pub cursor: SystemMouseCursor,
+}
+
+/// Keeps track of the cursor of each pointer device, from messages on [`MOUSE_CURSOR_CHANNEL`].
+///
+/// Devices that the framework hasn't set a cursor for have [`SystemMouseCursor::Basic`].
+#[derive(Debug, Default, Clone)]
+pub struct MouseCursorTracker {
+    cursors: HashMap<i32, SystemMouseCursor>,
+}
+
+impl MouseCursorTracker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn cursor(&self, device: i32) -> SystemMouseCursor {
+        self.cursors.get(&device).copied().unwrap_or_default()
+    }
+
+    /// Forgets the cursor of a device, e.g. when it's removed.
+    pub fn remove_device(&mut self, device: i32) {
+        self.cursors.remove(&device);
+    }
+
+    /// Handles a message on [`MOUSE_CURSOR_CHANNEL`], and responds to it.
+    ///
+    /// Returns the new cursor, if it changed. Unknown cursor kinds are treated as [`SystemMouseCursor::Basic`].
+    pub fn platform_message(
+        &mut self,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) -> Option<CursorChange> {
+        let (reply, change) = self.handle(message);
+        if let Err(err) = response.send(&reply) {
+            warn!("failed to respond to a mouse cursor message: {err}");
+        }
+        change
+    }
+
+    fn handle(&mut self, message: &[u8]) -> (Vec<u8>, Option<CursorChange>) {
+        let Some(call) = StandardMethodCall::decode(message) else {
+            return malformed("malformed message");
+        };
+        if call.method != "activateSystemCursor" {
+            // not implemented
+            return (Vec::new(), None);
+        }
+
+        let (Some(device), Some(kind)) = (
+            call.args.get("device").and_then(StandardValue::as_int),
+            call.args.get("kind").and_then(StandardValue::as_str),
+        ) else {
+            return malformed("missing device or kind");
+        };
+        let Ok(device) = i32::try_from(device) else {
+            return malformed("invalid device");
+        };
+
+        let cursor = SystemMouseCursor::from_kind(kind).unwrap_or_else(|| {
+            warn!("unknown mouse cursor kind {kind:?}");
+            SystemMouseCursor::Basic
+        });
+        let changed = self.cursor(device) != cursor;
+        self.cursors.insert(device, cursor);
+        (
+            StandardMethodCall::success(&StandardValue::Null),
+            changed.then_some(CursorChange { device, cursor }),
+        )
+    }
+}
+
+fn malformed(message: &str) -> (Vec<u8>, Option<CursorChange>) {
+    let reply = StandardMethodCall::error("error", Some(message), &StandardValue::Null);
+    (reply, None)
+}
@@ -1,4 +1,4 @@
-use std::ffi::CStr;
+use std::{ffi::CStr, fmt};
 
 use crate::{sys, Engine};
 
@@ -27,6 +27,24 @@ pub enum DartObject<'a> {
     },
 }
 
+impl fmt::Debug for DartObject<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("Null"),
+            Self::Bool(b) => f.debug_tuple("Bool").field(b).finish(),
+            Self::Int32(i) => f.debug_tuple("Int32").field(i).finish(),
+            Self::Int64(i) => f.debug_tuple("Int64").field(i).finish(),
+            Self::Double(d) => f.debug_tuple("Double").field(d).finish(),
+            Self::String(s) => f.debug_tuple("String").field(s).finish(),
+            Self::Buffer(buffer) => f.debug_tuple("Buffer").field(&buffer.len()).finish(),
+            Self::SharedBuffer { data, .. } => f
+                .debug_struct("SharedBuffer")
+                .field("data", &data.len())
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
 impl Engine {
     /// Posts a Dart object to specified send port.
     /// The corresponding receive port for send port can be
@@ -41,6 +59,10 @@ impl Engine {
     /// corresponding send port is listening.
     ///
     /// Returns if the message was posted to the send port.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self, object))
+    )]
     pub fn post_dart_object(
         &mut self,
         port: sys::FlutterEngineDartPort,
@@ -99,6 +121,7 @@ impl Engine {
                     collect: Box<dyn FnOnce()>,
                 }
 
+                #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
                 unsafe extern "C" fn buffer_collect(user_data: *mut std::ffi::c_void) {
                     let user_data = user_data.cast::<UserData>();
                     let user_data = unsafe { Box::from_raw(user_data) };
@@ -0,0 +1,374 @@
+//! A preset for desktop embedders, that handles the platform channels every app uses.
+//!
+//! [`Shell`] routes the `flutter/platform`, `flutter/mousecursor` and `flutter/textinput` channels to
+//! a few small traits, which is where the embedder plugs in its clipboard, cursors and windows:
+//!
+//! ```ignore
+//! let mut shell = Shell::new(ShellHandlers {
+//!     clipboard: Box::new(my_clipboard),
+//!     cursor: Box::new(my_cursor),
+//!     application: Box::new(my_window),
+//!     virtual_keyboard: None,
+//!     text_input: None,
+//! });
+//!
+//! // in EngineHandler::platform_message
+//! shell.platform_message(cx, channel, message, response);
+//!
+//! // once the engine is running, and whenever they change
+//! desktop::send_settings(&mut engine, &PlatformSettings::default())?;
+//! desktop::set_lifecycle_state(&mut engine, AppLifecycleState::Resumed)?;
+//! ```
+//!
+//! Other channels can be added to [`Shell::router`].
+
+use std::{ffi::CStr, fmt};
+
+use tracing::warn;
+
+use crate::{
+    ChannelHandler, ChannelRouter, CursorChange, Engine, EngineContext, JsonValue,
+    MouseCursorTracker, PlatformMessageResponse, VirtualKeyboard, VirtualKeyboardHandler,
+    MOUSE_CURSOR_CHANNEL, TEXT_INPUT_CHANNEL,
+};
+
+/// The channel for clipboard, window title and app exit requests. Messages use the `JSONMethodCodec`.
+pub const PLATFORM_CHANNEL: &CStr = c"flutter/platform";
+/// The channel that user preferences are sent to the framework on. Messages use the `JSONMessageCodec`.
+pub const SETTINGS_CHANNEL: &CStr = c"flutter/settings";
+/// The channel that the app's lifecycle state is sent to the framework on. Messages use the `StringCodec`.
+pub const LIFECYCLE_CHANNEL: &CStr = c"flutter/lifecycle";
+
+/// The system clipboard, for `Clipboard` in Dart.
+pub trait Clipboard {
+    /// The text on the clipboard, if there is any.
+    fn text(&mut self) -> Option<String>;
+
+    fn set_text(&mut self, text: &str);
+}
+
+/// A clipboard that only exists within the app, for platforms where there's no system clipboard to talk to.
+#[derive(Debug, Clone, Default)]
+pub struct LocalClipboard {
+    text: Option<String>,
+}
+
+impl Clipboard for LocalClipboard {
+    fn text(&mut self) -> Option<String> {
+        self.text.clone()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.text = Some(text.to_owned());
+    }
+}
+
+/// Shows the cursors that the framework asks for.
+pub trait CursorHandler {
+    /// Only called when the cursor of a device actually changes.
+    fn set_cursor(&mut self, change: CursorChange);
+}
+
+/// The parts of `flutter/platform` that are about the app and its window.
+pub trait ApplicationHandler {
+    /// The app asked to be closed, with `SystemNavigator.pop()`.
+    fn exit_requested(&mut self);
+
+    /// The app set its title, e.g. through the `title` of a `MaterialApp`.
+    fn set_title(&mut self, title: &str);
+
+    /// The app asked for a system sound, e.g. `"SystemSoundType.click"`. Most desktops don't have those.
+    fn play_sound(&mut self, sound: &str) {
+        _ = sound;
+    }
+}
+
+/// The handlers that a [`Shell`] is wired to.
+pub struct ShellHandlers {
+    pub clipboard: Box<dyn Clipboard>,
+    pub cursor: Box<dyn CursorHandler>,
+    pub application: Box<dyn ApplicationHandler>,
+    /// Shown and hidden from the messages on [`TEXT_INPUT_CHANNEL`], if the desktop has an on-screen keyboard.
+    pub virtual_keyboard: Option<Box<dyn VirtualKeyboardHandler>>,
+    /// Handles the messages on [`TEXT_INPUT_CHANNEL`], e.g. by talking to an input method.
+    /// Without one, text input isn't implemented, and text fields only get hardware keyboard events.
+    pub text_input: Option<Box<dyn ChannelHandler>>,
+}
+
+impl fmt::Debug for ShellHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShellHandlers")
+            .field("virtual_keyboard", &self.virtual_keyboard.is_some())
+            .field("text_input", &self.text_input.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The platform channels of a desktop app, pre-wired to [`ShellHandlers`].
+#[derive(Debug)]
+pub struct Shell {
+    router: ChannelRouter,
+}
+
+impl Shell {
+    #[must_use]
+    pub fn new(handlers: ShellHandlers) -> Self {
+        let ShellHandlers {
+            clipboard,
+            cursor,
+            application,
+            virtual_keyboard,
+            text_input,
+        } = handlers;
+
+        let mut router = ChannelRouter::new();
+        router.register(
+            PLATFORM_CHANNEL,
+            PlatformChannel {
+                clipboard,
+                application,
+            },
+        );
+        router.register(
+            MOUSE_CURSOR_CHANNEL,
+            CursorChannel {
+                tracker: MouseCursorTracker::new(),
+                handler: cursor,
+            },
+        );
+        router.register(
+            TEXT_INPUT_CHANNEL,
+            TextInputChannel {
+                keyboard: virtual_keyboard.map(VirtualKeyboard::new),
+                handler: text_input,
+            },
+        );
+        Self { router }
+    }
+
+    /// The router behind the shell, to handle more channels or replace the built-in handlers.
+    pub fn router(&mut self) -> &mut ChannelRouter {
+        &mut self.router
+    }
+
+    /// Handles a message from [`crate::EngineHandler::platform_message`].
+    pub fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        channel: &CStr,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        self.router.platform_message(cx, channel, message, response);
+    }
+}
+
+/// Whether the user prefers light or dark colors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Brightness {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// The user preferences that the framework needs from the platform.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PlatformSettings {
+    pub text_scale_factor: f64,
+    pub always_use_24_hour_format: bool,
+    pub platform_brightness: Brightness,
+}
+
+impl Default for PlatformSettings {
+    fn default() -> Self {
+        Self {
+            text_scale_factor: 1.0,
+            always_use_24_hour_format: false,
+            platform_brightness: Brightness::Light,
+        }
+    }
+}
+
+impl PlatformSettings {
+    /// The message to send on [`SETTINGS_CHANNEL`].
+    #[must_use]
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            (
+                "textScaleFactor".into(),
+                JsonValue::Number(self.text_scale_factor),
+            ),
+            (
+                "alwaysUse24HourFormat".into(),
+                JsonValue::Bool(self.always_use_24_hour_format),
+            ),
+            (
+                "platformBrightness".into(),
+                match self.platform_brightness {
+                    Brightness::Light => "light",
+                    Brightness::Dark => "dark",
+                }
+                .into(),
+            ),
+        ])
+    }
+}
+
+/// Sends the user's preferences to the framework. The framework waits for these before it lays out text,
+/// so send them once the engine is running, and again whenever they change.
+pub fn send_settings(engine: &mut Engine, settings: &PlatformSettings) -> crate::Result<()> {
+    engine.send_platform_message(
+        SETTINGS_CHANNEL,
+        settings.to_json().to_string().as_bytes(),
+        |_| {},
+    )
+}
+
+/// The state of the app, i.e. `AppLifecycleState` in Dart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AppLifecycleState {
+    /// Visible and focused.
+    Resumed,
+    /// Visible, but not focused.
+    Inactive,
+    /// Not visible, e.g. minimized.
+    Hidden,
+    /// Not visible, and not expected to be shown again soon.
+    Paused,
+    /// About to shut down.
+    Detached,
+}
+
+impl AppLifecycleState {
+    /// The message to send on [`LIFECYCLE_CHANNEL`].
+    #[must_use]
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Resumed => "AppLifecycleState.resumed",
+            Self::Inactive => "AppLifecycleState.inactive",
+            Self::Hidden => "AppLifecycleState.hidden",
+            Self::Paused => "AppLifecycleState.paused",
+            Self::Detached => "AppLifecycleState.detached",
+        }
+    }
+}
+
+/// Tells the framework that the app's lifecycle state changed, e.g. when its window is focused or minimized.
+pub fn set_lifecycle_state(engine: &mut Engine, state: AppLifecycleState) -> crate::Result<()> {
+    engine.send_platform_message(LIFECYCLE_CHANNEL, state.message().as_bytes(), |_| {})
+}
+
+struct PlatformChannel {
+    clipboard: Box<dyn Clipboard>,
+    application: Box<dyn ApplicationHandler>,
+}
+
+impl PlatformChannel {
+    /// The response to a method call, in the `JSONMethodCodec`.
+    fn handle(&mut self, message: &[u8]) -> String {
+        let Some(call) = std::str::from_utf8(message).ok().and_then(JsonValue::parse) else {
+            return r#"["malformed","malformed message",null]"#.to_owned();
+        };
+        let args = call.get("args");
+        let result = match call.get("method").and_then(JsonValue::as_str) {
+            Some("Clipboard.getData") => match self.clipboard.text() {
+                Some(text) => JsonValue::Object(vec![("text".into(), text.into())]),
+                None => JsonValue::Null,
+            },
+            Some("Clipboard.setData") => {
+                let text = args
+                    .and_then(|args| args.get("text"))
+                    .and_then(JsonValue::as_str)
+                    .unwrap_or_default();
+                self.clipboard.set_text(text);
+                JsonValue::Null
+            }
+            Some("Clipboard.hasStrings") => {
+                let has_strings = self.clipboard.text().is_some_and(|text| !text.is_empty());
+                JsonValue::Object(vec![("value".into(), has_strings.into())])
+            }
+            Some("SystemNavigator.pop") => {
+                self.application.exit_requested();
+                JsonValue::Null
+            }
+            Some("SystemChrome.setApplicationSwitcherDescription") => {
+                if let Some(label) = args
+                    .and_then(|args| args.get("label"))
+                    .and_then(JsonValue::as_str)
+                {
+                    self.application.set_title(label);
+                }
+                JsonValue::Null
+            }
+            Some("SystemSound.play") => {
+                if let Some(sound) = args.and_then(JsonValue::as_str) {
+                    self.application.play_sound(sound);
+                }
+                JsonValue::Null
+            }
+            // there's no haptic feedback on desktops, but the call shouldn't fail
+            Some(method) if method.starts_with("HapticFeedback.") => JsonValue::Null,
+            // not implemented
+            _ => return String::new(),
+        };
+        JsonValue::Array(vec![result]).to_string()
+    }
+}
+
+impl ChannelHandler for PlatformChannel {
+    fn platform_message(
+        &mut self,
+        _cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        let reply = self.handle(message);
+        if let Err(err) = response.send(reply.as_bytes()) {
+            warn!("failed to respond to a platform message: {err}");
+        }
+    }
+}
+
+struct CursorChannel {
+    tracker: MouseCursorTracker,
+    handler: Box<dyn CursorHandler>,
+}
+
+impl ChannelHandler for CursorChannel {
+    fn platform_message(
+        &mut self,
+        _cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        if let Some(change) = self.tracker.platform_message(message, response) {
+            self.handler.set_cursor(change);
+        }
+    }
+}
+
+struct TextInputChannel {
+    keyboard: Option<VirtualKeyboard<Box<dyn VirtualKeyboardHandler>>>,
+    handler: Option<Box<dyn ChannelHandler>>,
+}
+
+impl ChannelHandler for TextInputChannel {
+    fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        if let Some(keyboard) = &mut self.keyboard {
+            keyboard.observe(message);
+        }
+        match &mut self.handler {
+            Some(handler) => handler.platform_message(cx, message, response),
+            None => {
+                if let Err(err) = response.send(&[]) {
+                    warn!("failed to respond to a text input message: {err}");
+                }
+            }
+        }
+    }
+}
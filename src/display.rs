@@ -1,4 +1,4 @@
-use crate::{sys, Engine};
+use crate::{sys, Engine, WindowMetricsEvent};
 
 simple_enum! {
     pub enum DisplaysUpdateType(sys::FlutterEngineDisplaysUpdateType) {
@@ -11,6 +11,7 @@ simple_enum! {
     }
 }
 
+#[derive(Debug)]
 pub struct Display {
     pub display_id: sys::FlutterEngineDisplayId,
 
@@ -51,21 +52,46 @@ impl Engine {
     /// Posts updates corresponding to display changes to a running engine instance.
     ///
     /// There must be at least one display in the list of displays.
+    ///
+    /// Views on a display whose pixel ratio changed get their metrics sent again, with the new pixel ratio.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip(self, displays), fields(displays = displays.len())))]
     pub fn notify_display_update(
         &mut self,
         update_type: DisplaysUpdateType,
         displays: &[Display],
     ) -> crate::Result<()> {
-        let displays: Box<[sys::FlutterEngineDisplay]> = displays.iter().map(Into::into).collect();
+        let raw_displays: Box<[sys::FlutterEngineDisplay]> =
+            displays.iter().map(Into::into).collect();
 
         unsafe {
             sys::NotifyDisplayUpdate(
                 self.inner.engine,
                 update_type.into(),
-                displays.as_ptr(),
-                displays.len(),
+                raw_displays.as_ptr(),
+                raw_displays.len(),
             )
         }
-        .to_result()
+        .to_result()?;
+
+        // a borrowed engine doesn't know the metrics of its views, so it has nothing to resend
+        let changed = self
+            .all_view_metrics()
+            .into_iter()
+            .filter_map(|metrics| {
+                let display = displays.iter().find(|display| {
+                    display.single_display || display.display_id == metrics.display_id
+                })?;
+                #[allow(clippy::float_cmp)]
+                let changed = metrics.pixel_ratio != display.device_pixel_ratio;
+                changed.then_some(WindowMetricsEvent {
+                    pixel_ratio: display.device_pixel_ratio,
+                    ..metrics
+                })
+            })
+            .collect::<Vec<_>>();
+        for metrics in changed {
+            self.send_window_metrics_event(metrics)?;
+        }
+        Ok(())
     }
 }
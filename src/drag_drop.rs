@@ -0,0 +1,222 @@
+use std::{ffi::CStr, time::Duration};
+
+use crate::{Engine, JsonValue, PointerDeviceKind, PointerState, ViewId};
+
+/// The channel that drag-and-drop events are sent to the app on.
+///
+/// Messages are JSON method calls (`JSONMethodCodec` on the Dart side), with coordinates in physical pixels:
+///
+/// - `entered`, `{"viewId": int, "x": num, "y": num}`
+/// - `updated`, `{"viewId": int, "x": num, "y": num}`
+/// - `exited`, `{"viewId": int}`
+/// - `dropped`, `{"viewId": int, "x": num, "y": num, "uris": [string], "text": string?}`
+pub const DRAG_AND_DROP_CHANNEL: &CStr = c"volito/drag_and_drop";
+
+/// The MIME type of a list of URIs, as offered by file managers.
+pub const URI_LIST_MIME_TYPE: &str = "text/uri-list";
+/// The MIME type of plain text, as offered by text editors and browsers.
+pub const TEXT_MIME_TYPE: &str = "text/plain;charset=utf-8";
+
+/// The data of a drop, as it's sent to the app.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DropData {
+    /// The dropped URIs, usually `file://` URIs.
+    pub uris: Vec<String>,
+    /// The dropped text, if the drag offered any.
+    pub text: Option<String>,
+}
+
+impl DropData {
+    /// Parses the contents of a `text/uri-list`, as described in RFC 2483.
+    ///
+    /// Comments and blank lines are skipped, and lines may end with either `\r\n` or `\n`.
+    #[must_use]
+    pub fn from_uri_list(uri_list: &str) -> Self {
+        Self {
+            uris: uri_list
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect(),
+            text: None,
+        }
+    }
+}
+
+/// Decides which drags a [`DragAndDrop`] accepts, and sees what is dropped before the app does.
+pub trait DropHandler {
+    /// Whether a drag offering these MIME types can be dropped on the app.
+    ///
+    /// By default, drags that offer a URI list or text are accepted.
+    fn accepts(&mut self, mime_types: &[String]) -> bool {
+        mime_types
+            .iter()
+            .any(|mime_type| mime_type == URI_LIST_MIME_TYPE || mime_type.starts_with("text/plain"))
+    }
+
+    /// Called when something is dropped on a view, at a point in physical pixels.
+    ///
+    /// The data can be changed before it's sent to the app. Returning `false` keeps it from the app entirely.
+    fn dropped(&mut self, view_id: ViewId, x: f64, y: f64, data: &mut DropData) -> bool {
+        let _ = (view_id, x, y, data);
+        true
+    }
+}
+
+/// Accepts URI lists and text, and sends all drops to the app unchanged.
+impl DropHandler for () {}
+
+/// Turns the drag-and-drop events of a windowing system into input for the engine.
+///
+/// While a drag is over a view, it's a hovering mouse pointer (with its own device id), so that hover effects follow it.
+/// The app is also told about the drag on [`DRAG_AND_DROP_CHANNEL`], and gets the dropped data from there.
+///
+/// Drags that the [`DropHandler`] doesn't accept are ignored entirely.
+#[derive(Debug)]
+pub struct DragAndDrop<H: DropHandler = ()> {
+    handler: H,
+    pointer: PointerState,
+    active: bool,
+}
+
+impl<H: DropHandler> DragAndDrop<H> {
+    /// `device` is the pointer device id used for drags; it must not be used by any other pointer.
+    pub fn new(device: i32, handler: H) -> Self {
+        Self {
+            handler,
+            pointer: PointerState::new(ViewId::IMPLICIT, device, PointerDeviceKind::Mouse),
+            active: false,
+        }
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Whether a drag that was accepted is over a view.
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// A drag entered a view, at a point in physical pixels. Returns whether it was accepted.
+    ///
+    /// If a drag was already over another view, that view sees it leave first.
+    pub fn enter(
+        &mut self,
+        engine: &mut Engine,
+        view_id: ViewId,
+        timestamp: Duration,
+        x: f64,
+        y: f64,
+        mime_types: &[String],
+    ) -> crate::Result<bool> {
+        if self.active && self.pointer.view_id() != view_id {
+            self.leave(engine, timestamp)?;
+        }
+        if !self.handler.accepts(mime_types) {
+            return Ok(false);
+        }
+
+        let events = self
+            .pointer
+            .enter(view_id, timestamp, x, y)
+            .collect::<Vec<_>>();
+        engine.send_pointer_event(&events)?;
+        self.active = true;
+        send(engine, "entered", position_args(view_id, x, y))?;
+        Ok(true)
+    }
+
+    /// An accepted drag moved within its view.
+    pub fn motion(
+        &mut self,
+        engine: &mut Engine,
+        timestamp: Duration,
+        x: f64,
+        y: f64,
+    ) -> crate::Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        let event = self.pointer.motion(timestamp, x, y);
+        engine.send_pointer_event(&[event])?;
+        send(
+            engine,
+            "updated",
+            position_args(self.pointer.view_id(), x, y),
+        )
+    }
+
+    /// The drag left its view without being dropped, or was cancelled.
+    pub fn leave(&mut self, engine: &mut Engine, timestamp: Duration) -> crate::Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        self.active = false;
+        let events = self.pointer.leave(timestamp).collect::<Vec<_>>();
+        engine.send_pointer_event(&events)?;
+        send(engine, "exited", view_args(self.pointer.view_id()))
+    }
+
+    /// The drag was dropped where it last was. Returns whether the drop was sent to the app.
+    ///
+    /// The drag pointer is removed afterwards; the windowing system usually sends a new pointer enter right after a drop.
+    pub fn drop_data(
+        &mut self,
+        engine: &mut Engine,
+        timestamp: Duration,
+        mut data: DropData,
+    ) -> crate::Result<bool> {
+        if !self.active {
+            return Ok(false);
+        }
+        let view_id = self.pointer.view_id();
+        let (x, y) = self.pointer.position();
+
+        self.active = false;
+        let events = self.pointer.leave(timestamp).collect::<Vec<_>>();
+        engine.send_pointer_event(&events)?;
+
+        if !self.handler.dropped(view_id, x, y, &mut data) {
+            send(engine, "exited", view_args(view_id))?;
+            return Ok(false);
+        }
+
+        let mut args = position_args(view_id, x, y);
+        args.push((
+            "uris".into(),
+            JsonValue::Array(data.uris.into_iter().map(JsonValue::from).collect()),
+        ));
+        args.push((
+            "text".into(),
+            data.text.map_or(JsonValue::Null, JsonValue::from),
+        ));
+        send(engine, "dropped", args)?;
+        Ok(true)
+    }
+}
+
+fn view_args(view_id: ViewId) -> Vec<(String, JsonValue)> {
+    vec![("viewId".into(), view_id.0.into())]
+}
+
+fn position_args(view_id: ViewId, x: f64, y: f64) -> Vec<(String, JsonValue)> {
+    let mut args = view_args(view_id);
+    args.push(("x".into(), x.into()));
+    args.push(("y".into(), y.into()));
+    args
+}
+
+fn send(engine: &mut Engine, method: &str, args: Vec<(String, JsonValue)>) -> crate::Result<()> {
+    let message = JsonValue::Object(vec![
+        ("method".into(), method.into()),
+        ("args".into(), JsonValue::Object(args)),
+    ]);
+    engine.send_platform_message(
+        DRAG_AND_DROP_CHANNEL,
+        message.to_string().as_bytes(),
+        |_| {},
+    )
+}
@@ -0,0 +1,399 @@
+//! Driving a Flutter app through the embedder, for end-to-end tests.
+//!
+//! [`WidgetDriver`] runs the app in a [`HeadlessEngine`], keeps track of its semantics tree,
+//! and records the platform messages it sends. Tests then inject input (taps, text) and wait for
+//! the semantics tree or the platform messages to show the result:
+//!
+//! ```ignore
+//! let mut driver = WidgetDriver::run(project_args, Size { width: 800, height: 600 }, 1.0)?;
+//! let button = driver.wait_for_node(|node| node.label.string.as_bytes() == b"Increment", TIMEOUT)?.unwrap();
+//! driver.tap_node(button.id)?;
+//! driver.wait_for_node(|node| node.label.string.as_bytes() == b"1", TIMEOUT)?.unwrap();
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CStr, CString},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Engine, EngineContext, EngineHandler, HeadlessEngine, JsonValue, KeyEvent,
+    PlatformMessageResponse, PointerButtons, PointerDeviceKind, PointerState, ProjectArgs,
+    SemanticsNode, SemanticsUpdate, Size, ViewFocusEvent, ViewId, VsyncBaton,
+};
+
+/// How long to wait for a single frame while pumping.
+const FRAME_TIMEOUT: Duration = Duration::from_millis(100);
+/// The device id of the driver's mouse.
+const DRIVER_DEVICE: i32 = 1;
+/// The id of the root of the semantics tree.
+const ROOT_NODE: i32 = 0;
+
+/// A platform message that the app sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedMessage {
+    pub channel: CString,
+    pub message: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Recorded {
+    semantics: Vec<SemanticsUpdate>,
+    messages: Vec<RecordedMessage>,
+}
+
+/// Records semantics updates and platform messages, forwarding the messages to the app's own handler.
+struct RecordingHandler {
+    inner: Box<dyn EngineHandler>,
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+impl EngineHandler for RecordingHandler {
+    fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        channel: &CStr,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        self.recorded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .messages
+            .push(RecordedMessage {
+                channel: channel.to_owned(),
+                message: message.to_vec(),
+            });
+        self.inner.platform_message(cx, channel, message, response);
+    }
+
+    fn vsync(&mut self, baton: VsyncBaton) {
+        self.inner.vsync(baton);
+    }
+
+    fn update_semantics(&mut self, _cx: &mut EngineContext, update: SemanticsUpdate) {
+        self.recorded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .semantics
+            .push(update);
+    }
+
+    fn log_message(&mut self, tag: &CStr, message: &CStr) {
+        self.inner.log_message(tag, message);
+    }
+
+    fn on_pre_engine_restart(&mut self, cx: &mut EngineContext) {
+        self.inner.on_pre_engine_restart(cx);
+    }
+
+    fn channel_update(&mut self, cx: &mut EngineContext, channel: &CStr, listening: bool) {
+        self.inner.channel_update(cx, channel, listening);
+    }
+
+    fn view_focus_change_request(&mut self, cx: &mut EngineContext, request: ViewFocusEvent) {
+        self.inner.view_focus_change_request(cx, request);
+    }
+
+    fn root_isolate_created(&mut self) {
+        self.inner.root_isolate_created();
+    }
+}
+
+/// Applies a transformation to a point.
+fn transform_point(transform: &crate::Transformation<f64>, (x, y): (f64, f64)) -> (f64, f64) {
+    let w = transform.pers0 * x + transform.pers1 * y + transform.pers2;
+    (
+        (transform.scaleX * x + transform.skewX * y + transform.transX) / w,
+        (transform.skewY * x + transform.scaleY * y + transform.transY) / w,
+    )
+}
+
+/// The client id from a `TextInput.setClient` method call, which looks like `{"method":"TextInput.setClient","args":[1,{...}]}`.
+fn text_input_client(message: &[u8]) -> Option<i64> {
+    let call = std::str::from_utf8(message)
+        .ok()
+        .and_then(JsonValue::parse)?;
+    if call.get("method").and_then(JsonValue::as_str) != Some("TextInput.setClient") {
+        return None;
+    }
+    call.get("args")
+        .and_then(JsonValue::as_array)
+        .and_then(|args| args.first())
+        .and_then(JsonValue::as_i64)
+}
+
+/// Runs a Flutter app headlessly, for end-to-end tests. See the [module docs](self).
+///
+/// Semantics are enabled, and the app's semantics updates are consumed by the driver; they are not forwarded to the app's handler.
+/// Platform messages are recorded, and then forwarded to the app's handler as usual.
+pub struct WidgetDriver {
+    engine: HeadlessEngine,
+    recorded: Arc<Mutex<Recorded>>,
+    /// How many of the recorded messages have been looked at by [`WidgetDriver::wait_for_message`].
+    seen_messages: usize,
+    /// How many of the recorded messages have been checked for the text input client.
+    scanned_messages: usize,
+    semantics: HashMap<i32, SemanticsNode>,
+    parents: HashMap<i32, i32>,
+    text_input_client: Option<i64>,
+    pointer: PointerState,
+}
+
+impl fmt::Debug for WidgetDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WidgetDriver")
+            .field("engine", &self.engine)
+            .field("semantics_nodes", &self.semantics.len())
+            .field("text_input_client", &self.text_input_client)
+            .field("pointer", &self.pointer)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WidgetDriver {
+    /// Starts the app in a [`HeadlessEngine`] with the given view size (in physical pixels).
+    pub fn run(
+        mut project_args: ProjectArgs,
+        size: Size<u32>,
+        pixel_ratio: f64,
+    ) -> crate::Result<Self> {
+        let recorded = Arc::new(Mutex::new(Recorded::default()));
+        project_args.handler = Box::new(RecordingHandler {
+            inner: project_args.handler,
+            recorded: recorded.clone(),
+        });
+
+        let mut engine = HeadlessEngine::run(project_args, size, pixel_ratio)?;
+        engine.engine().update_semantics_enabled(true)?;
+
+        Ok(Self {
+            engine,
+            recorded,
+            seen_messages: 0,
+            scanned_messages: 0,
+            semantics: HashMap::new(),
+            parents: HashMap::new(),
+            text_input_client: None,
+            pointer: PointerState::new(ViewId::IMPLICIT, DRIVER_DEVICE, PointerDeviceKind::Mouse),
+        })
+    }
+
+    pub fn engine(&mut self) -> &mut HeadlessEngine {
+        &mut self.engine
+    }
+
+    /// Renders a frame (if the app has anything to render), and applies whatever the app sent in the meantime.
+    pub fn pump(&mut self) -> crate::Result<()> {
+        self.engine.render_frame(FRAME_TIMEOUT)?;
+
+        let mut recorded = self.recorded.lock().unwrap_or_else(PoisonError::into_inner);
+        let updated = !recorded.semantics.is_empty();
+        for update in recorded.semantics.drain(..) {
+            for node in update.nodes {
+                for &child in &node.children_in_traversal_order {
+                    self.parents.insert(child, node.id);
+                }
+                self.semantics.insert(node.id, node);
+            }
+        }
+        if let Some(client) = recorded.messages[self.scanned_messages..]
+            .iter()
+            .filter(|message| message.channel.as_bytes() == b"flutter/textinput")
+            .filter_map(|message| text_input_client(&message.message))
+            .next_back()
+        {
+            self.text_input_client = Some(client);
+        }
+        self.scanned_messages = recorded.messages.len();
+        drop(recorded);
+        if updated {
+            self.remove_detached_nodes();
+        }
+        Ok(())
+    }
+
+    /// Updates only list the nodes that changed, so nodes that were dropped from the tree are the ones
+    /// that are no longer reachable from the root.
+    fn remove_detached_nodes(&mut self) {
+        if !self.semantics.contains_key(&ROOT_NODE) {
+            return;
+        }
+        let mut reachable = HashSet::new();
+        let mut stack = vec![ROOT_NODE];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.semantics.get(&id) {
+                if reachable.insert(id) {
+                    stack.extend(&node.children_in_traversal_order);
+                }
+            }
+        }
+        self.semantics.retain(|id, _| reachable.contains(id));
+        self.parents.retain(|child, parent| {
+            reachable.contains(child)
+                && self
+                    .semantics
+                    .get(parent)
+                    .is_some_and(|parent| parent.children_in_traversal_order.contains(child))
+        });
+    }
+
+    /// The current semantics tree, by node id.
+    #[must_use]
+    pub fn semantics(&self) -> &HashMap<i32, SemanticsNode> {
+        &self.semantics
+    }
+
+    /// Finds a node in the current semantics tree.
+    pub fn find_node(
+        &self,
+        mut predicate: impl FnMut(&SemanticsNode) -> bool,
+    ) -> Option<&SemanticsNode> {
+        self.semantics.values().find(|node| predicate(node))
+    }
+
+    /// Pumps frames until a node matching `predicate` shows up in the semantics tree, or `timeout` passes.
+    pub fn wait_for_node(
+        &mut self,
+        mut predicate: impl FnMut(&SemanticsNode) -> bool,
+        timeout: Duration,
+    ) -> crate::Result<Option<&SemanticsNode>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.pump()?;
+            if let Some(id) = self.find_node(&mut predicate).map(|node| node.id) {
+                return Ok(self.semantics.get(&id));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Pumps frames until the app sends a platform message on `channel`, or `timeout` passes.
+    ///
+    /// Only messages that weren't returned by a previous call are considered.
+    pub fn wait_for_message(
+        &mut self,
+        channel: &CStr,
+        timeout: Duration,
+    ) -> crate::Result<Option<RecordedMessage>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.pump()?;
+            let recorded = self.recorded.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(index) = recorded.messages[self.seen_messages..]
+                .iter()
+                .position(|message| message.channel.as_c_str() == channel)
+            {
+                let index = self.seen_messages + index;
+                self.seen_messages = index + 1;
+                return Ok(Some(recorded.messages[index].clone()));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// The center of a node, in physical pixels relative to the view.
+    #[must_use]
+    pub fn node_center(&self, id: i32) -> Option<(f64, f64)> {
+        let node = self.semantics.get(&id)?;
+        let rect = node.rect;
+        let mut point = (
+            rect.left.midpoint(rect.right),
+            rect.top.midpoint(rect.bottom),
+        );
+        let mut current = Some(node);
+        while let Some(node) = current {
+            point = transform_point(&node.transform, point);
+            current = self
+                .parents
+                .get(&node.id)
+                .and_then(|parent| self.semantics.get(parent));
+        }
+        Some(point)
+    }
+
+    /// Taps at a point (in physical pixels), and pumps a frame.
+    pub fn tap(&mut self, x: f64, y: f64) -> crate::Result<()> {
+        let timestamp = Engine::get_current_time();
+        let mut events = self
+            .pointer
+            .enter(ViewId::IMPLICIT, timestamp, x, y)
+            .collect::<Vec<_>>();
+        events.push(
+            self.pointer
+                .button(timestamp, PointerButtons::MousePrimary, true),
+        );
+        events.push(
+            self.pointer
+                .button(timestamp, PointerButtons::MousePrimary, false),
+        );
+        events.extend(self.pointer.leave(timestamp));
+        self.engine.engine().send_pointer_event(&events)?;
+        self.pump()
+    }
+
+    /// Sends a key event, and pumps a frame. Returns whether the app handled it.
+    pub fn send_key(&mut self, event: KeyEvent) -> crate::Result<bool> {
+        let handled = Arc::new(AtomicBool::new(false));
+        self.engine.engine().send_key_event(event, {
+            let handled = handled.clone();
+            move |result| handled.store(result, Ordering::Relaxed)
+        })?;
+        self.pump()?;
+        Ok(handled.load(Ordering::Relaxed))
+    }
+
+    /// Taps the center of a semantics node. Returns `false` (without tapping) if there is no such node.
+    pub fn tap_node(&mut self, id: i32) -> crate::Result<bool> {
+        let Some((x, y)) = self.node_center(id) else {
+            return Ok(false);
+        };
+        self.tap(x, y)?;
+        Ok(true)
+    }
+
+    /// Replaces the text of the focused text field, as if the platform's text input had typed it.
+    ///
+    /// Returns `false` (without doing anything) if no text field has been focused yet.
+    pub fn enter_text(&mut self, text: &str) -> crate::Result<bool> {
+        let Some(client) = self.text_input_client else {
+            return Ok(false);
+        };
+        let len = i64::try_from(text.encode_utf16().count()).unwrap_or(i64::MAX);
+        let message = JsonValue::Object(vec![
+            ("method".into(), "TextInputClient.updateEditingState".into()),
+            (
+                "args".into(),
+                JsonValue::Array(vec![
+                    client.into(),
+                    JsonValue::Object(vec![
+                        ("text".into(), text.into()),
+                        ("selectionBase".into(), len.into()),
+                        ("selectionExtent".into(), len.into()),
+                        ("selectionAffinity".into(), "TextAffinity.downstream".into()),
+                        ("selectionIsDirectional".into(), false.into()),
+                        ("composingBase".into(), (-1).into()),
+                        ("composingExtent".into(), (-1).into()),
+                    ]),
+                ]),
+            ),
+        ]);
+        self.engine.engine().send_platform_message(
+            c"flutter/textinput",
+            message.to_string().as_bytes(),
+            |_| {},
+        )?;
+        self.pump()?;
+        Ok(true)
+    }
+}
@@ -1,47 +1,135 @@
 use std::{
+    collections::HashMap,
     ffi::{CStr, CString, OsStr},
-    mem::ManuallyDrop,
+    fmt,
+    mem::{ManuallyDrop, MaybeUninit},
     os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, PoisonError, RwLock,
+    },
     time::Duration,
 };
 
 use tracing::error;
 
+#[cfg(embedder_add_view)]
+use crate::CompositorRef;
 use crate::{
-    sys, AOTData, Compositor, CompositorUserData, CustomTaskRunnerUserData, CustomTaskRunners,
-    KeyEvent, PointerEvent, RendererConfig, RendererUserData, SemanticsUpdate, ViewId,
-    WindowMetricsEvent,
+    sys, util::UserDataPool, AOTData, Compositor, CompositorUserData, CustomTaskRunnerUserData,
+    CustomTaskRunners, EngineContext, FrameCallbacks, JsonValue, KeyEvent, PointerEvent,
+    RendererConfig, RendererUserData, SemanticsUpdate, ViewFocusEvent, ViewId, WindowMetricsEvent,
 };
 
 #[repr(transparent)]
 #[derive(Debug, Hash, PartialEq, Eq)] // HashSet?
 pub struct VsyncBaton(pub isize);
 
+/// The engine pointer, for things that outlive a single callback and may be used from any thread,
+/// like a [`PlatformMessageResponse`].
+///
+/// It is null until [`Engine::run`] returns, and again as soon as the engine starts shutting down,
+/// so that nothing can use the engine once it's gone.
+#[derive(Clone)]
+struct SharedEngine(Arc<RwLock<EnginePtr>>);
+
+struct EnginePtr(sys::FlutterEngine);
+
+// SAFETY: `SharedEngine` only passes the engine to embedder calls that may be made from any thread,
+// and never after shutdown.
+unsafe impl Send for EnginePtr {}
+unsafe impl Sync for EnginePtr {}
+
+impl SharedEngine {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(EnginePtr(std::ptr::null_mut()))))
+    }
+
+    fn get(&self) -> sys::FlutterEngine {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).0
+    }
+
+    fn set(&self, engine: sys::FlutterEngine) {
+        self.0.write().unwrap_or_else(PoisonError::into_inner).0 = engine;
+    }
+
+    /// Calls `f` with the engine, unless it isn't running. Shutdown waits for `f` to return.
+    ///
+    /// `f` must only make embedder calls that are safe to make from any thread.
+    fn with<R>(&self, f: impl FnOnce(sys::FlutterEngine) -> R) -> Option<R> {
+        let engine = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        (!engine.0.is_null()).then(|| f(engine.0))
+    }
+}
+
 pub struct PlatformMessageResponse {
-    engine: sys::FlutterEngine,
-    handle: *const sys::FlutterPlatformMessageResponseHandle,
+    target: ResponseTarget,
+}
+
+impl fmt::Debug for PlatformMessageResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let target = match self.target {
+            ResponseTarget::Engine { .. } => "engine",
+            ResponseTarget::Mock(_) => "mock",
+        };
+        f.debug_struct("PlatformMessageResponse")
+            .field("target", &target)
+            .finish_non_exhaustive()
+    }
+}
+
+enum ResponseTarget {
+    Engine {
+        engine: SharedEngine,
+        handle: ResponseHandle,
+    },
+    /// See [`crate::mock::MockEngine::send_platform_message`].
+    Mock(Arc<Mutex<Option<Vec<u8>>>>),
 }
 
-// TODO: is this safe?
-unsafe impl Send for PlatformMessageResponse {}
+struct ResponseHandle(*const sys::FlutterPlatformMessageResponseHandle);
+
+// SAFETY: the handle is only ever given back to the engine once, in `SendPlatformMessageResponse`,
+// which completes the response by posting it to the UI thread, and so may be called from any thread.
+unsafe impl Send for ResponseHandle {}
 
 impl PlatformMessageResponse {
+    pub(crate) fn mock(response: Arc<Mutex<Option<Vec<u8>>>>) -> Self {
+        Self {
+            target: ResponseTarget::Mock(response),
+        }
+    }
+
+    /// Sends the response. This may be done from any thread.
+    ///
+    /// Fails with [`crate::Error::InvalidArguments`] if the engine has already been shut down.
     pub fn send(self, response: &[u8]) -> crate::Result<()> {
         let this = ManuallyDrop::new(self);
-        unsafe {
-            // SAFETY: This is copied to a `std::vector` in the engine before the call returns.
-            // https://github.com/flutter/engine/blob/94dac953a95fde7da32cfe092f6350aaa4f93c3d/shell/platform/embedder/embedder.cc#L3005-L3006C11
-            // Therefore, we do not need to keep the slice alive.
-            sys::SendPlatformMessageResponse(
-                this.engine,
-                this.handle,
-                response.as_ptr(),
-                response.len(),
-            )
+        // SAFETY: `this` is never dropped, so the target is only ever read out of it once.
+        let target = unsafe { std::ptr::read(&raw const this.target) };
+        match target {
+            ResponseTarget::Engine { engine, handle } => engine
+                .with(|engine| {
+                    unsafe {
+                        // SAFETY: This is copied to a `std::vector` in the engine before the call returns.
+                        // https://github.com/flutter/engine/blob/94dac953a95fde7da32cfe092f6350aaa4f93c3d/shell/platform/embedder/embedder.cc#L3005-L3006C11
+                        // Therefore, we do not need to keep the slice alive.
+                        sys::SendPlatformMessageResponse(
+                            engine,
+                            handle.0,
+                            response.as_ptr(),
+                            response.len(),
+                        )
+                    }
+                    .to_result()
+                })
+                .unwrap_or(Err(crate::Error::InvalidArguments)),
+            ResponseTarget::Mock(slot) => {
+                *slot.lock().unwrap_or_else(PoisonError::into_inner) = Some(response.to_vec());
+                Ok(())
+            }
         }
-        .to_result()
     }
 }
 
@@ -54,6 +142,9 @@ impl Drop for PlatformMessageResponse {
     }
 }
 
+/// The callbacks that the engine makes to the embedder.
+///
+/// Those that are made on the platform thread get an [`EngineContext`], to call back into the engine.
 pub trait EngineHandler {
     /// The callback invoked by the engine in order to give the embedder the
     /// chance to respond to platform messages from the Dart application.
@@ -71,6 +162,7 @@ pub trait EngineHandler {
     /// Failure to do so will leak memory.
     fn platform_message(
         &mut self,
+        cx: &mut EngineContext,
         channel: &CStr,
         message: &[u8],
         response: PlatformMessageResponse,
@@ -93,7 +185,7 @@ pub trait EngineHandler {
     ///
     /// The callback will be invoked on the thread on which the `FlutterEngineRun`
     /// call is made.
-    fn update_semantics(&mut self, update: SemanticsUpdate);
+    fn update_semantics(&mut self, cx: &mut EngineContext, update: SemanticsUpdate);
 
     // Logging callback for Dart application messages.
     //
@@ -111,49 +203,317 @@ pub trait EngineHandler {
     // the engine starts.
     //
     // The first argument is the `user_data` from `FlutterEngineInitialize`.
-    fn on_pre_engine_restart(&mut self);
+    fn on_pre_engine_restart(&mut self, cx: &mut EngineContext);
 
     /// The callback invoked by the engine in response to a channel listener
     /// being registered on the framework side. The callback is invoked from
     /// a task posted to the platform thread.
     ///
     /// The first parameter is the name of the channel. The second parameter is true if a listener has been set, false if one has been cleared.
-    fn channel_update(&mut self, channel: &CStr, listening: bool);
+    ///
+    /// Never called if the engine was built from an `embedder.h` without channel updates.
+    fn channel_update(&mut self, cx: &mut EngineContext, channel: &CStr, listening: bool);
+
+    /// The framework wants input focus to move to or from a view, e.g. because the user tabbed out of its last focusable widget.
+    /// The platform should focus (or unfocus) the window of that view, and then tell the engine with [`Engine::send_view_focus_event`].
+    ///
+    /// Never called if the engine was built from an `embedder.h` without view focus events.
+    fn view_focus_change_request(&mut self, cx: &mut EngineContext, request: ViewFocusEvent);
 
     /// The callback invoked by the engine in root isolate scope.
     /// Called immediately after the root isolate has been created and marked runnable.
+    ///
+    /// Its service ID is sent on [`crate::ISOLATE_CHANNEL`] a little later; see [`crate::IsolateChannel`].
     fn root_isolate_created(&mut self);
 }
 
+/// Identifies an [`Engine`], for things it hands out that must only ever be given back to that same engine.
+///
+/// Unlike the engine pointer, this is never reused after the engine is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EngineId(u64);
+
+impl EngineId {
+    /// Not any engine's id.
+    pub(crate) const NONE: Self = Self(0);
+
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
 pub(crate) struct InnerEngine {
     pub(crate) engine: sys::FlutterEngine,
+    pub(crate) id: EngineId,
     // Actually a Box<EngineUserData>, but kept as a raw pointer.
     // This ensures that it has no mutable references, which would be unsound.
     // It must still be dropped like a Box when the engine is dropped.
     user_data: *mut EngineUserData,
+    /// Channel names passed as `&str` to [`Engine::send_platform_message_str`], up to [`MAX_CHANNEL_NAMES`].
+    channel_names: HashMap<Box<str>, Arc<CStr>>,
+    pub(crate) frame_callbacks: FrameCallbacks,
 }
 
 impl Drop for InnerEngine {
     fn drop(&mut self) {
+        // waits for any platform message responses that are being sent right now
+        unsafe { &(*self.user_data).engine }.set(std::ptr::null_mut());
         unsafe { sys::Shutdown(self.engine) };
+        crate::aot::dart_vm_engine_shut_down();
+        unsafe { pending::cancel_all(self.engine) };
         let user_data = unsafe { Box::from_raw(self.user_data) };
         drop(user_data);
     }
 }
 
+/// One-shot callbacks that the engine hasn't called yet.
+///
+/// The engine drops the callbacks that are still pending when it shuts down, without calling them,
+/// so every one-shot callback is registered here until it's called.
+/// When the [`Engine`] is dropped, the ones that are left are cancelled with a result saying that
+/// the operation didn't happen; see [`Engine`] for what each one gets.
+pub(crate) mod pending {
+    use std::{
+        collections::HashMap,
+        ffi::c_void,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            LazyLock, Mutex, PoisonError,
+        },
+        time::Duration,
+    };
+
+    use crate::sys;
+
+    /// When to give up on a callback, in the engine's clock, and what to do then.
+    type Timeout = (Duration, unsafe fn(*mut c_void));
+
+    struct Pending {
+        engine: usize,
+        /// `None` once it has timed out. The entry is kept as a tombstone, so that the late callback is ignored.
+        user_data: Option<usize>,
+        cancel: unsafe fn(*mut c_void),
+        timeout: Option<Timeout>,
+    }
+
+    /// Keyed by the address of the user data, or a token for callbacks with a timeout.
+    static PENDING: LazyLock<Mutex<HashMap<usize, Pending>>> = LazyLock::new(Mutex::default);
+
+    /// Registers `user_data` before it is given to the engine.
+    ///
+    /// `cancel` takes ownership of `user_data`, and is called if the engine shuts down before calling back.
+    pub(crate) fn register(
+        engine: sys::FlutterEngine,
+        user_data: *mut c_void,
+        cancel: unsafe fn(*mut c_void),
+    ) {
+        insert(engine, user_data as usize, user_data, cancel, None);
+    }
+
+    /// Like [`register`], but `time_out` is called by [`expire`] once `deadline` has passed.
+    /// It takes ownership of `user_data` just like `cancel`.
+    ///
+    /// Returns the token to give to the engine in place of `user_data`. Tokens are never reused,
+    /// so the tombstone that a timed out callback leaves behind can't be mistaken for another callback.
+    pub(crate) fn register_with_timeout(
+        engine: sys::FlutterEngine,
+        user_data: *mut c_void,
+        cancel: unsafe fn(*mut c_void),
+        deadline: Duration,
+        time_out: unsafe fn(*mut c_void),
+    ) -> *mut c_void {
+        // odd, so never the address of any user data
+        static NEXT_TOKEN: AtomicUsize = AtomicUsize::new(1);
+        let token = NEXT_TOKEN.fetch_add(2, Ordering::Relaxed);
+        insert(engine, token, user_data, cancel, Some((deadline, time_out)));
+        token as *mut c_void
+    }
+
+    fn insert(
+        engine: sys::FlutterEngine,
+        key: usize,
+        user_data: *mut c_void,
+        cancel: unsafe fn(*mut c_void),
+        timeout: Option<Timeout>,
+    ) {
+        PENDING
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                key,
+                Pending {
+                    engine: engine as usize,
+                    user_data: Some(user_data as usize),
+                    cancel,
+                    timeout,
+                },
+            );
+    }
+
+    /// Unregisters what was given to the engine as user data, when the engine calls back
+    /// (or won't, because the call failed).
+    ///
+    /// Returns the user data, or `None` if it was already cancelled or timed out, in which case there's nothing to call back.
+    pub(crate) fn complete(key: *mut c_void) -> Option<*mut c_void> {
+        PENDING
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&(key as usize))
+            .and_then(|pending| pending.user_data)
+            .map(|user_data| user_data as *mut c_void)
+    }
+
+    /// Times out the callbacks for `engine` whose deadline is at or before `now`.
+    /// Returns the next deadline that hasn't passed yet, if any.
+    ///
+    /// # Safety
+    ///
+    /// Must be called on the thread that `engine` calls these callbacks on, so that none of them run at the same time.
+    pub(crate) unsafe fn expire(engine: sys::FlutterEngine, now: Duration) -> Option<Duration> {
+        let mut expired = Vec::new();
+        let mut next = None::<Duration>;
+        {
+            let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+            for pending in pending.values_mut() {
+                if pending.engine != engine as usize {
+                    continue;
+                }
+                match pending.timeout {
+                    Some((deadline, time_out)) if deadline <= now => {
+                        pending.timeout = None;
+                        if let Some(user_data) = pending.user_data.take() {
+                            expired.push((user_data, time_out));
+                        }
+                    }
+                    Some((deadline, _)) => next = Some(next.map_or(deadline, |d| d.min(deadline))),
+                    None => {}
+                }
+            }
+        }
+        // the lock isn't held here, because timing out calls into user code
+        for (user_data, time_out) in expired {
+            unsafe { time_out(user_data as *mut c_void) };
+        }
+        next
+    }
+
+    /// Cancels every callback that is still pending for `engine`, and forgets its tombstones.
+    ///
+    /// # Safety
+    ///
+    /// `engine` must be shut down, so that it can't call any of them anymore.
+    pub(crate) unsafe fn cancel_all(engine: sys::FlutterEngine) {
+        let cancelled = {
+            let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+            let keys = pending
+                .iter()
+                .filter(|(_, pending)| pending.engine == engine as usize)
+                .map(|(&key, _)| key)
+                .collect::<Vec<_>>();
+            keys.into_iter()
+                .filter_map(|key| pending.remove(&key))
+                .filter_map(|pending| Some((pending.user_data?, pending.cancel)))
+                .collect::<Vec<_>>()
+        };
+        // the lock isn't held here, because cancelling calls into user code
+        for (user_data, cancel) in cancelled {
+            unsafe { cancel(user_data as *mut c_void) };
+        }
+    }
+}
+
+/// The error that [`Engine::send_platform_message_with_timeout`] calls back with
+/// when the framework didn't respond in time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ResponseTimedOut;
+
+impl fmt::Display for ResponseTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the platform message wasn't responded to in time")
+    }
+}
+
+impl std::error::Error for ResponseTimedOut {}
+
+/// The channel that Skia's settings are changed on. Messages use the `JSONMethodCodec`.
+pub const SKIA_CHANNEL: &CStr = c"flutter/skia";
+
+/// How many channel names [`Engine::send_platform_message_str`] keeps, so that made-up names can't grow it forever.
+const MAX_CHANNEL_NAMES: usize = 64;
+
+/// A running Flutter engine. Dropping it shuts the engine down.
+///
+/// One-shot callbacks that the engine hasn't called by the time it shuts down are cancelled:
+/// - [`Engine::send_key_event`], [`Engine::add_view`] and [`Engine::remove_view`] call back with `false`.
+/// - [`Engine::send_platform_message`] and [`Engine::send_platform_message_with_timeout`] call back with an empty response.
+/// - [`Engine::set_next_frame_callback`] and [`Engine::post_render_thread_task`] drop the callback without calling it.
 #[repr(transparent)]
 pub struct Engine {
     pub(crate) inner: InnerEngine,
 }
 
+impl fmt::Debug for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Engine")
+            .field("engine", &self.inner.engine)
+            .field("id", &self.inner.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Engine {
+    /// An `Engine` for a pointer that belongs to another one. It must never be dropped.
+    ///
+    /// It has no user data, so anything that needs it (like window metrics) fails with [`crate::Error::InvalidArguments`].
+    ///
+    /// # Safety
+    ///
+    /// `engine` must be null, or a running engine for as long as this is used.
+    pub(crate) unsafe fn borrowed(engine: sys::FlutterEngine) -> ManuallyDrop<Self> {
+        ManuallyDrop::new(Self {
+            inner: InnerEngine {
+                engine,
+                id: EngineId::NONE,
+                user_data: std::ptr::null_mut(),
+                channel_names: HashMap::new(),
+                frame_callbacks: FrameCallbacks::new(),
+            },
+        })
+    }
+
+    /// The engine handle, for calling functions in [`crate::sys`] that the safe API doesn't wrap yet.
+    ///
+    /// The handle is only valid until this `Engine` is dropped. The engine's user data belongs to this crate,
+    /// so it must not be passed to anything that replaces it or reads it.
+    #[cfg(feature = "sys")]
+    #[must_use]
+    pub fn as_raw(&self) -> sys::FlutterEngine {
+        self.inner.engine
+    }
+}
+
 #[repr(transparent)]
 pub struct InitializedEngine {
     inner: InnerEngine,
 }
 
+impl fmt::Debug for InitializedEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InitializedEngine")
+            .field("engine", &self.inner.engine)
+            .field("id", &self.inner.id)
+            .finish_non_exhaustive()
+    }
+}
+
 mod callbacks {
     use super::*;
 
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "trace", skip_all, fields(channel, bytes))
+    )]
     pub extern "C" fn platform_message(
         message: *const sys::FlutterPlatformMessage,
         user_data: *mut std::ffi::c_void,
@@ -170,17 +530,30 @@ mod callbacks {
                 message.message_size,
             )
         };
+        #[cfg(feature = "instrument")]
+        tracing::Span::current()
+            .record("channel", tracing::field::debug(channel))
+            .record("bytes", message_content.len());
 
         let response = PlatformMessageResponse {
-            engine: user_data.engine,
-            handle: message.response_handle,
+            target: ResponseTarget::Engine {
+                engine: user_data.engine.clone(),
+                handle: ResponseHandle(message.response_handle),
+            },
         };
 
-        user_data
-            .handler
-            .platform_message(channel, message_content, response)
+        user_data.handler.platform_message(
+            &mut user_data.context(),
+            channel,
+            message_content,
+            response,
+        )
     }
 
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "trace", skip(user_data))
+    )]
     pub extern "C" fn vsync(user_data: *mut std::ffi::c_void, baton: isize) {
         let user_data = user_data.cast::<EngineUserData>();
         let user_data = unsafe { &mut *user_data };
@@ -188,6 +561,7 @@ mod callbacks {
         user_data.handler.vsync(VsyncBaton(baton))
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn log_message(
         tag: *const std::os::raw::c_char,
         message: *const std::os::raw::c_char,
@@ -202,13 +576,22 @@ mod callbacks {
         user_data.handler.log_message(tag, message)
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn on_pre_engine_restart(user_data: *mut std::ffi::c_void) {
         let user_data = user_data.cast::<EngineUserData>();
         let user_data = unsafe { &mut *user_data };
 
-        user_data.handler.on_pre_engine_restart()
+        user_data
+            .handler
+            .on_pre_engine_restart(&mut user_data.context());
+
+        // the restarted isolate starts out with no metrics, which would leave every view blank
+        if let Err(err) = user_data.resend_view_metrics(user_data.engine.get()) {
+            error!("failed to resend window metrics after a restart: {err}");
+        }
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn update_semantics(
         update: *const sys::FlutterSemanticsUpdate2,
         user_data: *mut std::ffi::c_void,
@@ -220,9 +603,13 @@ mod callbacks {
 
         user_data
             .handler
-            .update_semantics(SemanticsUpdate::from_raw(update))
+            .update_semantics(&mut user_data.context(), unsafe {
+                SemanticsUpdate::from_raw(update)
+            })
     }
 
+    #[cfg(embedder_channel_update)]
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn channel_update_callback(
         channel_update: *const sys::FlutterChannelUpdate,
         user_data: *mut std::ffi::c_void,
@@ -235,9 +622,28 @@ mod callbacks {
         let channel = unsafe { CStr::from_ptr(channel_update.channel) };
         let listening = channel_update.listening;
 
-        user_data.handler.channel_update(channel, listening)
+        user_data
+            .handler
+            .channel_update(&mut user_data.context(), channel, listening)
     }
 
+    #[cfg(embedder_view_focus)]
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
+    pub extern "C" fn view_focus_change_request(
+        request: *const sys::FlutterViewFocusChangeRequest,
+        user_data: *mut std::ffi::c_void,
+    ) {
+        let user_data = user_data.cast::<EngineUserData>();
+        let user_data = unsafe { &mut *user_data };
+
+        let request = unsafe { &*request };
+
+        user_data
+            .handler
+            .view_focus_change_request(&mut user_data.context(), request.into());
+    }
+
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn root_isolate_create(user_data: *mut std::ffi::c_void) {
         let user_data = user_data.cast::<EngineUserData>();
         let user_data = unsafe { &mut *user_data };
@@ -251,12 +657,15 @@ mod callbacks {
     const _: sys::FlutterLogMessageCallback = Some(log_message);
     const _: sys::VoidCallback = Some(on_pre_engine_restart);
     const _: sys::FlutterUpdateSemanticsCallback2 = Some(update_semantics);
+    #[cfg(embedder_channel_update)]
     const _: sys::FlutterChannelUpdateCallback = Some(channel_update_callback);
+    #[cfg(embedder_view_focus)]
+    const _: sys::FlutterViewFocusChangeRequestCallback = Some(view_focus_change_request);
     const _: sys::VoidCallback = Some(root_isolate_create);
 }
 
 pub(crate) struct EngineUserData {
-    engine: sys::FlutterEngine,
+    engine: SharedEngine,
     pub(crate) renderer_user_data: RendererUserData,
 
     // Option<(Box<_>, Box<_>)>
@@ -265,12 +674,43 @@ pub(crate) struct EngineUserData {
         *mut sys::FlutterCustomTaskRunners,
     )>,
     compositor: Option<(*mut CompositorUserData, *mut sys::FlutterCompositor)>,
-    #[allow(dead_code)] // no custom drop glue, but must be kept alive.
-    aot_data: Option<Arc<AOTData>>,
+
+    /// The latest metrics of every view, to send again when the engine restarts or a display changes.
+    view_metrics: Mutex<HashMap<ViewId, WindowMetricsEvent>>,
+    /// The last value passed to `update_semantics_enabled`, shared with every [`EngineContext`].
+    pub(crate) semantics_enabled: Arc<AtomicBool>,
+    /// The user data of one-shot callbacks, which are all cancelled before this is dropped.
+    user_data_pool: UserDataPool,
 
     handler: Box<dyn EngineHandler>,
 }
 
+impl EngineUserData {
+    pub(crate) fn view_metrics(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<ViewId, WindowMetricsEvent>> {
+        self.view_metrics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sends the latest metrics of every view again.
+    fn resend_view_metrics(&self, engine: sys::FlutterEngine) -> crate::Result<()> {
+        let metrics = self.view_metrics().values().copied().collect::<Vec<_>>();
+        for metrics in metrics {
+            let event = metrics.into();
+            unsafe { sys::SendWindowMetricsEvent(engine, &raw const event) }.to_result()?;
+        }
+        Ok(())
+    }
+
+    /// For callbacks on the platform thread.
+    fn context(&self) -> EngineContext {
+        // SAFETY: the engine isn't shut down during a callback; it's null before it runs and once it starts shutting down
+        unsafe { EngineContext::new(self.engine.get(), self.semantics_enabled.clone()) }
+    }
+}
+
 impl Drop for EngineUserData {
     fn drop(&mut self) {
         if let Some((compositor_user_data, compositor)) = self.compositor {
@@ -392,9 +832,9 @@ pub struct ProjectArgs<'a> {
     /// The AOT data to be used in AOT operation.
     ///
     /// The AOT data can be created with [`AOTData::new`], and will be released when the object is dropped.
-    /// The engine holds an `Arc` to the data, so it will not be dropped until the engine is dropped.
-    /// If you pass `shutdown_dart_vm_when_done: true`, the AOT data will **not** be dropped when the engine is dropped.
-    /// In fact, it won't *ever* be dropped, because the Dart VM will not shut down. It will cause a memory leak.
+    /// The Dart VM may use it for as long as it's running, so an `Arc` to it is kept until the VM shuts down:
+    /// when the last engine is dropped, if the engine that started the VM had `shutdown_dart_vm_when_done` set.
+    /// Otherwise the VM, and the AOT data it used, is kept until the process exits.
     pub aot_data: Option<Arc<AOTData>>,
 
     pub handler: Box<dyn EngineHandler>,
@@ -414,6 +854,36 @@ pub struct ProjectArgs<'a> {
     pub compute_platform_resolved_locale: sys::FlutterComputePlatformResolvedLocaleCallback,
 }
 
+impl fmt::Debug for ProjectArgs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProjectArgs")
+            .field("assets_path", &self.assets_path)
+            .field("icu_data_path", &self.icu_data_path)
+            .field("command_line_argv", &self.command_line_argv)
+            .field("persistent_cache_path", &self.persistent_cache_path)
+            .field(
+                "is_persistent_cache_read_only",
+                &self.is_persistent_cache_read_only,
+            )
+            .field("custom_dart_entrypoint", &self.custom_dart_entrypoint)
+            .field("custom_task_runners", &self.custom_task_runners)
+            .field(
+                "shutdown_dart_vm_when_done",
+                &self.shutdown_dart_vm_when_done,
+            )
+            .field("compositor", &self.compositor)
+            .field("dart_entrypoint_argv", &self.dart_entrypoint_argv)
+            .field("log_tag", &self.log_tag)
+            .field("dart_old_gen_heap_size", &self.dart_old_gen_heap_size)
+            .field("aot_data", &self.aot_data)
+            .field(
+                "compute_platform_resolved_locale",
+                &self.compute_platform_resolved_locale,
+            )
+            .finish_non_exhaustive()
+    }
+}
+
 // impl InitializedEngine {
 //     pub fn run(self) -> crate::Result<Engine> {
 //         unsafe { sys::FlutterEngineRunInitialized(self.inner.engine) }
@@ -423,6 +893,7 @@ pub struct ProjectArgs<'a> {
 // }
 
 impl Engine {
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn run(
         renderer_config: impl Into<RendererConfig>,
         project_args: ProjectArgs,
@@ -437,28 +908,25 @@ impl Engine {
             (compositor_user_data, Box::into_raw(Box::new(compositor)))
         });
 
+        let id = EngineId::next();
+
         let custom_task_runners = project_args.custom_task_runners.map(|compositor| {
             let (compositor_user_data, compositor) = compositor.into();
+            compositor_user_data.set_engine(id);
             (
                 Box::into_raw(Box::new(compositor_user_data)),
                 Box::into_raw(Box::new(compositor)),
             )
         });
 
-        // Intentionally cause a memory leak.
-        // If the Dart VM is not shut down when the engine is, it is unsafe to drop the AOT data, ever.
-        if project_args.shutdown_dart_vm_when_done {
-            if let Some(ref aot_data) = project_args.aot_data {
-                let _ = Arc::into_raw(aot_data.clone());
-            }
-        }
-
         let user_data = Box::new(EngineUserData {
-            engine: std::ptr::null_mut(),
+            engine: SharedEngine::new(),
             renderer_user_data,
             compositor,
             custom_task_runners,
-            aot_data: project_args.aot_data.clone(),
+            view_metrics: Mutex::new(HashMap::new()),
+            semantics_enabled: Arc::new(AtomicBool::new(false)),
+            user_data_pool: UserDataPool::new(),
             handler: project_args.handler,
         });
 
@@ -544,7 +1012,10 @@ impl Engine {
             update_semantics_callback2: Some(callbacks::update_semantics),
             log_message_callback: Some(callbacks::log_message),
             on_pre_engine_restart_callback: Some(callbacks::on_pre_engine_restart),
+            #[cfg(embedder_channel_update)]
             channel_update_callback: Some(callbacks::channel_update_callback),
+            #[cfg(embedder_view_focus)]
+            view_focus_change_request_callback: Some(callbacks::view_focus_change_request),
             root_isolate_create_callback: Some(callbacks::root_isolate_create),
 
             // this callback is fucking stupid and doesn't take user data. but i will allow it to be used, still.
@@ -600,7 +1071,8 @@ impl Engine {
 
         let user_data = Box::into_raw(user_data);
 
-        unsafe {
+        let starts_vm = crate::aot::dart_vm_engine_launched(project_args.aot_data.as_ref());
+        let result = unsafe {
             sys::Run(
                 sys::FLUTTER_ENGINE_VERSION,
                 &raw const raw_renderer_config,
@@ -609,9 +1081,20 @@ impl Engine {
                 &raw mut engine,
             )
         }
-        .to_result()
-        .map(|()| {
-            let inner = InnerEngine { engine, user_data };
+        .to_result();
+        if result.is_err() {
+            crate::aot::dart_vm_engine_shut_down();
+        }
+        result.map(|()| {
+            crate::aot::dart_vm_engine_running(starts_vm, project_args.shutdown_dart_vm_when_done);
+            unsafe { &(*user_data).engine }.set(engine);
+            let inner = InnerEngine {
+                engine,
+                id,
+                user_data,
+                channel_names: HashMap::new(),
+                frame_callbacks: FrameCallbacks::new(),
+            };
             Self { inner }
         })
     }
@@ -633,6 +1116,11 @@ impl Engine {
     ///
     /// Returns the result of *starting* the asynchronous operation.
     /// If [`Ok()`], the `callback` will be invoked.
+    /// Metrics that the engine would reject fail with [`crate::Error::InvalidWindowMetrics`] instead.
+    ///
+    /// Once the view is added, [`crate::CompositorHandler::view_added`] is called before `callback`.
+    #[cfg(embedder_add_view)]
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(view_id = view_id.0)))]
     pub fn add_view(
         &mut self,
         view_id: ViewId,
@@ -643,21 +1131,44 @@ impl Engine {
             callback: Box<dyn FnOnce(bool)>,
         }
 
+        unsafe fn cancel(user_data: *mut std::ffi::c_void) {
+            let user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
+            (user_data.callback)(false);
+        }
+
+        #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
         extern "C" fn add_view_callback(result: *const sys::FlutterAddViewResult) {
             let result = unsafe { &*result };
-            let user_data = *unsafe { Box::from_raw(result.user_data.cast::<UserData>()) };
+            let Some(user_data) = pending::complete(result.user_data) else {
+                return;
+            };
+            let user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
             (user_data.callback)(result.added);
         }
 
         const _: sys::FlutterAddViewCallback = Some(add_view_callback);
 
-        let user_data = Box::new(UserData {
-            callback: Box::new(callback),
-        });
-
-        let user_data = Box::into_raw(user_data);
+        view_metrics.validate()?;
+        let compositor = self.compositor();
+        let user_data = UserDataPool::alloc(
+            self.user_data_pool(),
+            UserData {
+                callback: Box::new(move |added| {
+                    if let (true, Some(compositor)) = (added, compositor) {
+                        // the compositor outlives every pending callback
+                        unsafe { compositor.view_added(view_id) };
+                    }
+                    callback(added);
+                }),
+            },
+        );
+        pending::register(self.inner.engine, user_data.cast(), cancel);
 
-        let view_metrics = view_metrics.into();
+        let metrics = WindowMetricsEvent {
+            view_id,
+            ..view_metrics
+        };
+        let view_metrics = metrics.into();
 
         let info = sys::FlutterAddViewInfo {
             struct_size: std::mem::size_of::<sys::FlutterAddViewInfo>(),
@@ -671,8 +1182,10 @@ impl Engine {
 
         if result.is_err() {
             // the callback will never be invoked
-            let user_data = unsafe { Box::from_raw(user_data) };
-            drop(user_data);
+            pending::complete(user_data.cast());
+            drop(unsafe { UserDataPool::take(user_data) });
+        } else if let Ok(user_data) = self.user_data() {
+            user_data.view_metrics().insert(view_id, metrics);
         }
 
         result
@@ -692,6 +1205,10 @@ impl Engine {
     ///
     /// Returns the result of *starting* the asynchronous operation.
     /// If [`Ok()`], the `callback` will be invoked.
+    ///
+    /// Once the view is removed, [`crate::CompositorHandler::view_removed`] is called before `callback`.
+    #[cfg(embedder_add_view)]
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(view_id = view_id.0)))]
     pub fn remove_view(
         &mut self,
         view_id: ViewId,
@@ -701,19 +1218,37 @@ impl Engine {
             callback: Box<dyn FnOnce(bool)>,
         }
 
+        unsafe fn cancel(user_data: *mut std::ffi::c_void) {
+            let user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
+            (user_data.callback)(false);
+        }
+
+        #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
         extern "C" fn remove_view_callback(result: *const sys::FlutterRemoveViewResult) {
             let result = unsafe { &*result };
-            let user_data = *unsafe { Box::from_raw(result.user_data.cast::<UserData>()) };
+            let Some(user_data) = pending::complete(result.user_data) else {
+                return;
+            };
+            let user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
             (user_data.callback)(result.removed);
         }
 
         const _: sys::FlutterRemoveViewCallback = Some(remove_view_callback);
 
-        let user_data = Box::new(UserData {
-            callback: Box::new(callback),
-        });
-
-        let user_data = Box::into_raw(user_data);
+        let compositor = self.compositor();
+        let user_data = UserDataPool::alloc(
+            self.user_data_pool(),
+            UserData {
+                callback: Box::new(move |removed| {
+                    if let (true, Some(compositor)) = (removed, compositor) {
+                        // the compositor outlives every pending callback
+                        unsafe { compositor.view_removed(view_id) };
+                    }
+                    callback(removed);
+                }),
+            },
+        );
+        pending::register(self.inner.engine, user_data.cast(), cancel);
 
         let info = sys::FlutterRemoveViewInfo {
             struct_size: std::mem::size_of::<sys::FlutterAddViewInfo>(),
@@ -726,24 +1261,114 @@ impl Engine {
 
         if result.is_err() {
             // the callback will never be invoked
-            let user_data = unsafe { Box::from_raw(user_data) };
-            drop(user_data);
+            pending::complete(user_data.cast());
+            drop(unsafe { UserDataPool::take(user_data) });
+        } else if let Ok(user_data) = self.user_data() {
+            user_data.view_metrics().remove(&view_id);
         }
 
         result
     }
 
-    pub fn send_window_metrics_event(&mut self, event: WindowMetricsEvent) -> crate::Result<()> {
+    /// Sends new metrics for a view.
+    ///
+    /// Metrics that the engine would reject fail with [`crate::Error::InvalidWindowMetrics`], saying what's wrong with them.
+    ///
+    /// The latest metrics of each view are kept, and sent again whenever the engine restarts (e.g. hot restart)
+    /// or [`Engine::notify_display_update`] changes the pixel ratio of the display a view is on.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(view_id = metrics.view_id.0)))]
+    pub fn send_window_metrics_event(&mut self, metrics: WindowMetricsEvent) -> crate::Result<()> {
+        metrics.validate()?;
+        let event = metrics.into();
+
+        unsafe { sys::SendWindowMetricsEvent(self.inner.engine, &raw const event) }.to_result()?;
+        // a borrowed engine has nowhere to keep them, but the event still goes through
+        if let Ok(user_data) = self.user_data() {
+            user_data.view_metrics().insert(metrics.view_id, metrics);
+        }
+        Ok(())
+    }
+
+    /// The latest metrics sent for a view, through [`Engine::send_window_metrics_event`] or [`Engine::add_view`].
+    #[must_use]
+    pub fn view_metrics(&self, view_id: ViewId) -> Option<WindowMetricsEvent> {
+        self.user_data().ok()?.view_metrics().get(&view_id).copied()
+    }
+
+    /// The latest metrics of every view.
+    pub(crate) fn all_view_metrics(&self) -> Vec<WindowMetricsEvent> {
+        self.user_data()
+            .map(|user_data| user_data.view_metrics().values().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Sends the latest metrics of every view again.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
+    pub fn resend_view_metrics(&mut self) -> crate::Result<()> {
+        self.user_data()?.resend_view_metrics(self.inner.engine)
+    }
+
+    /// Whether this is the engine inside an [`EngineContext`], which has no user data.
+    pub(crate) fn is_borrowed(&self) -> bool {
+        self.inner.user_data.is_null()
+    }
+
+    #[cfg(embedder_add_view)]
+    fn compositor(&self) -> Option<CompositorRef> {
+        let (compositor, _) = self.user_data().ok()?.compositor?;
+        Some(CompositorRef::new(compositor))
+    }
+
+    /// Fails with [`crate::Error::InvalidArguments`] for a borrowed engine, which has no user data.
+    pub(crate) fn user_data(&self) -> crate::Result<&EngineUserData> {
+        if self.is_borrowed() {
+            return Err(crate::Error::InvalidArguments);
+        }
+        Ok(unsafe { &*self.inner.user_data })
+    }
+
+    /// What the user data of one-shot callbacks is allocated from; null for a borrowed engine.
+    pub(crate) fn user_data_pool(&self) -> *const UserDataPool {
+        self.user_data().map_or(std::ptr::null(), |user_data| {
+            &raw const user_data.user_data_pool
+        })
+    }
+
+    /// Tells the engine that a view gained or lost input focus, e.g. because its window was activated.
+    ///
+    /// Only available with an `embedder.h` that has view focus events.
+    #[cfg(embedder_view_focus)]
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(view_id = event.view_id.0)))]
+    pub fn send_view_focus_event(&mut self, event: ViewFocusEvent) -> crate::Result<()> {
         let event = event.into();
 
-        unsafe { sys::SendWindowMetricsEvent(self.inner.engine, &raw const event) }.to_result()
+        unsafe { sys::SendViewFocusEvent(self.inner.engine, &raw const event) }.to_result()
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(events = events.len())))]
     pub fn send_pointer_event(&mut self, events: &[PointerEvent]) -> crate::Result<()> {
-        let events: Box<[sys::FlutterPointerEvent]> =
-            events.iter().copied().map(Into::into).collect();
+        // Pointer events usually come a few at a time, and this is called for every one of them,
+        // so the common case is converted on the stack instead of allocating.
+        const INLINE_EVENTS: usize = 8;
+
+        if events.len() > INLINE_EVENTS {
+            let events: Box<[sys::FlutterPointerEvent]> =
+                events.iter().copied().map(Into::into).collect();
+
+            return unsafe {
+                sys::SendPointerEvent(self.inner.engine, events.as_ptr(), events.len())
+            }
+            .to_result();
+        }
+
+        let mut buffer: [MaybeUninit<sys::FlutterPointerEvent>; INLINE_EVENTS] =
+            [const { MaybeUninit::uninit() }; INLINE_EVENTS];
+        for (slot, &event) in buffer.iter_mut().zip(events) {
+            slot.write(event.into());
+        }
 
-        unsafe { sys::SendPointerEvent(self.inner.engine, events.as_ptr(), events.len()) }
+        // the first `events.len()` elements were just initialized
+        unsafe { sys::SendPointerEvent(self.inner.engine, buffer.as_ptr().cast(), events.len()) }
             .to_result()
     }
 
@@ -755,6 +1380,7 @@ impl Engine {
     ///
     /// The callback invoked by the engine when the Flutter application
     /// has decided whether it handles this event.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn send_key_event(
         &mut self,
         event: KeyEvent,
@@ -764,18 +1390,32 @@ impl Engine {
             callback: Box<dyn FnOnce(bool)>,
         }
 
+        unsafe fn cancel(user_data: *mut std::ffi::c_void) {
+            let user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
+            (user_data.callback)(false);
+        }
+
+        #[cfg_attr(
+            feature = "instrument",
+            tracing::instrument(level = "trace", skip_all, fields(handled))
+        )]
         extern "C" fn key_event_callback(handled: bool, user_data: *mut std::ffi::c_void) {
-            let user_data = *unsafe { Box::from_raw(user_data.cast::<UserData>()) };
+            let Some(user_data) = pending::complete(user_data) else {
+                return;
+            };
+            let user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
             (user_data.callback)(handled);
         }
 
         const _: sys::FlutterKeyEventCallback = Some(key_event_callback);
 
-        let user_data = Box::new(UserData {
-            callback: Box::new(callback),
-        });
-
-        let user_data = Box::into_raw(user_data);
+        let user_data = UserDataPool::alloc(
+            self.user_data_pool(),
+            UserData {
+                callback: Box::new(callback),
+            },
+        );
+        pending::register(self.inner.engine, user_data.cast(), cancel);
 
         let (character, key_event) = event.into();
 
@@ -798,73 +1438,167 @@ impl Engine {
 
         if result.is_err() {
             // the callback will never be invoked
-            let user_data = unsafe { Box::from_raw(user_data) };
-            drop(user_data);
+            pending::complete(user_data.cast());
+            drop(unsafe { UserDataPool::take(user_data) });
         }
 
         result
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(channel = ?channel, bytes = message.len())))]
     pub fn send_platform_message(
         &mut self,
         channel: &CStr,
         message: &[u8],
         response: impl FnOnce(&[u8]) + 'static,
+    ) -> crate::Result<()> {
+        // without a timeout, the callback is never called with an error
+        self.send_platform_message_inner(
+            channel,
+            message,
+            None,
+            Box::new(move |result| response(result.unwrap_or_default())),
+        )
+    }
+
+    /// Like [`Engine::send_platform_message`], but gives up on the response after `timeout`.
+    ///
+    /// Once the timeout has passed, `response` is called with [`ResponseTimedOut`] and the response handle is released,
+    /// so that a request the framework never answers doesn't hold on to them. A response that comes in later is ignored.
+    ///
+    /// Timeouts are only noticed in [`Engine::expire_platform_messages`], which the embedder's event loop must call.
+    /// If nothing polls it, `response` is never timed out, and stays alive until the framework responds or the engine shuts down.
+    /// The event loops of [`Engine::spawn`], [`crate::HeadlessEngine`] and the `app` feature already poll it.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(channel = ?channel, bytes = message.len(), ?timeout)))]
+    pub fn send_platform_message_with_timeout(
+        &mut self,
+        channel: &CStr,
+        message: &[u8],
+        timeout: Duration,
+        response: impl FnOnce(Result<&[u8], ResponseTimedOut>) + 'static,
+    ) -> crate::Result<()> {
+        self.send_platform_message_inner(channel, message, Some(timeout), Box::new(response))
+    }
+
+    /// Calls back every platform message response whose timeout has passed with [`ResponseTimedOut`].
+    /// See [`Engine::send_platform_message_with_timeout`].
+    ///
+    /// Returns when the next timeout is, in the engine's clock (see [`Engine::get_current_time`]), if any.
+    /// That's never a [`crate::Clock`]: unlike platform tasks, timeouts don't follow a virtual clock.
+    /// The event loop should call this again by then, like it runs platform tasks.
+    /// This must be called on the platform thread.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
+    pub fn expire_platform_messages(&mut self) -> Option<Duration> {
+        // responses are called back on the platform thread, which is this one
+        unsafe { pending::expire(self.inner.engine, Self::get_current_time()) }
+    }
+
+    #[allow(clippy::type_complexity)] // not a complex type
+    fn send_platform_message_inner(
+        &mut self,
+        channel: &CStr,
+        message: &[u8],
+        timeout: Option<Duration>,
+        callback: Box<dyn FnOnce(Result<&[u8], ResponseTimedOut>)>,
     ) -> crate::Result<()> {
         struct UserData {
             engine: sys::FlutterEngine,
             response: *mut sys::FlutterPlatformMessageResponseHandle,
 
             #[allow(clippy::type_complexity)] // not a complex type
-            callback: Option<Box<dyn FnOnce(&[u8])>>,
+            callback: Option<Box<dyn FnOnce(Result<&[u8], ResponseTimedOut>)>>,
         }
 
-        impl Drop for UserData {
-            fn drop(&mut self) {
+        impl UserData {
+            fn release_response(&mut self) {
+                if self.response.is_null() {
+                    return;
+                }
                 unsafe { sys::PlatformMessageReleaseResponseHandle(self.engine, self.response) }
                     .to_result()
-                    .expect("releasing response handle never fails")
+                    .expect("releasing response handle never fails");
+                self.response = std::ptr::null_mut();
+            }
+        }
+
+        impl Drop for UserData {
+            fn drop(&mut self) {
+                self.release_response();
+            }
+        }
+
+        unsafe fn cancel(user_data: *mut std::ffi::c_void) {
+            let mut user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
+            // the engine is gone, and took the response handle with it
+            user_data.response = std::ptr::null_mut();
+            if let Some(callback) = user_data.callback.take() {
+                callback(Ok(&[]));
+            }
+        }
+
+        unsafe fn time_out(user_data: *mut std::ffi::c_void) {
+            // only a tombstone stays registered, for the engine to find if it responds after all
+            let mut user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
+            user_data.release_response();
+            if let Some(callback) = user_data.callback.take() {
+                callback(Err(ResponseTimedOut));
             }
         }
 
+        #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all, fields(bytes = size)))]
         extern "C" fn message_response(
             data: *const u8,
             size: usize,
             user_data: *mut std::ffi::c_void,
         ) {
-            let mut user_data = *unsafe { Box::from_raw(user_data.cast::<UserData>()) };
+            let Some(user_data) = pending::complete(user_data) else {
+                return;
+            };
+            let mut user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
 
             let data = unsafe { crate::util::slice_from_raw_parts_with_invalid_empty(data, size) };
-            let callback = user_data
-                .callback
-                .take()
-                .expect("callback is only called once");
-            callback(data);
+            if let Some(callback) = user_data.callback.take() {
+                callback(Ok(data));
+            }
         }
 
         const _: sys::FlutterDataCallback = Some(message_response);
 
-        let user_data = Box::new(UserData {
-            engine: self.inner.engine,
-            response: std::ptr::null_mut(),
-            callback: Some(Box::new(response)),
-        });
-
-        let user_data = Box::into_raw(user_data);
+        let user_data = UserDataPool::alloc(
+            self.user_data_pool(),
+            UserData {
+                engine: self.inner.engine,
+                response: std::ptr::null_mut(),
+                callback: Some(callback),
+            },
+        );
+        // what the engine calls back with
+        let key = if let Some(timeout) = timeout {
+            pending::register_with_timeout(
+                self.inner.engine,
+                user_data.cast(),
+                cancel,
+                Self::get_current_time() + timeout,
+                time_out,
+            )
+        } else {
+            pending::register(self.inner.engine, user_data.cast(), cancel);
+            user_data.cast()
+        };
 
         if let Err(err) = unsafe {
             sys::PlatformMessageCreateResponseHandle(
                 self.inner.engine,
                 Some(message_response),
-                user_data.cast::<std::ffi::c_void>(),
+                key,
                 &raw mut (*user_data).response,
             )
         }
         .to_result()
         {
             // the callback will never be invoked
-            let user_data = unsafe { Box::from_raw(user_data) };
-            drop(user_data);
+            pending::complete(key);
+            drop(unsafe { UserDataPool::take(user_data) });
             return Err(err);
         }
 
@@ -876,7 +1610,45 @@ impl Engine {
             response_handle: unsafe { (*user_data).response },
         };
 
-        unsafe { sys::SendPlatformMessage(self.inner.engine, &raw const message) }.to_result()
+        let result =
+            unsafe { sys::SendPlatformMessage(self.inner.engine, &raw const message) }.to_result();
+
+        if result.is_err() {
+            // the callback will never be invoked
+            pending::complete(key);
+            drop(unsafe { UserDataPool::take(user_data) });
+        }
+
+        result
+    }
+
+    /// Like [`Engine::send_platform_message`], but the channel name is a `&str`.
+    ///
+    /// The engine keeps the C strings of the first few channel names it's given this way,
+    /// so chatty channels like `flutter/textinput` don't allocate a new one for every message.
+    ///
+    /// Fails with [`crate::Error::InvalidArguments`] if `channel` contains a nul byte.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(channel = %channel, bytes = message.len())))]
+    pub fn send_platform_message_str(
+        &mut self,
+        channel: &str,
+        message: &[u8],
+        response: impl FnOnce(&[u8]) + 'static,
+    ) -> crate::Result<()> {
+        let name = if let Some(name) = self.inner.channel_names.get(channel) {
+            name.clone()
+        } else {
+            let name: Arc<CStr> = CString::new(channel)
+                .map_err(|_| crate::Error::InvalidArguments)?
+                .into();
+            if self.inner.channel_names.len() < MAX_CHANNEL_NAMES {
+                self.inner
+                    .channel_names
+                    .insert(channel.into(), name.clone());
+            }
+            name
+        };
+        self.send_platform_message(&name, message, response)
     }
 
     /// Notify the engine that a vsync event occurred.
@@ -895,6 +1667,7 @@ impl Engine {
     /// the various threads are most likely to be idle.
     /// For example, for a 60Hz display, embedders should add 16.6 * 1e6 to the frame time field.
     #[allow(clippy::needless_pass_by_value)] // intentional to enforce the type semantics
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all, fields(baton = baton.0)))]
     pub fn on_vsync(
         &mut self,
         baton: VsyncBaton,
@@ -911,10 +1684,13 @@ impl Engine {
                 frame_target_time.as_nanos() as u64,
             )
         }
-        .to_result()
+        .to_result()?;
+        self.rearm_frame_callbacks();
+        Ok(())
     }
 
     /// Reloads the system fonts in the engine.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn reload_system_fonts(&mut self) -> crate::Result<()> {
         unsafe { sys::ReloadSystemFonts(self.inner.engine) }.to_result()
     }
@@ -933,6 +1709,10 @@ impl Engine {
     ///
     /// The parameter is the identifier of the texture to register  with the engine.
     /// The embedder may supply new frames to this texture using the same identifier.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     pub fn register_external_texture(&mut self, texture_identifier: i64) -> crate::Result<()> {
         unsafe { sys::RegisterExternalTexture(self.inner.engine, texture_identifier) }.to_result()
     }
@@ -940,11 +1720,19 @@ impl Engine {
     /// Unregister a previous texture registration.
     ///
     /// The parameter is the identifier of the texture for which new frame will not be available
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     pub fn unregister_external_texture(&mut self, texture_identifier: i64) -> crate::Result<()> {
         unsafe { sys::UnregisterExternalTexture(self.inner.engine, texture_identifier) }.to_result()
     }
 
     /// Mark that a new texture frame is available for a given texture identifier.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     pub fn mark_external_texture_frame_available(
         &mut self,
         texture_identifier: i64,
@@ -963,12 +1751,31 @@ impl Engine {
     ///
     /// Returns if the low memory notification was sent to the running engine instance.
     ///
-    /// Hint: combine this with something like <https://crates.io/crates/psi>
+    /// This also sends `memoryPressure` to the framework, so there's no need to call [`crate::send_memory_pressure`] too.
+    /// [`crate::MemoryPressureMonitor`] calls this when the kernel reports memory pressure.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn notify_low_memory_warning(&mut self) -> crate::Result<()> {
         unsafe { sys::NotifyLowMemoryWarning(self.inner.engine) }.to_result()
     }
 
+    /// Limits the size of the GPU resource cache (e.g. of images and glyphs) to `max_bytes`.
+    ///
+    /// The engine picks a limit from the size of the view by default, which can be a lot for a device with little memory.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    pub fn set_resource_cache_max_bytes(&mut self, max_bytes: usize) -> crate::Result<()> {
+        #[allow(clippy::cast_precision_loss)] // far beyond any cache size
+        let message = JsonValue::Object(vec![
+            ("method".into(), "Skia.setResourceCacheMaxBytes".into()),
+            ("args".into(), JsonValue::Number(max_bytes as f64)),
+        ]);
+        self.send_platform_message(SKIA_CHANNEL, message.to_string().as_bytes(), |_| {})
+    }
+
     /// Schedule a new frame to redraw the content.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn schedule_frame(&mut self) -> crate::Result<()> {
         unsafe { sys::ScheduleFrame(self.inner.engine) }.to_result()
     }
@@ -977,6 +1784,7 @@ impl Engine {
     /// This must be called from the platform thread.
     /// The callback is executed only once from the raster thread; embedders must re-thread if necessary.
     /// Performing blocking calls in this callback may introduce application jank.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn set_next_frame_callback(
         &mut self,
         callback: impl FnOnce() + 'static,
@@ -985,17 +1793,27 @@ impl Engine {
             callback: Box<dyn FnOnce()>,
         }
 
+        unsafe fn cancel(user_data: *mut std::ffi::c_void) {
+            drop(unsafe { UserDataPool::take(user_data.cast::<UserData>()) });
+        }
+
+        #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
         unsafe extern "C" fn next_frame_callback(user_data: *mut std::ffi::c_void) {
-            let user_data = user_data.cast::<UserData>();
-            let user_data = *unsafe { Box::from_raw(user_data) };
+            let Some(user_data) = pending::complete(user_data) else {
+                return;
+            };
+            let user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
             (user_data.callback)();
         }
         const _: sys::VoidCallback = Some(next_frame_callback);
 
-        let user_data = Box::new(UserData {
-            callback: Box::new(callback),
-        });
-        let user_data = Box::into_raw(user_data);
+        let user_data = UserDataPool::alloc(
+            self.user_data_pool(),
+            UserData {
+                callback: Box::new(callback),
+            },
+        );
+        pending::register(self.inner.engine, user_data.cast(), cancel);
 
         let result = unsafe {
             sys::SetNextFrameCallback(
@@ -1007,8 +1825,8 @@ impl Engine {
         .to_result();
 
         if result.is_err() {
-            let user_data = unsafe { Box::from_raw(user_data) };
-            drop(user_data);
+            pending::complete(user_data.cast());
+            drop(unsafe { UserDataPool::take(user_data) });
         }
 
         result
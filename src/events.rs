@@ -27,6 +27,75 @@ pub struct WindowMetricsEvent {
     /// The identifier of the display the view is rendering on.
     pub display_id: sys::FlutterEngineDisplayId,
 }
+
+impl WindowMetricsEvent {
+    /// Checks the metrics that the engine would reject, so that a mistake can be told apart
+    /// from any other [`crate::Error::InvalidArguments`].
+    ///
+    /// This is done by [`crate::Engine::send_window_metrics_event`] and [`crate::Engine::add_view`] before the engine sees them.
+    pub fn validate(&self) -> Result<(), WindowMetricsError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(WindowMetricsError::ZeroSize);
+        }
+        // also rejects NaN
+        if !(self.pixel_ratio > 0.0 && self.pixel_ratio.is_finite()) {
+            return Err(WindowMetricsError::InvalidPixelRatio);
+        }
+        let insets = [
+            self.physical_view_inset_top,
+            self.physical_view_inset_right,
+            self.physical_view_inset_bottom,
+            self.physical_view_inset_left,
+        ];
+        if !insets.iter().all(|&inset| inset >= 0.0) {
+            return Err(WindowMetricsError::NegativeInsets);
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let (width, height) = (self.width as f64, self.height as f64);
+        if self.physical_view_inset_top + self.physical_view_inset_bottom > height
+            || self.physical_view_inset_left + self.physical_view_inset_right > width
+        {
+            return Err(WindowMetricsError::InsetsLargerThanView);
+        }
+        Ok(())
+    }
+}
+
+/// Why a [`WindowMetricsEvent`] was rejected by [`WindowMetricsEvent::validate`].
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum WindowMetricsError {
+    /// The width or height is zero. A view that isn't visible should keep its last size, or be removed.
+    ZeroSize,
+    /// The pixel ratio isn't a positive number.
+    InvalidPixelRatio,
+    /// An inset is negative (or NaN).
+    NegativeInsets,
+    /// The insets on opposite sides add up to more than the width or height.
+    InsetsLargerThanView,
+}
+
+impl std::fmt::Display for WindowMetricsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindowMetricsError::ZeroSize => {
+                write!(f, "the view's width and height must not be zero")
+            }
+            WindowMetricsError::InvalidPixelRatio => {
+                write!(f, "the pixel ratio must be greater than zero")
+            }
+            WindowMetricsError::NegativeInsets => write!(f, "the view insets must not be negative"),
+            WindowMetricsError::InsetsLargerThanView => {
+                write!(
+                    f,
+                    "the view insets must not add up to more than the view's width or height"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for WindowMetricsError {}
+
 impl From<WindowMetricsEvent> for sys::FlutterWindowMetricsEvent {
     fn from(event: WindowMetricsEvent) -> Self {
         Self {
@@ -46,6 +115,69 @@ impl From<WindowMetricsEvent> for sys::FlutterWindowMetricsEvent {
     }
 }
 
+/// Whether a view has input focus. Without an `embedder.h` that has view focus events, the engine is never told.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum ViewFocusState {
+    Unfocused,
+    Focused,
+}
+
+/// How focus moved into a view, e.g. with tab or shift+tab.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Default)]
+pub enum ViewFocusDirection {
+    /// Focus moved some other way, e.g. by clicking on the view.
+    #[default]
+    Undefined,
+    Forward,
+    Backward,
+}
+
+/// A change in which view has input focus, sent by either the platform or the framework.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct ViewFocusEvent {
+    pub view_id: ViewId,
+    pub state: ViewFocusState,
+    pub direction: ViewFocusDirection,
+}
+
+#[cfg(embedder_view_focus)]
+impl From<ViewFocusEvent> for sys::FlutterViewFocusEvent {
+    fn from(event: ViewFocusEvent) -> Self {
+        Self {
+            struct_size: std::mem::size_of::<Self>(),
+            view_id: event.view_id.0,
+            state: match event.state {
+                ViewFocusState::Unfocused => sys::FlutterViewFocusState::Unfocused,
+                ViewFocusState::Focused => sys::FlutterViewFocusState::Focused,
+            },
+            direction: match event.direction {
+                ViewFocusDirection::Undefined => sys::FlutterViewFocusDirection::Undefined,
+                ViewFocusDirection::Forward => sys::FlutterViewFocusDirection::Forward,
+                ViewFocusDirection::Backward => sys::FlutterViewFocusDirection::Backward,
+            },
+        }
+    }
+}
+
+#[cfg(embedder_view_focus)]
+impl From<&sys::FlutterViewFocusChangeRequest> for ViewFocusEvent {
+    fn from(request: &sys::FlutterViewFocusChangeRequest) -> Self {
+        Self {
+            view_id: ViewId(request.view_id),
+            state: if request.state == sys::FlutterViewFocusState::Focused {
+                ViewFocusState::Focused
+            } else {
+                ViewFocusState::Unfocused
+            },
+            direction: match request.direction {
+                sys::FlutterViewFocusDirection::Forward => ViewFocusDirection::Forward,
+                sys::FlutterViewFocusDirection::Backward => ViewFocusDirection::Backward,
+                _ => ViewFocusDirection::Undefined,
+            },
+        }
+    }
+}
+
 simple_enum! {
     pub enum KeyPhase(sys::FlutterKeyEventType) {
         Up,
@@ -0,0 +1,152 @@
+use crate::{
+    Engine, JsonValue, KeyEvent, ViewFocusDirection, ViewFocusEvent, ViewFocusState, ViewId,
+};
+
+/// Keeps track of which view has keyboard focus, and makes sure the engine knows about it before it gets any keys.
+///
+/// The engine has no idea which window a key event came from; the framework sends keys to whichever view it thinks is focused.
+/// So whenever the platform moves focus between windows, that has to reach the engine first, which this does
+/// (with an `embedder.h` that has view focus events; otherwise it only keeps track).
+///
+/// It also remembers which view the focused text field is in (from [`crate::TEXT_INPUT_CHANNEL`]),
+/// so that an input method can be attached to the right window.
+#[derive(Debug, Default)]
+pub struct FocusManager {
+    focused: Option<ViewId>,
+    text_input_view: Option<ViewId>,
+}
+
+impl FocusManager {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The view that has keyboard focus, if any.
+    #[must_use]
+    pub fn focused(&self) -> Option<ViewId> {
+        self.focused
+    }
+
+    /// The view that text input should go to: the one with the focused text field, or else the focused view.
+    #[must_use]
+    pub fn text_input_view(&self) -> Option<ViewId> {
+        self.text_input_view.or(self.focused)
+    }
+
+    /// A view gained keyboard focus. The view that had it before (if any) loses it.
+    pub fn focus(
+        &mut self,
+        engine: &mut Engine,
+        view_id: ViewId,
+        direction: ViewFocusDirection,
+    ) -> crate::Result<()> {
+        if self.focused == Some(view_id) {
+            return Ok(());
+        }
+        if let Some(previous) = self.focused.replace(view_id) {
+            send(
+                engine,
+                ViewFocusEvent {
+                    view_id: previous,
+                    state: ViewFocusState::Unfocused,
+                    direction: ViewFocusDirection::Undefined,
+                },
+            )?;
+        }
+        send(
+            engine,
+            ViewFocusEvent {
+                view_id,
+                state: ViewFocusState::Focused,
+                direction,
+            },
+        )
+    }
+
+    /// A view lost keyboard focus, e.g. because its window was deactivated. Does nothing if it didn't have focus.
+    pub fn unfocus(&mut self, engine: &mut Engine, view_id: ViewId) -> crate::Result<()> {
+        if self.focused != Some(view_id) {
+            return Ok(());
+        }
+        self.focused = None;
+        send(
+            engine,
+            ViewFocusEvent {
+                view_id,
+                state: ViewFocusState::Unfocused,
+                direction: ViewFocusDirection::Undefined,
+            },
+        )
+    }
+
+    /// A view was removed. It loses focus without telling the engine, since the engine has already forgotten it.
+    pub fn remove_view(&mut self, view_id: ViewId) {
+        if self.focused == Some(view_id) {
+            self.focused = None;
+        }
+        if self.text_input_view == Some(view_id) {
+            self.text_input_view = None;
+        }
+    }
+
+    /// Applies a request from [`crate::EngineHandler::view_focus_change_request`], as if the platform had moved focus.
+    ///
+    /// The platform should still activate (or deactivate) the window of the view, as the engine doesn't do that.
+    pub fn handle_request(
+        &mut self,
+        engine: &mut Engine,
+        request: ViewFocusEvent,
+    ) -> crate::Result<()> {
+        match request.state {
+            ViewFocusState::Focused => self.focus(engine, request.view_id, request.direction),
+            ViewFocusState::Unfocused => self.unfocus(engine, request.view_id),
+        }
+    }
+
+    /// Sends a key event that the platform delivered to `view_id`. If that view didn't have focus, it gets it first.
+    ///
+    /// `callback` is as for [`Engine::send_key_event`].
+    pub fn send_key_event(
+        &mut self,
+        engine: &mut Engine,
+        view_id: ViewId,
+        event: KeyEvent,
+        callback: impl FnOnce(bool) + 'static,
+    ) -> crate::Result<()> {
+        self.focus(engine, view_id, ViewFocusDirection::Undefined)?;
+        engine.send_key_event(event, callback)
+    }
+
+    /// Looks at a message on [`crate::TEXT_INPUT_CHANNEL`], to see which view the focused text field is in.
+    pub fn observe_text_input(&mut self, message: &[u8]) {
+        let Some(call) = std::str::from_utf8(message).ok().and_then(JsonValue::parse) else {
+            return;
+        };
+        match call.get("method").and_then(JsonValue::as_str) {
+            Some("TextInput.setClient") => {
+                // [client id, configuration]; older frameworks don't say which view, so assume the focused one
+                self.text_input_view = call
+                    .get("args")
+                    .and_then(JsonValue::as_array)
+                    .and_then(|args| args.get(1))
+                    .and_then(|config| config.get("viewId"))
+                    .and_then(JsonValue::as_i64)
+                    .map(ViewId);
+            }
+            Some("TextInput.clearClient") => self.text_input_view = None,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(embedder_view_focus)]
+fn send(engine: &mut Engine, event: ViewFocusEvent) -> crate::Result<()> {
+    engine.send_view_focus_event(event)
+}
+
+#[cfg(not(embedder_view_focus))]
+#[allow(clippy::unnecessary_wraps)]
+fn send(_engine: &mut Engine, _event: ViewFocusEvent) -> crate::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::Engine;
+
+/// The channel for system-wide notifications to the framework, like `fontsChanged` and `memoryPressure`. Messages use the `JSONMessageCodec`.
+pub const SYSTEM_CHANNEL: &CStr = c"flutter/system";
+
+/// Notices when fonts are installed or removed, and makes the engine pick them up.
+///
+/// This looks at the modification times of the fontconfig font directories (and all directories in them),
+/// and of the fontconfig caches, which `fc-cache` rewrites. It doesn't run on its own;
+/// call [`FontWatcher::check`] every few seconds, or whenever the app's window gains focus.
+#[derive(Debug)]
+pub struct FontWatcher {
+    roots: Vec<PathBuf>,
+    mtimes: HashMap<PathBuf, SystemTime>,
+}
+
+impl FontWatcher {
+    /// Watches the usual fontconfig directories, as of when this is called.
+    #[must_use]
+    pub fn new() -> Self {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let xdg = |var: &str, fallback: &str| {
+            std::env::var_os(var)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| home.as_ref().map(|home| home.join(fallback)))
+        };
+
+        let mut roots = vec![
+            PathBuf::from("/usr/share/fonts"),
+            PathBuf::from("/usr/local/share/fonts"),
+            PathBuf::from("/var/cache/fontconfig"),
+        ];
+        roots.extend(xdg("XDG_DATA_HOME", ".local/share").map(|dir| dir.join("fonts")));
+        roots.extend(xdg("XDG_CACHE_HOME", ".cache").map(|dir| dir.join("fontconfig")));
+        roots.extend(home.map(|home| home.join(".fonts")));
+        Self::with_directories(roots)
+    }
+
+    /// Watches the given directories, and all directories in them.
+    #[must_use]
+    pub fn with_directories(roots: Vec<PathBuf>) -> Self {
+        let mut watcher = Self {
+            roots,
+            mtimes: HashMap::new(),
+        };
+        watcher.mtimes = watcher.scan();
+        watcher
+    }
+
+    /// Whether any of the directories changed since the last time this returned `true` (or since the watcher was created).
+    pub fn changed(&mut self) -> bool {
+        let mtimes = self.scan();
+        if mtimes == self.mtimes {
+            return false;
+        }
+        self.mtimes = mtimes;
+        true
+    }
+
+    /// If the fonts changed, reloads them in the engine and tells the framework, so that text is laid out again.
+    ///
+    /// Returns whether they changed.
+    pub fn check(&mut self, engine: &mut Engine) -> crate::Result<bool> {
+        if !self.changed() {
+            return Ok(false);
+        }
+        reload_fonts(engine)?;
+        Ok(true)
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut mtimes = HashMap::new();
+        for root in &self.roots {
+            scan_directory(root, &mut mtimes);
+        }
+        mtimes
+    }
+}
+
+impl Default for FontWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reloads the system fonts in the engine, and sends `fontsChanged` on [`SYSTEM_CHANNEL`].
+pub fn reload_fonts(engine: &mut Engine) -> crate::Result<()> {
+    engine.reload_system_fonts()?;
+    engine.send_platform_message(SYSTEM_CHANNEL, br#"{"type":"fontsChanged"}"#, |_| {})
+}
+
+fn scan_directory(dir: &Path, mtimes: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(mtime) = dir.metadata().and_then(|metadata| metadata.modified()) else {
+        // doesn't exist (yet); creating it counts as a change
+        return;
+    };
+    mtimes.insert(dir.to_owned(), mtime);
+
+    let Ok(entries) = dir.read_dir() else {
+        return;
+    };
+    for entry in entries.flatten() {
+        // symlinks aren't followed, so a link to a parent directory can't make this loop
+        if entry.file_type().is_ok_and(|ty| ty.is_dir()) {
+            scan_directory(&entry.path(), mtimes);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::ThreadId,
+};
+
+use tracing::error;
+
+use crate::Engine;
+
+/// The callbacks from [`Engine::on_frame`].
+pub(crate) struct FrameCallbacks {
+    /// The engine's next-frame callback can only be set on this thread.
+    platform_thread: ThreadId,
+    shared: Arc<Subscribers>,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    callbacks: Mutex<Vec<Subscriber>>,
+    /// The engine has a next-frame callback that will call these.
+    armed: AtomicBool,
+}
+
+struct Subscriber {
+    active: Arc<AtomicBool>,
+    callback: Box<dyn FnMut() + Send>,
+}
+
+impl FrameCallbacks {
+    /// Must be created on the platform thread.
+    pub(crate) fn new() -> Self {
+        Self {
+            platform_thread: std::thread::current().id(),
+            shared: Arc::default(),
+        }
+    }
+}
+
+impl Subscribers {
+    fn callbacks(&self) -> MutexGuard<'_, Vec<Subscriber>> {
+        self.callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn fire(&self) {
+        self.armed.store(false, Ordering::Release);
+        // not locked while the callbacks run, so they can subscribe and unsubscribe
+        let mut callbacks = std::mem::take(&mut *self.callbacks());
+        for subscriber in &mut callbacks {
+            if subscriber.active.load(Ordering::Acquire) {
+                (subscriber.callback)();
+            }
+        }
+        callbacks.retain(|subscriber| subscriber.active.load(Ordering::Acquire));
+        let mut current = self.callbacks();
+        callbacks.append(&mut current);
+        *current = callbacks;
+    }
+}
+
+/// Keeps a callback from [`Engine::on_frame`] subscribed. Dropping it unsubscribes.
+#[must_use = "the callback is unsubscribed when this is dropped"]
+#[derive(Debug)]
+pub struct FrameSubscription {
+    active: Arc<AtomicBool>,
+}
+
+impl FrameSubscription {
+    /// Same as dropping it.
+    pub fn unsubscribe(self) {}
+}
+
+impl Drop for FrameSubscription {
+    fn drop(&mut self) {
+        self.active.store(false, Ordering::Release);
+    }
+}
+
+impl Engine {
+    /// Calls `callback` after every frame is drawn, until the returned [`FrameSubscription`] is dropped.
+    ///
+    /// Like [`Engine::set_next_frame_callback`], it runs on the raster thread, and blocking in it introduces jank.
+    /// That callback only fires once, and can only be set on the platform thread. So after each frame,
+    /// it is set again the next time the engine is used on the platform thread after a frame:
+    /// in [`Engine::on_vsync`], or in [`Engine::run_task`] for a platform task.
+    /// This must be called on the platform thread.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
+    pub fn on_frame(
+        &mut self,
+        callback: impl FnMut() + Send + 'static,
+    ) -> crate::Result<FrameSubscription> {
+        let active = Arc::new(AtomicBool::new(true));
+        self.inner
+            .frame_callbacks
+            .shared
+            .callbacks()
+            .push(Subscriber {
+                active: active.clone(),
+                callback: Box::new(callback),
+            });
+        let subscription = FrameSubscription { active };
+        self.arm_frame_callbacks()?;
+        Ok(subscription)
+    }
+
+    /// Sets the next-frame callback for [`Engine::on_frame`] again after the engine was used on the platform thread.
+    ///
+    /// Failing to do so doesn't mean that whatever the engine was used for failed, so it's only logged.
+    pub(crate) fn rearm_frame_callbacks(&mut self) {
+        if let Err(err) = self.arm_frame_callbacks() {
+            error!("failed to set the next frame callback: {err}");
+        }
+    }
+
+    /// Sets the next-frame callback for [`Engine::on_frame`], if it's needed and this is the platform thread.
+    fn arm_frame_callbacks(&mut self) -> crate::Result<()> {
+        let callbacks = &self.inner.frame_callbacks;
+        if std::thread::current().id() != callbacks.platform_thread {
+            return Ok(());
+        }
+        let shared = callbacks.shared.clone();
+        {
+            let mut subscribers = shared.callbacks();
+            subscribers.retain(|subscriber| subscriber.active.load(Ordering::Acquire));
+            if subscribers.is_empty() || shared.armed.swap(true, Ordering::AcqRel) {
+                return Ok(());
+            }
+        }
+        let fire = shared.clone();
+        let result = self.set_next_frame_callback(move || fire.fire());
+        if result.is_err() {
+            shared.armed.store(false, Ordering::Release);
+        }
+        result
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use crate::{Engine, FrameSubscription};
+
+/// A summary of recent frames, from [`FrameStats::snapshot`].
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct FrameStatsSnapshot {
+    /// Frames per second over the window, counting only time spent drawing continuously.
+    pub fps: f64,
+    /// The time between frames that 95% of frames in the window beat.
+    pub p95_frame_time: Duration,
+    /// The longest time between frames in the window.
+    pub max_frame_time: Duration,
+    /// Frames in the window that took longer than the refresh interval, i.e. missed at least one vsync.
+    pub dropped_frames: u64,
+    /// Frames in the window.
+    pub frames: u64,
+    /// Frames that missed a vsync since the stats were created (or reset).
+    pub total_dropped_frames: u64,
+}
+
+/// Rolling frame rate and jank statistics, for an on-screen HUD or performance logs.
+///
+/// Frames are recorded as they're drawn (see [`FrameStats::attach`]), and the stats can be polled from anywhere.
+/// The time between two frames only counts if the engine was drawing continuously; a gap longer than
+/// [`FrameStats::IDLE_THRESHOLD`] means the app was idle, not that a frame took that long.
+///
+/// Cloning this gives another handle to the same stats.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    window: Duration,
+    refresh_interval: Duration,
+    last_frame: Option<Duration>,
+    /// When each frame in the window was drawn, and how long it took since the previous one.
+    frames: VecDeque<(Duration, Duration)>,
+    total_dropped_frames: u64,
+}
+
+impl FrameStats {
+    /// Gaps between frames longer than this are idle time, and not counted.
+    pub const IDLE_THRESHOLD: Duration = Duration::from_millis(250);
+
+    /// Stats over the last `window` of frames, for a display that refreshes every `refresh_interval`.
+    #[must_use]
+    pub fn new(window: Duration, refresh_interval: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                window,
+                refresh_interval,
+                last_frame: None,
+                frames: VecDeque::new(),
+                total_dropped_frames: 0,
+            })),
+        }
+    }
+
+    /// Records every frame the engine draws, until the subscription is dropped.
+    pub fn attach(&self, engine: &mut Engine) -> crate::Result<FrameSubscription> {
+        let stats = self.clone();
+        engine.on_frame(move || stats.record_frame(Engine::get_current_time()))
+    }
+
+    /// Changes the refresh interval, e.g. when the view moves to another display.
+    pub fn set_refresh_interval(&self, refresh_interval: Duration) {
+        self.state().refresh_interval = refresh_interval;
+    }
+
+    /// Records that a frame was drawn at `time`, in the engine's clock (see [`Engine::get_current_time`]).
+    pub fn record_frame(&self, time: Duration) {
+        let mut state = self.state();
+        let state = &mut *state;
+        if let Some(frame_time) = state
+            .last_frame
+            .replace(time)
+            .and_then(|last| time.checked_sub(last))
+            .filter(|&frame_time| frame_time <= Self::IDLE_THRESHOLD)
+        {
+            if state.is_dropped(frame_time) {
+                state.total_dropped_frames += 1;
+            }
+            state.frames.push_back((time, frame_time));
+        }
+        while state
+            .frames
+            .front()
+            .is_some_and(|&(at, _)| time.saturating_sub(at) > state.window)
+        {
+            state.frames.pop_front();
+        }
+    }
+
+    /// Forgets every frame recorded so far.
+    pub fn reset(&self) {
+        let mut state = self.state();
+        state.last_frame = None;
+        state.frames.clear();
+        state.total_dropped_frames = 0;
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> FrameStatsSnapshot {
+        let state = self.state();
+        let mut frame_times = state
+            .frames
+            .iter()
+            .map(|&(_, frame_time)| frame_time)
+            .collect::<Vec<_>>();
+        if frame_times.is_empty() {
+            return FrameStatsSnapshot {
+                total_dropped_frames: state.total_dropped_frames,
+                ..FrameStatsSnapshot::default()
+            };
+        }
+        frame_times.sort_unstable();
+
+        let busy = frame_times.iter().sum::<Duration>();
+        #[allow(
+            clippy::cast_precision_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        let p95 = ((frame_times.len() as f64 * 0.95).ceil() as usize).clamp(1, frame_times.len());
+        #[allow(clippy::cast_precision_loss)]
+        FrameStatsSnapshot {
+            fps: if busy.is_zero() {
+                0.0
+            } else {
+                frame_times.len() as f64 / busy.as_secs_f64()
+            },
+            p95_frame_time: frame_times[p95 - 1],
+            max_frame_time: frame_times[frame_times.len() - 1],
+            dropped_frames: frame_times
+                .iter()
+                .filter(|&&frame_time| state.is_dropped(frame_time))
+                .count() as u64,
+            frames: frame_times.len() as u64,
+            total_dropped_frames: state.total_dropped_frames,
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl State {
+    /// A frame that took more than one and a half refresh intervals missed a vsync.
+    fn is_dropped(&self, frame_time: Duration) -> bool {
+        frame_time > self.refresh_interval * 3 / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn stats_for(frame_times: &[u64]) -> FrameStats {
+        let stats = FrameStats::new(Duration::from_secs(1), ms(16));
+        let mut time = ms(1000);
+        stats.record_frame(time);
+        for &frame_time in frame_times {
+            time += ms(frame_time);
+            stats.record_frame(time);
+        }
+        stats
+    }
+
+    #[test]
+    fn counts_frames_and_drops() {
+        let snapshot = stats_for(&[16, 16, 16, 40, 16]).snapshot();
+        assert_eq!(snapshot.frames, 5);
+        assert_eq!(snapshot.dropped_frames, 1);
+        assert_eq!(snapshot.total_dropped_frames, 1);
+        assert_eq!(snapshot.max_frame_time, ms(40));
+        assert_eq!(snapshot.p95_frame_time, ms(40));
+        assert!((snapshot.fps - 5.0 / 0.104).abs() < 1e-9);
+    }
+
+    #[test]
+    fn a_frame_that_only_slightly_overran_is_not_dropped() {
+        let snapshot = stats_for(&[16, 24, 25]).snapshot();
+        assert_eq!(snapshot.dropped_frames, 1);
+        assert_eq!(snapshot.p95_frame_time, ms(25));
+    }
+
+    #[test]
+    fn idle_gaps_are_not_frames() {
+        let snapshot = stats_for(&[16, 500, 16]).snapshot();
+        assert_eq!(snapshot.frames, 2);
+        assert_eq!(snapshot.dropped_frames, 0);
+        assert_eq!(snapshot.max_frame_time, ms(16));
+    }
+
+    #[test]
+    fn only_the_window_is_summarized() {
+        // 100 frames over 1.6s, with a dropped one at the start that falls out of the 1s window
+        let mut frame_times = vec![50];
+        frame_times.extend([16; 99]);
+        let snapshot = stats_for(&frame_times).snapshot();
+        assert_eq!(snapshot.frames, 63);
+        assert_eq!(snapshot.dropped_frames, 0);
+        assert_eq!(snapshot.total_dropped_frames, 1);
+        assert_eq!(snapshot.max_frame_time, ms(16));
+    }
+
+    #[test]
+    fn frames_from_the_past_are_ignored() {
+        let stats = stats_for(&[16]);
+        stats.record_frame(ms(900));
+        stats.record_frame(ms(916));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.frames, 2);
+        assert_eq!(snapshot.max_frame_time, ms(16));
+    }
+
+    #[test]
+    fn reset_forgets_everything() {
+        let stats = stats_for(&[16, 40]);
+        stats.reset();
+        assert_eq!(stats.snapshot(), FrameStatsSnapshot::default());
+
+        // the next frame starts over, rather than measuring from the last one before the reset
+        stats.record_frame(ms(2000));
+        stats.record_frame(ms(2016));
+        assert_eq!(stats.snapshot().frames, 1);
+    }
+}
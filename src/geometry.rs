@@ -452,9 +452,11 @@ impl From<PlatformViewMutation> for sys::FlutterPlatformViewMutation {
         }
     }
 }
-impl From<sys::FlutterPlatformViewMutation> for PlatformViewMutation {
-    fn from(sys: sys::FlutterPlatformViewMutation) -> Self {
-        match sys.type_ {
+impl TryFrom<sys::FlutterPlatformViewMutation> for PlatformViewMutation {
+    type Error = crate::UnknownVariant<sys::FlutterPlatformViewMutationType>;
+
+    fn try_from(sys: sys::FlutterPlatformViewMutation) -> Result<Self, Self::Error> {
+        Ok(match sys.type_ {
             sys::FlutterPlatformViewMutationType::Opacity => {
                 PlatformViewMutation::Opacity(unsafe { sys.__bindgen_anon_1.opacity })
             }
@@ -471,7 +473,7 @@ impl From<sys::FlutterPlatformViewMutation> for PlatformViewMutation {
                     unsafe { sys.__bindgen_anon_1.transformation }.into(),
                 )
             }
-            _ => unreachable!("Unknown FlutterPlatformViewMutationType; cannot convert it."),
-        }
+            unknown => return Err(crate::UnknownVariant::new("PlatformViewMutation", unknown)),
+        })
     }
 }
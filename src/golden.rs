@@ -0,0 +1,300 @@
+//! Golden image tests: render a Flutter bundle without a window, and compare its frames against stored PNGs.
+//!
+//! ```ignore
+//! let mut test = GoldenTest::run(project_args, Size { width: 800, height: 600 }, 1.0, "tests/goldens")?;
+//! test.pump_frames(5)?;
+//! test.matches_golden("home_screen")?;
+//! ```
+//!
+//! A missing golden is an error, unless `VOLITO_UPDATE_GOLDENS` is set (to anything), in which case every golden is (re)written from the current frame.
+//! When a frame doesn't match, a `<name>.diff.png` is written next to the golden, with the differing pixels in red over a faded copy of the expected image,
+//! and `<name>.actual.png` has the frame that was rendered.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{HeadlessEngine, HeadlessFrame, ProjectArgs, Size, VirtualClock};
+
+/// How long to wait for a single frame before giving up.
+const FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+/// How far the clock moves between frames.
+const FRAME_INTERVAL: Duration = Duration::from_nanos(16_666_667);
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Engine(crate::Error),
+    /// The engine didn't render a frame within a reasonable time.
+    NoFrame,
+    Io(io::Error),
+    Decode(png::DecodingError),
+    Encode(png::EncodingError),
+    /// The golden doesn't exist, and `VOLITO_UPDATE_GOLDENS` isn't set.
+    MissingGolden(PathBuf),
+    /// The golden is not an 8-bit RGBA image.
+    UnsupportedGolden(PathBuf),
+    /// The frame doesn't match the golden. `diff` is the path of the diff image.
+    Mismatch {
+        golden: PathBuf,
+        diff: PathBuf,
+        /// The number of pixels that differ by more than the tolerance; every pixel if the sizes differ.
+        differing_pixels: usize,
+    },
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Engine(err) => err.fmt(f),
+            GoldenError::NoFrame => write!(f, "the engine did not render a frame"),
+            GoldenError::Io(err) => err.fmt(f),
+            GoldenError::Decode(err) => write!(f, "failed to decode golden: {err}"),
+            GoldenError::Encode(err) => write!(f, "failed to encode image: {err}"),
+            GoldenError::MissingGolden(path) => write!(
+                f,
+                "golden {} does not exist (set VOLITO_UPDATE_GOLDENS to create it)",
+                path.display()
+            ),
+            GoldenError::UnsupportedGolden(path) => {
+                write!(f, "golden {} is not an 8-bit RGBA PNG", path.display())
+            }
+            GoldenError::Mismatch {
+                golden,
+                diff,
+                differing_pixels,
+            } => write!(
+                f,
+                "{differing_pixels} pixels differ from golden {} (see {})",
+                golden.display(),
+                diff.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<crate::Error> for GoldenError {
+    fn from(err: crate::Error) -> Self {
+        Self::Engine(err)
+    }
+}
+
+impl From<io::Error> for GoldenError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<png::DecodingError> for GoldenError {
+    fn from(err: png::DecodingError) -> Self {
+        Self::Decode(err)
+    }
+}
+
+impl From<png::EncodingError> for GoldenError {
+    fn from(err: png::EncodingError) -> Self {
+        Self::Encode(err)
+    }
+}
+
+/// An RGBA8888 image, as stored in a golden.
+struct Image {
+    size: Size<u32>,
+    rgba: Vec<u8>,
+}
+
+impl Image {
+    fn read(path: &Path) -> Result<Self, GoldenError> {
+        let mut reader = png::Decoder::new(File::open(path)?).read_info()?;
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba)?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(GoldenError::UnsupportedGolden(path.to_owned()));
+        }
+        rgba.truncate(info.buffer_size());
+        Ok(Self {
+            size: Size {
+                width: info.width,
+                height: info.height,
+            },
+            rgba,
+        })
+    }
+
+    fn write(&self, path: &Path) -> Result<(), GoldenError> {
+        let mut encoder = png::Encoder::new(
+            BufWriter::new(File::create(path)?),
+            self.size.width,
+            self.size.height,
+        );
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.rgba)?;
+        Ok(())
+    }
+}
+
+impl From<&HeadlessFrame> for Image {
+    fn from(frame: &HeadlessFrame) -> Self {
+        Self {
+            size: frame.size,
+            rgba: frame.to_rgba(),
+        }
+    }
+}
+
+/// Compares two images, returning the number of differing pixels and a diff image if there are any.
+fn diff(expected: &Image, actual: &Image, tolerance: u8) -> Option<(usize, Image)> {
+    if expected.size != actual.size {
+        let size = actual.size;
+        return Some((
+            size.width as usize * size.height as usize,
+            Image {
+                size,
+                rgba: actual.rgba.clone(),
+            },
+        ));
+    }
+
+    let mut differing_pixels = 0;
+    let mut rgba = Vec::with_capacity(expected.rgba.len());
+    for (expected, actual) in expected
+        .rgba
+        .chunks_exact(4)
+        .zip(actual.rgba.chunks_exact(4))
+    {
+        let differs = expected
+            .iter()
+            .zip(actual)
+            .any(|(&e, &a)| e.abs_diff(a) > tolerance);
+        if differs {
+            differing_pixels += 1;
+            rgba.extend_from_slice(&[0xff, 0, 0, 0xff]);
+        } else {
+            rgba.extend_from_slice(&[expected[0] / 4, expected[1] / 4, expected[2] / 4, 0xff]);
+        }
+    }
+
+    (differing_pixels > 0).then_some((
+        differing_pixels,
+        Image {
+            size: expected.size,
+            rgba,
+        },
+    ))
+}
+
+/// A [`HeadlessEngine`] running a test bundle, with its goldens.
+pub struct GoldenTest {
+    engine: HeadlessEngine,
+    clock: VirtualClock,
+    goldens: PathBuf,
+    update: bool,
+    tolerance: u8,
+    frame: Option<HeadlessFrame>,
+}
+
+impl fmt::Debug for GoldenTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GoldenTest")
+            .field("engine", &self.engine)
+            .field("clock", &self.clock)
+            .field("goldens", &self.goldens)
+            .field("update", &self.update)
+            .field("tolerance", &self.tolerance)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GoldenTest {
+    /// Starts the engine with a headless view of `size` (in physical pixels). Goldens are stored in the `goldens` directory.
+    ///
+    /// `project_args` is as for [`HeadlessEngine::run`].
+    pub fn run(
+        project_args: ProjectArgs,
+        size: Size<u32>,
+        pixel_ratio: f64,
+        goldens: impl Into<PathBuf>,
+    ) -> Result<Self, GoldenError> {
+        let mut engine = HeadlessEngine::run(project_args, size, pixel_ratio)?;
+        let clock = VirtualClock::starting_now();
+        engine.set_clock(clock.clone());
+        engine.set_refresh_interval(FRAME_INTERVAL);
+        Ok(Self {
+            engine,
+            clock,
+            goldens: goldens.into(),
+            update: std::env::var_os("VOLITO_UPDATE_GOLDENS").is_some(),
+            tolerance: 0,
+            frame: None,
+        })
+    }
+
+    /// How much each channel of a pixel may differ from the golden before it counts as different. Defaults to 0.
+    #[must_use]
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The engine, e.g. to send input between frames.
+    pub fn engine(&mut self) -> &mut HeadlessEngine {
+        &mut self.engine
+    }
+
+    /// Renders `count` frames (at least one), one after another, and returns the last one.
+    ///
+    /// Frame timestamps come from a virtual clock that advances by exactly 1/60th of a second per frame,
+    /// so animations end up in the same state on every run.
+    pub fn pump_frames(&mut self, count: usize) -> Result<&HeadlessFrame, GoldenError> {
+        let mut frame = self.render_frame()?;
+        for _ in 1..count {
+            frame = self.render_frame()?;
+        }
+        Ok(self.frame.insert(frame))
+    }
+
+    fn render_frame(&mut self) -> Result<HeadlessFrame, GoldenError> {
+        self.clock.advance(FRAME_INTERVAL);
+        self.engine
+            .render_frame(FRAME_TIMEOUT)?
+            .ok_or(GoldenError::NoFrame)
+    }
+
+    /// Compares the last frame from [`GoldenTest::pump_frames`] (or a new one, if there isn't one yet) against `<name>.png`.
+    pub fn matches_golden(&mut self, name: &str) -> Result<(), GoldenError> {
+        let actual = if let Some(frame) = &self.frame {
+            Image::from(frame)
+        } else {
+            Image::from(self.pump_frames(1)?)
+        };
+
+        let golden = self.goldens.join(format!("{name}.png"));
+        if self.update {
+            std::fs::create_dir_all(&self.goldens)?;
+            return actual.write(&golden);
+        }
+        if !golden.exists() {
+            return Err(GoldenError::MissingGolden(golden));
+        }
+
+        let expected = Image::read(&golden)?;
+        let Some((differing_pixels, diff_image)) = diff(&expected, &actual, self.tolerance) else {
+            return Ok(());
+        };
+
+        let diff = self.goldens.join(format!("{name}.diff.png"));
+        diff_image.write(&diff)?;
+        actual.write(&self.goldens.join(format!("{name}.actual.png")))?;
+        Err(GoldenError::Mismatch {
+            golden,
+            diff,
+            differing_pixels,
+        })
+    }
+}
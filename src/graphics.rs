@@ -9,11 +9,11 @@ pub struct Region {
 
 // FlutterRegion and FlutterDamage have the same layout; so map them to one type in our API.
 impl Region {
-    pub(crate) fn from_raw(sys: &sys::FlutterRegion) -> Self {
-        let regions = unsafe { slice::from_raw_parts(&raw const *sys.rects, sys.rects_count) };
-        Self {
-            regions: regions.iter().copied().map(Rect::from).collect(),
-        }
+    /// Reuses the allocation of `regions`, which must be empty.
+    pub(crate) fn from_raw_in(sys: &sys::FlutterRegion, mut regions: Vec<Rect<f64>>) -> Self {
+        let rects = unsafe { slice::from_raw_parts(&raw const *sys.rects, sys.rects_count) };
+        regions.extend(rects.iter().copied().map(Rect::from));
+        Self { regions }
     }
 
     pub(crate) fn from_raw_damage(sys: &sys::FlutterDamage) -> Self {
@@ -24,6 +24,7 @@ impl Region {
     }
 }
 
+#[derive(Debug)]
 pub struct PresentInfo {
     /// Id of the fbo backing the surface that was presented.
     pub fbo_id: u32,
@@ -33,19 +34,33 @@ pub struct PresentInfo {
     pub buffer_damage: Region,
 }
 impl PresentInfo {
-    #[must_use]
-    pub(crate) fn from_raw(raw: &sys::FlutterPresentInfo) -> Self {
-        Self {
-            fbo_id: raw.fbo_id,
-            frame_damage: Region::from_raw_damage(&raw.frame_damage),
-            buffer_damage: Region::from_raw_damage(&raw.buffer_damage),
+    sys_pub! {
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        #[must_use]
+        unsafe fn from_raw(raw: &sys::FlutterPresentInfo) -> Self {
+            Self {
+                fbo_id: raw.fbo_id,
+                frame_damage: Region::from_raw_damage(&raw.frame_damage),
+                buffer_damage: Region::from_raw_damage(&raw.buffer_damage),
+            }
         }
     }
 }
 
+#[derive(Debug)]
 pub struct FrameInfo {
     size: Size<u32>,
 }
+impl FrameInfo {
+    /// The size of the surface that will be backed by the fbo.
+    #[must_use]
+    pub fn size(&self) -> Size<u32> {
+        self.size
+    }
+}
 impl From<FrameInfo> for sys::FlutterFrameInfo {
     fn from(frame_info: FrameInfo) -> Self {
         Self {
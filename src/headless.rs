@@ -0,0 +1,364 @@
+use std::{
+    ffi::CStr,
+    fmt,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::ThreadId,
+    time::Duration,
+};
+
+use tracing::warn;
+
+use crate::{
+    task_runners::TaskQueue, Clock, CustomTaskRunners, Engine, EngineClock, EngineContext,
+    EngineHandler, PlatformMessageResponse, ProjectArgs, SemanticsUpdate, Size,
+    SoftwareRendererConfig, SoftwareRendererHandler, Task, TaskRunnerDescription,
+    TaskRunnerHandler, ViewFocusEvent, ViewId, VsyncBaton, VsyncQueue, WindowMetricsEvent,
+};
+
+/// A frame rendered by a [`HeadlessEngine`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadlessFrame {
+    /// The size of the frame, in physical pixels.
+    pub size: Size<u32>,
+    /// The number of bytes between the start of each row.
+    pub row_bytes: usize,
+    /// The pixels, in [`crate::SoftwarePixelFormat::Native32`] with premultiplied alpha.
+    /// On little-endian Linux, that's BGRA in memory. See [`HeadlessFrame::to_rgba`].
+    pub pixels: Vec<u8>,
+}
+
+impl HeadlessFrame {
+    /// The pixels as tightly packed RGBA8888 (still premultiplied), without any row padding.
+    #[must_use]
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let width = self.size.width as usize;
+        let mut rgba = Vec::with_capacity(width * self.size.height as usize * 4);
+        for row in self.pixels.chunks(self.row_bytes) {
+            for pixel in row[..width * 4].chunks_exact(4) {
+                if cfg!(target_endian = "little") {
+                    rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+                } else {
+                    rgba.extend_from_slice(pixel);
+                }
+            }
+        }
+        rgba
+    }
+}
+
+enum Wake {
+    Task(Duration, Task),
+    Vsync,
+    Frame(HeadlessFrame),
+}
+
+struct HeadlessTaskRunner {
+    thread: ThreadId,
+    sender: Sender<Wake>,
+}
+
+impl TaskRunnerHandler for HeadlessTaskRunner {
+    fn runs_task_on_current_thread(&self) -> bool {
+        std::thread::current().id() == self.thread
+    }
+
+    fn post_task(&self, target_time: Duration, task: Task) {
+        if self.sender.send(Wake::Task(target_time, task)).is_err() {
+            warn!("a platform task was posted after the headless engine was dropped");
+        }
+    }
+}
+
+struct HeadlessRenderer {
+    sender: Sender<Wake>,
+}
+
+impl SoftwareRendererHandler for HeadlessRenderer {
+    fn surface_present(&mut self, allocation: *const u8, row_bytes: usize, height: usize) -> bool {
+        let pixels = unsafe { std::slice::from_raw_parts(allocation, row_bytes * height) }.to_vec();
+        #[allow(clippy::cast_possible_truncation)]
+        let size = Size {
+            width: (row_bytes / 4) as u32,
+            height: height as u32,
+        };
+        let _ = self.sender.send(Wake::Frame(HeadlessFrame {
+            size,
+            row_bytes,
+            pixels,
+        }));
+        true
+    }
+}
+
+/// Forwards everything to the user's handler, except vsync.
+struct HeadlessHandler {
+    inner: Box<dyn EngineHandler>,
+    vsync: VsyncQueue,
+    sender: Sender<Wake>,
+}
+
+impl EngineHandler for HeadlessHandler {
+    fn platform_message(
+        &mut self,
+        cx: &mut EngineContext,
+        channel: &CStr,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        self.inner.platform_message(cx, channel, message, response);
+    }
+
+    fn vsync(&mut self, baton: VsyncBaton) {
+        self.vsync.request(baton);
+        let _ = self.sender.send(Wake::Vsync);
+    }
+
+    fn update_semantics(&mut self, cx: &mut EngineContext, update: SemanticsUpdate) {
+        self.inner.update_semantics(cx, update);
+    }
+
+    fn log_message(&mut self, tag: &CStr, message: &CStr) {
+        self.inner.log_message(tag, message);
+    }
+
+    fn on_pre_engine_restart(&mut self, cx: &mut EngineContext) {
+        self.inner.on_pre_engine_restart(cx);
+    }
+
+    fn channel_update(&mut self, cx: &mut EngineContext, channel: &CStr, listening: bool) {
+        self.inner.channel_update(cx, channel, listening);
+    }
+
+    fn view_focus_change_request(&mut self, cx: &mut EngineContext, request: ViewFocusEvent) {
+        self.inner.view_focus_change_request(cx, request);
+    }
+
+    fn root_isolate_created(&mut self) {
+        self.inner.root_isolate_created();
+    }
+}
+
+/// An engine without a window, which renders frames on demand.
+///
+/// It uses the software renderer, and runs platform tasks on the thread that created it, whenever it is pumped
+/// (with [`HeadlessEngine::pump`] or [`HeadlessEngine::render_frame`]).
+/// Vsync only ever happens when a frame is requested, so the engine sits idle in between, no matter what the app is animating.
+///
+/// Frame timestamps and platform task delays are measured by a [`Clock`],
+/// which is the [`EngineClock`] unless changed with [`HeadlessEngine::set_clock`].
+/// Platform tasks still never run before their target time by the engine's own clock, because that is undefined behavior.
+///
+/// This is meant for rendering Flutter UI to images or video, e.g. on a server.
+pub struct HeadlessEngine {
+    engine: Engine,
+    receiver: Receiver<Wake>,
+    tasks: TaskQueue,
+    vsync: VsyncQueue,
+    size: Size<u32>,
+    pixel_ratio: f64,
+    latest_frame: Option<HeadlessFrame>,
+    clock: Arc<dyn Clock>,
+    refresh_interval: Duration,
+}
+
+impl fmt::Debug for HeadlessEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeadlessEngine")
+            .field("engine", &self.engine)
+            .field("pending_tasks", &self.tasks.len())
+            .field("vsync", &self.vsync)
+            .field("size", &self.size)
+            .field("pixel_ratio", &self.pixel_ratio)
+            .field("refresh_interval", &self.refresh_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HeadlessEngine {
+    /// Starts the engine, rendering the implicit view at `size` (in physical pixels).
+    ///
+    /// The renderer and platform task runner are provided by this; `project_args.custom_task_runners` must not set a platform task runner.
+    /// The `vsync` callback of `project_args.handler` is never called.
+    ///
+    /// # Panics
+    ///
+    /// If `project_args.custom_task_runners` has a platform task runner.
+    pub fn run(
+        mut project_args: ProjectArgs,
+        size: Size<u32>,
+        pixel_ratio: f64,
+    ) -> crate::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let vsync = VsyncQueue::new();
+
+        let platform_task_runner = TaskRunnerDescription {
+            identifier: 1,
+            handler: Box::new(HeadlessTaskRunner {
+                thread: std::thread::current().id(),
+                sender: sender.clone(),
+            }),
+        };
+        let custom_task_runners =
+            project_args
+                .custom_task_runners
+                .get_or_insert_with(|| CustomTaskRunners {
+                    platform_task_runner: None,
+                    render_task_runner: None,
+                    set_thread_priority: None,
+                });
+        assert!(
+            custom_task_runners.platform_task_runner.is_none(),
+            "HeadlessEngine provides its own platform task runner"
+        );
+        custom_task_runners.platform_task_runner = Some(platform_task_runner);
+
+        project_args.handler = Box::new(HeadlessHandler {
+            inner: project_args.handler,
+            vsync: vsync.clone(),
+            sender: sender.clone(),
+        });
+
+        let renderer = SoftwareRendererConfig {
+            handler: Box::new(HeadlessRenderer { sender }),
+        };
+
+        let engine = Engine::run(renderer, project_args)?;
+        let clock: Arc<dyn Clock> = Arc::new(EngineClock);
+        let mut headless = Self {
+            engine,
+            receiver,
+            tasks: TaskQueue::new(clock.clone()),
+            vsync,
+            size,
+            pixel_ratio,
+            latest_frame: None,
+            clock,
+            refresh_interval: Duration::from_nanos(16_666_667),
+        };
+        headless.send_metrics()?;
+        Ok(headless)
+    }
+
+    /// The underlying engine, e.g. to send input events or platform messages.
+    pub fn engine(&mut self) -> &mut Engine {
+        &mut self.engine
+    }
+
+    #[must_use]
+    pub fn size(&self) -> Size<u32> {
+        self.size
+    }
+
+    /// Changes where frame timestamps come from, and what platform task delays are measured by.
+    ///
+    /// With a [`crate::VirtualClock`] that is advanced by the refresh interval between frames,
+    /// animations and timers progress exactly the same way on every run, no matter how long rendering takes.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Arc::new(clock);
+        self.tasks.set_clock(self.clock.clone());
+    }
+
+    /// The time between frames that the engine is told about. Defaults to 60Hz.
+    pub fn set_refresh_interval(&mut self, refresh_interval: Duration) {
+        self.refresh_interval = refresh_interval;
+    }
+
+    /// Changes the size (in physical pixels) and pixel ratio of the implicit view.
+    pub fn resize(&mut self, size: Size<u32>, pixel_ratio: f64) -> crate::Result<()> {
+        self.size = size;
+        self.pixel_ratio = pixel_ratio;
+        self.send_metrics()
+    }
+
+    fn send_metrics(&mut self) -> crate::Result<()> {
+        self.engine.send_window_metrics_event(WindowMetricsEvent {
+            view_id: ViewId::IMPLICIT,
+            width: self.size.width as usize,
+            height: self.size.height as usize,
+            pixel_ratio: self.pixel_ratio,
+            left: 0,
+            top: 0,
+            physical_view_inset_top: 0.0,
+            physical_view_inset_right: 0.0,
+            physical_view_inset_bottom: 0.0,
+            physical_view_inset_left: 0.0,
+            display_id: 0,
+        })
+    }
+
+    /// The most recent frame the engine rendered, even if it wasn't requested.
+    #[must_use]
+    pub fn latest_frame(&self) -> Option<&HeadlessFrame> {
+        self.latest_frame.as_ref()
+    }
+
+    fn handle(&mut self, wake: Wake) -> Option<HeadlessFrame> {
+        match wake {
+            Wake::Task(target_time, task) => {
+                self.tasks.push(target_time, task);
+                None
+            }
+            // vsync batons are only returned while waiting for a frame
+            Wake::Vsync => None,
+            Wake::Frame(frame) => Some(frame),
+        }
+    }
+
+    /// Runs the platform tasks and times out the platform messages that are due. Returns how long until the next one, if any.
+    fn run_due_tasks(&mut self) -> crate::Result<Option<Duration>> {
+        self.tasks.run_due(&mut self.engine)
+    }
+
+    /// Handles everything that happened so far (platform tasks, frames) without blocking.
+    pub fn pump(&mut self) -> crate::Result<()> {
+        while let Ok(wake) = self.receiver.try_recv() {
+            if let Some(frame) = self.handle(wake) {
+                self.latest_frame = Some(frame);
+            }
+        }
+        self.run_due_tasks()?;
+        Ok(())
+    }
+
+    /// Asks the engine for a frame, and pumps platform tasks until it's rendered.
+    ///
+    /// Returns `None` if no frame was rendered within `timeout`; this happens before the app has rendered its first frame, for example.
+    pub fn render_frame(&mut self, timeout: Duration) -> crate::Result<Option<HeadlessFrame>> {
+        // frames that were rendered before this call aren't the one being asked for
+        self.pump()?;
+        let deadline = Engine::get_current_time() + timeout;
+        self.engine.schedule_frame()?;
+
+        loop {
+            let next_task = self.run_due_tasks()?;
+
+            if self.vsync.is_pending() {
+                self.vsync
+                    .frame(&mut self.engine, self.clock.now(), self.refresh_interval)?;
+            }
+
+            let now = Engine::get_current_time();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let wait = deadline
+                .saturating_sub(now)
+                .min(next_task.unwrap_or(Duration::MAX));
+            match self.receiver.recv_timeout(wait) {
+                Ok(wake) => {
+                    if let Some(frame) = self.handle(wake) {
+                        self.latest_frame = Some(frame.clone());
+                        return Ok(Some(frame));
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    unreachable!("the engine holds senders for as long as it's alive")
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! Recompiling the app's Dart code while it runs, and restarting the engine with it.
+//!
+//! This only works with a debug (JIT) engine, running a kernel snapshot (`kernel_blob.bin`) rather than AOT data.
+//! With `volito-build-support`, a debug build writes the paths this needs to a file, whose path is in `VOLITO_HOT_RELOAD`:
+//!
+//! ```ignore
+//! let config = FrontendServerConfig::load(env!("VOLITO_HOT_RELOAD"))?;
+//! let mut reloader = HotReloader::new(&config, || Engine::run(renderer(), project_args()))?;
+//! // ...whenever a Dart file changes:
+//! reloader.restart(&[changed_file])?;
+//! ```
+//!
+//! The engine itself is started anew for every restart, by the closure given to [`HotReloader::new`].
+//! Anything that should survive a restart (windows, GL contexts) must live outside of the engine's handlers,
+//! and be shared with the new ones. The metrics of every view are carried over to the new engine.
+
+use std::{
+    ffi::OsString,
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use crate::Engine;
+
+#[derive(Debug)]
+pub enum HotReloadError {
+    Io(io::Error),
+    /// A path is missing from the config file.
+    MissingConfig(&'static str),
+    /// The frontend server exited, or said something that doesn't make sense.
+    FrontendServer(String),
+    /// The Dart code doesn't compile. The previous kernel is still running.
+    Compile {
+        diagnostics: Vec<String>,
+    },
+    Engine(crate::Error),
+}
+
+impl fmt::Display for HotReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotReloadError::Io(err) => err.fmt(f),
+            HotReloadError::MissingConfig(key) => {
+                write!(f, "the hot reload config has no {key}")
+            }
+            HotReloadError::FrontendServer(message) => {
+                write!(f, "frontend server failed: {message}")
+            }
+            HotReloadError::Compile { diagnostics } => {
+                write!(f, "compilation failed")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{diagnostic}")?;
+                }
+                Ok(())
+            }
+            HotReloadError::Engine(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for HotReloadError {}
+
+impl From<io::Error> for HotReloadError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<crate::Error> for HotReloadError {
+    fn from(err: crate::Error) -> Self {
+        Self::Engine(err)
+    }
+}
+
+/// Where the frontend server and the app are.
+#[derive(Debug, Clone)]
+pub struct FrontendServerConfig {
+    /// The Dart runtime to run the frontend server with (`dart` or `dartaotruntime`).
+    pub dart: PathBuf,
+    /// The frontend server snapshot.
+    pub frontend_server: PathBuf,
+    /// The `flutter_patched_sdk` of the engine.
+    pub sdk_root: PathBuf,
+    /// The root of the Flutter project, with `pubspec.yaml` and `.dart_tool`.
+    pub project_root: PathBuf,
+    /// The Dart file with `main`.
+    pub entrypoint: PathBuf,
+    /// The asset directory the engine runs from, where `kernel_blob.bin` is replaced.
+    pub assets: PathBuf,
+    /// More arguments for the frontend server, e.g. `--enable-experiment=...`.
+    pub extra_args: Vec<OsString>,
+}
+
+impl FrontendServerConfig {
+    /// Reads the config file written by `volito-build-support` for debug builds.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HotReloadError> {
+        let config = std::fs::read_to_string(path)?;
+        let get = |key: &'static str| {
+            config
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .map(PathBuf::from)
+                .ok_or(HotReloadError::MissingConfig(key))
+        };
+        Ok(Self {
+            dart: get("dart")?,
+            frontend_server: get("frontend_server")?,
+            sdk_root: get("sdk_root")?,
+            project_root: get("project_root")?,
+            entrypoint: get("entrypoint")?,
+            assets: get("assets")?,
+            extra_args: Vec::new(),
+        })
+    }
+}
+
+/// The result of a successful compilation.
+#[derive(Debug, Clone)]
+pub struct CompileOutput {
+    /// The kernel file, which is either the whole program or just what changed since the last accepted compilation.
+    pub dill: PathBuf,
+    /// Warnings and such. Errors make the compilation fail instead.
+    pub diagnostics: Vec<String>,
+}
+
+/// A frontend server, compiling Dart to kernel incrementally.
+///
+/// Every compilation after the first one must be either [accepted](KernelCompiler::accept) or [rejected](KernelCompiler::reject)
+/// before the next one, which is done automatically for compilations that fail.
+pub struct KernelCompiler {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    entrypoint: PathBuf,
+    compiled: bool,
+    generation: u64,
+}
+
+impl fmt::Debug for KernelCompiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KernelCompiler")
+            .field("pid", &self.child.id())
+            .field("entrypoint", &self.entrypoint)
+            .field("compiled", &self.compiled)
+            .field("generation", &self.generation)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KernelCompiler {
+    /// Starts the frontend server. Nothing is compiled until [`KernelCompiler::compile`].
+    pub fn spawn(config: &FrontendServerConfig) -> Result<Self, HotReloadError> {
+        let output_dill = config
+            .project_root
+            .join(".dart_tool")
+            .join("volito")
+            .join("app.dill");
+        if let Some(parent) = output_dill.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut child = Command::new(&config.dart)
+            .current_dir(&config.project_root)
+            .arg(&config.frontend_server)
+            .arg("--sdk-root")
+            .arg(&config.sdk_root)
+            .args([
+                "--incremental",
+                "--target=flutter",
+                "--track-widget-creation",
+                "--enable-asserts",
+                "-Ddart.vm.profile=false",
+                "-Ddart.vm.product=false",
+            ])
+            .arg("--packages")
+            .arg(
+                config
+                    .project_root
+                    .join(".dart_tool")
+                    .join("package_config.json"),
+            )
+            .arg("--output-dill")
+            .arg(&output_dill)
+            .args(&config.extra_args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            unreachable!("stdin and stdout are piped");
+        };
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            entrypoint: config.entrypoint.clone(),
+            compiled: false,
+            generation: 0,
+        })
+    }
+
+    /// Compiles the whole program. This is slow the first time, and only needs to happen once.
+    pub fn compile(&mut self) -> Result<CompileOutput, HotReloadError> {
+        writeln!(self.stdin, "compile {}", self.entrypoint.display())?;
+        self.compiled = true;
+        self.read_result()
+    }
+
+    /// Compiles what changed since the last accepted compilation, which is the whole program if nothing was compiled yet.
+    pub fn recompile(&mut self, changed: &[PathBuf]) -> Result<CompileOutput, HotReloadError> {
+        if !self.compiled {
+            return self.compile();
+        }
+        self.generation += 1;
+        let boundary = format!("volito-{}", self.generation);
+        writeln!(
+            self.stdin,
+            "recompile {} {boundary}",
+            self.entrypoint.display()
+        )?;
+        for path in changed {
+            writeln!(self.stdin, "{}", path.display())?;
+        }
+        writeln!(self.stdin, "{boundary}")?;
+        self.read_result()
+    }
+
+    /// The last compilation was used, so the next one only has to include what changed since.
+    pub fn accept(&mut self) -> Result<(), HotReloadError> {
+        writeln!(self.stdin, "accept")?;
+        Ok(())
+    }
+
+    /// The last compilation wasn't used, so the next one has to include everything it did too.
+    pub fn reject(&mut self) -> Result<(), HotReloadError> {
+        writeln!(self.stdin, "reject")?;
+        // a rejection is answered with just the boundary, and no sources or output
+        let boundary = self.read_boundary()?;
+        let mut line = String::new();
+        while !line.starts_with(boundary.as_str()) {
+            line.clear();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(HotReloadError::FrontendServer("exited".into()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips output until `result <boundary>`, and returns the boundary.
+    fn read_boundary(&mut self) -> Result<String, HotReloadError> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(HotReloadError::FrontendServer("exited".into()));
+            }
+            if let Some(boundary) = line.trim_end().strip_prefix("result ") {
+                return Ok(boundary.to_owned());
+            }
+        }
+    }
+
+    /// Reads the frontend server's answer, which looks like this:
+    ///
+    /// ```text
+    /// result <boundary>
+    /// <diagnostics>
+    /// <boundary>
+    /// +<added source>
+    /// -<removed source>
+    /// <boundary> <output dill> <error count>
+    /// ```
+    fn read_result(&mut self) -> Result<CompileOutput, HotReloadError> {
+        let boundary = self.read_boundary()?;
+        let mut line = String::new();
+
+        let mut diagnostics = Vec::new();
+        let mut in_sources = false;
+        loop {
+            line.clear();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(HotReloadError::FrontendServer("exited".into()));
+            }
+            let line = line.trim_end();
+            if line == boundary {
+                if in_sources {
+                    // no output at all
+                    return self.failed(diagnostics);
+                }
+                in_sources = true;
+            } else if let Some(rest) = line
+                .strip_prefix(boundary.as_str())
+                .and_then(|rest| rest.strip_prefix(' '))
+            {
+                // the path may contain spaces, but the error count can't
+                let Some((dill, Ok(errors))) = rest
+                    .rsplit_once(' ')
+                    .map(|(dill, errors)| (dill, errors.parse::<usize>()))
+                else {
+                    return Err(HotReloadError::FrontendServer(format!(
+                        "invalid result: {line}"
+                    )));
+                };
+                if errors > 0 || dill.is_empty() {
+                    return self.failed(diagnostics);
+                }
+                return Ok(CompileOutput {
+                    dill: dill.into(),
+                    diagnostics,
+                });
+            } else if !in_sources {
+                diagnostics.push(line.to_owned());
+            }
+        }
+    }
+
+    fn failed(&mut self, diagnostics: Vec<String>) -> Result<CompileOutput, HotReloadError> {
+        // the first compilation has nothing to go back to
+        if self.generation > 0 {
+            self.reject()?;
+        }
+        Err(HotReloadError::Compile { diagnostics })
+    }
+}
+
+impl Drop for KernelCompiler {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "quit");
+        let _ = self.child.wait();
+    }
+}
+
+/// Keeps an engine running the latest version of the app's Dart code.
+pub struct HotReloader<F: FnMut() -> crate::Result<Engine>> {
+    compiler: KernelCompiler,
+    kernel_blob: PathBuf,
+    start_engine: F,
+    engine: Option<Engine>,
+}
+
+impl<F: FnMut() -> crate::Result<Engine>> fmt::Debug for HotReloader<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HotReloader")
+            .field("compiler", &self.compiler)
+            .field("kernel_blob", &self.kernel_blob)
+            .field("engine", &self.engine)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: FnMut() -> crate::Result<Engine>> HotReloader<F> {
+    /// Starts the frontend server, compiles the app, and then starts the engine with `start_engine`.
+    pub fn new(config: &FrontendServerConfig, mut start_engine: F) -> Result<Self, HotReloadError> {
+        let mut compiler = KernelCompiler::spawn(config)?;
+        let kernel_blob = config.assets.join("kernel_blob.bin");
+
+        // the bundle that the engine starts from was compiled separately, so replace it with what's compiled here,
+        // as later compilations only contain what changed from this one
+        let output = compiler.compile()?;
+        std::fs::copy(&output.dill, &kernel_blob)?;
+        compiler.accept()?;
+
+        let engine = start_engine()?;
+        Ok(Self {
+            compiler,
+            kernel_blob,
+            start_engine,
+            engine: Some(engine),
+        })
+    }
+
+    /// The running engine, or `None` if the last restart failed to start it.
+    pub fn engine(&mut self) -> Option<&mut Engine> {
+        self.engine.as_mut()
+    }
+
+    /// Recompiles with the changed Dart files, and restarts the engine with the new code.
+    ///
+    /// If the code doesn't compile, the engine keeps running and [`HotReloadError::Compile`] is returned.
+    /// The app starts over from `main`, like a hot restart; state in Dart is lost, but the windows stay.
+    pub fn restart(&mut self, changed: &[PathBuf]) -> Result<(), HotReloadError> {
+        let output = self.compiler.recompile(changed)?;
+
+        // incremental kernel can be appended to the full one, and the VM loads the whole thing
+        let delta = std::fs::read(&output.dill)?;
+        let mut kernel = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&self.kernel_blob)?;
+        if let Err(err) = kernel.write_all(&delta) {
+            self.compiler.reject()?;
+            return Err(err.into());
+        }
+        self.compiler.accept()?;
+
+        let metrics = self
+            .engine
+            .as_ref()
+            .map(Engine::all_view_metrics)
+            .unwrap_or_default();
+        // the old engine must be gone before the new one takes over the renderer
+        drop(self.engine.take());
+
+        let mut engine = (self.start_engine)()?;
+        for metrics in metrics {
+            engine.send_window_metrics_event(metrics)?;
+        }
+        self.engine = Some(engine);
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use std::ffi::CStr;
+
+use tracing::warn;
+
+use crate::{ChannelHandler, EngineContext, PlatformMessageResponse};
+
+/// The channel the engine announces the root isolate on. The message is the isolate's service ID
+/// (e.g. `"isolates/1234"`), as UTF-8, and is sent every time a root isolate is launched, including after a hot restart.
+pub const ISOLATE_CHANNEL: &CStr = c"flutter/isolate";
+
+/// Gets told about root isolates as they're registered with the VM service.
+pub trait IsolateHandler {
+    /// A root isolate was launched, and can be found in the VM service under `service_id`.
+    ///
+    /// This comes after [`crate::EngineHandler::root_isolate_created`], once the isolate is running.
+    fn root_isolate_registered(&mut self, service_id: &str);
+}
+
+impl<F: FnMut(&str)> IsolateHandler for F {
+    fn root_isolate_registered(&mut self, service_id: &str) {
+        self(service_id);
+    }
+}
+
+/// Handles [`ISOLATE_CHANNEL`], keeping track of the current root isolate. Register it in a [`crate::ChannelRouter`].
+#[derive(Debug, Default, Clone)]
+pub struct IsolateChannel<H> {
+    service_id: Option<String>,
+    handler: H,
+}
+
+impl<H: IsolateHandler> IsolateChannel<H> {
+    #[must_use]
+    pub fn new(handler: H) -> Self {
+        Self {
+            service_id: None,
+            handler,
+        }
+    }
+
+    /// The service ID of the current root isolate, if one has been announced.
+    #[must_use]
+    pub fn service_id(&self) -> Option<&str> {
+        self.service_id.as_deref()
+    }
+
+    /// Forgets the current root isolate, e.g. in [`crate::EngineHandler::on_pre_engine_restart`],
+    /// since the old one is shut down and a new one will be announced.
+    pub fn reset(&mut self) {
+        self.service_id = None;
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+}
+
+impl<H: IsolateHandler> ChannelHandler for IsolateChannel<H> {
+    fn platform_message(
+        &mut self,
+        _cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        match std::str::from_utf8(message) {
+            Ok(service_id) => {
+                self.service_id = Some(service_id.to_owned());
+                self.handler.root_isolate_registered(service_id);
+            }
+            Err(err) => warn!("root isolate service ID is not UTF-8: {err}"),
+        }
+        if let Err(err) = response.send(&[]) {
+            warn!("failed to respond to an isolate message: {err}");
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ffi::CString,
+    time::Duration,
+};
+
+use crate::{KeyEvent, KeyEventDeviceType, KeyPhase};
+
+// Flutter's logical key ids are namespaced into "planes" in the upper bits.
+// https://github.com/flutter/flutter/blob/3.27.0/packages/flutter/lib/src/services/keyboard_key.g.dart
+const UNICODE_PLANE: u64 = 0x00_0000_0000;
+const UNPRINTABLE_PLANE: u64 = 0x01_0000_0000;
+const FLUTTER_PLANE: u64 = 0x02_0000_0000;
+// keysyms we don't know about are passed through, the same way the GTK embedder does it.
+const GTK_PLANE: u64 = 0x15_0000_0000;
+
+// Physical keys are USB HID usages on the keyboard page.
+const HID_KEYBOARD_PAGE: u64 = 0x0007_0000;
+
+/// The physical key (a USB HID usage) for a Linux input event code (`KEY_*` from `linux/input-event-codes.h`).
+///
+/// These are the codes used by `wl_keyboard.key` and libinput.
+/// XKB keycodes (as used by X11 and `xkbcommon`) are these codes plus 8.
+#[must_use]
+pub fn physical_key_from_evdev(code: u32) -> Option<u64> {
+    #[rustfmt::skip]
+    let usage = match code {
+        1 => 0x29, // Escape
+        2..=10 => 0x1e + u64::from(code - 2), // Digit1..Digit9
+        11 => 0x27, // Digit0
+        12 => 0x2d, // Minus
+        13 => 0x2e, // Equal
+        14 => 0x2a, // Backspace
+        15 => 0x2b, // Tab
+        16 => 0x14, 17 => 0x1a, 18 => 0x08, 19 => 0x15, 20 => 0x17, // QWERT
+        21 => 0x1c, 22 => 0x18, 23 => 0x0c, 24 => 0x12, 25 => 0x13, // YUIOP
+        26 => 0x2f, // BracketLeft
+        27 => 0x30, // BracketRight
+        28 => 0x28, // Enter
+        29 => 0xe0, // ControlLeft
+        30 => 0x04, 31 => 0x16, 32 => 0x07, 33 => 0x09, 34 => 0x0a, // ASDFG
+        35 => 0x0b, 36 => 0x0d, 37 => 0x0e, 38 => 0x0f, // HJKL
+        39 => 0x33, // Semicolon
+        40 => 0x34, // Quote
+        41 => 0x35, // Backquote
+        42 => 0xe1, // ShiftLeft
+        43 => 0x31, // Backslash
+        44 => 0x1d, 45 => 0x1b, 46 => 0x06, 47 => 0x19, 48 => 0x05, // ZXCVB
+        49 => 0x11, 50 => 0x10, // NM
+        51 => 0x36, // Comma
+        52 => 0x37, // Period
+        53 => 0x38, // Slash
+        54 => 0xe5, // ShiftRight
+        55 => 0x55, // NumpadMultiply
+        56 => 0xe2, // AltLeft
+        57 => 0x2c, // Space
+        58 => 0x39, // CapsLock
+        59..=68 => 0x3a + u64::from(code - 59), // F1..F10
+        69 => 0x53, // NumLock
+        70 => 0x47, // ScrollLock
+        71 => 0x5f, 72 => 0x60, 73 => 0x61, // Numpad7..Numpad9
+        74 => 0x56, // NumpadSubtract
+        75 => 0x5c, 76 => 0x5d, 77 => 0x5e, // Numpad4..Numpad6
+        78 => 0x57, // NumpadAdd
+        79 => 0x59, 80 => 0x5a, 81 => 0x5b, // Numpad1..Numpad3
+        82 => 0x62, // Numpad0
+        83 => 0x63, // NumpadDecimal
+        86 => 0x64, // IntlBackslash
+        87 => 0x44, // F11
+        88 => 0x45, // F12
+        89 => 0x87, // IntlRo
+        96 => 0x58, // NumpadEnter
+        97 => 0xe4, // ControlRight
+        98 => 0x54, // NumpadDivide
+        99 => 0x46, // PrintScreen
+        100 => 0xe6, // AltRight
+        102 => 0x4a, // Home
+        103 => 0x52, // ArrowUp
+        104 => 0x4b, // PageUp
+        105 => 0x50, // ArrowLeft
+        106 => 0x4f, // ArrowRight
+        107 => 0x4d, // End
+        108 => 0x51, // ArrowDown
+        109 => 0x4e, // PageDown
+        110 => 0x49, // Insert
+        111 => 0x4c, // Delete
+        113 => 0x7f, // AudioVolumeMute
+        114 => 0x81, // AudioVolumeDown
+        115 => 0x80, // AudioVolumeUp
+        116 => 0x66, // Power
+        117 => 0x67, // NumpadEqual
+        119 => 0x48, // Pause
+        121 => 0x85, // NumpadComma
+        124 => 0x89, // IntlYen
+        125 => 0xe3, // MetaLeft
+        126 => 0xe7, // MetaRight
+        127 => 0x65, // ContextMenu
+        183..=194 => 0x68 + u64::from(code - 183), // F13..F24
+        _ => return None,
+    };
+    Some(HID_KEYBOARD_PAGE | usage)
+}
+
+/// The logical key for an XKB keysym.
+///
+/// `character` is the text the keysym produces, if any. For letters, this should be the unshifted keysym,
+/// because Flutter's logical keys are case-insensitive (`LogicalKeyboardKey.keyA` is `a`, not `A`).
+#[must_use]
+pub fn logical_key_from_keysym(keysym: u32, character: Option<char>) -> u64 {
+    #[rustfmt::skip]
+    let unprintable = match keysym {
+        0xff08 => Some(UNPRINTABLE_PLANE | 0x008), // BackSpace
+        0xff09 | 0xfe20 => Some(UNPRINTABLE_PLANE | 0x009), // Tab, ISO_Left_Tab
+        0xff0d => Some(UNPRINTABLE_PLANE | 0x00d), // Return
+        0xff1b => Some(UNPRINTABLE_PLANE | 0x01b), // Escape
+        0xffff => Some(UNPRINTABLE_PLANE | 0x07f), // Delete
+        0xffe5 => Some(UNPRINTABLE_PLANE | 0x104), // Caps_Lock
+        0xff7f => Some(UNPRINTABLE_PLANE | 0x10a), // Num_Lock
+        0xff14 => Some(UNPRINTABLE_PLANE | 0x10c), // Scroll_Lock
+        0xff54 => Some(UNPRINTABLE_PLANE | 0x301), // Down
+        0xff51 => Some(UNPRINTABLE_PLANE | 0x302), // Left
+        0xff53 => Some(UNPRINTABLE_PLANE | 0x303), // Right
+        0xff52 => Some(UNPRINTABLE_PLANE | 0x304), // Up
+        0xff57 => Some(UNPRINTABLE_PLANE | 0x305), // End
+        0xff50 => Some(UNPRINTABLE_PLANE | 0x306), // Home
+        0xff56 => Some(UNPRINTABLE_PLANE | 0x307), // Page_Down
+        0xff55 => Some(UNPRINTABLE_PLANE | 0x308), // Page_Up
+        0xff63 => Some(UNPRINTABLE_PLANE | 0x407), // Insert
+        0xff67 => Some(UNPRINTABLE_PLANE | 0x505), // Menu (ContextMenu)
+        0xff13 => Some(UNPRINTABLE_PLANE | 0x509), // Pause
+        0xff61 => Some(UNPRINTABLE_PLANE | 0x608), // Print
+        0xffbe..=0xffd5 => Some(UNPRINTABLE_PLANE | (0x801 + u64::from(keysym - 0xffbe))), // F1..F24
+        0xffe3 => Some(FLUTTER_PLANE | 0x100), // Control_L
+        0xffe4 => Some(FLUTTER_PLANE | 0x101), // Control_R
+        0xffe1 => Some(FLUTTER_PLANE | 0x102), // Shift_L
+        0xffe2 => Some(FLUTTER_PLANE | 0x103), // Shift_R
+        0xffe9 => Some(FLUTTER_PLANE | 0x104), // Alt_L
+        0xffea => Some(FLUTTER_PLANE | 0x105), // Alt_R
+        0xfe03 => Some(UNPRINTABLE_PLANE | 0x103), // ISO_Level3_Shift (AltGraph)
+        0xffeb | 0xffe7 => Some(FLUTTER_PLANE | 0x106), // Super_L, Meta_L
+        0xffec | 0xffe8 => Some(FLUTTER_PLANE | 0x107), // Super_R, Meta_R
+        0xff8d => Some(FLUTTER_PLANE | 0x20d), // KP_Enter
+        _ => None,
+    };
+    if let Some(logical) = unprintable {
+        return logical;
+    }
+
+    match character {
+        Some(c) if !c.is_control() => {
+            // only map to the lowercase letter if that's a single character; otherwise leave it be
+            let mut lower = c.to_lowercase();
+            let c = match (lower.next(), lower.next()) {
+                (Some(lower), None) => lower,
+                _ => c,
+            };
+            UNICODE_PLANE | u64::from(u32::from(c))
+        }
+        _ => GTK_PLANE | u64::from(keysym),
+    }
+}
+
+fn physical_key(evdev_code: u32) -> u64 {
+    physical_key_from_evdev(evdev_code).unwrap_or_else(|| GTK_PLANE | u64::from(evdev_code))
+}
+
+/// A key that the platform says is pressed, for [`KeyboardState::sync`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PressedKey {
+    pub evdev_code: u32,
+    /// As for [`KeyboardState::key`].
+    pub keysym: u32,
+    pub character: Option<char>,
+}
+
+/// Tracks which keys are pressed, producing [`KeyEvent`]s that follow the engine's rules:
+///
+/// - A press of a key that is already pressed is a [`KeyPhase::Repeat`].
+/// - A release has the same logical key as the press, even if the layout or modifiers changed in between.
+/// - A release of a key that isn't pressed (e.g. it was pressed before the window got focus) is an empty event.
+/// - When the layout changes, held keys whose logical key changed are released and pressed again; see [`KeyboardState::layout_changed`].
+#[derive(Debug, Clone, Default)]
+pub struct KeyboardState {
+    // physical -> (evdev code, logical)
+    pressed: HashMap<u64, (u32, u64)>,
+}
+
+impl KeyboardState {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the given physical key is currently pressed.
+    #[must_use]
+    pub fn is_pressed(&self, physical: u64) -> bool {
+        self.pressed.contains_key(&physical)
+    }
+
+    /// A key was pressed or released.
+    ///
+    /// `keysym` and `character` should be for the key without modifiers applied; see [`logical_key_from_keysym`].
+    /// `text` is what the key types with modifiers applied, which is only used for presses.
+    ///
+    /// Keys with unknown evdev codes are still delivered, with a physical key derived from the code,
+    /// so that they can at least be matched against each other.
+    pub fn key(
+        &mut self,
+        timestamp: Duration,
+        evdev_code: u32,
+        keysym: u32,
+        character: Option<char>,
+        text: Option<char>,
+        pressed: bool,
+    ) -> KeyEvent {
+        let physical = physical_key(evdev_code);
+
+        let (phase, logical) = if pressed {
+            match self.pressed.entry(physical) {
+                // keep the logical key from the original press
+                Entry::Occupied(entry) => (KeyPhase::Repeat, entry.get().1),
+                Entry::Vacant(entry) => {
+                    let logical = logical_key_from_keysym(keysym, character);
+                    entry.insert((evdev_code, logical));
+                    (KeyPhase::Down, logical)
+                }
+            }
+        } else {
+            match self.pressed.remove(&physical) {
+                Some((_, logical)) => (KeyPhase::Up, logical),
+                None => return Self::empty_event(timestamp),
+            }
+        };
+
+        let character = match phase {
+            KeyPhase::Up => None,
+            KeyPhase::Down | KeyPhase::Repeat => text
+                .filter(|c| !c.is_control())
+                .and_then(|c| CString::new(c.to_string()).ok()),
+        };
+
+        KeyEvent {
+            timestamp,
+            phase,
+            physical,
+            logical,
+            character,
+            synthesized: false,
+            device_type: KeyEventDeviceType::Keyboard,
+        }
+    }
+
+    /// An event that the framework ignores, for when a platform event should produce no key events.
+    ///
+    /// The engine requires that *some* key event is sent for each platform key event.
+    #[must_use]
+    pub fn empty_event(timestamp: Duration) -> KeyEvent {
+        KeyEvent {
+            timestamp,
+            phase: KeyPhase::Down,
+            physical: 0,
+            logical: 0,
+            character: None,
+            synthesized: false,
+            device_type: KeyEventDeviceType::Keyboard,
+        }
+    }
+
+    /// Releases all pressed keys, e.g. when the window loses keyboard focus.
+    ///
+    /// The returned events are synthesized, and should all be sent to the engine.
+    pub fn release_all(&mut self, timestamp: Duration) -> impl Iterator<Item = KeyEvent> + '_ {
+        self.pressed
+            .drain()
+            .map(move |(physical, (_, logical))| KeyEvent {
+                timestamp,
+                phase: KeyPhase::Up,
+                physical,
+                logical,
+                character: None,
+                synthesized: true,
+                device_type: KeyEventDeviceType::Keyboard,
+            })
+    }
+
+    /// Brings the pressed keys in line with the platform, e.g. when the window gains keyboard focus.
+    ///
+    /// Keys that were released while the window didn't have focus are released, and keys that were pressed
+    /// (like the modifiers held for alt-tab) are pressed, so the framework doesn't end up with stuck or missing modifiers.
+    /// The returned events are synthesized, and should all be sent to the engine.
+    pub fn sync(&mut self, timestamp: Duration, pressed: &[PressedKey]) -> Vec<KeyEvent> {
+        let pressed = pressed
+            .iter()
+            .map(|key| (physical_key(key.evdev_code), key))
+            .collect::<HashMap<_, _>>();
+
+        let released = self
+            .pressed
+            .iter()
+            .filter(|(physical, _)| !pressed.contains_key(physical))
+            .map(|(&physical, &(_, logical))| (physical, logical))
+            .collect::<Vec<_>>();
+        let mut events = Vec::new();
+        for (physical, logical) in released {
+            self.pressed.remove(&physical);
+            events.push(KeyEvent {
+                timestamp,
+                phase: KeyPhase::Up,
+                physical,
+                logical,
+                character: None,
+                synthesized: true,
+                device_type: KeyEventDeviceType::Keyboard,
+            });
+        }
+
+        for (physical, key) in pressed {
+            if let Entry::Vacant(entry) = self.pressed.entry(physical) {
+                let logical = logical_key_from_keysym(key.keysym, key.character);
+                entry.insert((key.evdev_code, logical));
+                events.push(KeyEvent {
+                    timestamp,
+                    phase: KeyPhase::Down,
+                    physical,
+                    logical,
+                    character: None,
+                    synthesized: true,
+                    device_type: KeyEventDeviceType::Keyboard,
+                });
+            }
+        }
+        events
+    }
+
+    /// The keyboard layout changed (e.g. the user switched to another XKB group, or the keymap was replaced).
+    ///
+    /// `keysym` gives the keysym and character of an evdev code in the new layout, like for [`KeyboardState::key`].
+    /// Held keys that now have another logical key are released and pressed again with the new one,
+    /// so that shortcuts combining them with keys pressed afterwards match the new layout.
+    /// The returned events are synthesized, and should all be sent to the engine.
+    pub fn layout_changed(
+        &mut self,
+        timestamp: Duration,
+        mut keysym: impl FnMut(u32) -> (u32, Option<char>),
+    ) -> Vec<KeyEvent> {
+        let mut events = Vec::new();
+        for (&physical, (evdev_code, logical)) in &mut self.pressed {
+            let (keysym, character) = keysym(*evdev_code);
+            let relaid = logical_key_from_keysym(keysym, character);
+            if relaid == *logical {
+                continue;
+            }
+            for (phase, logical) in [(KeyPhase::Up, *logical), (KeyPhase::Down, relaid)] {
+                events.push(KeyEvent {
+                    timestamp,
+                    phase,
+                    physical,
+                    logical,
+                    character: None,
+                    synthesized: true,
+                    device_type: KeyEventDeviceType::Keyboard,
+                });
+            }
+            *logical = relaid;
+        }
+        events
+    }
+}
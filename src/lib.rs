@@ -40,14 +40,14 @@ macro_rules! simple_enum {
             }
 
             impl ::std::convert::TryFrom<$c_type> for $name {
-                type Error = $c_type;
+                type Error = crate::UnknownVariant<$c_type>;
 
                 fn try_from(value: $c_type) -> ::std::result::Result<Self, Self::Error> {
                     match value {
                         $(
                             <$c_type>::$variant => Ok(Self::$variant),
                         )*
-                        unknown => Err(unknown),
+                        unknown => Err(crate::UnknownVariant::new(stringify!($name), unknown)),
                     }
                 }
             }
@@ -72,7 +72,7 @@ macro_rules! bitfield {
     ) => {
         $(
             $(#[$meta])*
-            #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+            #[derive(Copy, Clone, Hash, PartialEq, Eq)]
             $v struct $name($c_type);
 
             impl $name {
@@ -81,6 +81,90 @@ macro_rules! bitfield {
                     #[allow(non_upper_case_globals)]
                     pub const $variant: Self = Self(bitfield!(@ $($value;)? <$c_type>::$variant));
                 )*
+
+                /// Every flag with its name, in declaration order. Flags that share a bit all appear.
+                const NAMED: &'static [(&'static str, Self)] = &[$((stringify!($variant), Self::$variant),)*];
+
+                fn bits(self) -> u64 {
+                    crate::BitfieldRepr::to_bits(self.0)
+                }
+
+                fn from_bits(bits: u64) -> Self {
+                    Self(crate::BitfieldRepr::from_bits(bits))
+                }
+
+                /// No flags at all.
+                #[must_use]
+                pub fn empty() -> Self {
+                    Self::from_bits(0)
+                }
+
+                #[must_use]
+                pub fn is_empty(self) -> bool {
+                    self.bits() == 0
+                }
+
+                /// Whether every flag in `other` is set.
+                #[must_use]
+                pub fn contains(self, other: Self) -> bool {
+                    self.bits() & other.bits() == other.bits()
+                }
+
+                /// Sets every flag in `other`.
+                pub fn insert(&mut self, other: Self) {
+                    *self = Self::from_bits(self.bits() | other.bits());
+                }
+
+                /// Clears every flag in `other`.
+                pub fn remove(&mut self, other: Self) {
+                    *self = Self::from_bits(self.bits() & !other.bits());
+                }
+
+                /// Flips every flag in `other`.
+                pub fn toggle(&mut self, other: Self) {
+                    *self = Self::from_bits(self.bits() ^ other.bits());
+                }
+
+                /// Each set bit on its own, from the lowest. Bits without a name are included too.
+                pub fn iter(self) -> impl Iterator<Item = Self> {
+                    let mut bits = self.bits();
+                    ::std::iter::from_fn(move || {
+                        if bits == 0 {
+                            return None;
+                        }
+                        let bit = bits & bits.wrapping_neg();
+                        bits &= !bit;
+                        Some(Self::from_bits(bit))
+                    })
+                }
+
+                /// The name of the first flag declared with exactly these bits.
+                fn name(self) -> Option<&'static str> {
+                    Self::NAMED
+                        .iter()
+                        .find(|(_, flag)| flag.bits() == self.bits())
+                        .map(|&(name, _)| name)
+                }
+            }
+
+            impl ::std::fmt::Debug for $name {
+                /// Lists the names of the set flags, like `Name(A | B)`. Unnamed bits are written in hex.
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    write!(f, "{}(", stringify!($name))?;
+                    if self.is_empty() {
+                        write!(f, "empty")?;
+                    }
+                    for (i, flag) in self.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, " | ")?;
+                        }
+                        match flag.name() {
+                            Some(name) => write!(f, "{name}")?,
+                            None => write!(f, "{:#x}", flag.bits())?,
+                        }
+                    }
+                    write!(f, ")")
+                }
             }
 
             impl ::std::convert::From<$name> for $c_type {
@@ -125,6 +209,24 @@ macro_rules! bitfield {
     };
 }
 
+/// Functions that are `pub` with the `sys` feature, and `pub(crate)` without it.
+///
+/// These convert from the raw `embedder.h` types, which only make sense outside the crate when those are public.
+macro_rules! sys_pub {
+    (
+        $(#[$meta:meta])*
+        unsafe fn $($rest:tt)*
+    ) => {
+        $(#[$meta])*
+        #[cfg(feature = "sys")]
+        pub unsafe fn $($rest)*
+
+        $(#[$meta])*
+        #[cfg(not(feature = "sys"))]
+        pub(crate) unsafe fn $($rest)*
+    };
+}
+
 macro_rules! modules {
     (
         $(
@@ -139,6 +241,13 @@ macro_rules! modules {
 }
 
 pub mod proc_table;
+/// The raw bindings to `embedder.h`, as generated by bindgen, with the `FlutterEngine` prefix stripped from function names.
+///
+/// These are for calling into the engine where the safe API doesn't reach yet.
+/// They follow the engine's header, not this crate's semver.
+#[cfg(feature = "sys")]
+pub mod sys;
+#[cfg(not(feature = "sys"))]
 mod sys;
 
 const _CHECK_ENGINE_VERSION: () = {
@@ -171,22 +280,72 @@ const _CHECK_ENGINE_VERSION: () = {
 
 modules![
     aot,
+    build_info,
+    channels,
+    clock,
     compositor,
+    context,
+    cursor,
     dart_object,
     display,
+    drag_drop,
     engine,
     enums,
     events,
+    focus,
+    fonts,
+    frame_callbacks,
+    frame_stats,
     geometry,
     graphics,
+    headless,
+    hot_reload,
+    isolate,
+    json,
+    keyboard,
     locale,
+    memory_pressure,
     pointer,
+    process_text,
     renderer,
     semantics,
+    spawn,
+    standard_codec,
+    supervisor,
     task_runners,
+    text_input,
+    undo_manager,
     util,
+    view_ids,
+    vsync,
+    watchdog,
 ];
+pub mod accessibility;
+#[cfg(feature = "app")]
+pub mod app;
+#[cfg(feature = "pipewire")]
+pub mod camera;
+pub mod desktop;
+pub mod driver;
+#[cfg(feature = "golden")]
+pub mod golden;
+pub mod mock;
+#[cfg(feature = "overlay")]
+pub mod overlay;
+#[cfg(feature = "dbus")]
+pub mod portal;
+#[cfg(feature = "smithay")]
+pub mod smithay;
+pub mod software_views;
 pub mod trace;
+#[cfg(feature = "gstreamer")]
+pub mod video;
+#[cfg(feature = "wayland")]
+pub mod wayland;
+#[cfg(feature = "raw-window-handle")]
+pub mod window_handle;
+#[cfg(feature = "x11")]
+pub mod x11;
 
 pub fn get_proc_table() -> crate::Result<sys::FlutterEngineProcTable> {
     let mut proc_table: sys::FlutterEngineProcTable = unsafe { std::mem::zeroed() };
@@ -195,3 +354,60 @@ pub fn get_proc_table() -> crate::Result<sys::FlutterEngineProcTable> {
         .to_result()
         .map(|()| proc_table)
 }
+
+#[cfg(test)]
+mod tests {
+    bitfield! {
+        struct Flags(i64) {
+            A = 1 << 0,
+            B = 1 << 1,
+            AB = 1 << 0 | 1 << 1,
+            Alias = 1 << 0,
+            High = i64::MIN,
+        }
+    }
+
+    #[test]
+    fn bitfield_set_operations() {
+        let mut flags = Flags::empty();
+        assert!(flags.is_empty());
+
+        flags.insert(Flags::A);
+        flags.insert(Flags::B);
+        assert_eq!(flags, Flags::AB);
+        assert!(flags.contains(Flags::A));
+        assert!(flags.contains(Flags::AB));
+        assert!(!flags.contains(Flags::High));
+
+        flags.remove(Flags::A);
+        assert_eq!(flags, Flags::B);
+        assert!(!flags.contains(Flags::AB));
+
+        flags.toggle(Flags::AB);
+        assert_eq!(flags, Flags::Alias);
+        assert_eq!(Flags::A | Flags::B, Flags::AB);
+        assert_eq!(Flags::AB & Flags::B, Flags::B);
+    }
+
+    #[test]
+    fn bitfield_iterates_single_bits() {
+        let flags = Flags::AB | Flags::High | Flags::from(1 << 5);
+        assert_eq!(
+            flags.iter().map(i64::from).collect::<Vec<_>>(),
+            [1 << 0, 1 << 1, 1 << 5, i64::MIN],
+        );
+        assert_eq!(Flags::empty().iter().count(), 0);
+    }
+
+    #[test]
+    fn bitfield_debug() {
+        assert_eq!(format!("{:?}", Flags::empty()), "Flags(empty)");
+        // bits are named by the first flag declared with them, and listed one at a time
+        assert_eq!(format!("{:?}", Flags::Alias), "Flags(A)");
+        assert_eq!(format!("{:?}", Flags::AB), "Flags(A | B)");
+        assert_eq!(
+            format!("{:?}", Flags::B | Flags::High | Flags::from(1 << 5)),
+            "Flags(B | 0x20 | High)",
+        );
+    }
+}
@@ -2,6 +2,7 @@ use std::ffi::{CStr, CString};
 
 use crate::{sys, Engine};
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Locale {
     /// The language code of the locale. For example, "en".
     pub language_code: CString,
@@ -13,11 +14,65 @@ pub struct Locale {
     pub variant_code: Option<CString>,
 }
 
+impl From<&str> for Locale {
+    /// Parses a BCP 47 language tag like `en-US` or `zh-Hant-TW`, or a POSIX locale name like `en_US.UTF-8`.
+    fn from(tag: &str) -> Self {
+        // POSIX locale names can end in a codeset and a modifier, which Flutter has no use for
+        let tag = tag.split(['.', '@']).next().unwrap_or_default();
+        let mut subtags = tag
+            .split(['-', '_'])
+            .filter(|subtag| !subtag.is_empty())
+            .peekable();
+        let is_alpha = |subtag: &str| subtag.bytes().all(|c| c.is_ascii_alphabetic());
+        let is_digit = |subtag: &str| subtag.bytes().all(|c| c.is_ascii_digit());
+
+        let language_code = c_subtag(subtags.next().unwrap_or("und"));
+        let script_code = subtags
+            .next_if(|subtag| subtag.len() == 4 && is_alpha(subtag))
+            .map(c_subtag);
+        let country_code = subtags
+            .next_if(|subtag| {
+                (subtag.len() == 2 && is_alpha(subtag)) || (subtag.len() == 3 && is_digit(subtag))
+            })
+            .map(c_subtag);
+        let variant_code = subtags.next().map(c_subtag);
+        Self {
+            language_code,
+            country_code,
+            script_code,
+            variant_code,
+        }
+    }
+}
+
+impl From<String> for Locale {
+    fn from(tag: String) -> Self {
+        Self::from(tag.as_str())
+    }
+}
+
+impl From<&Locale> for Locale {
+    fn from(locale: &Locale) -> Self {
+        locale.clone()
+    }
+}
+
+fn c_subtag(subtag: &str) -> CString {
+    CString::new(subtag.replace('\0', "")).expect("nul bytes were removed")
+}
+
 impl Engine {
     /// Notify a running engine instance that the locale has been updated.
     /// The preferred locale must be the first item in the list of locales supplied.
     /// The other entries will be used as a fallback.
-    pub fn update_locales(&mut self, locales: &[Locale]) -> crate::Result<()> {
+    ///
+    /// Locales can be given as language tags, e.g. `engine.update_locales(["en-US", "fr"])`.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
+    pub fn update_locales(
+        &mut self,
+        locales: impl IntoIterator<Item = impl Into<Locale>>,
+    ) -> crate::Result<()> {
+        let locales: Vec<Locale> = locales.into_iter().map(Into::into).collect();
         let locales: Box<[sys::FlutterLocale]> = locales
             .iter()
             .map(|locale| sys::FlutterLocale {
@@ -45,3 +100,81 @@ impl Engine {
             .to_result()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale(
+        language: &CStr,
+        script: Option<&CStr>,
+        country: Option<&CStr>,
+        variant: Option<&CStr>,
+    ) -> Locale {
+        Locale {
+            language_code: language.to_owned(),
+            country_code: country.map(CStr::to_owned),
+            script_code: script.map(CStr::to_owned),
+            variant_code: variant.map(CStr::to_owned),
+        }
+    }
+
+    #[test]
+    fn bcp47_tags() {
+        assert_eq!(Locale::from("en"), locale(c"en", None, None, None));
+        assert_eq!(
+            Locale::from("en-US"),
+            locale(c"en", None, Some(c"US"), None)
+        );
+        assert_eq!(
+            Locale::from("zh-Hant-TW"),
+            locale(c"zh", Some(c"Hant"), Some(c"TW"), None)
+        );
+        assert_eq!(
+            Locale::from("sr-Latn"),
+            locale(c"sr", Some(c"Latn"), None, None)
+        );
+        assert_eq!(
+            Locale::from("es-419"),
+            locale(c"es", None, Some(c"419"), None)
+        );
+        assert_eq!(
+            Locale::from("de-DE-1996"),
+            locale(c"de", None, Some(c"DE"), Some(c"1996"))
+        );
+        assert_eq!(
+            Locale::from("ca-valencia"),
+            locale(c"ca", None, None, Some(c"valencia"))
+        );
+    }
+
+    #[test]
+    fn posix_names() {
+        assert_eq!(
+            Locale::from("en_US.UTF-8"),
+            locale(c"en", None, Some(c"US"), None)
+        );
+        assert_eq!(
+            Locale::from("sr_RS@latin"),
+            locale(c"sr", None, Some(c"RS"), None)
+        );
+        assert_eq!(
+            Locale::from("zh_Hans_CN.GB18030@stroke"),
+            locale(c"zh", Some(c"Hans"), Some(c"CN"), None)
+        );
+    }
+
+    #[test]
+    fn malformed_tags() {
+        assert_eq!(Locale::from(""), locale(c"und", None, None, None));
+        assert_eq!(Locale::from(".UTF-8"), locale(c"und", None, None, None));
+        assert_eq!(
+            Locale::from("en--US_"),
+            locale(c"en", None, Some(c"US"), None)
+        );
+        assert_eq!(
+            Locale::from("e\0n-US"),
+            locale(c"en", None, Some(c"US"), None)
+        );
+    }
+}
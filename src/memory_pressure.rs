@@ -0,0 +1,186 @@
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::{Engine, SYSTEM_CHANNEL};
+
+/// One line of a PSI file, e.g. `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct PressureAverages {
+    /// Percentage of time stalled on memory, over the last 10 seconds.
+    pub avg10: f64,
+    /// Over the last 60 seconds.
+    pub avg60: f64,
+    /// Over the last 300 seconds.
+    pub avg300: f64,
+    /// Total time stalled, in microseconds.
+    pub total: u64,
+}
+
+/// The contents of `/proc/pressure/memory`, or of a cgroup's `memory.pressure`.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct MemoryPressure {
+    /// Some tasks were stalled on memory.
+    pub some: PressureAverages,
+    /// All non-idle tasks were stalled on memory at once.
+    pub full: PressureAverages,
+}
+
+impl MemoryPressure {
+    /// Parses a PSI file. Returns `None` if it has no `some` line.
+    #[must_use]
+    pub fn parse(psi: &str) -> Option<Self> {
+        let mut some = None;
+        let mut full = None;
+        for line in psi.lines() {
+            let mut fields = line.split_whitespace();
+            let slot = match fields.next() {
+                Some("some") => &mut some,
+                Some("full") => &mut full,
+                _ => continue,
+            };
+            let mut averages = PressureAverages::default();
+            for field in fields {
+                let Some((key, value)) = field.split_once('=') else {
+                    continue;
+                };
+                match key {
+                    "avg10" => averages.avg10 = value.parse().ok()?,
+                    "avg60" => averages.avg60 = value.parse().ok()?,
+                    "avg300" => averages.avg300 = value.parse().ok()?,
+                    "total" => averages.total = value.parse().ok()?,
+                    _ => {}
+                }
+            }
+            *slot = Some(averages);
+        }
+        Some(Self {
+            some: some?,
+            // older kernels only report `some` for memory in cgroups
+            full: full.unwrap_or_default(),
+        })
+    }
+}
+
+/// When a [`MemoryPressureMonitor`] warns the engine.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MemoryPressureThresholds {
+    /// Warn when `some avg10` reaches this percentage.
+    pub some_avg10: f64,
+    /// Warn when `full avg10` reaches this percentage.
+    pub full_avg10: f64,
+    /// After warning, don't warn again for this long, even if the pressure stays high.
+    pub backoff: Duration,
+}
+
+impl Default for MemoryPressureThresholds {
+    fn default() -> Self {
+        Self {
+            some_avg10: 10.0,
+            full_avg10: 5.0,
+            backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Calls [`Engine::notify_low_memory_warning`] when the system (or the app's cgroup) is under memory pressure,
+/// as reported by the kernel's pressure stall information.
+///
+/// It doesn't run on its own; call [`MemoryPressureMonitor::check`] regularly, e.g. once a second.
+#[derive(Debug)]
+pub struct MemoryPressureMonitor {
+    path: PathBuf,
+    thresholds: MemoryPressureThresholds,
+    last_warning: Option<Instant>,
+}
+
+impl MemoryPressureMonitor {
+    /// Monitors the whole system, with `/proc/pressure/memory`.
+    #[must_use]
+    pub fn system(thresholds: MemoryPressureThresholds) -> Self {
+        Self::with_path("/proc/pressure/memory", thresholds)
+    }
+
+    /// Monitors the cgroup (v2) that this process is in, which is what matters under a memory limit,
+    /// e.g. in a container or a systemd unit.
+    pub fn current_cgroup(thresholds: MemoryPressureThresholds) -> io::Result<Self> {
+        let cgroups = std::fs::read_to_string("/proc/self/cgroup")?;
+        // the unified hierarchy is the one with id 0 and no controllers
+        let Some(cgroup) = cgroups.lines().find_map(|line| line.strip_prefix("0::")) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "not in a cgroup v2 hierarchy",
+            ));
+        };
+        let path = PathBuf::from("/sys/fs/cgroup")
+            .join(cgroup.trim_start_matches('/'))
+            .join("memory.pressure");
+        Ok(Self::with_path(path, thresholds))
+    }
+
+    /// Monitors any PSI file.
+    #[must_use]
+    pub fn with_path(path: impl Into<PathBuf>, thresholds: MemoryPressureThresholds) -> Self {
+        Self {
+            path: path.into(),
+            thresholds,
+            last_warning: None,
+        }
+    }
+
+    pub fn thresholds(&mut self) -> &mut MemoryPressureThresholds {
+        &mut self.thresholds
+    }
+
+    /// Reads the current pressure.
+    pub fn read(&self) -> io::Result<MemoryPressure> {
+        let psi = std::fs::read_to_string(&self.path)?;
+        MemoryPressure::parse(&psi).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a PSI file", self.path.display()),
+            )
+        })
+    }
+
+    /// Whether the engine should be warned about `pressure` now, given the thresholds and the backoff.
+    /// If so, this counts as a warning for the backoff.
+    pub fn should_warn(&mut self, pressure: &MemoryPressure, now: Instant) -> bool {
+        let high = pressure.some.avg10 >= self.thresholds.some_avg10
+            || pressure.full.avg10 >= self.thresholds.full_avg10;
+        let backing_off = self
+            .last_warning
+            .is_some_and(|last| now.duration_since(last) < self.thresholds.backoff);
+        if !high || backing_off {
+            return false;
+        }
+        self.last_warning = Some(now);
+        true
+    }
+
+    /// Reads the pressure, and warns the engine if it's high. Returns whether it did.
+    ///
+    /// If PSI isn't available (the kernel wasn't built with it, or it's disabled with `psi=0`), this never warns.
+    pub fn check(&mut self, engine: &mut Engine) -> crate::Result<bool> {
+        let Ok(pressure) = self.read() else {
+            return Ok(false);
+        };
+        if !self.should_warn(&pressure, Instant::now()) {
+            return Ok(false);
+        }
+        engine.notify_low_memory_warning()?;
+        Ok(true)
+    }
+}
+
+/// Tells the framework that memory is low, so `WidgetsBindingObserver.didHaveMemoryPressure` fires
+/// and the image cache is cleared. Unlike [`Engine::notify_low_memory_warning`], this leaves the engine's own caches alone.
+///
+/// [`Engine::notify_low_memory_warning`] sends this same message after the engine has released what it can,
+/// so it's the right call when the whole app should shrink. Use this instead when only the Dart side should react,
+/// e.g. to drop app-level caches while a frame-heavy animation keeps the engine's GPU caches warm.
+pub fn send_memory_pressure(engine: &mut Engine) -> crate::Result<()> {
+    engine.send_platform_message(SYSTEM_CHANNEL, br#"{"type":"memoryPressure"}"#, |_| {})
+}
@@ -0,0 +1,314 @@
+//! A stand-in for the engine, for unit testing the code that the engine calls into.
+//!
+//! [`MockEngine`] invokes an [`EngineHandler`] (and optionally a [`CompositorHandler`] and task runners)
+//! the same way the engine would, but never calls into the engine itself. Nothing here needs a running engine,
+//! so handlers can be tested in isolation, e.g. answering platform messages or presenting layers.
+//!
+//! Handlers get an [`EngineContext`] that isn't backed by an engine. Everything they do through it is recorded instead,
+//! for the test to look at with [`MockEngine::take_calls`], [`MockEngine::take_sent_messages`] and so on.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use crate::{
+    BackingStore, BackingStoreConfig, CompositorHandler, EngineContext, EngineHandler, Layer,
+    PlatformMessageResponse, ResponseTimedOut, SemanticsAction, SemanticsUpdate, Task,
+    TaskRunnerHandler, ViewFocusEvent, ViewId, VsyncBaton, WindowMetricsEvent,
+};
+
+/// A call that a handler made through its [`EngineContext`], see [`MockEngine::take_calls`].
+///
+/// Platform messages, frame callbacks and render thread tasks aren't calls, because they have to be answered or run;
+/// see [`MockEngine::take_sent_messages`], [`MockEngine::run_next_frame_callbacks`] and [`MockEngine::run_render_thread_tasks`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCall {
+    SendWindowMetricsEvent(WindowMetricsEvent),
+    ScheduleFrame,
+    UpdateSemanticsEnabled(bool),
+    DispatchSemanticsAction {
+        node_id: u64,
+        action: SemanticsAction,
+        data: Vec<u8>,
+    },
+    RegisterExternalTexture(i64),
+    UnregisterExternalTexture(i64),
+    MarkExternalTextureFrameAvailable(i64),
+}
+
+/// A platform message that a handler sent to the framework, see [`MockEngine::take_sent_messages`].
+pub struct SentPlatformMessage {
+    pub channel: CString,
+    pub message: Vec<u8>,
+    /// The timeout it was sent with, if any.
+    pub timeout: Option<Duration>,
+    #[allow(clippy::type_complexity)] // not a complex type
+    response: Box<dyn FnOnce(Result<&[u8], ResponseTimedOut>)>,
+}
+
+impl fmt::Debug for SentPlatformMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentPlatformMessage")
+            .field("channel", &self.channel)
+            .field("message", &self.message)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SentPlatformMessage {
+    /// Responds to the message, as the framework would.
+    pub fn respond(self, response: &[u8]) {
+        (self.response)(Ok(response));
+    }
+
+    /// Gives up on the response, as [`crate::Engine::expire_platform_messages`] does once the timeout has passed.
+    ///
+    /// # Panics
+    ///
+    /// If the message was sent without a timeout, so it can't time out.
+    pub fn time_out(self) {
+        assert!(
+            self.timeout.is_some(),
+            "a platform message sent without a timeout can't time out"
+        );
+        (self.response)(Err(ResponseTimedOut));
+    }
+}
+
+/// Everything that handlers did through the [`EngineContext`]s that a [`MockEngine`] gave them.
+#[derive(Default)]
+pub(crate) struct MockRecorder {
+    pub(crate) calls: Vec<MockCall>,
+    messages: Vec<SentPlatformMessage>,
+    window_metrics: HashMap<ViewId, WindowMetricsEvent>,
+    pub(crate) next_frame_callbacks: Vec<Box<dyn FnOnce()>>,
+    pub(crate) render_thread_tasks: Vec<Box<dyn FnOnce()>>,
+}
+
+impl fmt::Debug for MockRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockRecorder")
+            .field("calls", &self.calls)
+            .field("messages", &self.messages)
+            .field("window_metrics", &self.window_metrics)
+            .field("next_frame_callbacks", &self.next_frame_callbacks.len())
+            .field("render_thread_tasks", &self.render_thread_tasks.len())
+            .finish()
+    }
+}
+
+impl MockRecorder {
+    #[allow(clippy::type_complexity)] // not a complex type
+    pub(crate) fn record_message(
+        &mut self,
+        channel: &CStr,
+        message: &[u8],
+        timeout: Option<Duration>,
+        response: Box<dyn FnOnce(Result<&[u8], ResponseTimedOut>)>,
+    ) {
+        self.messages.push(SentPlatformMessage {
+            channel: channel.to_owned(),
+            message: message.to_vec(),
+            timeout,
+            response,
+        });
+    }
+
+    pub(crate) fn record_window_metrics(&mut self, metrics: WindowMetricsEvent) {
+        self.window_metrics.insert(metrics.view_id, metrics);
+        self.calls.push(MockCall::SendWindowMetricsEvent(metrics));
+    }
+}
+
+/// Drives an [`EngineHandler`] like the engine would. See the [module docs](self).
+pub struct MockEngine {
+    handler: Box<dyn EngineHandler>,
+    compositor: Option<Box<dyn CompositorHandler>>,
+    recorder: Rc<RefCell<MockRecorder>>,
+    semantics_enabled: Arc<AtomicBool>,
+    next_baton: isize,
+    next_task: u64,
+}
+
+impl fmt::Debug for MockEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockEngine")
+            .field("has_compositor", &self.compositor.is_some())
+            .field("recorder", &self.recorder)
+            .field("next_baton", &self.next_baton)
+            .field("next_task", &self.next_task)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MockEngine {
+    #[must_use]
+    pub fn new(handler: Box<dyn EngineHandler>) -> Self {
+        Self {
+            handler,
+            compositor: None,
+            recorder: Rc::default(),
+            semantics_enabled: Arc::default(),
+            next_baton: 1,
+            next_task: 1,
+        }
+    }
+
+    /// Also drive a compositor, through [`MockEngine::create_backing_store`] etc.
+    #[must_use]
+    pub fn with_compositor(mut self, compositor: Box<dyn CompositorHandler>) -> Self {
+        self.compositor = Some(compositor);
+        self
+    }
+
+    pub fn handler(&mut self) -> &mut dyn EngineHandler {
+        &mut *self.handler
+    }
+
+    fn context(&self) -> EngineContext {
+        EngineContext::mock(self.recorder.clone(), self.semantics_enabled.clone())
+    }
+
+    /// The calls that handlers made through their [`EngineContext`] since the last time, in order.
+    pub fn take_calls(&mut self) -> Vec<MockCall> {
+        std::mem::take(&mut self.recorder.borrow_mut().calls)
+    }
+
+    /// The platform messages that handlers sent since the last time, in order.
+    /// Each one's response is called back when it's responded to (or timed out), and never if it's dropped.
+    pub fn take_sent_messages(&mut self) -> Vec<SentPlatformMessage> {
+        std::mem::take(&mut self.recorder.borrow_mut().messages)
+    }
+
+    /// The latest metrics that handlers sent for a view.
+    #[must_use]
+    pub fn window_metrics(&self, view_id: ViewId) -> Option<WindowMetricsEvent> {
+        self.recorder.borrow().window_metrics.get(&view_id).copied()
+    }
+
+    /// The last value that handlers passed to [`EngineContext::update_semantics_enabled`].
+    #[must_use]
+    pub fn semantics_enabled(&self) -> bool {
+        self.semantics_enabled.load(Ordering::Acquire)
+    }
+
+    /// Calls the callbacks that handlers passed to [`EngineContext::set_next_frame_callback`], as if a frame was presented.
+    pub fn run_next_frame_callbacks(&mut self) {
+        let callbacks = std::mem::take(&mut self.recorder.borrow_mut().next_frame_callbacks);
+        for callback in callbacks {
+            callback();
+        }
+    }
+
+    /// Runs the tasks that handlers passed to [`EngineContext::post_render_thread_task`].
+    ///
+    /// They run on this thread; there is no render thread.
+    pub fn run_render_thread_tasks(&mut self) {
+        let tasks = std::mem::take(&mut self.recorder.borrow_mut().render_thread_tasks);
+        for task in tasks {
+            task();
+        }
+    }
+
+    /// Sends a platform message from the "Dart side" to the handler.
+    ///
+    /// Returns the response, if the handler sent one before returning.
+    /// Handlers that respond later (e.g. from another thread) can't be observed this way.
+    pub fn send_platform_message(&mut self, channel: &CStr, message: &[u8]) -> Option<Vec<u8>> {
+        let slot = Arc::new(Mutex::new(None));
+        self.handler.platform_message(
+            &mut self.context(),
+            channel,
+            message,
+            PlatformMessageResponse::mock(slot.clone()),
+        );
+        let response = slot.lock().unwrap_or_else(PoisonError::into_inner).take();
+        response
+    }
+
+    /// Asks the handler for vsync, like the engine does before every frame. Returns the baton it was given.
+    pub fn request_vsync(&mut self) -> VsyncBaton {
+        let baton = self.next_baton;
+        self.next_baton += 1;
+        self.handler.vsync(VsyncBaton(baton));
+        VsyncBaton(baton)
+    }
+
+    /// Sends a semantics update, as if the framework had changed its semantics tree.
+    pub fn update_semantics(&mut self, update: SemanticsUpdate) {
+        self.handler.update_semantics(&mut self.context(), update);
+    }
+
+    pub fn log_message(&mut self, tag: &CStr, message: &CStr) {
+        self.handler.log_message(tag, message);
+    }
+
+    /// Notifies the handler that a channel listener was set (or cleared) on the framework side.
+    pub fn channel_update(&mut self, channel: &CStr, listening: bool) {
+        self.handler
+            .channel_update(&mut self.context(), channel, listening);
+    }
+
+    /// Simulates the framework asking for input focus to move to or from a view.
+    pub fn view_focus_change_request(&mut self, request: ViewFocusEvent) {
+        self.handler
+            .view_focus_change_request(&mut self.context(), request);
+    }
+
+    pub fn root_isolate_created(&mut self) {
+        self.handler.root_isolate_created();
+    }
+
+    /// Simulates a hot restart.
+    pub fn restart(&mut self) {
+        self.handler.on_pre_engine_restart(&mut self.context());
+    }
+
+    /// Posts a task to a task runner. Returns the identifier of the task, which is [`Task::task`].
+    ///
+    /// The task doesn't belong to any engine, so [`crate::Engine::run_task`] rejects it.
+    pub fn post_task(&mut self, runner: &dyn TaskRunnerHandler, target_time: Duration) -> u64 {
+        let task = self.next_task;
+        self.next_task += 1;
+        runner.post_task(target_time, Task::mock(task));
+        task
+    }
+
+    fn compositor(&mut self) -> &mut dyn CompositorHandler {
+        &mut **self
+            .compositor
+            .as_mut()
+            .expect("MockEngine has no compositor; see MockEngine::with_compositor")
+    }
+
+    /// # Panics
+    ///
+    /// If there is no compositor.
+    pub fn create_backing_store(&mut self, config: BackingStoreConfig) -> Option<BackingStore> {
+        self.compositor().create_backing_store(config)
+    }
+
+    /// # Panics
+    ///
+    /// If there is no compositor.
+    pub fn collect_backing_store(&mut self, backing_store: BackingStore) -> bool {
+        self.compositor().collect_backing_store(backing_store)
+    }
+
+    /// # Panics
+    ///
+    /// If there is no compositor.
+    pub fn present_view(&mut self, view_id: ViewId, layers: &[Layer]) -> bool {
+        self.compositor().present_view(view_id, layers)
+    }
+}
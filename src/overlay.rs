@@ -0,0 +1,425 @@
+//! Rendering the engine's views into Vulkan images, for a game (or any other renderer) to draw them as it sees fit.
+//!
+//! The engine shares the game's `VkDevice`. Every layer of every view is rendered into its own image,
+//! and the latest [`OverlayFrame`] of a view can be taken at any time, as plain Vulkan handles.
+//! This way it doesn't matter whether the game uses `ash`, `wgpu` (through its hal), or anything else,
+//! as long as it can sample a `VkImage`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{c_void, CStr, CString},
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use ash::vk::{self, Handle};
+use tracing::{error, warn};
+
+use crate::{
+    lock_vulkan_queue, renderer::locked_instance_proc_address, sys, BackingStore,
+    BackingStoreConfig, Compositor, CompositorHandler, FrameInfo, Layer, LayerContent, Point, Size,
+    ViewId, VulkanBackingStore, VulkanImage, VulkanRendererConfig, VulkanRendererHandler,
+};
+
+pub use ::ash;
+
+/// The format of every image in an [`OverlayFrame`].
+pub const OVERLAY_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+/// The game's Vulkan device, which the engine will render with.
+///
+/// All of these must stay valid until the engine has shut down and the [`OverlayTextures`] are dropped.
+/// The queue must only be used while holding [`lock_vulkan_queue`].
+#[derive(Clone)]
+pub struct OverlayDevice {
+    pub entry: ash::Entry,
+    pub instance: ash::Instance,
+    /// The Vulkan API version the instance was created with.
+    pub version: u32,
+    pub physical_device: vk::PhysicalDevice,
+    pub device: ash::Device,
+    pub queue_family_index: u32,
+    pub queue: vk::Queue,
+    /// The extensions the instance was created with. Any subset is fine.
+    pub enabled_instance_extensions: Vec<CString>,
+    /// The extensions the device was created with. Any subset is fine.
+    pub enabled_device_extensions: Vec<CString>,
+}
+
+impl fmt::Debug for OverlayDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverlayDevice")
+            .field("instance", &self.instance.handle())
+            .field("version", &self.version)
+            .field("physical_device", &self.physical_device)
+            .field("device", &self.device.handle())
+            .field("queue_family_index", &self.queue_family_index)
+            .field("queue", &self.queue)
+            .field(
+                "enabled_instance_extensions",
+                &self.enabled_instance_extensions,
+            )
+            .field("enabled_device_extensions", &self.enabled_device_extensions)
+            .finish_non_exhaustive()
+    }
+}
+
+/// One layer of an [`OverlayFrame`].
+///
+/// The image is [`OVERLAY_FORMAT`], with premultiplied alpha, and in `VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL`.
+/// It can be sampled (`VK_IMAGE_USAGE_SAMPLED_BIT`) and copied from (`VK_IMAGE_USAGE_TRANSFER_SRC_BIT`).
+/// The engine never renders into it again, so transition it to whatever layout you need.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlayLayer {
+    /// The `VkImage` handle.
+    pub image: u64,
+    pub size: Size<u32>,
+    /// Where the layer goes, relative to the top left of the view (in physical pixels).
+    pub offset: Point<f64>,
+}
+
+/// Everything the engine presented for a view, at once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayFrame {
+    /// Increases with every frame presented, across all views.
+    pub serial: u64,
+    /// The layers, from bottom to top.
+    pub layers: Vec<OverlayLayer>,
+}
+
+struct Image {
+    memory: vk::DeviceMemory,
+    size: Size<u32>,
+}
+
+#[derive(Default)]
+struct Shared {
+    serial: u64,
+    views: HashMap<ViewId, OverlayFrame>,
+    images: HashMap<vk::Image, Image>,
+    // images the engine is done with, but which the game may still be drawing
+    collected: HashSet<vk::Image>,
+}
+
+/// The engine's half of [`OverlayTextures`]. It runs on the raster thread.
+struct OverlayCompositor {
+    device: ash::Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl OverlayCompositor {
+    fn create_image(&self, size: Size<u32>) -> Result<(vk::Image, vk::DeviceMemory), vk::Result> {
+        let image = unsafe {
+            self.device.create_image(
+                &vk::ImageCreateInfo::default()
+                    .image_type(vk::ImageType::TYPE_2D)
+                    .format(OVERLAY_FORMAT)
+                    .extent(vk::Extent3D {
+                        width: size.width,
+                        height: size.height,
+                        depth: 1,
+                    })
+                    .mip_levels(1)
+                    .array_layers(1)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .tiling(vk::ImageTiling::OPTIMAL)
+                    .usage(
+                        vk::ImageUsageFlags::COLOR_ATTACHMENT
+                            | vk::ImageUsageFlags::SAMPLED
+                            | vk::ImageUsageFlags::TRANSFER_SRC,
+                    )
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED),
+                None,
+            )
+        }?;
+
+        let requirements = unsafe { self.device.get_image_memory_requirements(image) };
+        let memory_types = &self.memory_properties.memory_types
+            [..self.memory_properties.memory_type_count as usize];
+        let Some(memory_type_index) = (0..)
+            .zip(memory_types)
+            .find(|&(index, memory_type)| {
+                requirements.memory_type_bits & (1 << index) != 0
+                    && memory_type
+                        .property_flags
+                        .contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            })
+            .map(|(index, _)| index)
+        else {
+            unsafe { self.device.destroy_image(image, None) };
+            return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+        };
+
+        let memory = unsafe {
+            self.device.allocate_memory(
+                &vk::MemoryAllocateInfo::default()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(memory_type_index),
+                None,
+            )
+        };
+        let memory = match memory.and_then(|memory| {
+            unsafe { self.device.bind_image_memory(image, memory, 0) }.map(|()| memory)
+        }) {
+            Ok(memory) => memory,
+            Err(err) => {
+                unsafe { self.device.destroy_image(image, None) };
+                return Err(err);
+            }
+        };
+
+        Ok((image, memory))
+    }
+}
+
+impl CompositorHandler for OverlayCompositor {
+    fn create_backing_store(&mut self, config: BackingStoreConfig) -> Option<BackingStore> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let size = Size {
+            width: config.size.width.ceil() as u32,
+            height: config.size.height.ceil() as u32,
+        };
+
+        let (image, memory) = self
+            .create_image(size)
+            .inspect_err(|err| error!("failed to create an overlay image: {err}"))
+            .ok()?;
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .images
+            .insert(image, Image { memory, size });
+
+        #[allow(clippy::cast_sign_loss)]
+        Some(BackingStore::Vulkan(VulkanBackingStore {
+            image: VulkanImage {
+                image_handle: image.as_raw(),
+                format: OVERLAY_FORMAT.as_raw() as u32,
+            },
+        }))
+    }
+
+    fn collect_backing_store(&mut self, backing_store: BackingStore) -> bool {
+        let BackingStore::Vulkan(backing_store) = backing_store else {
+            unreachable!("OverlayCompositor only creates Vulkan images");
+        };
+        // the image is destroyed in `release_unused`, once the game is no longer drawing it
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .collected
+            .insert(vk::Image::from_raw(backing_store.image.image_handle));
+        true
+    }
+
+    fn present_view(&mut self, view_id: ViewId, layers: &[Layer]) -> bool {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let layers = layers
+            .iter()
+            .filter_map(|layer| match &layer.content {
+                LayerContent::BackingStore(BackingStore::Vulkan(backing_store), _) => {
+                    let image = vk::Image::from_raw(backing_store.image.image_handle);
+                    Some(OverlayLayer {
+                        image: backing_store.image.image_handle,
+                        size: shared.images[&image].size,
+                        offset: layer.offset,
+                    })
+                }
+                LayerContent::BackingStore(..) => {
+                    unreachable!("OverlayCompositor only creates Vulkan images")
+                }
+                LayerContent::PlatformView(view) => {
+                    warn!(
+                        "platform view {} is not supported by OverlayTextures, and will not be shown",
+                        view.identifier
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        shared.serial += 1;
+        let serial = shared.serial;
+        shared
+            .views
+            .insert(view_id, OverlayFrame { serial, layers });
+        true
+    }
+
+    fn view_removed(&mut self, view_id: ViewId) {
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .views
+            .remove(&view_id);
+    }
+}
+
+/// The renderer handler that goes with the [`OverlayCompositor`].
+///
+/// The engine never renders to a swapchain; everything goes through the compositor.
+struct OverlayRenderer {
+    entry: ash::Entry,
+}
+
+impl VulkanRendererHandler for OverlayRenderer {
+    fn get_instance_proc_address(
+        &mut self,
+        instance: sys::FlutterVulkanInstanceHandle,
+        name: &CStr,
+    ) -> *mut c_void {
+        unsafe {
+            locked_instance_proc_address(&self.entry, vk::Instance::from_raw(instance as u64), name)
+        }
+    }
+
+    fn get_next_image(&mut self, frame_info: FrameInfo) -> VulkanImage {
+        let _ = frame_info;
+        unreachable!("the engine only calls get_next_image without a compositor")
+    }
+
+    fn present_image(&mut self, image: VulkanImage) -> bool {
+        let _ = image;
+        unreachable!("the engine only calls present_image without a compositor")
+    }
+}
+
+/// Gives the engine's views to a game as Vulkan images.
+///
+/// Pass [`OverlayTextures::renderer`] and [`OverlayTextures::compositor`] to the engine, and only drop this after the engine has shut down.
+/// Then, every frame, draw the layers of [`OverlayTextures::frame`] for each view (e.g. as textured quads, in order),
+/// and call [`OverlayTextures::release_unused`] once the GPU is done with older frames.
+///
+/// Platform views are not supported; their layers are skipped.
+pub struct OverlayTextures {
+    device: OverlayDevice,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl fmt::Debug for OverlayTextures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverlayTextures")
+            .field("device", &self.device)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OverlayTextures {
+    #[must_use]
+    pub fn new(device: OverlayDevice) -> Self {
+        Self {
+            device,
+            shared: Arc::default(),
+        }
+    }
+
+    /// The renderer to pass to the engine in [`crate::ProjectArgs::renderer`].
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)] // dispatchable handles are pointers
+    pub fn renderer(&self) -> VulkanRendererConfig {
+        let device = &self.device;
+        VulkanRendererConfig {
+            version: device.version,
+            instance: device.instance.handle().as_raw() as usize as *mut c_void,
+            physical_device: device.physical_device.as_raw() as usize as *mut c_void,
+            device: device.device.handle().as_raw() as usize as *mut c_void,
+            queue_family_index: device.queue_family_index,
+            queue: device.queue.as_raw() as usize as *mut c_void,
+            enabled_instance_extensions: device.enabled_instance_extensions.clone(),
+            enabled_device_extensions: device.enabled_device_extensions.clone(),
+            handler: Box::new(OverlayRenderer {
+                entry: device.entry.clone(),
+            }),
+        }
+    }
+
+    /// The compositor to pass to the engine in [`crate::ProjectArgs::compositor`].
+    #[must_use]
+    pub fn compositor(&self) -> Compositor {
+        let memory_properties = unsafe {
+            self.device
+                .instance
+                .get_physical_device_memory_properties(self.device.physical_device)
+        };
+        Compositor {
+            // the engine reuses cached backing stores right after presenting them,
+            // which would race with the game drawing them. so every frame gets new ones.
+            avoid_backing_store_cache: true,
+            handler: Box::new(OverlayCompositor {
+                device: self.device.device.clone(),
+                memory_properties,
+                shared: self.shared.clone(),
+            }),
+        }
+    }
+
+    /// The latest frame the engine presented for `view_id`, if any.
+    #[must_use]
+    pub fn frame(&self, view_id: ViewId) -> Option<OverlayFrame> {
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .views
+            .get(&view_id)
+            .cloned()
+    }
+
+    /// Forgets the frames of a view that no longer exists, so that its images can be released.
+    ///
+    /// Views removed with [`crate::Engine::remove_view`] are forgotten on their own.
+    pub fn remove_view(&mut self, view_id: ViewId) {
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .views
+            .remove(&view_id);
+    }
+
+    /// Destroys the images that the engine is done with, and that aren't part of the latest frame of any view.
+    ///
+    /// Only call this when the GPU is done with every frame but the latest ones,
+    /// e.g. after waiting on the fence of the previous submission that drew them.
+    pub fn release_unused(&mut self) {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        let shared = &mut *shared;
+
+        let in_use = shared
+            .views
+            .values()
+            .flat_map(|frame| &frame.layers)
+            .map(|layer| vk::Image::from_raw(layer.image))
+            .collect::<HashSet<_>>();
+        shared.collected.retain(|image| {
+            if in_use.contains(image) {
+                return true;
+            }
+            if let Some(Image { memory, .. }) = shared.images.remove(image) {
+                unsafe {
+                    self.device.device.destroy_image(*image, None);
+                    self.device.device.free_memory(memory, None);
+                }
+            }
+            false
+        });
+    }
+}
+
+impl Drop for OverlayTextures {
+    fn drop(&mut self) {
+        let shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        // the engine is shut down, but the game may still be drawing the latest frames
+        {
+            let _guard = lock_vulkan_queue();
+            let _ = unsafe { self.device.device.device_wait_idle() };
+        }
+        for (image, Image { memory, .. }) in &shared.images {
+            unsafe {
+                self.device.device.destroy_image(*image, None);
+                self.device.device.free_memory(*memory, None);
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use crate::{sys, ViewId};
 
@@ -82,22 +82,27 @@ bitfield! {
 }
 
 impl PointerButtons {
-    #[must_use]
-    pub fn empty() -> Self {
-        Self::default()
-    }
-
-    #[must_use]
-    pub fn is_empty(&self) -> bool {
-        self == &Self::empty()
-    }
-
     pub fn press(&mut self, button: PointerButtons) {
-        self.0 |= button.0;
+        self.insert(button);
     }
 
     pub fn release(&mut self, button: PointerButtons) {
-        self.0 &= !button.0;
+        self.remove(button);
+    }
+
+    /// The button for a Linux input event code (`BTN_*` from `linux/input-event-codes.h`).
+    ///
+    /// These are the codes used by `wl_pointer.button`, libinput, and most compositor toolkits.
+    #[must_use]
+    pub fn from_evdev(code: u32) -> Option<Self> {
+        match code {
+            0x110 => Some(Self::MousePrimary),         // BTN_LEFT
+            0x111 => Some(Self::MouseSecondary),       // BTN_RIGHT
+            0x112 => Some(Self::MouseMiddle),          // BTN_MIDDLE
+            0x113 | 0x116 => Some(Self::MouseBack),    // BTN_SIDE, BTN_BACK
+            0x114 | 0x115 => Some(Self::MouseForward), // BTN_EXTRA, BTN_FORWARD
+            _ => None,
+        }
     }
 }
 
@@ -178,3 +183,271 @@ impl From<PointerEvent> for sys::FlutterPointerEvent {
         }
     }
 }
+
+/// How scrolling feels for one device; see [`ScrollConfig`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScrollSettings {
+    /// How far one line scrolls, in logical pixels.
+    pub pixels_per_line: f64,
+    /// How many lines one click of a scroll wheel scrolls.
+    pub lines_per_click: f64,
+    /// Whether the content follows the fingers (or wheel), like on a touchscreen, instead of the scrollbar.
+    /// This inverts every scroll delta, so leave it off if the platform already did.
+    pub natural_scrolling: bool,
+}
+
+impl Default for ScrollSettings {
+    /// One click scrolls 53 logical pixels, like in GTK.
+    fn default() -> Self {
+        Self {
+            pixels_per_line: 53.0,
+            lines_per_click: 1.0,
+            natural_scrolling: false,
+        }
+    }
+}
+
+/// How wheel clicks and lines turn into the scroll deltas of [`PointerEvent`]s, which are in physical pixels.
+///
+/// Give it to [`PointerState::set_scroll_config`], and scroll with [`PointerState::scroll_clicks`] or [`PointerState::scroll_lines`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScrollConfig {
+    /// For devices without an override.
+    pub default: ScrollSettings,
+    /// Overrides, by the device ID of the [`PointerState`].
+    pub devices: HashMap<i32, ScrollSettings>,
+}
+
+impl ScrollConfig {
+    /// The settings that apply to `device`.
+    #[must_use]
+    pub fn for_device(&self, device: i32) -> ScrollSettings {
+        self.devices.get(&device).copied().unwrap_or(self.default)
+    }
+}
+
+/// Tracks a single pointer device, producing [`PointerEvent`]s that follow the engine's rules:
+///
+/// - A pointer must be added before it sends any other events, and removed when it leaves.
+/// - Only the first pressed button is a [`PointerPhase::Down`], and only the last released button is a [`PointerPhase::Up`].
+///   Any other button change is a [`PointerPhase::Move`].
+/// - Motion is a [`PointerPhase::Move`] while any button is pressed, and a [`PointerPhase::Hover`] otherwise.
+///
+/// Coordinates are in physical pixels, relative to the view.
+#[derive(Debug, Clone)]
+pub struct PointerState {
+    view_id: ViewId,
+    device: i32,
+    device_kind: PointerDeviceKind,
+    added: bool,
+    buttons: PointerButtons,
+    x: f64,
+    y: f64,
+    scroll: ScrollSettings,
+}
+
+impl PointerState {
+    #[must_use]
+    pub fn new(view_id: ViewId, device: i32, device_kind: PointerDeviceKind) -> Self {
+        Self {
+            view_id,
+            device,
+            device_kind,
+            added: false,
+            buttons: PointerButtons::empty(),
+            x: 0.0,
+            y: 0.0,
+            scroll: ScrollSettings::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn view_id(&self) -> ViewId {
+        self.view_id
+    }
+
+    #[must_use]
+    pub fn is_added(&self) -> bool {
+        self.added
+    }
+
+    #[must_use]
+    pub fn buttons(&self) -> PointerButtons {
+        self.buttons
+    }
+
+    #[must_use]
+    pub fn position(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    /// Uses the settings for this device from `config` for scrolling from now on.
+    pub fn set_scroll_config(&mut self, config: &ScrollConfig) {
+        self.scroll = config.for_device(self.device);
+    }
+
+    #[must_use]
+    pub fn scroll_settings(&self) -> ScrollSettings {
+        self.scroll
+    }
+
+    fn event(&self, phase: PointerPhase, timestamp: Duration) -> PointerEvent {
+        PointerEvent {
+            view_id: self.view_id,
+            phase,
+            timestamp,
+            x: self.x,
+            y: self.y,
+            device: self.device,
+            signal_kind: PointerSignalKind::None,
+            scroll_delta_x: 0.0,
+            scroll_delta_y: 0.0,
+            device_kind: self.device_kind,
+            buttons: self.buttons,
+            pan_x: 0.0,
+            pan_y: 0.0,
+            scale: 1.0,
+            rotation: 0.0,
+        }
+    }
+
+    /// The pointer entered the view, possibly a different one than it was last in.
+    ///
+    /// If it was still in another view, that view gets the events from [`PointerState::leave`] first.
+    pub fn enter(
+        &mut self,
+        view_id: ViewId,
+        timestamp: Duration,
+        x: f64,
+        y: f64,
+    ) -> impl Iterator<Item = PointerEvent> {
+        let left = if self.added && self.view_id != view_id {
+            Some(self.leave(timestamp))
+        } else {
+            None
+        };
+
+        self.view_id = view_id;
+        self.x = x;
+        self.y = y;
+        let added = if self.added {
+            self.event(PointerPhase::Hover, timestamp)
+        } else {
+            self.added = true;
+            self.event(PointerPhase::Add, timestamp)
+        };
+
+        left.into_iter().flatten().chain(std::iter::once(added))
+    }
+
+    /// The pointer left the view.
+    ///
+    /// If any buttons are still pressed, the gesture is cancelled before the pointer is removed.
+    pub fn leave(&mut self, timestamp: Duration) -> impl Iterator<Item = PointerEvent> {
+        let cancelled = if self.buttons.is_empty() {
+            None
+        } else {
+            let event = self.event(PointerPhase::Cancel, timestamp);
+            self.buttons = PointerButtons::empty();
+            Some(event)
+        };
+
+        let removed = if self.added {
+            self.added = false;
+            Some(self.event(PointerPhase::Remove, timestamp))
+        } else {
+            None
+        };
+
+        cancelled.into_iter().chain(removed)
+    }
+
+    pub fn motion(&mut self, timestamp: Duration, x: f64, y: f64) -> PointerEvent {
+        self.x = x;
+        self.y = y;
+        if self.added {
+            let phase = if self.buttons.is_empty() {
+                PointerPhase::Hover
+            } else {
+                PointerPhase::Move
+            };
+            self.event(phase, timestamp)
+        } else {
+            self.added = true;
+            self.event(PointerPhase::Add, timestamp)
+        }
+    }
+
+    pub fn button(
+        &mut self,
+        timestamp: Duration,
+        button: PointerButtons,
+        pressed: bool,
+    ) -> PointerEvent {
+        let was_empty = self.buttons.is_empty();
+        if pressed {
+            self.buttons.press(button);
+        } else {
+            self.buttons.release(button);
+        }
+
+        let phase = match (was_empty, self.buttons.is_empty()) {
+            (true, false) => PointerPhase::Down,
+            (false, true) => PointerPhase::Up,
+            // releasing a button that wasn't pressed, or pressing one that already was
+            (true, true) => PointerPhase::Hover,
+            (false, false) => PointerPhase::Move,
+        };
+        self.event(phase, timestamp)
+    }
+
+    /// A scroll, with deltas in physical pixels.
+    ///
+    /// Positive deltas scroll down and right, i.e. towards the end of the content. They are inverted for natural scrolling.
+    pub fn scroll(&mut self, timestamp: Duration, delta_x: f64, delta_y: f64) -> PointerEvent {
+        let (delta_x, delta_y) = if self.scroll.natural_scrolling {
+            (-delta_x, -delta_y)
+        } else {
+            (delta_x, delta_y)
+        };
+        let phase = if self.buttons.is_empty() {
+            PointerPhase::Hover
+        } else {
+            PointerPhase::Move
+        };
+        PointerEvent {
+            signal_kind: PointerSignalKind::Scroll,
+            scroll_delta_x: delta_x,
+            scroll_delta_y: delta_y,
+            ..self.event(phase, timestamp)
+        }
+    }
+
+    /// A scroll by lines, like [`PointerState::scroll`].
+    ///
+    /// `pixel_ratio` is the ratio of physical to logical pixels of the view.
+    pub fn scroll_lines(
+        &mut self,
+        timestamp: Duration,
+        lines_x: f64,
+        lines_y: f64,
+        pixel_ratio: f64,
+    ) -> PointerEvent {
+        let line = self.scroll.pixels_per_line * pixel_ratio;
+        self.scroll(timestamp, lines_x * line, lines_y * line)
+    }
+
+    /// A scroll by wheel clicks (which may be fractional, for high-resolution wheels), like [`PointerState::scroll`].
+    ///
+    /// `pixel_ratio` is the ratio of physical to logical pixels of the view.
+    pub fn scroll_clicks(
+        &mut self,
+        timestamp: Duration,
+        clicks_x: f64,
+        clicks_y: f64,
+        pixel_ratio: f64,
+    ) -> PointerEvent {
+        let lines = self.scroll.lines_per_click;
+        self.scroll_lines(timestamp, clicks_x * lines, clicks_y * lines, pixel_ratio)
+    }
+}
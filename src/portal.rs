@@ -0,0 +1,252 @@
+//! Follows the desktop's appearance settings through the XDG settings portal, over D-Bus.
+//!
+//! `org.freedesktop.portal.Settings` is implemented by every desktop's portal backend, including inside Flatpak.
+//! [`SettingsPortal`] reads the color scheme, contrast and text scale from it, and listens for changes:
+//!
+//! ```ignore
+//! let handle = Arc::new(Engine::spawn(start)?);
+//! // dark mode switches now reach the framework on their own
+//! let portal = SettingsPortal::send_settings_to(handle.clone(), PlatformSettings::default())?;
+//! ```
+
+use std::{
+    fmt,
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use dbus::{
+    arg::{RefArg, Variant},
+    blocking::{Connection, Proxy},
+    message::MatchRule,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    desktop::{self, Brightness, PlatformSettings},
+    EngineHandle,
+};
+
+const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS_INTERFACE: &str = "org.freedesktop.portal.Settings";
+
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const GNOME_INTERFACE_NAMESPACE: &str = "org.gnome.desktop.interface";
+
+/// How long to wait for the portal to answer a call.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the portal thread checks whether it should stop.
+const STOP_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The appearance settings that the portal knows about.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Appearance {
+    /// The color scheme the user prefers, or `None` if they have no preference.
+    pub color_scheme: Option<Brightness>,
+    /// Whether the user asked for high contrast.
+    ///
+    /// The settings channel has no room for this; it goes to [`crate::AccessibilityFeature::HighContrast`].
+    pub high_contrast: bool,
+    /// How much to scale text by, if the desktop says.
+    /// Only GNOME does, through its own namespace of settings.
+    pub text_scale_factor: Option<f64>,
+}
+
+impl Appearance {
+    /// Overrides the parts of `settings` that the portal has an opinion on.
+    pub fn apply(&self, settings: &mut PlatformSettings) {
+        if let Some(brightness) = self.color_scheme {
+            settings.platform_brightness = brightness;
+        }
+        if let Some(text_scale_factor) = self.text_scale_factor {
+            settings.text_scale_factor = text_scale_factor;
+        }
+    }
+
+    /// Reads the current settings from the portal.
+    pub fn read() -> Result<Self, dbus::Error> {
+        let connection = Connection::new_session()?;
+        Ok(Self::read_from(&portal(&connection)))
+    }
+
+    fn read_from(portal: &Proxy<'_, &Connection>) -> Self {
+        let mut appearance = Self::default();
+        for (namespace, key) in [
+            (APPEARANCE_NAMESPACE, "color-scheme"),
+            (APPEARANCE_NAMESPACE, "contrast"),
+            (GNOME_INTERFACE_NAMESPACE, "text-scaling-factor"),
+        ] {
+            if let Some(value) = read_setting(portal, namespace, key) {
+                appearance.update(namespace, key, &*value);
+            }
+        }
+        appearance
+    }
+
+    /// Applies a changed setting. Returns whether it was one of these.
+    fn update(&mut self, namespace: &str, key: &str, value: &dyn RefArg) -> bool {
+        match (namespace, key) {
+            (APPEARANCE_NAMESPACE, "color-scheme") => {
+                self.color_scheme = match value.as_u64() {
+                    Some(1) => Some(Brightness::Dark),
+                    Some(2) => Some(Brightness::Light),
+                    _ => None,
+                };
+            }
+            (APPEARANCE_NAMESPACE, "contrast") => {
+                self.high_contrast = value.as_u64() == Some(1);
+            }
+            (GNOME_INTERFACE_NAMESPACE, "text-scaling-factor") => {
+                self.text_scale_factor = value.as_f64();
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+fn portal(connection: &Connection) -> Proxy<'_, &Connection> {
+    connection.with_proxy(PORTAL_DESTINATION, PORTAL_PATH, TIMEOUT)
+}
+
+/// Reads one setting, or `None` if the portal doesn't have it.
+fn read_setting(
+    portal: &Proxy<'_, &Connection>,
+    namespace: &str,
+    key: &str,
+) -> Option<Box<dyn RefArg>> {
+    let result: Result<(Variant<Box<dyn RefArg>>,), _> =
+        portal.method_call(SETTINGS_INTERFACE, "ReadOne", (namespace, key));
+    let result = match result {
+        // `ReadOne` is new in version 2 of the interface. `Read` wraps the value in another variant,
+        // which `RefArg` sees through.
+        Err(err) if err.name() == Some("org.freedesktop.DBus.Error.UnknownMethod") => {
+            portal.method_call(SETTINGS_INTERFACE, "Read", (namespace, key))
+        }
+        result => result,
+    };
+    match result {
+        Ok((Variant(value),)) => Some(value),
+        Err(err) => {
+            debug!("couldn't read {namespace} {key} from the settings portal: {err}");
+            None
+        }
+    }
+}
+
+/// Listens to the settings portal on a thread of its own, and reports when the appearance changes.
+///
+/// Dropping it stops the thread, without waiting for it.
+pub struct SettingsPortal {
+    stop: mpsc::Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for SettingsPortal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SettingsPortal").finish_non_exhaustive()
+    }
+}
+
+impl SettingsPortal {
+    /// Connects to the session bus, on a new thread.
+    ///
+    /// `changed` is called on that thread with the current appearance right away, and again whenever it changes.
+    ///
+    /// # Panics
+    ///
+    /// If the thread can't be spawned.
+    pub fn spawn(changed: impl FnMut(Appearance) + Send + 'static) -> Result<Self, dbus::Error> {
+        let (stop, stopped) = mpsc::channel();
+        let (started_sender, started) = mpsc::sync_channel(1);
+
+        let thread = std::thread::Builder::new()
+            .name("settings-portal".to_owned())
+            .spawn(move || {
+                let connection = match listen(changed) {
+                    Ok(connection) => connection,
+                    Err(err) => {
+                        let _ = started_sender.send(Err(err));
+                        return;
+                    }
+                };
+                let _ = started_sender.send(Ok(()));
+                while let Err(TryRecvError::Empty) = stopped.try_recv() {
+                    if let Err(err) = connection.process(STOP_INTERVAL) {
+                        warn!("lost the connection to the settings portal: {err}");
+                        return;
+                    }
+                }
+            })
+            .expect("failed to spawn the settings portal thread");
+
+        // the thread always reports back before listening
+        started.recv().unwrap_or_else(|_| {
+            Err(dbus::Error::new_failed(
+                "the settings portal thread stopped",
+            ))
+        })?;
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Sends `settings` to the engine, with the portal's appearance applied, now and whenever the appearance changes.
+    /// See [`desktop::send_settings`].
+    pub fn send_settings_to(
+        engine: Arc<EngineHandle>,
+        settings: PlatformSettings,
+    ) -> Result<Self, dbus::Error> {
+        Self::spawn(move |appearance| {
+            let mut settings = settings;
+            appearance.apply(&mut settings);
+            engine.post(move |engine| {
+                if let Err(err) = desktop::send_settings(engine, &settings) {
+                    warn!("failed to send the portal's settings: {err}");
+                }
+            });
+        })
+    }
+
+    /// Stops listening, and waits for the thread to finish.
+    pub fn stop(mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SettingsPortal {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+    }
+}
+
+/// Connects, reports the current appearance, and subscribes to changes.
+fn listen(mut changed: impl FnMut(Appearance) + Send + 'static) -> Result<Connection, dbus::Error> {
+    let connection = Connection::new_session()?;
+    let mut appearance = Appearance::read_from(&portal(&connection));
+    changed(appearance);
+
+    let rule = MatchRule::new_signal(SETTINGS_INTERFACE, "SettingChanged");
+    connection.add_match(
+        rule,
+        move |(namespace, key, Variant(value)): (String, String, Variant<Box<dyn RefArg>>),
+              _,
+              _| {
+            let previous = appearance;
+            if appearance.update(&namespace, &key, &*value) && appearance != previous {
+                changed(appearance);
+            }
+            true
+        },
+    )?;
+    Ok(connection)
+}
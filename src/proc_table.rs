@@ -3,6 +3,7 @@ use crate::sys;
 macro_rules! gen {
     (
         $(
+            $(#[$attr:meta])*
             fn $fn:ident($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)?;
         )*
         $(@ $($t:tt)*)?
@@ -10,14 +11,17 @@ macro_rules! gen {
         #[allow(non_snake_case, clippy::missing_safety_doc)]
         pub trait FlutterProcTable {
             $(
+                $(#[$attr])*
                 unsafe fn $fn(&self, $($arg: $arg_ty),*) $(-> $ret)?;
             )*
         }
 
+        #[derive(Debug)]
         pub struct StaticProcTable;
 
         impl FlutterProcTable for StaticProcTable {
             $(
+                $(#[$attr])*
                 unsafe fn $fn(&self, $($arg: $arg_ty),*) $(-> $ret)? {
                     unsafe { sys::$fn($($arg),*) }
                 }
@@ -25,14 +29,17 @@ macro_rules! gen {
         }
 
         #[allow(non_snake_case)]
+        #[derive(Debug)]
         pub struct DynamicProcTable {
             $(
+                $(#[$attr])*
                 pub $fn: unsafe extern "C" fn($($arg_ty),*) $(-> $ret)?,
             )*
         }
 
         impl FlutterProcTable for DynamicProcTable {
             $(
+                $(#[$attr])*
                 unsafe fn $fn(&self, $($arg: $arg_ty),*) $(-> $ret)? {
                     unsafe { (self.$fn)($($arg),*) }
                 }
@@ -51,9 +58,12 @@ macro_rules! gen {
                 unsafe { GetProcAddresses(&mut table) }
                     .to_result()
                     .map(|()| {
-                        $(let $fn = table.$fn.expect(concat!("missing proc table entry for ", stringify!($fn)));)*
+                        $(
+                            $(#[$attr])*
+                            let $fn = table.$fn.expect(concat!("missing proc table entry for ", stringify!($fn)));
+                        )*
 
-                        Self { $($fn),* }
+                        Self { $($(#[$attr])* $fn,)* }
                     })
             }
         }
@@ -68,6 +78,7 @@ macro_rules! gen {
             fn from(StaticProcTable: StaticProcTable) -> Self {
                 Self {
                     $(
+                        $(#[$attr])*
                         $fn: sys::$fn,
                     )*
                 }
@@ -80,6 +91,7 @@ macro_rules! gen {
                 Self {
                     struct_size: std::mem::size_of::<sys::FlutterEngineProcTable>(),
                     $(
+                        $(#[$attr])*
                         $fn: Some(table.$fn),
                     )*
                 }
@@ -218,4 +230,9 @@ gen! {
         callback: sys::VoidCallback,
         user_data: *mut ::std::os::raw::c_void,
     ) -> sys::FlutterEngineResult;
+    #[cfg(embedder_view_focus)]
+    fn SendViewFocusEvent(
+        engine: sys::FlutterEngine,
+        event: *const sys::FlutterViewFocusEvent,
+    ) -> sys::FlutterEngineResult;
 }
@@ -0,0 +1,110 @@
+use std::ffi::CStr;
+
+use tracing::warn;
+
+use crate::{
+    ChannelHandler, EngineContext, PlatformMessageResponse, StandardMethodCall, StandardValue,
+};
+
+/// The channel that the framework asks for text processing actions on, i.e. the extra items in the
+/// context menu of a text selection. Messages use the `StandardMethodCodec`.
+pub const PROCESS_TEXT_CHANNEL: &CStr = c"flutter/processtext";
+
+/// An action that can be applied to selected text, e.g. "Translate" or "Search the web".
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextAction {
+    /// Identifies the action in [`ProcessTextHandler::process_text`]. It's never shown to the user.
+    pub id: String,
+    /// The label of the action in the context menu.
+    pub label: String,
+}
+
+/// Provides the actions for `ProcessTextService` in Dart.
+pub trait ProcessTextHandler {
+    /// The actions that can currently be applied to text. This is asked for once per context menu.
+    fn text_actions(&mut self) -> Vec<TextAction>;
+
+    /// Applies the action `id` to `text`.
+    ///
+    /// Returns the text to replace the selection with, if any. It's ignored if the text is `read_only`,
+    /// so actions like "Search the web" should just return `None`.
+    fn process_text(&mut self, id: &str, text: &str, read_only: bool) -> Option<String>;
+}
+
+/// Handles [`PROCESS_TEXT_CHANNEL`] with a [`ProcessTextHandler`]. Register it in a [`crate::ChannelRouter`].
+#[derive(Debug, Default, Clone)]
+pub struct ProcessTextChannel<H> {
+    handler: H,
+}
+
+impl<H: ProcessTextHandler> ProcessTextChannel<H> {
+    #[must_use]
+    pub fn new(handler: H) -> Self {
+        Self { handler }
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    fn handle(&mut self, message: &[u8]) -> Vec<u8> {
+        let Some(call) = StandardMethodCall::decode(message) else {
+            return malformed("malformed message");
+        };
+        match call.method.as_str() {
+            "ProcessText.queryTextActions" => {
+                let actions = self
+                    .handler
+                    .text_actions()
+                    .into_iter()
+                    .map(|action| {
+                        (
+                            StandardValue::String(action.id),
+                            StandardValue::String(action.label),
+                        )
+                    })
+                    .collect();
+                StandardMethodCall::success(&StandardValue::Map(actions))
+            }
+            "ProcessText.processTextAction" => {
+                // the arguments are [id, text, readOnly]
+                let StandardValue::List(args) = &call.args else {
+                    return malformed("arguments are not a list");
+                };
+                let (Some(id), Some(text), Some(read_only)) = (
+                    args.first().and_then(StandardValue::as_str),
+                    args.get(1).and_then(StandardValue::as_str),
+                    args.get(2).and_then(StandardValue::as_bool),
+                ) else {
+                    return malformed("missing id, text or readOnly");
+                };
+                let result = self
+                    .handler
+                    .process_text(id, text, read_only)
+                    .filter(|_| !read_only)
+                    .map_or(StandardValue::Null, StandardValue::String);
+                StandardMethodCall::success(&result)
+            }
+            // not implemented
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl<H: ProcessTextHandler> ChannelHandler for ProcessTextChannel<H> {
+    fn platform_message(
+        &mut self,
+        _cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        let reply = self.handle(message);
+        if let Err(err) = response.send(&reply) {
+            warn!("failed to respond to a process text message: {err}");
+        }
+    }
+}
+
+fn malformed(message: &str) -> Vec<u8> {
+    StandardMethodCall::error("error", Some(message), &StandardValue::Null)
+}
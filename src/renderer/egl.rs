@@ -0,0 +1,274 @@
+use std::{any::Any, ffi::CStr, fmt};
+
+use khronos_egl as egl;
+use tracing::error;
+
+use crate::{FrameInfo, OpenGLRendererHandler, OpenGLTexture, PresentInfo, Region};
+
+#[derive(Debug)]
+pub enum EglError {
+    /// `libEGL` couldn't be loaded, or it doesn't support EGL 1.5.
+    Load(String),
+    Egl(egl::Error),
+    /// No EGL config matches what the engine needs (RGBA8888, OpenGL ES 2).
+    NoConfig,
+}
+
+impl fmt::Display for EglError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EglError::Load(err) => write!(f, "failed to load libEGL: {err}"),
+            EglError::Egl(err) => write!(f, "EGL call failed: {err}"),
+            EglError::NoConfig => write!(f, "no suitable EGL config"),
+        }
+    }
+}
+
+impl std::error::Error for EglError {}
+
+impl From<egl::Error> for EglError {
+    fn from(err: egl::Error) -> Self {
+        Self::Egl(err)
+    }
+}
+
+/// Whatever the native window handle passed to EGL points into, kept alive for as long as the surface.
+pub(crate) trait EglNativeWindow: Any {
+    /// The engine is about to render a frame of this size.
+    fn resize(&self, width: u32, height: u32) {
+        let _ = (width, height);
+    }
+}
+
+/// An EGL display, with the contexts that the engine renders with, and no surface yet.
+pub(crate) struct EglContexts {
+    pub(crate) egl: egl::DynamicInstance<egl::EGL1_5>,
+    pub(crate) display: egl::Display,
+    config: egl::Config,
+    pub(crate) context: egl::Context,
+    pub(crate) resource_context: egl::Context,
+}
+
+impl EglContexts {
+    /// # Safety
+    ///
+    /// `native_display` must be valid for `platform`.
+    pub(crate) unsafe fn new(
+        platform: egl::Enum,
+        native_display: *mut std::ffi::c_void,
+        display_attribs: &[egl::Attrib],
+    ) -> Result<Self, EglError> {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+            .map_err(|err| EglError::Load(err.to_string()))?;
+
+        let display =
+            unsafe { egl.get_platform_display(platform, native_display, display_attribs) }?;
+        egl.initialize(display)?;
+        egl.bind_api(egl::OPENGL_ES_API)?;
+
+        let config = egl
+            .choose_first_config(
+                display,
+                &[
+                    egl::RED_SIZE,
+                    8,
+                    egl::GREEN_SIZE,
+                    8,
+                    egl::BLUE_SIZE,
+                    8,
+                    egl::ALPHA_SIZE,
+                    8,
+                    egl::SURFACE_TYPE,
+                    egl::WINDOW_BIT,
+                    egl::RENDERABLE_TYPE,
+                    egl::OPENGL_ES2_BIT,
+                    egl::NONE,
+                ],
+            )?
+            .ok_or(EglError::NoConfig)?;
+
+        let context_attribs = [egl::CONTEXT_CLIENT_VERSION, 2, egl::NONE];
+        let context = egl.create_context(display, config, None, &context_attribs)?;
+        let resource_context =
+            match egl.create_context(display, config, Some(context), &context_attribs) {
+                Ok(resource_context) => resource_context,
+                Err(err) => {
+                    let _ = egl.destroy_context(display, context);
+                    return Err(err.into());
+                }
+            };
+
+        Ok(Self {
+            egl,
+            display,
+            config,
+            context,
+            resource_context,
+        })
+    }
+
+    /// # Safety
+    ///
+    /// `native_window` must be valid for the platform of the display, and stay valid until the surface is destroyed.
+    pub(crate) unsafe fn create_window_surface(
+        &self,
+        native_window: *mut std::ffi::c_void,
+    ) -> Result<egl::Surface, EglError> {
+        let surface = unsafe {
+            self.egl.create_platform_window_surface(
+                self.display,
+                self.config,
+                native_window,
+                &[egl::ATTRIB_NONE],
+            )
+        }?;
+        Ok(surface)
+    }
+
+    /// Makes the context current without a surface, which requires `EGL_KHR_surfaceless_context`.
+    /// Every Mesa driver has it.
+    pub(crate) fn make_surfaceless_current(&self, context: egl::Context) -> bool {
+        self.egl
+            .make_current(self.display, None, None, Some(context))
+            .is_ok()
+    }
+
+    pub(crate) fn gl_proc_resolver(
+        &self,
+        name: *const std::os::raw::c_char,
+    ) -> *mut std::ffi::c_void {
+        let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+            return std::ptr::null_mut();
+        };
+        self.egl
+            .get_proc_address(name)
+            .map_or(std::ptr::null_mut(), |f| f as *mut std::ffi::c_void)
+    }
+}
+
+impl Drop for EglContexts {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self
+            .egl
+            .destroy_context(self.display, self.resource_context);
+        let _ = self.egl.destroy_context(self.display, self.context);
+    }
+}
+
+/// EGL contexts rendering to a native window, as the handler of an [`crate::OpenGLRendererConfig`].
+///
+/// `libEGL` is loaded at runtime.
+pub struct EglWindowContext {
+    contexts: EglContexts,
+    surface: egl::Surface,
+    // dropped after the surface
+    native: Box<dyn EglNativeWindow>,
+}
+
+impl fmt::Debug for EglWindowContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EglWindowContext")
+            .field("display", &self.contexts.display.as_ptr())
+            .field("surface", &self.surface.as_ptr())
+            .field("context", &self.contexts.context.as_ptr())
+            .field("resource_context", &self.contexts.resource_context.as_ptr())
+            .finish_non_exhaustive()
+    }
+}
+
+impl EglWindowContext {
+    /// # Safety
+    ///
+    /// `native_display` and `native_window` must be valid for `platform`, and `native_window` must stay valid
+    /// for as long as `native` is alive.
+    pub(crate) unsafe fn new(
+        platform: egl::Enum,
+        native_display: *mut std::ffi::c_void,
+        display_attribs: &[egl::Attrib],
+        native_window: *mut std::ffi::c_void,
+        native: Box<dyn EglNativeWindow>,
+    ) -> Result<Self, EglError> {
+        let contexts = unsafe { EglContexts::new(platform, native_display, display_attribs) }?;
+        let surface = unsafe { contexts.create_window_surface(native_window) }?;
+        Ok(Self {
+            contexts,
+            surface,
+            native,
+        })
+    }
+}
+
+impl OpenGLRendererHandler for EglWindowContext {
+    fn make_current(&mut self) -> bool {
+        let contexts = &self.contexts;
+        contexts
+            .egl
+            .make_current(
+                contexts.display,
+                Some(self.surface),
+                Some(self.surface),
+                Some(contexts.context),
+            )
+            .inspect_err(|err| error!("failed to make the EGL context current: {err}"))
+            .is_ok()
+    }
+
+    fn clear_current(&mut self) -> bool {
+        self.contexts
+            .egl
+            .make_current(self.contexts.display, None, None, None)
+            .is_ok()
+    }
+
+    fn present(&mut self, present_info: PresentInfo) -> bool {
+        let _ = present_info;
+        self.contexts
+            .egl
+            .swap_buffers(self.contexts.display, self.surface)
+            .inspect_err(|err| error!("failed to swap buffers: {err}"))
+            .is_ok()
+    }
+
+    fn fbo_callback(&mut self, frame_info: FrameInfo) -> u32 {
+        let size = frame_info.size();
+        self.native.resize(size.width, size.height);
+        0
+    }
+
+    fn make_resource_current(&mut self) -> bool {
+        self.contexts
+            .make_surfaceless_current(self.contexts.resource_context)
+    }
+
+    fn gl_proc_resolver(&mut self, name: *const std::os::raw::c_char) -> *mut std::ffi::c_void {
+        self.contexts.gl_proc_resolver(name)
+    }
+
+    fn gl_external_texture_frame(
+        &mut self,
+        texture_id: i64,
+        width: usize,
+        height: usize,
+    ) -> Option<OpenGLTexture> {
+        let _ = (texture_id, width, height);
+        None
+    }
+
+    fn populate_existing_damage(&mut self, fbo_id: isize) -> Region {
+        let _ = fbo_id;
+        Region {
+            regions: Vec::new(),
+        }
+    }
+}
+
+impl Drop for EglWindowContext {
+    fn drop(&mut self) {
+        let contexts = &self.contexts;
+        let _ = contexts
+            .egl
+            .make_current(contexts.display, None, None, None);
+        let _ = contexts.egl.destroy_surface(contexts.display, self.surface);
+    }
+}
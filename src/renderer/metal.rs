@@ -1,9 +1,11 @@
-use std::mem::ManuallyDrop;
+use std::{fmt, mem::ManuallyDrop};
 
-use metal::foreign_types::{ForeignType, ForeignTypeRef};
+use metal::foreign_types::ForeignType;
+use tracing::warn;
 
 use crate::{sys, EngineUserData, FrameInfo};
 
+#[derive(Debug)]
 pub enum MetalBackingStore {
     Texture(MetalTexture),
 }
@@ -24,13 +26,21 @@ impl From<MetalBackingStore> for sys::FlutterMetalBackingStore {
 }
 
 impl MetalBackingStore {
-    pub(crate) fn from_raw(raw: &sys::FlutterMetalBackingStore) -> Self {
-        if raw.struct_size != std::mem::size_of::<MetalBackingStore>() {
-            panic!("FlutterMetalBackingStore has an unexpected size. It likely has a union tag that i don't know how to handle. It cannot be safely used.");
+    sys_pub! {
+        /// `None` (with a warning) if the struct isn't the size this crate knows, or it wasn't made by this crate.
+        ///
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        unsafe fn from_raw(raw: &sys::FlutterMetalBackingStore) -> Option<Self> {
+            if raw.struct_size != std::mem::size_of::<sys::FlutterMetalBackingStore>() {
+                warn!("FlutterMetalBackingStore has an unexpected size. It likely has a union tag that i don't know how to handle; ignoring the backing store");
+                return None;
+            }
+            MetalTexture::from_raw(unsafe { &raw.__bindgen_anon_1.texture })
+                .map(MetalBackingStore::Texture)
         }
-        MetalBackingStore::Texture(MetalTexture::from_raw(unsafe {
-            &raw.__bindgen_anon_1.texture
-        }))
     }
 }
 
@@ -48,52 +58,53 @@ pub struct MetalTexture {
     texture: metal::Texture,
 }
 
-pub extern "C" fn destroy_metal_texture_callback(user_data: *mut std::ffi::c_void) {
-    let mtl_texture = user_data as *mut metal::MTLTexture;
-    let mtl_texture = unsafe { metal::Texture::from_ptr(mtl_texture) };
+impl fmt::Debug for MetalTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetalTexture")
+            .field("texture_id", &self.texture_id)
+            .field("texture", &self.texture.as_ptr())
+            .finish()
+    }
+}
 
-    drop(mtl_texture);
+pub extern "C" fn destroy_metal_texture_callback(user_data: *mut std::ffi::c_void) {
+    let texture = unsafe { Box::from_raw(user_data.cast::<MetalTexture>()) };
+    drop(texture);
 }
 const _: sys::VoidCallback = Some(destroy_metal_texture_callback);
 
 impl From<MetalTexture> for sys::FlutterMetalTexture {
     fn from(texture: MetalTexture) -> Self {
-        // This transfers ownership of MTLTexture from MetalTexture to the new FlutterMetalTexture.
-
-        // into_ptr forgets the previous owner, so it manually gets dropped in the destruction callback.
-        let mtl_texture: *mut metal::MTLTexture = texture.texture.into_ptr();
+        // The box owns the MTLTexture until the engine calls the destruction callback,
+        // so the handle given to the engine stays valid for as long as the box does.
+        let texture_id = texture.texture_id;
+        let handle = texture.texture.as_ptr();
+        let texture = Box::into_raw(Box::new(texture));
 
         Self {
             struct_size: std::mem::size_of::<Self>(),
-            user_data: mtl_texture as *mut std::ffi::c_void,
+            user_data: texture.cast::<std::ffi::c_void>(),
             destruction_callback: Some(destroy_metal_texture_callback),
 
-            texture_id: texture.texture_id,
+            texture_id,
             // FlutterMetalTextureHandle represents *mut MTLTexture.
-            texture: mtl_texture as sys::FlutterMetalTextureHandle,
+            texture: handle as sys::FlutterMetalTextureHandle,
         }
     }
 }
 impl MetalTexture {
-    fn from_raw(raw: &sys::FlutterMetalTexture) -> Self {
-        assert!(raw.destruction_callback == Some(destroy_metal_texture_callback),
-         "from_raw(&sys::FlutterMetalTexture) called with a metal texture for which we didn't set the destruction callback"
-        );
-
-        // This doesn't increment the refcount; runtime thinks only sys::FlutterMetalTexture owns the texture.
-        let mtl_texture_ref =
-            unsafe { metal::TextureRef::from_ptr(raw.texture as *mut metal::MTLTexture) };
-
-        // But now we increment the refcount, so that this MetalTexture owns the texture.
-        let mtl_texture = mtl_texture_ref.to_owned();
-
-        // (yes, i know those statements are obvious, but i'm writing them for clarity
-        // because working with objc is unintuitive to me and i want to make sure it's correct)
-
-        Self {
-            texture_id: raw.texture_id,
-            texture: mtl_texture,
+    /// `None` (with a warning) if it wasn't made by this crate, because then its user data isn't a texture.
+    fn from_raw(raw: &sys::FlutterMetalTexture) -> Option<Self> {
+        if !super::is_our_destruction_callback(
+            raw.destruction_callback,
+            destroy_metal_texture_callback,
+        ) {
+            warn!("from_raw(&sys::FlutterMetalTexture) called with a metal texture for which we didn't set the destruction callback; ignoring it");
+            return None;
         }
+
+        // The engine still owns the box, so this is a new reference to the same texture.
+        Some(unsafe { &*raw.user_data.cast::<MetalTexture>() }.clone())
     }
 }
 
@@ -109,6 +120,7 @@ simple_enum! {
     }
 }
 
+#[derive(Debug)]
 pub struct MetalExternalTexture {
     width: usize,
     height: usize,
@@ -162,6 +174,15 @@ pub struct MetalRendererConfig {
     pub handler: Box<dyn MetalRendererHandler>,
 }
 
+impl fmt::Debug for MetalRendererConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetalRendererConfig")
+            .field("device", &self.device)
+            .field("present_command_queue", &self.present_command_queue)
+            .finish_non_exhaustive()
+    }
+}
+
 impl From<MetalRendererConfig> for super::RendererConfig {
     fn from(config: MetalRendererConfig) -> Self {
         Self::Metal(config)
@@ -177,6 +198,7 @@ mod callbacks {
 
     use super::*;
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn get_next_drawable(
         engine_user_data: *mut std::ffi::c_void,
         texture: *const sys::FlutterFrameInfo,
@@ -193,6 +215,7 @@ mod callbacks {
         user_data.handler.get_next_drawable(frame_info).into()
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn present_drawable(
         engine_user_data: *mut std::ffi::c_void,
         texture: *const sys::FlutterMetalTexture,
@@ -204,11 +227,17 @@ mod callbacks {
             unreachable!("Metal renderer callback called with non-metal renderer user data.");
         };
 
-        let texture = MetalTexture::from_raw(unsafe { &*texture });
+        let Some(texture) = MetalTexture::from_raw(unsafe { &*texture }) else {
+            return false;
+        };
 
         user_data.handler.present_drawable(texture)
     }
 
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "trace", skip(engine_user_data, texture_out))
+    )]
     pub extern "C" fn external_texture_frame(
         engine_user_data: *mut std::ffi::c_void,
         texture_id: i64,
@@ -1,7 +1,14 @@
 use std::mem::ManuallyDrop;
 
+use tracing::warn;
+
 use crate::{sys, Size, ViewId};
 
+#[cfg(all(
+    feature = "opengl",
+    any(feature = "x11", feature = "raw-window-handle")
+))]
+mod egl;
 #[cfg(feature = "metal")]
 mod metal;
 #[cfg(feature = "opengl")]
@@ -9,7 +16,17 @@ mod opengl;
 mod software;
 #[cfg(feature = "vulkan")]
 mod vulkan;
+#[cfg(all(
+    feature = "vulkan",
+    any(feature = "raw-window-handle", feature = "overlay")
+))]
+mod vulkan_queue;
 
+#[cfg(all(
+    feature = "opengl",
+    any(feature = "x11", feature = "raw-window-handle")
+))]
+pub use egl::*;
 #[cfg(feature = "metal")]
 pub use metal::*;
 #[cfg(feature = "opengl")]
@@ -17,7 +34,21 @@ pub use opengl::*;
 pub use software::*;
 #[cfg(feature = "vulkan")]
 pub use vulkan::*;
+#[cfg(all(
+    feature = "vulkan",
+    any(feature = "raw-window-handle", feature = "overlay")
+))]
+pub use vulkan_queue::*;
+
+/// Whether a destruction callback from the engine is `ours`, i.e. the struct it's in was made by this crate.
+fn is_our_destruction_callback(
+    callback: sys::VoidCallback,
+    ours: extern "C" fn(*mut std::ffi::c_void),
+) -> bool {
+    callback.is_some_and(|callback| std::ptr::fn_addr_eq(callback, ours))
+}
 
+#[derive(Debug)]
 pub struct BackingStoreConfig {
     pub size: Size<f64>,
     pub view_id: ViewId,
@@ -43,10 +74,13 @@ impl From<BackingStoreConfig> for sys::FlutterBackingStoreConfig {
 }
 
 // Native type has a did_update field. It's never used in Flutter, so i don't bother with it.
+#[derive(Debug)]
 pub enum BackingStore {
     #[cfg(feature = "opengl")]
     OpenGL(OpenGLBackingStore),
     Software(SoftwareBackingStore),
+    /// Only for interoperating with code that uses the deprecated type; use [`BackingStore::Software`] instead.
+    LegacySoftware(LegacySoftwareBackingStore),
     #[cfg(feature = "metal")]
     Metal(MetalBackingStore),
     #[cfg(feature = "vulkan")]
@@ -69,6 +103,12 @@ impl From<BackingStore> for sys::FlutterBackingStore {
                     software2: ManuallyDrop::new(software.into()),
                 },
             ),
+            BackingStore::LegacySoftware(software) => (
+                sys::FlutterBackingStoreType::Software,
+                sys::FlutterBackingStore__bindgen_ty_1 {
+                    software: ManuallyDrop::new(software.into()),
+                },
+            ),
             #[cfg(feature = "metal")]
             BackingStore::Metal(metal) => (
                 sys::FlutterBackingStoreType::Metal,
@@ -96,54 +136,56 @@ impl From<BackingStore> for sys::FlutterBackingStore {
 }
 
 impl BackingStore {
-    pub(crate) fn from_raw(backing_store: &sys::FlutterBackingStore) -> Self {
-        match backing_store.type_ {
-            #[cfg(feature = "opengl")]
-            sys::FlutterBackingStoreType::OpenGL => {
-                BackingStore::OpenGL(OpenGLBackingStore::from_raw(unsafe {
-                    &backing_store.__bindgen_anon_1.open_gl
-                }))
-            }
-            #[cfg(not(feature = "opengl"))]
-            sys::FlutterBackingStoreType::OpenGL => {
-                panic!("OpenGL feature is not enabled. Cannot create the backing store.")
-            }
-            sys::FlutterBackingStoreType::Software => {
-                // unreachable!() because voltio never constructs this type; you have to use underlying sys apis to construct it.
-                unreachable!(
-                    "Deprecated software backing store type is unsupported. Don't construct it."
-                )
-            }
-            sys::FlutterBackingStoreType::Software2 => {
-                BackingStore::Software(SoftwareBackingStore::from_raw(unsafe {
-                    &backing_store.__bindgen_anon_1.software2
-                }))
-            }
-            #[cfg(feature = "metal")]
-            sys::FlutterBackingStoreType::Metal => {
-                BackingStore::Metal(MetalBackingStore::from_raw(unsafe {
-                    &backing_store.__bindgen_anon_1.metal
-                }))
-            }
-            #[cfg(not(feature = "metal"))]
-            sys::FlutterBackingStoreType::Metal => {
-                panic!("Metal feature is not enabled. Cannot create the backing store.")
-            }
-            #[cfg(feature = "vulkan")]
-            sys::FlutterBackingStoreType::Vulkan => {
-                BackingStore::Vulkan(VulkanBackingStore::from_raw(unsafe {
-                    &backing_store.__bindgen_anon_1.vulkan
-                }))
-            }
-            #[cfg(not(feature = "vulkan"))]
-            sys::FlutterBackingStoreType::Vulkan => {
-                panic!("Vulkan feature is not enabled. Cannot create the backing store.")
-            }
-            _ => panic!("Unknown FlutterBackingStoreType. Cannot create the backing store."),
+    sys_pub! {
+        /// `None` (with a warning) for a type of backing store that this crate doesn't know, or whose feature is disabled.
+        /// This crate never creates those, but the engine shouldn't be able to abort the process mid-frame with one.
+        ///
+        /// # Safety
+        ///
+        /// `backing_store` must be valid like when the engine passes it to a compositor:
+        /// every pointer in it must be valid, and its union must hold the variant its type says.
+        ///
+        /// # Panics
+        ///
+        /// If it's a software (`Software2`) or Vulkan backing store whose destruction callback
+        /// wasn't set by this crate.
+        unsafe fn from_raw(backing_store: &sys::FlutterBackingStore) -> Option<Self> {
+            Some(match backing_store.type_ {
+                #[cfg(feature = "opengl")]
+                sys::FlutterBackingStoreType::OpenGL => {
+                    let raw = unsafe { &backing_store.__bindgen_anon_1.open_gl };
+                    BackingStore::OpenGL(unsafe { OpenGLBackingStore::from_raw(raw) }?)
+                }
+                sys::FlutterBackingStoreType::Software => {
+                    let raw = unsafe { &backing_store.__bindgen_anon_1.software };
+                    BackingStore::LegacySoftware(unsafe {
+                        LegacySoftwareBackingStore::from_raw(raw)
+                    })
+                }
+                sys::FlutterBackingStoreType::Software2 => {
+                    let raw = unsafe { &backing_store.__bindgen_anon_1.software2 };
+                    BackingStore::Software(unsafe { SoftwareBackingStore::from_raw(raw) }?)
+                }
+                #[cfg(feature = "metal")]
+                sys::FlutterBackingStoreType::Metal => {
+                    let raw = unsafe { &backing_store.__bindgen_anon_1.metal };
+                    BackingStore::Metal(unsafe { MetalBackingStore::from_raw(raw) }?)
+                }
+                #[cfg(feature = "vulkan")]
+                sys::FlutterBackingStoreType::Vulkan => {
+                    let raw = unsafe { &backing_store.__bindgen_anon_1.vulkan };
+                    BackingStore::Vulkan(unsafe { VulkanBackingStore::from_raw(raw) }?)
+                }
+                unknown => {
+                    warn!("unsupported {unknown:?}; ignoring the backing store");
+                    return None;
+                }
+            })
         }
     }
 }
 
+#[derive(Debug)]
 pub enum RendererConfig {
     #[cfg(feature = "opengl")]
     OpenGL(OpenGLRendererConfig),
@@ -1,9 +1,10 @@
-use std::{collections::HashMap, mem::ManuallyDrop};
+use std::{collections::HashMap, fmt, mem::ManuallyDrop};
 
-use tracing::trace;
+use tracing::{trace, warn};
 
 use crate::{sys, FrameInfo, PresentInfo, Region, Transformation};
 
+#[derive(Debug)]
 pub enum OpenGLBackingStore {
     Texture(OpenGLTexture),
     Framebuffer(OpenGLFramebuffer),
@@ -28,28 +29,38 @@ impl From<OpenGLBackingStore> for sys::FlutterOpenGLBackingStore {
 }
 
 impl OpenGLBackingStore {
-    pub(crate) fn from_raw(raw: &sys::FlutterOpenGLBackingStore) -> Self {
-        match raw.type_ {
-            sys::FlutterOpenGLTargetType::Texture => {
-                OpenGLBackingStore::Texture(OpenGLTexture::from_raw(unsafe {
-                    &raw.__bindgen_anon_1.texture
-                }))
-            }
-            sys::FlutterOpenGLTargetType::Framebuffer => {
-                OpenGLBackingStore::Framebuffer(OpenGLFramebuffer::from_raw(unsafe {
-                    &raw.__bindgen_anon_1.framebuffer
-                }))
-            }
-            sys::FlutterOpenGLTargetType::Surface => {
-                unimplemented!();
-            }
-            _ => unreachable!("Unknown FlutterOpenGLTargetType. Cannot use it."),
+    sys_pub! {
+        /// `None` (with a warning) for surfaces, which this crate doesn't support, unknown target types,
+        /// and textures or framebuffers that weren't made by this crate.
+        ///
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        unsafe fn from_raw(raw: &sys::FlutterOpenGLBackingStore) -> Option<Self> {
+            Some(match raw.type_ {
+                sys::FlutterOpenGLTargetType::Texture => {
+                    OpenGLBackingStore::Texture(OpenGLTexture::from_raw(unsafe {
+                        &raw.__bindgen_anon_1.texture
+                    })?)
+                }
+                sys::FlutterOpenGLTargetType::Framebuffer => {
+                    OpenGLBackingStore::Framebuffer(OpenGLFramebuffer::from_raw(unsafe {
+                        &raw.__bindgen_anon_1.framebuffer
+                    })?)
+                }
+                unknown => {
+                    warn!("unsupported {unknown:?}; ignoring the backing store");
+                    return None;
+                }
+            })
         }
     }
 }
 
+#[derive(Debug)]
 pub struct OpenGLTexture {
-    /// Target texture of the active texture unit (example `GL_TEXTURE_2D` or `GL_TEXTURE_RECTANGLE`).
+    /// Target texture of the active texture unit (example `GL_TEXTURE_2D`, `GL_TEXTURE_RECTANGLE` or `GL_TEXTURE_EXTERNAL_OES`).
     pub target: u32,
     /// The name of the texture.
     pub name: u32,
@@ -87,24 +98,59 @@ impl From<OpenGLTexture> for sys::FlutterOpenGLTexture {
 }
 
 impl OpenGLTexture {
-    fn from_raw(texture: &sys::FlutterOpenGLTexture) -> Self {
-        assert!(texture.destruction_callback == Some(destroy_opengl_texture_callback),
-         "from_raw(&sys::FlutterOpenGLTexture) for an OpenGL texture for which we didn't set the destruction callback"
-        );
+    /// `GL_TEXTURE_2D`
+    pub const TEXTURE_2D: u32 = 0x0DE1;
+    /// `GL_TEXTURE_RECTANGLE`
+    pub const TEXTURE_RECTANGLE: u32 = 0x84F5;
+    /// `GL_TEXTURE_EXTERNAL_OES`, from `GL_OES_EGL_image_external`.
+    ///
+    /// This is what an `EGLImage` is bound to when it can't be sampled as a regular texture,
+    /// e.g. a YUV video frame imported from a dmabuf.
+    pub const TEXTURE_EXTERNAL_OES: u32 = 0x8D65;
 
+    /// An external texture with an `EGLImage` bound to it (with `glEGLImageTargetTexture2DOES`), for
+    /// [`OpenGLRendererHandler::gl_external_texture_frame`].
+    ///
+    /// External textures can't have mipmaps or repeat, so the renderer sets linear filtering and clamps to the edge
+    /// on them before the engine samples them.
+    #[must_use]
+    pub fn external_oes(name: u32, width: usize, height: usize) -> Self {
         Self {
+            target: Self::TEXTURE_EXTERNAL_OES,
+            name,
+            // GL_RGBA8; the actual format is whatever the image is, and the driver converts it when sampling
+            format: 0x8058,
+            width,
+            height,
+        }
+    }
+
+    fn from_raw(texture: &sys::FlutterOpenGLTexture) -> Option<Self> {
+        if !super::is_our_destruction_callback(
+            texture.destruction_callback,
+            destroy_opengl_texture_callback,
+        ) {
+            warn!("from_raw(&sys::FlutterOpenGLTexture) for an OpenGL texture for which we didn't set the destruction callback; ignoring the backing store");
+            return None;
+        }
+
+        Some(Self {
             target: texture.target,
             name: texture.name,
             format: texture.format,
             width: texture.width,
             height: texture.height,
-        }
+        })
     }
 }
 
+/// A framebuffer for the engine to render into.
+///
+/// Its color attachment may be a multisampled renderbuffer, which the embedder resolves after the engine presents it.
+#[derive(Debug)]
 pub struct OpenGLFramebuffer {
     /// The format of the color attachment of the frame-buffer. For example,
-    /// GL_RGBA8.
+    /// `GL_RGBA8`, or `GL_SRGB8_ALPHA8` if the compositor allocated it to hold sRGB-encoded colors.
     ///
     /// In case of ambiguity when dealing with Window bound frame-buffers, 0 may
     /// be used.
@@ -132,15 +178,19 @@ impl From<OpenGLFramebuffer> for sys::FlutterOpenGLFramebuffer {
     }
 }
 impl OpenGLFramebuffer {
-    fn from_raw(raw: &sys::FlutterOpenGLFramebuffer) -> Self {
-        assert!(raw.destruction_callback == Some(destroy_opengl_framebuffer_callback),
-         "from_raw(&sys::FlutterOpenGLFramebuffer) for an OpenGL framebuffer for which we didn't set the destruction callback"
-        );
+    fn from_raw(raw: &sys::FlutterOpenGLFramebuffer) -> Option<Self> {
+        if !super::is_our_destruction_callback(
+            raw.destruction_callback,
+            destroy_opengl_framebuffer_callback,
+        ) {
+            warn!("from_raw(&sys::FlutterOpenGLFramebuffer) for an OpenGL framebuffer for which we didn't set the destruction callback; ignoring the backing store");
+            return None;
+        }
 
-        Self {
+        Some(Self {
             format: raw.target,
             name: raw.name,
-        }
+        })
     }
 }
 
@@ -192,6 +242,9 @@ pub trait OpenGLRendererHandler {
     /// engine will call this method (on an internal engine managed thread) so
     /// that external texture details can be supplied to the engine for subsequent
     /// composition.
+    ///
+    /// On GLES, video frames imported as an `EGLImage` can be returned without copying them,
+    /// with [`OpenGLTexture::external_oes`].
     fn gl_external_texture_frame(
         &mut self,
         texture_id: i64,
@@ -222,6 +275,14 @@ pub struct OpenGLRendererConfig {
     pub handler: Box<dyn OpenGLRendererHandler>,
 }
 
+impl fmt::Debug for OpenGLRendererConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenGLRendererConfig")
+            .field("fbo_reset_after_present", &self.fbo_reset_after_present)
+            .finish_non_exhaustive()
+    }
+}
+
 impl From<OpenGLRendererConfig> for super::RendererConfig {
     fn from(config: OpenGLRendererConfig) -> Self {
         Self::OpenGL(config)
@@ -244,12 +305,68 @@ pub(crate) struct OpenGLRendererUserData {
     ///
     /// Why doesn't this just have a destruction callback like some other objects?
     existing_damage_map: HashMap<isize, *mut [sys::FlutterRect]>,
+    /// Resolved the first time an external OES texture is returned.
+    sampler_functions: Option<SamplerFunctions>,
     handler: Box<dyn OpenGLRendererHandler>,
 }
 
+type GlBindTexture = unsafe extern "system" fn(target: u32, texture: u32);
+type GlTexParameteri = unsafe extern "system" fn(target: u32, pname: u32, param: i32);
+
+#[derive(Clone, Copy)]
+struct SamplerFunctions {
+    bind_texture: GlBindTexture,
+    tex_parameteri: GlTexParameteri,
+}
+
+impl SamplerFunctions {
+    fn resolve(handler: &mut dyn OpenGLRendererHandler) -> Option<Self> {
+        let bind_texture = handler.gl_proc_resolver(c"glBindTexture".as_ptr());
+        let tex_parameteri = handler.gl_proc_resolver(c"glTexParameteri".as_ptr());
+        if bind_texture.is_null() || tex_parameteri.is_null() {
+            return None;
+        }
+        Some(unsafe {
+            Self {
+                bind_texture: std::mem::transmute::<*mut std::ffi::c_void, GlBindTexture>(
+                    bind_texture,
+                ),
+                tex_parameteri: std::mem::transmute::<*mut std::ffi::c_void, GlTexParameteri>(
+                    tex_parameteri,
+                ),
+            }
+        })
+    }
+
+    /// Sets the only sampler state that external textures support. The engine would otherwise try to use
+    /// its defaults, which may include mipmapped filtering, and sample nothing at all.
+    fn apply_external_oes(self, texture: &OpenGLTexture) {
+        const TEXTURE_MAG_FILTER: u32 = 0x2800;
+        const TEXTURE_MIN_FILTER: u32 = 0x2801;
+        const TEXTURE_WRAP_S: u32 = 0x2802;
+        const TEXTURE_WRAP_T: u32 = 0x2803;
+        const LINEAR: i32 = 0x2601;
+        const CLAMP_TO_EDGE: i32 = 0x812F;
+
+        let target = OpenGLTexture::TEXTURE_EXTERNAL_OES;
+        unsafe {
+            (self.bind_texture)(target, texture.name);
+            (self.tex_parameteri)(target, TEXTURE_MIN_FILTER, LINEAR);
+            (self.tex_parameteri)(target, TEXTURE_MAG_FILTER, LINEAR);
+            (self.tex_parameteri)(target, TEXTURE_WRAP_S, CLAMP_TO_EDGE);
+            (self.tex_parameteri)(target, TEXTURE_WRAP_T, CLAMP_TO_EDGE);
+            (self.bind_texture)(target, 0);
+        }
+    }
+}
+
 mod callbacks {
+    use tracing::warn;
+
+    use super::{OpenGLTexture, SamplerFunctions};
     use crate::{sys, util::return_out_param, EngineUserData, PresentInfo, RendererUserData};
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn make_current(engine_user_data: *mut std::ffi::c_void) -> bool {
         let engine_user_data = engine_user_data.cast::<EngineUserData>();
         let engine_user_data = unsafe { &mut *engine_user_data };
@@ -261,6 +378,7 @@ mod callbacks {
         user_data.handler.make_current()
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn clear_current(engine_user_data: *mut std::ffi::c_void) -> bool {
         let engine_user_data = engine_user_data.cast::<EngineUserData>();
         let engine_user_data = unsafe { &mut *engine_user_data };
@@ -272,6 +390,7 @@ mod callbacks {
         user_data.handler.clear_current()
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn present_with_info(
         engine_user_data: *mut std::ffi::c_void,
         present_info: *const sys::FlutterPresentInfo,
@@ -283,7 +402,7 @@ mod callbacks {
             unreachable!("OpenGL renderer callback called with non-OpenGL renderer user data.");
         };
 
-        let present_info: PresentInfo = PresentInfo::from_raw(unsafe { &*present_info });
+        let present_info: PresentInfo = unsafe { PresentInfo::from_raw(&*present_info) };
 
         // see field documentation for `existing_damage_map`
         if let Some(existing_damage) = user_data.existing_damage_map.remove(
@@ -302,6 +421,7 @@ mod callbacks {
         user_data.handler.present(present_info)
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn fbo_with_frame_info(
         engine_user_data: *mut std::ffi::c_void,
         frame_info: *const sys::FlutterFrameInfo,
@@ -318,6 +438,7 @@ mod callbacks {
         user_data.handler.fbo_callback(frame_info)
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn make_resource_current(engine_user_data: *mut std::ffi::c_void) -> bool {
         let engine_user_data = engine_user_data.cast::<EngineUserData>();
         let engine_user_data = unsafe { &mut *engine_user_data };
@@ -329,6 +450,7 @@ mod callbacks {
         user_data.handler.make_resource_current()
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn surface_transformation(
         engine_user_data: *mut std::ffi::c_void,
     ) -> sys::FlutterTransformation {
@@ -342,6 +464,7 @@ mod callbacks {
         user_data.handler.surface_transformation().into()
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn gl_proc_resolver(
         engine_user_data: *mut std::ffi::c_void,
         name: *const std::os::raw::c_char,
@@ -356,6 +479,10 @@ mod callbacks {
         user_data.handler.gl_proc_resolver(name)
     }
 
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "trace", skip(engine_user_data, texture_out))
+    )]
     pub extern "C" fn gl_external_texture_frame(
         engine_user_data: *mut std::ffi::c_void,
         texture_id: i64,
@@ -370,16 +497,31 @@ mod callbacks {
             unreachable!("OpenGL renderer callback called with non-OpenGL renderer user data.");
         };
 
-        unsafe {
-            return_out_param(
-                texture_out,
-                user_data
-                    .handler
-                    .gl_external_texture_frame(texture_id, width, height),
-            )
+        let texture = user_data
+            .handler
+            .gl_external_texture_frame(texture_id, width, height);
+
+        if let Some(texture) = &texture {
+            if texture.target == OpenGLTexture::TEXTURE_EXTERNAL_OES {
+                if user_data.sampler_functions.is_none() {
+                    user_data.sampler_functions =
+                        SamplerFunctions::resolve(&mut *user_data.handler);
+                }
+                if let Some(functions) = user_data.sampler_functions {
+                    functions.apply_external_oes(texture);
+                } else {
+                    warn!("can't set sampler state of external texture {texture_id}: glTexParameteri is unavailable");
+                }
+            }
         }
+
+        unsafe { return_out_param(texture_out, texture) }
     }
 
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "trace", skip(engine_user_data, existing_damage_out))
+    )]
     pub extern "C" fn populate_existing_damage(
         engine_user_data: *mut std::ffi::c_void,
         fbo_id: isize,
@@ -433,6 +575,7 @@ impl From<OpenGLRendererConfig> for (OpenGLRendererUserData, sys::FlutterOpenGLR
         (
             OpenGLRendererUserData {
                 existing_damage_map: HashMap::new(),
+                sampler_functions: None,
                 handler: config.handler,
             },
             sys::FlutterOpenGLRendererConfig {
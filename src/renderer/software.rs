@@ -1,4 +1,6 @@
-use tracing::error;
+use std::fmt;
+
+use tracing::warn;
 
 use crate::sys;
 
@@ -61,6 +63,7 @@ simple_enum! {
     }
 }
 
+#[derive(Debug)]
 pub struct SoftwareBackingStore {
     /// A pointer to the raw bytes of the allocation described by this software backing store.
     pub allocation: *mut u8,
@@ -93,21 +96,75 @@ impl From<SoftwareBackingStore> for sys::FlutterSoftwareBackingStore2 {
     }
 }
 impl SoftwareBackingStore {
-    pub(crate) fn from_raw(raw: &sys::FlutterSoftwareBackingStore2) -> Self {
-        let our_callback: sys::VoidCallback = Some(destroy_software_callback);
-        if raw.destruction_callback != Some(destroy_software_callback) {
-            error!("from_raw(&sys::FlutterSoftwareBackingStore2) with destruction callback: {:?} instead of {:?}",
-                raw.destruction_callback, our_callback
-            );
+    sys_pub! {
+        /// `None` (with a warning) if the pixel format isn't known to this crate.
+        ///
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        /// Also `None` if it wasn't made by this crate.
+        unsafe fn from_raw(raw: &sys::FlutterSoftwareBackingStore2) -> Option<Self> {
+            if !super::is_our_destruction_callback(raw.destruction_callback, destroy_software_callback) {
+                warn!("from_raw(&sys::FlutterSoftwareBackingStore2) for a software buffer for which we didn't set the destruction callback; ignoring the backing store");
+                return None;
+            }
+            let pixel_format = match raw.pixel_format.try_into() {
+                Ok(pixel_format) => pixel_format,
+                Err(err) => {
+                    warn!("{err}; ignoring the backing store");
+                    return None;
+                }
+            };
+            Some(Self {
+                allocation: raw.allocation as *mut u8,
+                row_bytes: raw.row_bytes,
+                height: raw.height,
+                pixel_format,
+            })
         }
-        assert!(raw.destruction_callback == Some(destroy_software_callback),
-            "from_raw(&sys::FlutterSoftwareBackingStore2) for a software buffer for which we didn't set the destruction callback"
-        );
+    }
+}
+
+/// The deprecated software backing store, which predates [`SoftwareBackingStore`]'s pixel formats.
+///
+/// The engine always renders into it as [`SoftwarePixelFormat::Native32`].
+/// Prefer [`SoftwareBackingStore`]; this exists for engines and code that still use the old type.
+#[derive(Debug)]
+pub struct LegacySoftwareBackingStore {
+    /// A pointer to the raw bytes of the allocation described by this software backing store.
+    pub allocation: *mut u8,
+    /// The number of bytes in a single row of the allocation.
+    pub row_bytes: usize,
+    /// The number of rows in the allocation.
+    pub height: usize,
+}
+
+impl From<LegacySoftwareBackingStore> for sys::FlutterSoftwareBackingStore {
+    fn from(software: LegacySoftwareBackingStore) -> Self {
         Self {
-            allocation: raw.allocation as *mut u8,
-            row_bytes: raw.row_bytes,
-            height: raw.height,
-            pixel_format: raw.pixel_format.try_into().unwrap(),
+            user_data: std::ptr::null_mut(),
+            destruction_callback: Some(destroy_software_callback),
+
+            allocation: software.allocation as *const std::ffi::c_void,
+            row_bytes: software.row_bytes,
+            height: software.height,
+        }
+    }
+}
+impl LegacySoftwareBackingStore {
+    sys_pub! {
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        unsafe fn from_raw(raw: &sys::FlutterSoftwareBackingStore) -> Self {
+            // the allocation belongs to whoever made it, whether or not that was volito; there's nothing to check
+            Self {
+                allocation: raw.allocation as *mut u8,
+                row_bytes: raw.row_bytes,
+                height: raw.height,
+            }
         }
     }
 }
@@ -123,6 +180,13 @@ pub struct SoftwareRendererConfig {
     pub handler: Box<dyn SoftwareRendererHandler>,
 }
 
+impl fmt::Debug for SoftwareRendererConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoftwareRendererConfig")
+            .finish_non_exhaustive()
+    }
+}
+
 impl From<SoftwareRendererConfig> for super::RendererConfig {
     fn from(config: SoftwareRendererConfig) -> Self {
         Self::Software(config)
@@ -138,6 +202,10 @@ mod callbacks {
 
     use super::*;
 
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "trace", skip(engine_user_data, allocation))
+    )]
     pub extern "C" fn surface_present(
         engine_user_data: *mut std::ffi::c_void,
         allocation: *const std::ffi::c_void,
@@ -1,9 +1,13 @@
-use std::ffi::{CStr, CString};
+use std::{
+    ffi::{CStr, CString},
+    fmt,
+};
 
-use tracing::trace;
+use tracing::{trace, warn};
 
 use crate::{sys, FrameInfo};
 
+#[derive(Debug)]
 pub struct VulkanImage {
     /// Handle to the `VkImage` that is owned by the embedder. The engine will
     /// bind this image for writing the frame.
@@ -30,6 +34,7 @@ impl From<sys::FlutterVulkanImage> for VulkanImage {
     }
 }
 
+#[derive(Debug)]
 pub struct VulkanBackingStore {
     /// The image that the layer will be rendered to. This image must already be
     /// available for the engine to bind for writing when it's given to the engine
@@ -64,13 +69,21 @@ impl From<VulkanBackingStore> for sys::FlutterVulkanBackingStore {
     }
 }
 impl VulkanBackingStore {
-    pub(crate) fn from_raw(raw: &sys::FlutterVulkanBackingStore) -> Self {
-        assert!(
-            raw.destruction_callback == Some(destroy_vulkan_callback),
-            "from_raw(&sys::FlutterVulkanBackingStore) for a vulkan buffer for which we didn't set the destruction callback"
-        );
-        Self {
-            image: VulkanImage::from(unsafe { *raw.image }),
+    sys_pub! {
+        /// `None` (with a warning) if it wasn't made by this crate.
+        ///
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        unsafe fn from_raw(raw: &sys::FlutterVulkanBackingStore) -> Option<Self> {
+            if !super::is_our_destruction_callback(raw.destruction_callback, destroy_vulkan_callback) {
+                warn!("from_raw(&sys::FlutterVulkanBackingStore) for a vulkan buffer for which we didn't set the destruction callback; ignoring the backing store");
+                return None;
+            }
+            Some(Self {
+                image: VulkanImage::from(unsafe { *raw.image }),
+            })
         }
     }
 }
@@ -141,6 +154,24 @@ pub struct VulkanRendererConfig {
     pub handler: Box<dyn VulkanRendererHandler>,
 }
 
+impl fmt::Debug for VulkanRendererConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VulkanRendererConfig")
+            .field("version", &self.version)
+            .field("instance", &self.instance)
+            .field("physical_device", &self.physical_device)
+            .field("device", &self.device)
+            .field("queue_family_index", &self.queue_family_index)
+            .field("queue", &self.queue)
+            .field(
+                "enabled_instance_extensions",
+                &self.enabled_instance_extensions,
+            )
+            .field("enabled_device_extensions", &self.enabled_device_extensions)
+            .finish_non_exhaustive()
+    }
+}
+
 impl From<VulkanRendererConfig> for super::RendererConfig {
     fn from(config: VulkanRendererConfig) -> Self {
         Self::Vulkan(config)
@@ -180,6 +211,7 @@ mod callbacks {
 
     use super::*;
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn get_instance_proc_address(
         engine_user_data: *mut std::ffi::c_void,
         instance: sys::FlutterVulkanInstanceHandle,
@@ -197,6 +229,7 @@ mod callbacks {
             .get_instance_proc_address(instance, unsafe { CStr::from_ptr(name) })
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn get_next_image(
         engine_user_data: *mut std::ffi::c_void,
         frame_info: *const sys::FlutterFrameInfo,
@@ -213,6 +246,7 @@ mod callbacks {
         user_data.handler.get_next_image(frame_info).into()
     }
 
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
     pub extern "C" fn present_image(
         engine_user_data: *mut std::ffi::c_void,
         image: *const sys::FlutterVulkanImage,
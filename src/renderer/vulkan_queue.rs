@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    ffi::{c_void, CStr},
+    sync::{LazyLock, Mutex, MutexGuard, OnceLock, PoisonError},
+};
+
+use ash::vk;
+use tracing::warn;
+
+/// The engine submits to its queue from its own threads, so every use of it goes through this lock.
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+/// The real `vkQueueSubmit` and `vkQueueWaitIdle`.
+///
+/// These are the loader's trampolines from `vkGetInstanceProcAddr`, which dispatch on the queue,
+/// so they're the same for every instance (and every engine), unlike the state in [`SUBMIT_SYNC`].
+static QUEUE_FNS: OnceLock<(vk::PFN_vkQueueSubmit, vk::PFN_vkQueueWaitIdle)> = OnceLock::new();
+
+/// Locks the `VkQueue` of the Vulkan renderers that volito sets up.
+///
+/// The engine uses the queue from its own threads, behind this lock. Hold it around any other use of the queue:
+/// submitting, presenting, or waiting for it (or the whole device) to idle.
+pub fn lock_vulkan_queue() -> MutexGuard<'static, ()> {
+    QUEUE_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Semaphores added to the engine's queue submissions, so that its rendering can be ordered with other work on the GPU.
+///
+/// The engine still waits for its work on the host before presenting, but work that the embedder submits afterwards
+/// (or a compositor, with DRM explicit sync) can wait on the timeline instead of on the host.
+#[derive(Default)]
+struct SubmitSync {
+    /// Waited on by the engine's next submission, and then forgotten.
+    waits: Vec<SubmitWait>,
+    /// Signaled after every submission of the engine, with a value one higher each time.
+    timeline: Option<(vk::Semaphore, u64)>,
+}
+
+struct SubmitWait {
+    semaphore: vk::Semaphore,
+    value: u64,
+    stage: vk::PipelineStageFlags,
+}
+
+/// Keyed by the queue, so that every engine only ever sees its own semaphores.
+static SUBMIT_SYNC: LazyLock<Mutex<HashMap<vk::Queue, SubmitSync>>> = LazyLock::new(Mutex::default);
+
+fn submit_sync() -> MutexGuard<'static, HashMap<vk::Queue, SubmitSync>> {
+    SUBMIT_SYNC.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Makes the engine's next submission to `queue` wait on a semaphore, e.g. one signaled by the embedder's own queue,
+/// or imported from a DRM sync file, before the image it renders to is written.
+///
+/// `value` is the value to wait for on a timeline semaphore, and ignored for binary semaphores.
+/// Call it from [`crate::VulkanRendererHandler::get_next_image`] or the compositor's backing store callbacks,
+/// so that the wait happens before the frame is rendered.
+///
+/// Only submissions that go through [`locked_instance_proc_address`] wait on it. That's the renderers and overlays
+/// that volito sets up, and any [`crate::VulkanRendererHandler`] that resolves its functions with it.
+/// If nothing ever did, this logs a warning, because the semaphore would never be waited on.
+pub fn wait_before_next_vulkan_submit(
+    queue: vk::Queue,
+    semaphore: vk::Semaphore,
+    value: u64,
+    stage: vk::PipelineStageFlags,
+) {
+    if QUEUE_FNS.get().is_none() {
+        warn!("wait_before_next_vulkan_submit: no Vulkan renderer resolves its queue functions through locked_instance_proc_address, so the engine won't wait on this semaphore");
+    }
+    submit_sync()
+        .entry(queue)
+        .or_default()
+        .waits
+        .push(SubmitWait {
+            semaphore,
+            value,
+            stage,
+        });
+}
+
+/// Makes every submission of the engine to `queue` signal a timeline semaphore, starting after `value`.
+/// `None` stops signaling it.
+///
+/// When the engine presents (with [`crate::VulkanRendererHandler::present_image`] or the compositor),
+/// [`vulkan_timeline_value`] is reached once everything it rendered is done. The semaphore can be waited on by other
+/// queues, or exported for DRM explicit sync, without waiting on the host.
+///
+/// The semaphore must be created with `VK_SEMAPHORE_TYPE_TIMELINE`, and nothing else may signal it while it's in use.
+/// Like [`wait_before_next_vulkan_submit`], this only applies to submissions through [`locked_instance_proc_address`].
+pub fn signal_vulkan_timeline(queue: vk::Queue, timeline: Option<(vk::Semaphore, u64)>) {
+    submit_sync().entry(queue).or_default().timeline = timeline;
+}
+
+/// The last value that the engine's submissions to `queue` signal on the semaphore from [`signal_vulkan_timeline`].
+#[must_use]
+pub fn vulkan_timeline_value(queue: vk::Queue) -> Option<u64> {
+    submit_sync().get(&queue)?.timeline.map(|(_, value)| value)
+}
+
+/// The `VkTimelineSemaphoreSubmitInfo` in a `p_next` chain, if any, and whether it's the first struct in the chain.
+unsafe fn find_timeline_info<'a>(
+    mut next: *const c_void,
+) -> Option<(&'a vk::TimelineSemaphoreSubmitInfo<'a>, bool)> {
+    let mut first = true;
+    while !next.is_null() {
+        let base = unsafe { &*next.cast::<vk::BaseInStructure<'_>>() };
+        if base.s_type == vk::StructureType::TIMELINE_SEMAPHORE_SUBMIT_INFO {
+            return Some((unsafe { &*next.cast() }, first));
+        }
+        next = base.p_next.cast();
+        first = false;
+    }
+    None
+}
+
+// the counts are of semaphores in a single submission
+#[allow(clippy::cast_possible_truncation)]
+unsafe extern "system" fn locked_queue_submit(
+    queue: vk::Queue,
+    submit_count: u32,
+    submits: *const vk::SubmitInfo<'_>,
+    fence: vk::Fence,
+) -> vk::Result {
+    let _guard = lock_vulkan_queue();
+    let (queue_submit, _) = QUEUE_FNS.get().unwrap();
+
+    let mut sync = submit_sync();
+    let sync = match sync.get_mut(&queue) {
+        Some(sync) if submit_count != 0 && (!sync.waits.is_empty() || sync.timeline.is_some()) => {
+            sync
+        }
+        _ => return unsafe { queue_submit(queue, submit_count, submits, fence) },
+    };
+
+    let mut submits =
+        unsafe { std::slice::from_raw_parts(submits, submit_count as usize) }.to_vec();
+
+    // The waits go on the first submission, merged with the engine's own timeline values, if it has any.
+    // If its `VkTimelineSemaphoreSubmitInfo` isn't at the head of the chain, it can't be swapped out for a merged one,
+    // so the waits are left for a later submission.
+    let first = submits[0];
+    let existing = unsafe { find_timeline_info(first.p_next) };
+    let waits = match existing {
+        Some((_, false)) => {
+            warn!("the engine's queue submission has timeline values further down its p_next chain; waiting on the next one instead");
+            Vec::new()
+        }
+        _ => std::mem::take(&mut sync.waits),
+    };
+    let (mut wait_semaphores, mut wait_stages) = unsafe {
+        (
+            raw_slice(first.p_wait_semaphores, first.wait_semaphore_count).to_vec(),
+            raw_slice(first.p_wait_dst_stage_mask, first.wait_semaphore_count).to_vec(),
+        )
+    };
+    let mut wait_values = match existing {
+        Some((info, _)) if info.wait_semaphore_value_count != 0 => unsafe {
+            raw_slice(
+                info.p_wait_semaphore_values,
+                info.wait_semaphore_value_count,
+            )
+            .to_vec()
+        },
+        // binary semaphores ignore their values
+        _ => vec![0; wait_semaphores.len()],
+    };
+    for wait in &waits {
+        wait_semaphores.push(wait.semaphore);
+        wait_stages.push(wait.stage);
+        wait_values.push(wait.value);
+    }
+    let first_values = match existing {
+        Some((info, _)) => vk::TimelineSemaphoreSubmitInfo {
+            wait_semaphore_value_count: wait_values.len() as u32,
+            p_wait_semaphore_values: wait_values.as_ptr(),
+            ..*info
+        },
+        None => vk::TimelineSemaphoreSubmitInfo {
+            p_next: first.p_next,
+            wait_semaphore_value_count: wait_values.len() as u32,
+            p_wait_semaphore_values: wait_values.as_ptr(),
+            ..Default::default()
+        },
+    };
+    if !waits.is_empty() {
+        let first = &mut submits[0];
+        first.wait_semaphore_count = wait_semaphores.len() as u32;
+        first.p_wait_semaphores = wait_semaphores.as_ptr();
+        first.p_wait_dst_stage_mask = wait_stages.as_ptr();
+        first.p_next = (&raw const first_values).cast();
+    }
+
+    // The timeline is signaled by a submission of its own, after the engine's.
+    // Its signal waits for everything before it on the queue, and the engine's own signals are left alone.
+    let next_value = sync
+        .timeline
+        .map(|(semaphore, value)| (semaphore, value + 1));
+    let signal = next_value.map(|(semaphore, value)| ([semaphore], [value]));
+    let signal_info = signal.as_ref().map(|(_, values)| {
+        vk::TimelineSemaphoreSubmitInfo::default().signal_semaphore_values(values)
+    });
+    if let (Some((semaphores, _)), Some(signal_info)) = (&signal, &signal_info) {
+        submits.push(vk::SubmitInfo {
+            p_next: (&raw const *signal_info).cast(),
+            signal_semaphore_count: 1,
+            p_signal_semaphores: semaphores.as_ptr(),
+            ..Default::default()
+        });
+    }
+
+    let result = unsafe { queue_submit(queue, submits.len() as u32, submits.as_ptr(), fence) };
+    if result == vk::Result::SUCCESS {
+        sync.timeline = next_value;
+    } else {
+        // they weren't waited on after all
+        sync.waits.splice(0..0, waits);
+    }
+    result
+}
+
+/// A slice from a Vulkan array, which may be null if it's empty.
+unsafe fn raw_slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if count == 0 || ptr.is_null() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(ptr, count as usize) }
+    }
+}
+
+unsafe extern "system" fn locked_queue_wait_idle(queue: vk::Queue) -> vk::Result {
+    let _guard = lock_vulkan_queue();
+    let (_, queue_wait_idle) = QUEUE_FNS.get().unwrap();
+    unsafe { queue_wait_idle(queue) }
+}
+
+/// An implementation of [`crate::VulkanRendererHandler::get_instance_proc_address`],
+/// which swaps out the queue functions for ones that hold [`lock_vulkan_queue`],
+/// and add the semaphores from [`wait_before_next_vulkan_submit`] and [`signal_vulkan_timeline`].
+///
+/// The renderers that volito sets up already use it. A handler for a [`crate::VulkanRendererConfig`] of its own
+/// should return this from `get_instance_proc_address` too, and use the queue only while holding [`lock_vulkan_queue`].
+///
+/// # Safety
+///
+/// `instance` must be null or a valid instance created from `entry`.
+///
+/// # Panics
+///
+/// If the loader doesn't have `vkQueueSubmit` or `vkQueueWaitIdle`, which every Vulkan loader has.
+pub unsafe fn locked_instance_proc_address(
+    entry: &ash::Entry,
+    instance: vk::Instance,
+    name: &CStr,
+) -> *mut c_void {
+    let function: vk::PFN_vkVoidFunction = match name.to_bytes() {
+        b"vkQueueSubmit" | b"vkQueueWaitIdle" => {
+            QUEUE_FNS.get_or_init(|| unsafe {
+                let queue_submit = entry
+                    .get_instance_proc_addr(instance, c"vkQueueSubmit".as_ptr())
+                    .expect("vkQueueSubmit is a core function");
+                let queue_wait_idle = entry
+                    .get_instance_proc_addr(instance, c"vkQueueWaitIdle".as_ptr())
+                    .expect("vkQueueWaitIdle is a core function");
+                (
+                    std::mem::transmute::<unsafe extern "system" fn(), vk::PFN_vkQueueSubmit>(
+                        queue_submit,
+                    ),
+                    std::mem::transmute::<unsafe extern "system" fn(), vk::PFN_vkQueueWaitIdle>(
+                        queue_wait_idle,
+                    ),
+                )
+            });
+            if name.to_bytes() == b"vkQueueSubmit" {
+                Some(unsafe {
+                    std::mem::transmute::<vk::PFN_vkQueueSubmit, unsafe extern "system" fn()>(
+                        locked_queue_submit,
+                    )
+                })
+            } else {
+                Some(unsafe {
+                    std::mem::transmute::<vk::PFN_vkQueueWaitIdle, unsafe extern "system" fn()>(
+                        locked_queue_wait_idle,
+                    )
+                })
+            }
+        }
+        _ => unsafe { entry.get_instance_proc_addr(instance, name.as_ptr()) },
+    };
+    function.map_or(std::ptr::null_mut(), |f| f as *mut c_void)
+}
@@ -1,6 +1,11 @@
-use std::ffi::{CStr, CString};
+use std::{
+    ffi::{CStr, CString},
+    sync::atomic::Ordering,
+};
 
-use crate::{sys, Engine, Rect, Transformation};
+use tracing::warn;
+
+use crate::{sys, Engine, Rect, StandardValue, Transformation};
 
 simple_enum! {
     pub enum TextDirection(sys::FlutterTextDirection) {
@@ -205,23 +210,34 @@ impl StringAttribute {
         range.clone()
     }
 
-    pub(crate) fn from_raw(raw: &sys::FlutterStringAttribute) -> Self {
-        let range = raw.start..raw.end;
+    sys_pub! {
+        /// `None` (with a warning) for a kind of attribute that this crate doesn't know.
+        ///
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        unsafe fn from_raw(raw: &sys::FlutterStringAttribute) -> Option<Self> {
+            let range = raw.start..raw.end;
 
-        match raw.type_ {
-            sys::FlutterStringAttributeType::SpellOut => {
-                let spell_out = unsafe { &*raw.__bindgen_anon_1.spell_out };
-                let _ = spell_out; // this struct is actually just empty; but to be clear, it does *exist*.
+            Some(match raw.type_ {
+                sys::FlutterStringAttributeType::SpellOut => {
+                    let spell_out = unsafe { &*raw.__bindgen_anon_1.spell_out };
+                    let _ = spell_out; // this struct is actually just empty; but to be clear, it does *exist*.
 
-                Self::SpellOut { range }
-            }
-            sys::FlutterStringAttributeType::Locale => {
-                let locale = unsafe { &*raw.__bindgen_anon_1.locale };
-                let locale = unsafe { CStr::from_ptr(locale.locale) }.to_owned();
+                    Self::SpellOut { range }
+                }
+                sys::FlutterStringAttributeType::Locale => {
+                    let locale = unsafe { &*raw.__bindgen_anon_1.locale };
+                    let locale = unsafe { CStr::from_ptr(locale.locale) }.to_owned();
 
-                Self::Locale { range, locale }
-            }
-            _ => panic!("Unknown FlutterStringAttributeType: {:?}", raw.type_),
+                    Self::Locale { range, locale }
+                }
+                unknown => {
+                    warn!("unsupported {unknown:?}; ignoring the attribute");
+                    return None;
+                }
+            })
         }
     }
 }
@@ -243,12 +259,13 @@ impl AttributedString {
     ) -> Self {
         Self {
             string: unsafe { CStr::from_ptr(string) }.to_owned(),
-            attributes: unsafe { crate::util::slice_from_raw_parts_with_invalid_empty(attributes, attribute_count) }
-                .iter()
-                .copied()
-                .map(|raw| unsafe { &*raw })
-                .map(StringAttribute::from_raw)
-                .collect(),
+            attributes: unsafe {
+                crate::util::slice_from_raw_parts_with_invalid_empty(attributes, attribute_count)
+            }
+            .iter()
+            .copied()
+            .filter_map(|raw| unsafe { StringAttribute::from_raw(&*raw) })
+            .collect(),
         }
     }
 }
@@ -261,6 +278,7 @@ impl AttributedString {
 /// `FlutterUpdateSemanticsCallback2`.
 ///
 /// See: <https://api.flutter.dev/flutter/semantics/SemanticsNode-class.html>
+#[derive(Debug)]
 pub struct SemanticsNode {
     /// The unique identifier for this node.
     pub id: i32,
@@ -321,68 +339,80 @@ pub struct SemanticsNode {
 }
 
 impl SemanticsNode {
-    pub(crate) fn from_raw(raw: &sys::FlutterSemanticsNode2) -> Self {
-        Self {
-            id: raw.id,
-            flags: raw.flags.into(),
-            actions: raw.actions.into(),
-            text_selection: raw.text_selection_base..raw.text_selection_extent,
-            scroll_child_count: raw.scroll_child_count,
-            scroll_index: raw.scroll_index,
-            scroll_position: raw.scroll_position,
-            scroll_extent_max: raw.scroll_extent_max,
-            scroll_extent_min: raw.scroll_extent_min,
-            elevation: raw.elevation,
-            thickness: raw.thickness,
-            label: AttributedString::from_raw(
-                raw.label,
-                raw.label_attribute_count,
-                raw.label_attributes,
-            ),
-            hint: AttributedString::from_raw(
-                raw.hint,
-                raw.hint_attribute_count,
-                raw.hint_attributes,
-            ),
-            value: AttributedString::from_raw(
-                raw.value,
-                raw.value_attribute_count,
-                raw.value_attributes,
-            ),
-            increased_value: AttributedString::from_raw(
-                raw.increased_value,
-                raw.increased_value_attribute_count,
-                raw.increased_value_attributes,
-            ),
-            decreased_value: AttributedString::from_raw(
-                raw.decreased_value,
-                raw.decreased_value_attribute_count,
-                raw.decreased_value_attributes,
-            ),
-            text_direction: raw
-                .text_direction
-                .try_into()
-                .expect("FlutterTextDirection should always be RTL, LTR, or Unknown"),
-            rect: raw.rect.into(),
-            transform: raw.transform.into(),
-            child_count: raw.child_count,
-            children_in_traversal_order: unsafe {
-                crate::util::slice_from_raw_parts_with_invalid_empty(raw.children_in_traversal_order, raw.child_count)
-            }
-            .to_vec(),
-            children_in_hit_test_order: unsafe {
-                crate::util::slice_from_raw_parts_with_invalid_empty(raw.children_in_hit_test_order, raw.child_count)
-            }
-            .to_vec(),
-            custom_accessibility_actions: unsafe {
-                crate::util::slice_from_raw_parts_with_invalid_empty(
-                    raw.custom_accessibility_actions,
-                    raw.custom_accessibility_actions_count,
-                )
+    sys_pub! {
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        unsafe fn from_raw(raw: &sys::FlutterSemanticsNode2) -> Self {
+            Self {
+                id: raw.id,
+                flags: raw.flags.into(),
+                actions: raw.actions.into(),
+                text_selection: raw.text_selection_base..raw.text_selection_extent,
+                scroll_child_count: raw.scroll_child_count,
+                scroll_index: raw.scroll_index,
+                scroll_position: raw.scroll_position,
+                scroll_extent_max: raw.scroll_extent_max,
+                scroll_extent_min: raw.scroll_extent_min,
+                elevation: raw.elevation,
+                thickness: raw.thickness,
+                label: AttributedString::from_raw(
+                    raw.label,
+                    raw.label_attribute_count,
+                    raw.label_attributes,
+                ),
+                hint: AttributedString::from_raw(
+                    raw.hint,
+                    raw.hint_attribute_count,
+                    raw.hint_attributes,
+                ),
+                value: AttributedString::from_raw(
+                    raw.value,
+                    raw.value_attribute_count,
+                    raw.value_attributes,
+                ),
+                increased_value: AttributedString::from_raw(
+                    raw.increased_value,
+                    raw.increased_value_attribute_count,
+                    raw.increased_value_attributes,
+                ),
+                decreased_value: AttributedString::from_raw(
+                    raw.decreased_value,
+                    raw.decreased_value_attribute_count,
+                    raw.decreased_value_attributes,
+                ),
+                text_direction: raw.text_direction.try_into().unwrap_or_else(|err| {
+                    warn!("{err}; the text direction is unknown");
+                    TextDirection::Unknown
+                }),
+                rect: raw.rect.into(),
+                transform: raw.transform.into(),
+                child_count: raw.child_count,
+                children_in_traversal_order: unsafe {
+                    crate::util::slice_from_raw_parts_with_invalid_empty(
+                        raw.children_in_traversal_order,
+                        raw.child_count,
+                    )
+                }
+                .to_vec(),
+                children_in_hit_test_order: unsafe {
+                    crate::util::slice_from_raw_parts_with_invalid_empty(
+                        raw.children_in_hit_test_order,
+                        raw.child_count,
+                    )
+                }
+                .to_vec(),
+                custom_accessibility_actions: unsafe {
+                    crate::util::slice_from_raw_parts_with_invalid_empty(
+                        raw.custom_accessibility_actions,
+                        raw.custom_accessibility_actions_count,
+                    )
+                }
+                .to_vec(),
+                platform_view_id: raw.platform_view_id,
+                tooltip: unsafe { CStr::from_ptr(raw.tooltip) }.to_owned(),
             }
-            .to_vec(),
-            platform_view_id: raw.platform_view_id,
-            tooltip: unsafe { CStr::from_ptr(raw.tooltip) }.to_owned(),
         }
     }
 }
@@ -398,6 +428,7 @@ impl SemanticsNode {
 /// enum.
 ///
 /// See: <https://api.flutter.dev/flutter/semantics/CustomSemanticsAction-class.html>
+#[derive(Debug)]
 pub struct SemanticsCustomAction {
     /// The unique custom action or action override ID.
     pub id: i32,
@@ -411,52 +442,225 @@ pub struct SemanticsCustomAction {
 }
 
 impl SemanticsCustomAction {
-    pub(crate) fn from_raw(raw: &sys::FlutterSemanticsCustomAction2) -> Self {
-        Self {
-            id: raw.id,
-            override_action: raw.override_action.into(),
-            label: unsafe { CStr::from_ptr(raw.label) }.to_owned(),
-            hint: unsafe { CStr::from_ptr(raw.hint) }.to_owned(),
+    sys_pub! {
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        unsafe fn from_raw(raw: &sys::FlutterSemanticsCustomAction2) -> Self {
+            Self {
+                id: raw.id,
+                override_action: raw.override_action.into(),
+                label: unsafe { CStr::from_ptr(raw.label) }.to_owned(),
+                hint: unsafe { CStr::from_ptr(raw.hint) }.to_owned(),
+            }
         }
     }
 }
 
+#[derive(Debug)]
 pub struct SemanticsUpdate {
     pub nodes: Vec<SemanticsNode>,
     pub custom_actions: Vec<SemanticsCustomAction>,
 }
 
 impl SemanticsUpdate {
-    pub(crate) fn from_raw(raw: &sys::FlutterSemanticsUpdate2) -> Self {
-        Self {
-            nodes: unsafe { crate::util::slice_from_raw_parts_with_invalid_empty(raw.nodes, raw.node_count) }
+    sys_pub! {
+        /// # Safety
+        ///
+        /// `raw` must be valid like when the engine passes it: every pointer in it must be valid,
+        /// and any union in it must hold the variant its type says.
+        unsafe fn from_raw(raw: &sys::FlutterSemanticsUpdate2) -> Self {
+            Self {
+                nodes: unsafe {
+                    crate::util::slice_from_raw_parts_with_invalid_empty(raw.nodes, raw.node_count)
+                }
+                .iter()
+                .map(|&raw| unsafe { SemanticsNode::from_raw(&*raw) })
+                .collect(),
+                custom_actions: unsafe {
+                    crate::util::slice_from_raw_parts_with_invalid_empty(
+                        raw.custom_actions,
+                        raw.custom_action_count,
+                    )
+                }
                 .iter()
-                .copied()
-                .map(|raw| unsafe { &*raw })
-                .map(SemanticsNode::from_raw)
+                .map(|&raw| unsafe { SemanticsCustomAction::from_raw(&*raw) })
                 .collect(),
-            custom_actions: unsafe {
-                crate::util::slice_from_raw_parts_with_invalid_empty(raw.custom_actions, raw.custom_action_count)
             }
-            .iter()
-            .copied()
-            .map(|raw| unsafe { &*raw })
-            .map(SemanticsCustomAction::from_raw)
-            .collect(),
+        }
+    }
+}
+
+/// Which way to scroll a node, for [`Engine::semantics_scroll`].
+///
+/// These are named like the swipes of [`SemanticsAction::ScrollUp`] and friends:
+/// scrolling up reveals what is further down, like swiping up on a touch screen.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl From<ScrollDirection> for SemanticsAction {
+    fn from(direction: ScrollDirection) -> Self {
+        match direction {
+            ScrollDirection::Up => SemanticsAction::ScrollUp,
+            ScrollDirection::Down => SemanticsAction::ScrollDown,
+            ScrollDirection::Left => SemanticsAction::ScrollLeft,
+            ScrollDirection::Right => SemanticsAction::ScrollRight,
+        }
+    }
+}
+
+/// How far to move the cursor in a text field, for [`Engine::semantics_move_cursor`].
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum CursorMovement {
+    ForwardByCharacter,
+    BackwardByCharacter,
+    ForwardByWord,
+    BackwardByWord,
+}
+
+impl From<CursorMovement> for SemanticsAction {
+    fn from(movement: CursorMovement) -> Self {
+        match movement {
+            CursorMovement::ForwardByCharacter => SemanticsAction::MoveCursorForwardByCharacter,
+            CursorMovement::BackwardByCharacter => SemanticsAction::MoveCursorBackwardByCharacter,
+            CursorMovement::ForwardByWord => SemanticsAction::MoveCursorForwardByWord,
+            CursorMovement::BackwardByWord => SemanticsAction::MoveCursorBackwardByWord,
         }
     }
 }
 
 impl Engine {
+    /// Scrolls a node by `amount` steps, each of which is one scroll action (usually most of a page).
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    pub fn semantics_scroll(
+        &mut self,
+        node_id: u64,
+        direction: ScrollDirection,
+        amount: u32,
+    ) -> crate::Result<()> {
+        for _ in 0..amount {
+            self.dispatch_semantics_action(node_id, direction.into(), &[])?;
+        }
+        Ok(())
+    }
+
+    /// Increases (for a positive `delta`) or decreases (for a negative one) the value of a node, like a slider, by `|delta|` steps.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    pub fn semantics_adjust(&mut self, node_id: u64, delta: i32) -> crate::Result<()> {
+        let action = if delta < 0 {
+            SemanticsAction::Decrease
+        } else {
+            SemanticsAction::Increase
+        };
+        for _ in 0..delta.unsigned_abs() {
+            self.dispatch_semantics_action(node_id, action, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Moves the cursor of a text field, extending the selection along with it if `extend_selection` is set.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    pub fn semantics_move_cursor(
+        &mut self,
+        node_id: u64,
+        movement: CursorMovement,
+        extend_selection: bool,
+    ) -> crate::Result<()> {
+        let data = StandardValue::Bool(extend_selection).encode();
+        self.dispatch_semantics_action(node_id, movement.into(), &data)
+    }
+
+    /// Selects the text between `base` and `extent` (in UTF-16 code units) of a text field.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    pub fn semantics_set_selection(
+        &mut self,
+        node_id: u64,
+        base: i64,
+        extent: i64,
+    ) -> crate::Result<()> {
+        let data = StandardValue::Map(vec![
+            ("base".into(), StandardValue::Int(base)),
+            ("extent".into(), StandardValue::Int(extent)),
+        ])
+        .encode();
+        self.dispatch_semantics_action(node_id, SemanticsAction::SetSelection, &data)
+    }
+
+    /// Replaces the text of a text field.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self, text))
+    )]
+    pub fn semantics_set_text(&mut self, node_id: u64, text: &str) -> crate::Result<()> {
+        let data = StandardValue::from(text).encode();
+        self.dispatch_semantics_action(node_id, SemanticsAction::SetText, &data)
+    }
+
+    /// Invokes one of the [`SemanticsCustomAction`]s of a node.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    pub fn semantics_custom_action(&mut self, node_id: u64, action_id: i32) -> crate::Result<()> {
+        let data = StandardValue::Int(action_id.into()).encode();
+        self.dispatch_semantics_action(node_id, SemanticsAction::CustomAction, &data)
+    }
+
     /// Enable or disable accessibility semantics.
     ///
     /// When enabled, changes to the semantic contents of the window are sent via the
     /// [`EngineHandler::update_semantics`] callback passed in [`FlutterProjectArgs`].
+    ///
+    /// The value is remembered, as [`Engine::semantics_enabled`].
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     pub fn update_semantics_enabled(&mut self, enabled: bool) -> crate::Result<()> {
-        unsafe { sys::UpdateSemanticsEnabled(self.inner.engine, enabled) }.to_result()
+        unsafe { sys::UpdateSemanticsEnabled(self.inner.engine, enabled) }.to_result()?;
+        // an `EngineContext` keeps track of it on its own
+        if let Ok(user_data) = self.user_data() {
+            user_data
+                .semantics_enabled
+                .store(enabled, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    /// Whether semantics were last enabled or disabled with [`Engine::update_semantics_enabled`],
+    /// here or through an [`crate::EngineContext`]. They start out disabled.
+    ///
+    /// Anything that needs semantics (an accessibility bridge, a test driver, a debug overlay) should check this
+    /// before changing it, so that one doesn't disable them while another still relies on them.
+    #[must_use]
+    pub fn semantics_enabled(&self) -> bool {
+        self.user_data()
+            .is_ok_and(|user_data| user_data.semantics_enabled.load(Ordering::Acquire))
     }
 
     /// Sets additional accessibility features.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     pub fn update_accessibility_features(
         &mut self,
         features: AccessibilityFeature,
@@ -465,6 +669,7 @@ impl Engine {
     }
 
     /// Dispatch a semantics action to the specified semantics node.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip(self, data), fields(bytes = data.len())))]
     pub fn dispatch_semantics_action(
         &mut self,
         node_id: u64,
@@ -0,0 +1,663 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    ffi::CStr,
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use ::smithay::{
+    backend::{
+        egl::{self, EGLContext},
+        input::{ButtonState, KeyState},
+        renderer::{
+            element::{
+                texture::{TextureRenderBuffer, TextureRenderElement},
+                Kind,
+            },
+            gles::{ffi, GlesRenderer, GlesTexture},
+        },
+    },
+    input::{
+        keyboard::KeysymHandle,
+        pointer::{AxisFrame, ButtonEvent, MotionEvent},
+    },
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Size, Transform},
+};
+use tracing::{error, warn};
+
+use crate::{
+    BackingStore, BackingStoreConfig, Compositor, CompositorHandler, FrameInfo, KeyEvent,
+    KeyboardState, Layer, LayerContent, OpenGLBackingStore, OpenGLFramebuffer,
+    OpenGLRendererHandler, OpenGLTexture, PointerButtons, PointerEvent, PointerState, PresentInfo,
+    Region, ViewId,
+};
+
+/// The engine's GL contexts, sharing resources with a [`GlesRenderer`].
+///
+/// Use this as the handler of an [`crate::OpenGLRendererConfig`], together with the [`Compositor`] from [`FlutterTextures::compositor`].
+/// The engine never renders to a window surface; everything goes through the compositor.
+pub struct FlutterGlContext {
+    context: EGLContext,
+    resource_context: EGLContext,
+}
+
+impl fmt::Debug for FlutterGlContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlutterGlContext").finish_non_exhaustive()
+    }
+}
+
+impl FlutterGlContext {
+    pub fn new(renderer: &GlesRenderer) -> Result<Self, egl::Error> {
+        let shared = renderer.egl_context();
+        let display = shared.display();
+        Ok(Self {
+            context: EGLContext::new_shared(display, shared)?,
+            resource_context: EGLContext::new_shared(display, shared)?,
+        })
+    }
+}
+
+impl OpenGLRendererHandler for FlutterGlContext {
+    fn make_current(&mut self) -> bool {
+        unsafe { self.context.make_current() }
+            .inspect_err(|err| error!("failed to make the flutter context current: {err}"))
+            .is_ok()
+    }
+
+    fn clear_current(&mut self) -> bool {
+        self.context.unbind().is_ok()
+    }
+
+    fn present(&mut self, present_info: PresentInfo) -> bool {
+        // only called when rendering to the window surface, which we don't have
+        let _ = present_info;
+        true
+    }
+
+    fn fbo_callback(&mut self, frame_info: FrameInfo) -> u32 {
+        let _ = frame_info;
+        0
+    }
+
+    fn make_resource_current(&mut self) -> bool {
+        unsafe { self.resource_context.make_current() }.is_ok()
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // the engine always passes a valid C string
+    fn gl_proc_resolver(&mut self, name: *const std::os::raw::c_char) -> *mut std::ffi::c_void {
+        let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+            return std::ptr::null_mut();
+        };
+        unsafe { egl::get_proc_address(name) }.cast_mut()
+    }
+
+    fn gl_external_texture_frame(
+        &mut self,
+        texture_id: i64,
+        width: usize,
+        height: usize,
+    ) -> Option<OpenGLTexture> {
+        let _ = (texture_id, width, height);
+        None
+    }
+
+    fn populate_existing_damage(&mut self, fbo_id: isize) -> Region {
+        let _ = fbo_id;
+        Region {
+            regions: Vec::new(),
+        }
+    }
+}
+
+/// The color format of the textures and framebuffers that the compositor of [`FlutterTextures`] allocates for the engine.
+///
+/// Only backing stores allocated here can be sRGB. Window-bound framebuffers, like those of the `window_handle` module,
+/// get their format from the surface.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    /// `GL_RGBA8`. Blending happens on the encoded values, like the engine does without a compositor.
+    #[default]
+    Rgba8,
+    /// `GL_SRGB8_ALPHA8`. The texture holds sRGB-encoded colors, but sampling it decodes them to linear ones,
+    /// for a renderer that blends in linear space. Needs GLES 3 or `GL_EXT_sRGB`.
+    Srgb8Alpha8,
+}
+
+impl TextureFormat {
+    /// The sized internal format, as in [`OpenGLTexture::format`] and [`OpenGLFramebuffer::format`].
+    #[must_use]
+    pub fn gl_format(self) -> u32 {
+        match self {
+            Self::Rgba8 => ffi::RGBA8,
+            Self::Srgb8Alpha8 => ffi::SRGB8_ALPHA8,
+        }
+    }
+}
+
+struct PresentedLayer {
+    texture: u32,
+    format: u32,
+    size: Size<i32, Buffer>,
+    offset: Point<f64, Physical>,
+}
+
+struct PresentedFrame {
+    serial: u64,
+    layers: Vec<PresentedLayer>,
+}
+
+#[derive(Default)]
+struct Shared {
+    serial: u64,
+    views: HashMap<ViewId, PresentedFrame>,
+    // textures the engine is done with, but which may still be on screen
+    collected: HashSet<u32>,
+}
+
+/// A multisampled framebuffer that the engine renders into, and the texture it's resolved into for presenting.
+struct MsaaTarget {
+    renderbuffer: u32,
+    resolve_framebuffer: u32,
+    texture: u32,
+    width: i32,
+    height: i32,
+}
+
+/// The engine's half of [`FlutterTextures`]. It runs on the raster thread.
+struct FlutterCompositor {
+    gl: Mutex<ffi::Gles2>,
+    /// More than 1 to render into multisampled framebuffers instead of textures.
+    samples: i32,
+    format: TextureFormat,
+    /// By the name of the multisampled framebuffer.
+    msaa_targets: HashMap<u32, MsaaTarget>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl FlutterCompositor {
+    /// Attaches `texture` to a new framebuffer to resolve into, and renders into a multisampled one instead.
+    fn create_msaa_target(
+        &mut self,
+        texture: u32,
+        width: i32,
+        height: i32,
+    ) -> Option<OpenGLFramebuffer> {
+        let gl = self.gl.lock().unwrap_or_else(PoisonError::into_inner);
+        let (mut framebuffer, mut renderbuffer, mut resolve_framebuffer) = (0, 0, 0);
+        let complete = unsafe {
+            gl.GenFramebuffers(1, &raw mut resolve_framebuffer);
+            gl.BindFramebuffer(ffi::FRAMEBUFFER, resolve_framebuffer);
+            gl.FramebufferTexture2D(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::TEXTURE_2D,
+                texture,
+                0,
+            );
+
+            gl.GenRenderbuffers(1, &raw mut renderbuffer);
+            gl.BindRenderbuffer(ffi::RENDERBUFFER, renderbuffer);
+            gl.RenderbufferStorageMultisample(
+                ffi::RENDERBUFFER,
+                self.samples,
+                self.format.gl_format(),
+                width,
+                height,
+            );
+            gl.BindRenderbuffer(ffi::RENDERBUFFER, 0);
+
+            gl.GenFramebuffers(1, &raw mut framebuffer);
+            gl.BindFramebuffer(ffi::FRAMEBUFFER, framebuffer);
+            gl.FramebufferRenderbuffer(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::RENDERBUFFER,
+                renderbuffer,
+            );
+            let complete = gl.CheckFramebufferStatus(ffi::FRAMEBUFFER) == ffi::FRAMEBUFFER_COMPLETE;
+            gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+            complete
+        };
+
+        if !complete {
+            error!("{}x MSAA framebuffer is incomplete", self.samples);
+            unsafe {
+                gl.DeleteFramebuffers(1, &raw const framebuffer);
+                gl.DeleteFramebuffers(1, &raw const resolve_framebuffer);
+                gl.DeleteRenderbuffers(1, &raw const renderbuffer);
+                gl.DeleteTextures(1, &raw const texture);
+            }
+            return None;
+        }
+
+        drop(gl);
+        self.msaa_targets.insert(
+            framebuffer,
+            MsaaTarget {
+                renderbuffer,
+                resolve_framebuffer,
+                texture,
+                width,
+                height,
+            },
+        );
+        Some(OpenGLFramebuffer {
+            format: self.format.gl_format(),
+            name: framebuffer,
+        })
+    }
+}
+
+impl CompositorHandler for FlutterCompositor {
+    #[allow(clippy::cast_possible_wrap)] // GL takes enums as GLint in some places
+    fn create_backing_store(&mut self, config: BackingStoreConfig) -> Option<BackingStore> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (width, height) = (
+            config.size.width.ceil() as i32,
+            config.size.height.ceil() as i32,
+        );
+
+        let gl = self.gl.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut texture = 0;
+        // the engine's context is current on this thread whenever it calls the compositor
+        unsafe {
+            gl.GenTextures(1, &raw mut texture);
+            gl.BindTexture(ffi::TEXTURE_2D, texture);
+            gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_MIN_FILTER, ffi::LINEAR as i32);
+            gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_MAG_FILTER, ffi::LINEAR as i32);
+            gl.TexParameteri(
+                ffi::TEXTURE_2D,
+                ffi::TEXTURE_WRAP_S,
+                ffi::CLAMP_TO_EDGE as i32,
+            );
+            gl.TexParameteri(
+                ffi::TEXTURE_2D,
+                ffi::TEXTURE_WRAP_T,
+                ffi::CLAMP_TO_EDGE as i32,
+            );
+            gl.TexImage2D(
+                ffi::TEXTURE_2D,
+                0,
+                self.format.gl_format() as i32,
+                width,
+                height,
+                0,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl.BindTexture(ffi::TEXTURE_2D, 0);
+        }
+        drop(gl);
+
+        if self.samples > 1 {
+            return self
+                .create_msaa_target(texture, width, height)
+                .map(|framebuffer| {
+                    BackingStore::OpenGL(OpenGLBackingStore::Framebuffer(framebuffer))
+                });
+        }
+
+        #[allow(clippy::cast_sign_loss)]
+        Some(BackingStore::OpenGL(OpenGLBackingStore::Texture(
+            OpenGLTexture {
+                target: ffi::TEXTURE_2D,
+                name: texture,
+                format: self.format.gl_format(),
+                width: width as usize,
+                height: height as usize,
+            },
+        )))
+    }
+
+    fn collect_backing_store(&mut self, backing_store: BackingStore) -> bool {
+        let texture = match backing_store {
+            BackingStore::OpenGL(OpenGLBackingStore::Texture(texture)) => texture.name,
+            BackingStore::OpenGL(OpenGLBackingStore::Framebuffer(framebuffer)) => {
+                let target = self
+                    .msaa_targets
+                    .remove(&framebuffer.name)
+                    .expect("FlutterCompositor created this framebuffer");
+                let gl = self.gl.lock().unwrap_or_else(PoisonError::into_inner);
+                unsafe {
+                    gl.DeleteFramebuffers(1, &raw const framebuffer.name);
+                    gl.DeleteFramebuffers(1, &raw const target.resolve_framebuffer);
+                    gl.DeleteRenderbuffers(1, &raw const target.renderbuffer);
+                }
+                target.texture
+            }
+            _ => unreachable!("FlutterCompositor only creates OpenGL textures and framebuffers"),
+        };
+        // the texture is deleted on the main thread, once it's no longer on screen
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .collected
+            .insert(texture);
+        true
+    }
+
+    fn present_view(&mut self, view_id: ViewId, layers: &[Layer]) -> bool {
+        let gl = self.gl.lock().unwrap_or_else(PoisonError::into_inner);
+        // resolve the multisampled layers into their textures
+        for layer in layers {
+            if let LayerContent::BackingStore(
+                BackingStore::OpenGL(OpenGLBackingStore::Framebuffer(framebuffer)),
+                _,
+            ) = &layer.content
+            {
+                let target = &self.msaa_targets[&framebuffer.name];
+                unsafe {
+                    gl.BindFramebuffer(ffi::READ_FRAMEBUFFER, framebuffer.name);
+                    gl.BindFramebuffer(ffi::DRAW_FRAMEBUFFER, target.resolve_framebuffer);
+                    gl.BlitFramebuffer(
+                        0,
+                        0,
+                        target.width,
+                        target.height,
+                        0,
+                        0,
+                        target.width,
+                        target.height,
+                        ffi::COLOR_BUFFER_BIT,
+                        ffi::NEAREST,
+                    );
+                }
+            }
+        }
+        unsafe {
+            gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+            // make sure the textures are fully rendered before the main thread samples them
+            gl.Finish();
+        }
+        drop(gl);
+
+        let layers = layers
+            .iter()
+            .filter_map(|layer| match &layer.content {
+                LayerContent::BackingStore(
+                    BackingStore::OpenGL(OpenGLBackingStore::Texture(texture)),
+                    _,
+                ) => {
+                    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+                    Some(PresentedLayer {
+                        texture: texture.name,
+                        format: texture.format,
+                        size: Size::from((texture.width as i32, texture.height as i32)),
+                        offset: Point::from((layer.offset.x, layer.offset.y)),
+                    })
+                }
+                LayerContent::BackingStore(
+                    BackingStore::OpenGL(OpenGLBackingStore::Framebuffer(framebuffer)),
+                    _,
+                ) => {
+                    let target = &self.msaa_targets[&framebuffer.name];
+                    Some(PresentedLayer {
+                        texture: target.texture,
+                        format: framebuffer.format,
+                        size: Size::from((target.width, target.height)),
+                        offset: Point::from((layer.offset.x, layer.offset.y)),
+                    })
+                }
+                LayerContent::BackingStore(..) => {
+                    unreachable!("FlutterCompositor only creates OpenGL textures and framebuffers")
+                }
+                LayerContent::PlatformView(view) => {
+                    warn!(
+                        "platform view {} is not supported by FlutterTextures, and will not be shown",
+                        view.identifier
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        shared.serial += 1;
+        let serial = shared.serial;
+        shared
+            .views
+            .insert(view_id, PresentedFrame { serial, layers });
+        true
+    }
+
+    fn view_removed(&mut self, view_id: ViewId) {
+        // its textures are deleted on the main thread, like any others that are no longer on screen
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .views
+            .remove(&view_id);
+    }
+}
+
+/// Shows the engine's views as render elements of a [`GlesRenderer`].
+///
+/// The engine renders each layer into its own texture, on its own thread, in a context shared with the renderer.
+/// This owns those textures on the compositor's side, and turns the latest frame of each view into [`TextureRenderElement`]s.
+///
+/// Textures are only deleted from [`FlutterTextures::render_elements`], so call that regularly (i.e. every frame) for every view.
+/// Platform views are not supported; their layers are skipped.
+pub struct FlutterTextures {
+    shared: Arc<Mutex<Shared>>,
+    buffers: HashMap<u32, TextureRenderBuffer<GlesTexture>>,
+    rendered: HashMap<ViewId, u64>,
+}
+
+impl fmt::Debug for FlutterTextures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlutterTextures")
+            .field("buffers", &self.buffers.keys())
+            .field("rendered", &self.rendered)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FlutterTextures {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::default(),
+            buffers: HashMap::new(),
+            rendered: HashMap::new(),
+        }
+    }
+
+    /// The compositor to pass to the engine in [`crate::ProjectArgs::compositor`].
+    ///
+    /// This must only be called after the renderer has been created, because it loads GL functions through EGL.
+    #[must_use]
+    pub fn compositor(&self) -> Compositor {
+        self.multisampled_compositor(1)
+    }
+
+    /// Like [`FlutterTextures::compositor`], but the engine renders with `samples`x MSAA.
+    ///
+    /// Each layer is rendered into a multisampled renderbuffer, and resolved into its texture when it's presented.
+    /// The elements are the same either way. `samples` must be at most `GL_MAX_SAMPLES`; 1 (or 0) means no MSAA.
+    #[must_use]
+    pub fn multisampled_compositor(&self, samples: u32) -> Compositor {
+        self.compositor_with_format(samples, TextureFormat::Rgba8)
+    }
+
+    /// Like [`FlutterTextures::multisampled_compositor`], but the engine renders into textures of the given `format`.
+    ///
+    /// The elements' textures have the same format, so a renderer that blends in linear space
+    /// should use [`TextureFormat::Srgb8Alpha8`] to have them decoded when it samples them.
+    #[must_use]
+    pub fn compositor_with_format(&self, samples: u32, format: TextureFormat) -> Compositor {
+        let gl = ffi::Gles2::load_with(|symbol| unsafe { egl::get_proc_address(symbol) });
+        Compositor {
+            // the engine reuses cached backing stores right after presenting them,
+            // which would race with the main thread drawing them. so every frame gets new ones.
+            avoid_backing_store_cache: true,
+            handler: Box::new(FlutterCompositor {
+                gl: Mutex::new(gl),
+                samples: i32::try_from(samples).unwrap_or(i32::MAX),
+                format,
+                msaa_targets: HashMap::new(),
+                shared: self.shared.clone(),
+            }),
+        }
+    }
+
+    /// The layers of the latest frame of `view_id`, from bottom to top.
+    ///
+    /// `location` is where the view's top left corner goes, in the same space as the elements.
+    pub fn render_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        view_id: ViewId,
+        location: Point<f64, Physical>,
+    ) -> Vec<TextureRenderElement<GlesTexture>> {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        let shared = &mut *shared;
+
+        let on_screen = shared
+            .views
+            .values()
+            .flat_map(|frame| &frame.layers)
+            .map(|layer| layer.texture)
+            .collect::<HashSet<_>>();
+        shared.collected.retain(|texture| {
+            if on_screen.contains(texture) {
+                return true;
+            }
+            // dropping the buffer deletes the texture, once any elements still using it are gone
+            if self.buffers.remove(texture).is_none() {
+                // never rendered, so it was never given to the renderer
+                let _ = renderer.with_context(|gl| unsafe { gl.DeleteTextures(1, texture) });
+            }
+            false
+        });
+
+        let Some(frame) = shared.views.get(&view_id) else {
+            return Vec::new();
+        };
+        let is_new = self.rendered.insert(view_id, frame.serial) != Some(frame.serial);
+
+        frame
+            .layers
+            .iter()
+            .map(|layer| {
+                let buffer = self.buffers.entry(layer.texture).or_insert_with(|| {
+                    let texture = unsafe {
+                        GlesTexture::from_raw(
+                            renderer,
+                            Some(layer.format),
+                            false,
+                            layer.texture,
+                            layer.size,
+                        )
+                    };
+                    // the engine renders into textures with a bottom-left origin
+                    TextureRenderBuffer::from_texture(
+                        renderer,
+                        texture,
+                        1,
+                        Transform::Flipped180,
+                        None,
+                    )
+                });
+
+                if is_new {
+                    // the engine doesn't say what changed, so it's all damaged
+                    let _: Result<(), Infallible> = buffer
+                        .render()
+                        .draw(|_| Ok(vec![Rectangle::from_size(layer.size)]));
+                }
+
+                TextureRenderElement::from_texture_render_buffer(
+                    location + layer.offset,
+                    buffer,
+                    None,
+                    None,
+                    None,
+                    Kind::Unspecified,
+                )
+            })
+            .collect()
+    }
+}
+
+impl Default for FlutterTextures {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn timestamp(time_msec: u32) -> Duration {
+    // smithay's input timestamps come from libinput (or winit), which use CLOCK_MONOTONIC like the engine does.
+    Duration::from_millis(u64::from(time_msec))
+}
+
+/// Translates a pointer motion from a Smithay seat.
+///
+/// `view_origin` is the location of the view in compositor space, and `scale` is the output's scale factor.
+pub fn pointer_motion(
+    pointer: &mut PointerState,
+    event: &MotionEvent,
+    view_origin: Point<f64, Logical>,
+    scale: f64,
+) -> PointerEvent {
+    let location = (event.location - view_origin).to_physical(scale);
+    pointer.motion(timestamp(event.time), location.x, location.y)
+}
+
+/// Translates a pointer button from a Smithay seat, or [`None`] for buttons that Flutter doesn't know about.
+pub fn pointer_button(pointer: &mut PointerState, event: &ButtonEvent) -> Option<PointerEvent> {
+    let button = PointerButtons::from_evdev(event.button)?;
+    let pressed = event.state == ButtonState::Pressed;
+    Some(pointer.button(timestamp(event.time), button, pressed))
+}
+
+/// Translates a frame of axis events from a Smithay seat into a scroll, or [`None`] if it doesn't scroll.
+///
+/// Wheel clicks scroll as far as the [`crate::ScrollConfig`] given to [`PointerState::set_scroll_config`] says.
+/// Continuous scrolls (e.g. touchpads) scroll by the seat's own deltas, except for natural scrolling.
+pub fn pointer_axis(
+    pointer: &mut PointerState,
+    frame: &AxisFrame,
+    scale: f64,
+) -> Option<PointerEvent> {
+    let time = timestamp(frame.time);
+    if let Some((x, y)) = frame.v120.filter(|&v120| v120 != (0, 0)) {
+        // wheel clicks, in 120ths
+        return Some(pointer.scroll_clicks(
+            time,
+            f64::from(x) / 120.0,
+            f64::from(y) / 120.0,
+            scale,
+        ));
+    }
+    let (x, y) = frame.axis;
+    if x == 0.0 && y == 0.0 {
+        return None;
+    }
+    Some(pointer.scroll(time, x * scale, y * scale))
+}
+
+/// Translates a key from a Smithay seat, for use in the filter of `KeyboardHandle::input`.
+pub fn key(
+    keyboard: &mut KeyboardState,
+    keysym: &KeysymHandle<'_>,
+    state: KeyState,
+    time: u32,
+) -> KeyEvent {
+    // xkb keycodes are evdev codes offset by 8
+    let evdev_code = keysym.raw_code().raw() - 8;
+    let unmodified = keysym
+        .raw_latin_sym_or_raw_current_sym()
+        .unwrap_or_else(|| keysym.modified_sym());
+    keyboard.key(
+        timestamp(time),
+        evdev_code,
+        unmodified.raw(),
+        unmodified.key_char(),
+        keysym.modified_sym().key_char(),
+        state == KeyState::Pressed,
+    )
+}
@@ -0,0 +1,278 @@
+//! Software rendering for embedders with more than one view.
+//!
+//! [`SoftwareRendererHandler::surface_present`] doesn't say which view a frame is for, so it only works for the implicit view.
+//! [`SoftwareViews`] renders through a compositor instead, which gets the view of every frame,
+//! and flattens the layers of each view into a CPU buffer of its own:
+//!
+//! ```ignore
+//! let views = SoftwareViews::new(move |view_id| {
+//!     // wake the window of `view_id`, which draws `views.frame(view_id)`
+//! });
+//! let engine = Engine::run(views.renderer(), ProjectArgs {
+//!     compositor: Some(views.compositor()),
+//!     ..project_args()
+//! })?;
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use tracing::warn;
+
+use crate::{
+    BackingStore, BackingStoreConfig, Compositor, CompositorHandler, Layer, LayerContent, Size,
+    SoftwareBackingStore, SoftwarePixelFormat, SoftwareRendererConfig, SoftwareRendererHandler,
+    ViewId,
+};
+
+/// Everything the engine presented for a view, flattened into one image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoftwareFrame {
+    /// Increases with every frame presented, across all views.
+    pub serial: u64,
+    /// The size of the frame, in physical pixels. It covers every layer of the view.
+    pub size: Size<u32>,
+    /// The pixels as tightly packed RGBA8888 with premultiplied alpha, without any row padding.
+    pub pixels: Vec<u8>,
+}
+
+type FramePresented = dyn Fn(ViewId) + Send + Sync;
+
+#[derive(Default)]
+struct Shared {
+    serial: u64,
+    views: HashMap<ViewId, Arc<SoftwareFrame>>,
+}
+
+/// The renderer that goes with the [`SoftwareCompositor`].
+///
+/// The engine never presents to a surface; everything goes through the compositor.
+struct SoftwareViewsRenderer;
+
+impl SoftwareRendererHandler for SoftwareViewsRenderer {
+    fn surface_present(&mut self, allocation: *const u8, row_bytes: usize, height: usize) -> bool {
+        let _ = (allocation, row_bytes, height);
+        unreachable!("the engine only calls surface_present without a compositor")
+    }
+}
+
+/// The engine's half of [`SoftwareViews`]. It runs on the raster thread.
+struct SoftwareCompositor {
+    /// The backing stores the engine hasn't collected yet, by their address.
+    allocations: HashMap<usize, Vec<u8>>,
+    shared: Arc<Mutex<Shared>>,
+    presented: Arc<FramePresented>,
+}
+
+impl CompositorHandler for SoftwareCompositor {
+    fn create_backing_store(&mut self, config: BackingStoreConfig) -> Option<BackingStore> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (width, height) = (
+            config.size.width.ceil() as usize,
+            config.size.height.ceil() as usize,
+        );
+        let row_bytes = width * 4;
+        let mut allocation = vec![0; row_bytes * height];
+        let pointer = allocation.as_mut_ptr();
+        // moving the vec doesn't move its contents
+        self.allocations.insert(pointer as usize, allocation);
+        Some(BackingStore::Software(SoftwareBackingStore {
+            allocation: pointer,
+            row_bytes,
+            height,
+            pixel_format: SoftwarePixelFormat::RGBA8888,
+        }))
+    }
+
+    fn collect_backing_store(&mut self, backing_store: BackingStore) -> bool {
+        let BackingStore::Software(backing_store) = backing_store else {
+            unreachable!("SoftwareCompositor only creates software backing stores")
+        };
+        self.allocations
+            .remove(&(backing_store.allocation as usize))
+            .is_some()
+    }
+
+    fn present_view(&mut self, view_id: ViewId, layers: &[Layer]) -> bool {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let size = layers.iter().fold(
+            Size {
+                width: 0,
+                height: 0,
+            },
+            |size: Size<u32>, layer| Size {
+                width: size
+                    .width
+                    .max((layer.offset.x + layer.size.width).ceil() as u32),
+                height: size
+                    .height
+                    .max((layer.offset.y + layer.size.height).ceil() as u32),
+            },
+        );
+        let mut pixels = vec![0; size.width as usize * size.height as usize * 4];
+
+        for layer in layers {
+            match &layer.content {
+                LayerContent::BackingStore(BackingStore::Software(backing_store), _) => {
+                    let source = unsafe {
+                        std::slice::from_raw_parts(
+                            backing_store.allocation,
+                            backing_store.row_bytes * backing_store.height,
+                        )
+                    };
+                    blend(&mut pixels, size, source, backing_store, layer);
+                }
+                LayerContent::BackingStore(..) => {
+                    unreachable!("SoftwareCompositor only creates software backing stores")
+                }
+                LayerContent::PlatformView(view) => {
+                    warn!(
+                        "platform view {} is not supported by SoftwareViews, and will not be shown",
+                        view.identifier
+                    );
+                }
+            }
+        }
+
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        shared.serial += 1;
+        let serial = shared.serial;
+        shared.views.insert(
+            view_id,
+            Arc::new(SoftwareFrame {
+                serial,
+                size,
+                pixels,
+            }),
+        );
+        drop(shared);
+
+        (self.presented)(view_id);
+        true
+    }
+
+    fn view_removed(&mut self, view_id: ViewId) {
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .views
+            .remove(&view_id);
+    }
+}
+
+/// Draws a premultiplied RGBA layer over the frame, at the layer's offset (rounded to whole pixels).
+#[allow(clippy::cast_possible_wrap)] // backing stores are nowhere near that large
+fn blend(
+    pixels: &mut [u8],
+    size: Size<u32>,
+    source: &[u8],
+    backing_store: &SoftwareBackingStore,
+    layer: &Layer,
+) {
+    #[allow(clippy::cast_possible_truncation)]
+    let (x, y) = (layer.offset.x.round() as i64, layer.offset.y.round() as i64);
+    let width = backing_store.row_bytes / 4;
+    for row in 0..backing_store.height {
+        let Ok(target_y) = usize::try_from(y + row as i64) else {
+            continue;
+        };
+        if target_y >= size.height as usize {
+            break;
+        }
+        for column in 0..width {
+            let Ok(target_x) = usize::try_from(x + column as i64) else {
+                continue;
+            };
+            if target_x >= size.width as usize {
+                break;
+            }
+            let from = row * backing_store.row_bytes + column * 4;
+            let to = (target_y * size.width as usize + target_x) * 4;
+            let src = &source[from..from + 4];
+            let dst = &mut pixels[to..to + 4];
+            let inverse_alpha = 255 - u16::from(src[3]);
+            for (dst, &src) in dst.iter_mut().zip(src) {
+                // source over, for premultiplied colors
+                #[allow(clippy::cast_possible_truncation)]
+                let blended = u16::from(src) + (u16::from(*dst) * inverse_alpha + 127) / 255;
+                *dst = blended.min(255) as u8;
+            }
+        }
+    }
+}
+
+/// Renders every view of the engine in software, into a CPU buffer per view.
+///
+/// Pass [`SoftwareViews::renderer`] and [`SoftwareViews::compositor`] to the engine.
+/// Then draw [`SoftwareViews::frame`] for a view whenever the engine presents a new one.
+///
+/// Cloning this gives another handle to the same frames, e.g. for another window's thread.
+/// Platform views are not supported; their layers are skipped.
+#[derive(Clone)]
+pub struct SoftwareViews {
+    shared: Arc<Mutex<Shared>>,
+    presented: Arc<FramePresented>,
+}
+
+impl fmt::Debug for SoftwareViews {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoftwareViews").finish_non_exhaustive()
+    }
+}
+
+impl SoftwareViews {
+    /// `presented` is called on the raster thread after the engine presents a frame for a view.
+    pub fn new(presented: impl Fn(ViewId) + Send + Sync + 'static) -> Self {
+        Self {
+            shared: Arc::default(),
+            presented: Arc::new(presented),
+        }
+    }
+
+    /// The renderer to pass to the engine in [`crate::ProjectArgs::renderer`].
+    #[must_use]
+    pub fn renderer(&self) -> SoftwareRendererConfig {
+        SoftwareRendererConfig {
+            handler: Box::new(SoftwareViewsRenderer),
+        }
+    }
+
+    /// The compositor to pass to the engine in [`crate::ProjectArgs::compositor`].
+    #[must_use]
+    pub fn compositor(&self) -> Compositor {
+        Compositor {
+            // every frame is copied out of the backing stores when it's presented, so they can be reused right away
+            avoid_backing_store_cache: false,
+            handler: Box::new(SoftwareCompositor {
+                allocations: HashMap::new(),
+                shared: self.shared.clone(),
+                presented: self.presented.clone(),
+            }),
+        }
+    }
+
+    /// The latest frame the engine presented for `view_id`, if any.
+    #[must_use]
+    pub fn frame(&self, view_id: ViewId) -> Option<Arc<SoftwareFrame>> {
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .views
+            .get(&view_id)
+            .cloned()
+    }
+
+    /// Forgets the frame of a view that no longer exists.
+    ///
+    /// Views removed with [`crate::Engine::remove_view`] are forgotten on their own.
+    pub fn remove_view(&self, view_id: ViewId) {
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .views
+            .remove(&view_id);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{JoinHandle, ThreadId},
+    time::Duration,
+};
+
+use tracing::{error, warn};
+
+use crate::{
+    task_runners::TaskQueue, Clock, CustomTaskRunners, Engine, EngineClock, ProjectArgs,
+    RendererConfig, Task, TaskRunnerDescription, TaskRunnerHandler,
+};
+
+enum Message {
+    Task(Duration, Task),
+    Run(Box<dyn FnOnce(&mut Engine) + Send>),
+    Shutdown,
+}
+
+struct SpawnedTaskRunner {
+    thread: ThreadId,
+    sender: Sender<Message>,
+}
+
+impl TaskRunnerHandler for SpawnedTaskRunner {
+    fn runs_task_on_current_thread(&self) -> bool {
+        std::thread::current().id() == self.thread
+    }
+
+    fn post_task(&self, target_time: Duration, task: Task) {
+        if self.sender.send(Message::Task(target_time, task)).is_err() {
+            warn!("a platform task was posted after the engine thread stopped");
+        }
+    }
+}
+
+/// An engine running on its own thread, from [`Engine::spawn`]. This can be sent to and shared between threads.
+///
+/// Dropping it shuts the engine down, without waiting for it.
+#[derive(Debug)]
+pub struct EngineHandle {
+    sender: Sender<Message>,
+    thread: Option<JoinHandle<crate::Result<()>>>,
+}
+
+impl EngineHandle {
+    /// Runs `f` with the engine, on its thread. Returns `false` if the engine thread has stopped.
+    pub fn post(&self, f: impl FnOnce(&mut Engine) + Send + 'static) -> bool {
+        self.sender.send(Message::Run(Box::new(f))).is_ok()
+    }
+
+    /// Runs `f` with the engine, on its thread, and waits for the result.
+    /// Returns `None` if the engine thread has stopped.
+    ///
+    /// This must not be called from the engine's own thread (e.g. in an [`crate::EngineHandler`]), which would wait forever.
+    pub fn call<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Engine) -> R + Send + 'static,
+    ) -> Option<R> {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let posted = self.post(move |engine| {
+            let _ = sender.send(f(engine));
+        });
+        if !posted {
+            return None;
+        }
+        receiver.recv().ok()
+    }
+
+    /// Whether the engine thread has stopped, e.g. because running a task failed.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Shuts the engine down, and waits for its thread to finish.
+    ///
+    /// Returns the error that stopped the thread early, if any.
+    ///
+    /// # Panics
+    ///
+    /// If the engine thread panicked.
+    pub fn shutdown(mut self) -> crate::Result<()> {
+        let _ = self.sender.send(Message::Shutdown);
+        let thread = self.thread.take().expect("only taken here");
+        thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+impl Drop for EngineHandle {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Shutdown);
+    }
+}
+
+impl Engine {
+    /// Runs an engine on a new thread, for applications whose main thread can't be given to Flutter.
+    ///
+    /// `make` runs on the new thread, and returns the renderer config and project args to run the engine with:
+    ///
+    /// ```ignore
+    /// let handle = Engine::spawn(|| (renderer(), project_args()))?;
+    /// handle.post(|engine| engine.send_window_metrics_event(metrics).unwrap());
+    /// ```
+    ///
+    /// They're made on that thread, so their handlers don't need to be [`Send`].
+    /// That thread is the platform thread: every [`crate::EngineHandler`] callback that gets an [`crate::EngineContext`] runs there.
+    /// Its platform task runner is provided by this; `custom_task_runners` must not set one.
+    ///
+    /// Returns once the engine is running, or with the error it failed to run with.
+    ///
+    /// # Panics
+    ///
+    /// If the thread can't be spawned, or `custom_task_runners` has a platform task runner.
+    pub fn spawn<R: Into<RendererConfig>>(
+        make: impl FnOnce() -> (R, ProjectArgs<'static>) + Send + 'static,
+    ) -> crate::Result<EngineHandle> {
+        Self::spawn_with_clock(EngineClock, make)
+    }
+
+    /// Like [`Engine::spawn`], but platform task delays are measured by `clock`, e.g. a [`crate::VirtualClock`] in tests.
+    ///
+    /// The engine thread doesn't notice when a virtual clock is advanced, so it checks again after what's left of a delay.
+    /// Post anything with [`EngineHandle::post`] to have it check right away.
+    ///
+    /// # Panics
+    ///
+    /// If the thread can't be spawned, or `custom_task_runners` has a platform task runner.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
+    pub fn spawn_with_clock<R: Into<RendererConfig>>(
+        clock: impl Clock + 'static,
+        make: impl FnOnce() -> (R, ProjectArgs<'static>) + Send + 'static,
+    ) -> crate::Result<EngineHandle> {
+        let (sender, receiver) = mpsc::channel();
+        let (started_sender, started) = mpsc::sync_channel(1);
+
+        let task_sender = sender.clone();
+        let thread = std::thread::Builder::new()
+            .name("flutter-platform".to_owned())
+            .spawn(move || {
+                let (renderer_config, mut project_args) = make();
+                let platform_task_runner = TaskRunnerDescription {
+                    identifier: 1,
+                    handler: Box::new(SpawnedTaskRunner {
+                        thread: std::thread::current().id(),
+                        sender: task_sender,
+                    }),
+                };
+                let custom_task_runners =
+                    project_args
+                        .custom_task_runners
+                        .get_or_insert_with(|| CustomTaskRunners {
+                            platform_task_runner: None,
+                            render_task_runner: None,
+                            set_thread_priority: None,
+                        });
+                assert!(
+                    custom_task_runners.platform_task_runner.is_none(),
+                    "Engine::spawn provides its own platform task runner"
+                );
+                custom_task_runners.platform_task_runner = Some(platform_task_runner);
+
+                let engine = match Engine::run(renderer_config, project_args) {
+                    Ok(engine) => engine,
+                    Err(err) => {
+                        let _ = started_sender.send(Err(err));
+                        return Ok(());
+                    }
+                };
+                let _ = started_sender.send(Ok(()));
+                run_message_loop(engine, TaskQueue::new(Arc::new(clock)), &receiver)
+            })
+            .expect("failed to spawn the engine thread");
+
+        // the thread always reports back before doing anything else, unless it panicked
+        if let Ok(result) = started.recv() {
+            result?;
+        } else {
+            let panic = thread
+                .join()
+                .expect_err("the thread reports back unless it panics");
+            std::panic::resume_unwind(panic);
+        }
+        Ok(EngineHandle {
+            sender,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn run_message_loop(
+    mut engine: Engine,
+    mut tasks: TaskQueue,
+    receiver: &Receiver<Message>,
+) -> crate::Result<()> {
+    loop {
+        let next_task = match tasks.run_due(&mut engine) {
+            Ok(next_task) => next_task,
+            Err(err) => {
+                error!("failed to run a platform task, stopping the engine thread: {err}");
+                return Err(err);
+            }
+        };
+        let message = match next_task {
+            Some(wait) => match receiver.recv_timeout(wait) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            },
+            None => match receiver.recv() {
+                Ok(message) => message,
+                Err(_) => return Ok(()),
+            },
+        };
+        match message {
+            Message::Task(target_time, task) => tasks.push(target_time, task),
+            Message::Run(f) => f(&mut engine),
+            Message::Shutdown => return Ok(()),
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Flutter's `StandardMessageCodec`, which most of the framework's platform channels use.
+
+/// A value that can be sent with the `StandardMessageCodec`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StandardValue {
+    Null,
+    Bool(bool),
+    /// Both 32-bit and 64-bit integers; they're encoded as whichever fits.
+    Int(i64),
+    /// An integer too large for 64 bits, as a hexadecimal string. Dart never sends these anymore.
+    LargeInt(String),
+    Float(f64),
+    String(String),
+    Uint8List(Vec<u8>),
+    Int32List(Vec<i32>),
+    Int64List(Vec<i64>),
+    Float32List(Vec<f32>),
+    Float64List(Vec<f64>),
+    List(Vec<StandardValue>),
+    Map(Vec<(StandardValue, StandardValue)>),
+}
+
+impl StandardValue {
+    /// Decodes a whole message, which must contain exactly one value.
+    #[must_use]
+    pub fn decode(message: &[u8]) -> Option<Self> {
+        let mut reader = Reader { message, pos: 0 };
+        let value = reader.value()?;
+        (reader.pos == message.len()).then_some(value)
+    }
+
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut message = Vec::new();
+        self.write(&mut message);
+        message
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            StandardValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            StandardValue::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            StandardValue::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Looks up a string key, if this is a map.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&StandardValue> {
+        match self {
+            StandardValue::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            StandardValue::Null => out.push(0),
+            StandardValue::Bool(true) => out.push(1),
+            StandardValue::Bool(false) => out.push(2),
+            &StandardValue::Int(i) => {
+                if let Ok(i) = i32::try_from(i) {
+                    out.push(3);
+                    out.extend_from_slice(&i.to_le_bytes());
+                } else {
+                    out.push(4);
+                    out.extend_from_slice(&i.to_le_bytes());
+                }
+            }
+            StandardValue::LargeInt(s) => {
+                out.push(5);
+                write_bytes(out, s.as_bytes());
+            }
+            StandardValue::Float(f) => {
+                out.push(6);
+                align(out, 8);
+                out.extend_from_slice(&f.to_le_bytes());
+            }
+            StandardValue::String(s) => {
+                out.push(7);
+                write_bytes(out, s.as_bytes());
+            }
+            StandardValue::Uint8List(list) => {
+                out.push(8);
+                write_bytes(out, list);
+            }
+            StandardValue::Int32List(list) => {
+                out.push(9);
+                write_size(out, list.len());
+                align(out, 4);
+                for i in list {
+                    out.extend_from_slice(&i.to_le_bytes());
+                }
+            }
+            StandardValue::Int64List(list) => {
+                out.push(10);
+                write_size(out, list.len());
+                align(out, 8);
+                for i in list {
+                    out.extend_from_slice(&i.to_le_bytes());
+                }
+            }
+            StandardValue::Float64List(list) => {
+                out.push(11);
+                write_size(out, list.len());
+                align(out, 8);
+                for f in list {
+                    out.extend_from_slice(&f.to_le_bytes());
+                }
+            }
+            StandardValue::List(list) => {
+                out.push(12);
+                write_size(out, list.len());
+                for value in list {
+                    value.write(out);
+                }
+            }
+            StandardValue::Map(entries) => {
+                out.push(13);
+                write_size(out, entries.len());
+                for (key, value) in entries {
+                    key.write(out);
+                    value.write(out);
+                }
+            }
+            StandardValue::Float32List(list) => {
+                out.push(14);
+                write_size(out, list.len());
+                align(out, 4);
+                for f in list {
+                    out.extend_from_slice(&f.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+impl From<&str> for StandardValue {
+    fn from(s: &str) -> Self {
+        StandardValue::String(s.to_owned())
+    }
+}
+
+/// A method call, as sent with the `StandardMethodCodec`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StandardMethodCall {
+    pub method: String,
+    pub args: StandardValue,
+}
+
+impl StandardMethodCall {
+    #[must_use]
+    pub fn decode(message: &[u8]) -> Option<Self> {
+        let mut reader = Reader { message, pos: 0 };
+        let StandardValue::String(method) = reader.value()? else {
+            return None;
+        };
+        let args = reader.value()?;
+        (reader.pos == message.len()).then_some(Self { method, args })
+    }
+
+    /// The reply to a method call that succeeded.
+    #[must_use]
+    pub fn success(result: &StandardValue) -> Vec<u8> {
+        let mut reply = vec![0];
+        result.write(&mut reply);
+        reply
+    }
+
+    /// The reply to a method call that failed.
+    #[must_use]
+    pub fn error(code: &str, message: Option<&str>, details: &StandardValue) -> Vec<u8> {
+        let mut reply = vec![1];
+        StandardValue::from(code).write(&mut reply);
+        message
+            .map_or(StandardValue::Null, StandardValue::from)
+            .write(&mut reply);
+        details.write(&mut reply);
+        reply
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_size(out: &mut Vec<u8>, size: usize) {
+    match size {
+        0..254 => out.push(size as u8),
+        254..=0xffff => {
+            out.push(254);
+            out.extend_from_slice(&(size as u16).to_le_bytes());
+        }
+        _ => {
+            out.push(255);
+            out.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_size(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn align(out: &mut Vec<u8>, alignment: usize) {
+    out.resize(out.len().next_multiple_of(alignment), 0);
+}
+
+struct Reader<'a> {
+    message: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.message.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.bytes(N)?.try_into().ok()
+    }
+
+    fn size(&mut self) -> Option<usize> {
+        match self.array::<1>()?[0] {
+            254 => Some(u16::from_le_bytes(self.array()?).into()),
+            255 => usize::try_from(u32::from_le_bytes(self.array()?)).ok(),
+            size => Some(size.into()),
+        }
+    }
+
+    fn align(&mut self, alignment: usize) -> Option<()> {
+        let aligned = self.pos.next_multiple_of(alignment);
+        self.bytes(aligned - self.pos).map(|_| ())
+    }
+
+    fn list<const N: usize, T>(&mut self, from_le_bytes: fn([u8; N]) -> T) -> Option<Vec<T>> {
+        let len = self.size()?;
+        self.align(N)?;
+        (0..len).map(|_| self.array().map(from_le_bytes)).collect()
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.size()?;
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+
+    fn value(&mut self) -> Option<StandardValue> {
+        Some(match self.array::<1>()?[0] {
+            0 => StandardValue::Null,
+            1 => StandardValue::Bool(true),
+            2 => StandardValue::Bool(false),
+            3 => StandardValue::Int(i32::from_le_bytes(self.array()?).into()),
+            4 => StandardValue::Int(i64::from_le_bytes(self.array()?)),
+            5 => StandardValue::LargeInt(self.string()?),
+            6 => {
+                self.align(8)?;
+                StandardValue::Float(f64::from_le_bytes(self.array()?))
+            }
+            7 => StandardValue::String(self.string()?),
+            8 => {
+                let len = self.size()?;
+                StandardValue::Uint8List(self.bytes(len)?.to_vec())
+            }
+            9 => StandardValue::Int32List(self.list(i32::from_le_bytes)?),
+            10 => StandardValue::Int64List(self.list(i64::from_le_bytes)?),
+            11 => StandardValue::Float64List(self.list(f64::from_le_bytes)?),
+            12 => {
+                let len = self.size()?;
+                StandardValue::List((0..len).map(|_| self.value()).collect::<Option<_>>()?)
+            }
+            13 => {
+                let len = self.size()?;
+                StandardValue::Map(
+                    (0..len)
+                        .map(|_| Some((self.value()?, self.value()?)))
+                        .collect::<Option<_>>()?,
+                )
+            }
+            14 => StandardValue::Float32List(self.list(f32::from_le_bytes)?),
+            _ => return None,
+        })
+    }
+}
@@ -0,0 +1,211 @@
+//! Relaunches the engine when it breaks, for deployments that must recover on their own (e.g. kiosks).
+//!
+//! ```ignore
+//! let watch = FatalLogWatch::new();
+//! let mut supervisor = EngineSupervisor::new(|| Engine::run(renderer(), project_args()))?
+//!     .with_log_watch(watch.clone());
+//!
+//! // in the engine's log handler, or wherever the engine's logs end up
+//! watch.observe(line);
+//!
+//! // in the event loop
+//! supervisor.check()?;
+//! supervisor.call(|engine| engine.send_pointer_event(&events))?;
+//! ```
+//!
+//! Like with [`crate::HotReloader`], the engine is started anew by the closure given to [`EngineSupervisor::new`],
+//! so anything that should survive a relaunch (windows, GL contexts) must live outside of the engine's handlers.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use tracing::{error, warn};
+
+use crate::{Engine, ViewId, WindowMetricsEvent};
+
+/// Notices fatal errors in the engine's logs. Cloning this gives another handle to the same watch.
+///
+/// The engine's own `FATAL` logs (e.g. a failed `FML_CHECK`) abort the process right after they're written,
+/// so there is no engine left to relaunch, and only a process supervisor can recover from them.
+/// This only catches fatal errors that are logged without aborting, and those reported with [`FatalLogWatch::trip`].
+#[derive(Debug, Clone, Default)]
+pub struct FatalLogWatch {
+    tripped: Arc<AtomicBool>,
+}
+
+impl FatalLogWatch {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks at one line of logs, tripping the watch on a `[FATAL:...]` line that didn't take the process down with it.
+    pub fn observe(&self, line: &str) {
+        if line.contains("[FATAL:") {
+            self.trip();
+        }
+    }
+
+    /// Marks the engine as broken, for errors that this doesn't recognize on its own.
+    pub fn trip(&self) {
+        self.tripped.store(true, Ordering::Release);
+    }
+
+    /// Whether a fatal error was seen since the last call.
+    #[must_use]
+    pub fn take(&self) -> bool {
+        self.tripped.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// Owns an engine, and relaunches it when an engine call fails with [`crate::Error::InternalInconsistency`]
+/// or its [`FatalLogWatch`] sees a fatal error.
+///
+/// The new engine gets the views and metrics of the old one, and the external textures registered through the supervisor.
+pub struct EngineSupervisor<F: FnMut() -> crate::Result<Engine>> {
+    start_engine: F,
+    engine: Option<Engine>,
+    watch: Option<FatalLogWatch>,
+    /// The metrics of every view of the last engine, kept for when a launch fails and there's no engine to ask.
+    view_metrics: Vec<WindowMetricsEvent>,
+    textures: BTreeSet<i64>,
+    relaunches: u64,
+}
+
+impl<F: FnMut() -> crate::Result<Engine>> fmt::Debug for EngineSupervisor<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EngineSupervisor")
+            .field("engine", &self.engine)
+            .field("watch", &self.watch)
+            .field("view_metrics", &self.view_metrics)
+            .field("textures", &self.textures)
+            .field("relaunches", &self.relaunches)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: FnMut() -> crate::Result<Engine>> EngineSupervisor<F> {
+    /// Starts the engine with `start_engine`, which is called again for every relaunch.
+    pub fn new(mut start_engine: F) -> crate::Result<Self> {
+        let engine = start_engine()?;
+        Ok(Self {
+            start_engine,
+            engine: Some(engine),
+            watch: None,
+            view_metrics: Vec::new(),
+            textures: BTreeSet::new(),
+            relaunches: 0,
+        })
+    }
+
+    /// Relaunches the engine when `watch` sees a fatal error, on the next [`EngineSupervisor::check`].
+    #[must_use]
+    pub fn with_log_watch(mut self, watch: FatalLogWatch) -> Self {
+        self.watch = Some(watch);
+        self
+    }
+
+    /// The running engine, or `None` if the last relaunch failed to start it.
+    pub fn engine(&mut self) -> Option<&mut Engine> {
+        self.engine.as_mut()
+    }
+
+    /// How many times the engine has been relaunched.
+    #[must_use]
+    pub fn relaunches(&self) -> u64 {
+        self.relaunches
+    }
+
+    /// Calls into the engine, relaunching it if the call fails with [`crate::Error::InternalInconsistency`].
+    ///
+    /// The error is still returned, since the call didn't happen; it's up to the caller whether to try it again.
+    /// If the engine isn't running because the last relaunch failed, it's relaunched before the call.
+    pub fn call<R>(&mut self, f: impl FnOnce(&mut Engine) -> crate::Result<R>) -> crate::Result<R> {
+        let engine = match &mut self.engine {
+            Some(engine) => engine,
+            None => self.launch()?,
+        };
+        let result = f(engine);
+        if let Err(crate::Error::InternalInconsistency) = result {
+            error!("the engine is in an inconsistent state, relaunching it");
+            if let Err(err) = self.relaunch() {
+                error!("failed to relaunch the engine: {err}");
+            }
+        }
+        result
+    }
+
+    /// Relaunches the engine if the [`FatalLogWatch`] saw a fatal error, or if the last relaunch failed.
+    /// Call this regularly, e.g. once per iteration of the event loop.
+    ///
+    /// Returns whether the engine was relaunched.
+    pub fn check(&mut self) -> crate::Result<bool> {
+        let fatal = self.watch.as_ref().is_some_and(FatalLogWatch::take);
+        if !fatal && self.engine.is_some() {
+            return Ok(false);
+        }
+        if fatal {
+            error!("the engine logged a fatal error, relaunching it");
+        }
+        self.relaunch()?;
+        Ok(true)
+    }
+
+    /// Shuts the engine down and starts a new one, with the same views, metrics and external textures.
+    pub fn relaunch(&mut self) -> crate::Result<()> {
+        self.launch().map(|_| ())
+    }
+
+    fn launch(&mut self) -> crate::Result<&mut Engine> {
+        // if the last launch failed, the metrics from before it are still here
+        if let Some(engine) = &self.engine {
+            self.view_metrics = engine.all_view_metrics();
+        }
+        // the old engine must be gone before the new one takes over the renderer
+        drop(self.engine.take());
+        self.relaunches += 1;
+
+        let mut engine = (self.start_engine)()?;
+        for &metrics in &self.view_metrics {
+            if metrics.view_id == ViewId::IMPLICIT {
+                engine.send_window_metrics_event(metrics)?;
+                continue;
+            }
+            #[cfg(embedder_add_view)]
+            engine.add_view(metrics.view_id, metrics, move |added| {
+                if !added {
+                    warn!(
+                        "failed to add view {:?} to the relaunched engine",
+                        metrics.view_id
+                    );
+                }
+            })?;
+            #[cfg(not(embedder_add_view))]
+            engine.send_window_metrics_event(metrics)?;
+        }
+        for &texture in &self.textures {
+            engine.register_external_texture(texture)?;
+            engine.mark_external_texture_frame_available(texture)?;
+        }
+        Ok(self.engine.insert(engine))
+    }
+
+    /// Like [`Engine::register_external_texture`], but the texture is registered again after a relaunch.
+    pub fn register_external_texture(&mut self, texture_identifier: i64) -> crate::Result<()> {
+        self.call(|engine| engine.register_external_texture(texture_identifier))?;
+        self.textures.insert(texture_identifier);
+        Ok(())
+    }
+
+    /// Like [`Engine::unregister_external_texture`].
+    pub fn unregister_external_texture(&mut self, texture_identifier: i64) -> crate::Result<()> {
+        self.textures.remove(&texture_identifier);
+        self.call(|engine| engine.unregister_external_texture(texture_identifier))
+    }
+}
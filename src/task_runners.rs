@@ -1,29 +1,48 @@
-use std::time::Duration;
+use std::{fmt, sync::Arc, time::Duration};
 
 use tracing::error;
 
-use crate::{sys, Engine};
+use crate::{engine::pending, sys, util::UserDataPool, Clock, Engine, EngineId};
 
+/// A task posted by the engine, to be given back to it with [`Engine::run_task`].
+///
+/// Tasks are posted from the engine's threads, so they can be sent to the thread they're meant to run on.
+#[derive(Debug)]
 pub struct Task {
-    runner: sys::FlutterTaskRunner,
-    task: u64,
+    runner: TaskRunnerPtr,
+    id: u64,
+    /// The engine that posted it; it can't be run by any other.
+    engine: EngineId,
 }
 
-// TODO: is this safe?
-unsafe impl Send for Task {}
+#[derive(Debug)]
+struct TaskRunnerPtr(sys::FlutterTaskRunner);
+
+// SAFETY: this is only an identifier; it is never dereferenced here.
+// The engine only uses it in `RunTask`, which needs the `Engine` and is called on the task runner's own thread.
+unsafe impl Send for TaskRunnerPtr {}
 
 impl Task {
+    /// A task that doesn't belong to any engine, see [`crate::mock::MockEngine::post_task`].
+    pub(crate) fn mock(task: u64) -> Self {
+        Self {
+            runner: TaskRunnerPtr(std::ptr::null_mut()),
+            id: task,
+            engine: EngineId::NONE,
+        }
+    }
+
     #[must_use]
     pub fn task(&self) -> u64 {
-        self.task
+        self.id
     }
 }
 
 impl From<Task> for sys::FlutterTask {
     fn from(task: Task) -> Self {
         sys::FlutterTask {
-            runner: task.runner,
-            task: task.task,
+            runner: task.runner.0,
+            task: task.id,
         }
     }
 }
@@ -33,9 +52,18 @@ impl Engine {
     /// This task has been given to the engine via the [`TaskRunnerHandler::post_task`].
     /// This call must only be made at the target time specified in that callback.
     /// Running the task before that time is undefined behavior.
+    ///
+    /// Fails with [`crate::Error::InvalidArguments`] if the task was posted by a different engine,
+    /// including one that has since been dropped.
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn run_task(&mut self, task: Task) -> crate::Result<()> {
+        if task.engine != self.inner.id {
+            return Err(crate::Error::InvalidArguments);
+        }
         let task = task.into();
-        unsafe { sys::RunTask(self.inner.engine, &raw const task) }.to_result()
+        unsafe { sys::RunTask(self.inner.engine, &raw const task) }.to_result()?;
+        self.rearm_frame_callbacks();
+        Ok(())
     }
 }
 
@@ -68,10 +96,103 @@ pub struct TaskRunnerDescription {
     pub handler: Box<dyn TaskRunnerHandler>,
 }
 
+impl fmt::Debug for TaskRunnerDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskRunnerDescription")
+            .field("identifier", &self.identifier)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Platform tasks waiting for their target time, for the event loops in this crate.
+///
+/// A task is due once its delay has passed by the [`Clock`], measured from when it was posted.
+/// It never runs before its target time by the engine's clock though, because that's undefined behavior.
+/// So with a [`crate::VirtualClock`], a task posted with a delay runs once the clock has been advanced by that much,
+/// and no earlier than it would with the [`crate::EngineClock`].
+pub(crate) struct TaskQueue {
+    clock: Arc<dyn Clock>,
+    tasks: Vec<QueuedTask>,
+}
+
+struct QueuedTask {
+    target_time: Duration,
+    /// The target time in the queue's [`Clock`].
+    due_at: Duration,
+    task: Task,
+}
+
+impl TaskQueue {
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            tasks: Vec::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Changes the clock, keeping what's left of the delay of every pending task.
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        let (old, new) = (self.clock.now(), clock.now());
+        for queued in &mut self.tasks {
+            queued.due_at = new + queued.due_at.saturating_sub(old);
+        }
+        self.clock = clock;
+    }
+
+    pub(crate) fn push(&mut self, target_time: Duration, task: Task) {
+        let delay = target_time.saturating_sub(Engine::get_current_time());
+        self.tasks.push(QueuedTask {
+            target_time,
+            due_at: self.clock.now() + delay,
+            task,
+        });
+    }
+
+    /// Runs the platform tasks and times out the platform messages that are due.
+    ///
+    /// Returns how long to wait before calling this again, if anything is pending.
+    /// A task that waits for the [`Clock`] is checked again after what's left of its delay,
+    /// or sooner if the event loop wakes up for something else.
+    pub(crate) fn run_due(&mut self, engine: &mut Engine) -> crate::Result<Option<Duration>> {
+        let (engine_now, now) = (Engine::get_current_time(), self.clock.now());
+        let (due, pending) = std::mem::take(&mut self.tasks)
+            .into_iter()
+            .partition::<Vec<_>, _>(|queued| {
+                queued.target_time <= engine_now && queued.due_at <= now
+            });
+        self.tasks = pending;
+        let mut due = due.into_iter();
+        while let Some(queued) = due.next() {
+            if let Err(err) = engine.run_task(queued.task) {
+                self.tasks.extend(due);
+                return Err(err);
+            }
+        }
+
+        let next_timeout = engine.expire_platform_messages();
+        let (engine_now, now) = (Engine::get_current_time(), self.clock.now());
+        Ok(self
+            .tasks
+            .iter()
+            .map(|queued| {
+                let wait = queued.target_time.saturating_sub(engine_now);
+                wait.max(queued.due_at.saturating_sub(now))
+            })
+            .chain(next_timeout.map(|timeout| timeout.saturating_sub(engine_now)))
+            .min())
+    }
+}
+
 pub(crate) struct TaskRunnerUserData {
     handler: Box<dyn TaskRunnerHandler>,
+    engine: EngineId,
 }
 
+#[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
 extern "C" fn runs_task_on_current_thread(user_data: *mut std::ffi::c_void) -> bool {
     let user_data = user_data.cast::<TaskRunnerUserData>();
     let user_data = unsafe { &*user_data };
@@ -79,6 +200,10 @@ extern "C" fn runs_task_on_current_thread(user_data: *mut std::ffi::c_void) -> b
     user_data.handler.runs_task_on_current_thread()
 }
 
+#[cfg_attr(
+    feature = "instrument",
+    tracing::instrument(level = "trace", skip(task, user_data))
+)]
 extern "C" fn post_task(
     task: sys::FlutterTask,
     target_time_nanos: u64,
@@ -90,8 +215,9 @@ extern "C" fn post_task(
     user_data.handler.post_task(
         Duration::from_nanos(target_time_nanos),
         Task {
-            runner: task.runner,
-            task: task.task,
+            runner: TaskRunnerPtr(task.runner),
+            id: task.task,
+            engine: user_data.engine,
         },
     )
 }
@@ -103,6 +229,8 @@ impl From<TaskRunnerDescription> for (*mut TaskRunnerUserData, sys::FlutterTaskR
     fn from(description: TaskRunnerDescription) -> Self {
         let user_data = Box::new(TaskRunnerUserData {
             handler: description.handler,
+            // set by `CustomTaskRunnerUserData::set_engine` before the engine runs
+            engine: EngineId::NONE,
         });
 
         let user_data = Box::into_raw(user_data);
@@ -122,6 +250,7 @@ impl From<TaskRunnerDescription> for (*mut TaskRunnerUserData, sys::FlutterTaskR
     }
 }
 
+#[derive(Debug)]
 pub struct CustomTaskRunners {
     /// Specify the task runner for the thread on which the `FlutterEngineRun`
     /// call is made. The same task runner description can be specified for both
@@ -151,6 +280,15 @@ pub(crate) struct CustomTaskRunnerUserData {
     )>,
 }
 
+impl CustomTaskRunnerUserData {
+    /// Sets the engine that the task runners post tasks for. Must be called before the engine runs.
+    pub(crate) fn set_engine(&self, engine: EngineId) {
+        for (user_data, _) in self.platform.iter().chain(&self.render) {
+            unsafe { (**user_data).engine = engine };
+        }
+    }
+}
+
 impl Drop for CustomTaskRunnerUserData {
     fn drop(&mut self) {
         unsafe {
@@ -226,6 +364,7 @@ impl Engine {
     /// Posts a task onto the Flutter render thread.
     // Typically, this may be called from any thread as long as the specific engine has not already been dropped (shutdown).
     // (but we don't include that line in the doc comment because you can't call this method if the engine is dropped)
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn post_render_thread_task(
         &mut self,
         callback: impl FnOnce() + 'static,
@@ -234,17 +373,27 @@ impl Engine {
             callback: Box<dyn FnOnce()>,
         }
 
+        unsafe fn cancel(user_data: *mut std::ffi::c_void) {
+            drop(unsafe { UserDataPool::take(user_data.cast::<UserData>()) });
+        }
+
+        #[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip_all))]
         unsafe extern "C" fn task_callback(user_data: *mut std::ffi::c_void) {
-            let user_data = user_data.cast::<UserData>();
-            let user_data = *unsafe { Box::from_raw(user_data) };
+            let Some(user_data) = pending::complete(user_data) else {
+                return;
+            };
+            let user_data = unsafe { UserDataPool::take(user_data.cast::<UserData>()) };
             (user_data.callback)();
         }
         const _: sys::VoidCallback = Some(task_callback);
 
-        let user_data = Box::new(UserData {
-            callback: Box::new(callback),
-        });
-        let user_data = Box::into_raw(user_data);
+        let user_data = UserDataPool::alloc(
+            self.user_data_pool(),
+            UserData {
+                callback: Box::new(callback),
+            },
+        );
+        pending::register(self.inner.engine, user_data.cast(), cancel);
 
         let result = unsafe {
             sys::PostRenderThreadTask(
@@ -256,8 +405,8 @@ impl Engine {
         .to_result();
 
         if result.is_err() {
-            let user_data = unsafe { Box::from_raw(user_data) };
-            drop(user_data);
+            pending::complete(user_data.cast());
+            drop(unsafe { UserDataPool::take(user_data) });
         }
 
         result
@@ -267,6 +416,7 @@ impl Engine {
     // Typically, this may be called from any thread as long as the specific engine has not already been dropped (shutdown).
     // (but we don't include that line in the doc comment because you can't call this method if the engine is dropped)
     // TODO: what the fuck that looks like it causes memory leaks and is not at all threadsafe
+    #[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
     pub fn post_callback_on_all_native_threads(
         &mut self,
         callback: impl Fn(NativeThreadType) + 'static,
@@ -275,6 +425,10 @@ impl Engine {
             callback: Box<dyn Fn(NativeThreadType)>,
         }
 
+        #[cfg_attr(
+            feature = "instrument",
+            tracing::instrument(level = "trace", skip(user_data))
+        )]
         unsafe extern "C" fn thread_callback(
             kind: sys::FlutterNativeThreadType,
             user_data: *mut std::ffi::c_void,
@@ -283,8 +437,8 @@ impl Engine {
             let user_data = unsafe { &*user_data };
             match kind.try_into() {
                 Ok(kind) => (user_data.callback)(kind),
-                Err(kind) => {
-                    error!("Invalid FlutterNativeThreadType: {kind:?}");
+                Err(err) => {
+                    error!("{err}; not calling back on that thread");
                 }
             }
         }
@@ -0,0 +1,260 @@
+use std::{ffi::CStr, fmt};
+
+use crate::JsonValue;
+
+/// The channel the framework talks to the text input plugin on. Messages use the `JSONMethodCodec`.
+pub const TEXT_INPUT_CHANNEL: &CStr = c"flutter/textinput";
+
+/// The kind of text a text field wants, i.e. `TextInputType` in Dart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum TextInputType {
+    #[default]
+    Text,
+    Multiline,
+    Number {
+        signed: bool,
+        decimal: bool,
+    },
+    Phone,
+    Datetime,
+    EmailAddress,
+    Url,
+    VisiblePassword,
+    Name,
+    Address,
+    WebSearch,
+    Twitter,
+    /// The text field doesn't want an on-screen keyboard, e.g. because the app has its own.
+    None,
+}
+
+impl TextInputType {
+    /// Parses the `inputType` of a text input configuration, e.g. `{"name":"TextInputType.number","signed":false,"decimal":true}`.
+    ///
+    /// Types this doesn't know about are [`TextInputType::Text`].
+    #[must_use]
+    pub fn from_json(input_type: &JsonValue) -> Self {
+        let flag = |key| {
+            input_type
+                .get(key)
+                .and_then(JsonValue::as_bool)
+                .unwrap_or(false)
+        };
+        match input_type.get("name").and_then(JsonValue::as_str) {
+            Some("TextInputType.multiline") => Self::Multiline,
+            Some("TextInputType.number") => Self::Number {
+                signed: flag("signed"),
+                decimal: flag("decimal"),
+            },
+            Some("TextInputType.phone") => Self::Phone,
+            Some("TextInputType.datetime") => Self::Datetime,
+            Some("TextInputType.emailAddress") => Self::EmailAddress,
+            Some("TextInputType.url") => Self::Url,
+            Some("TextInputType.visiblePassword") => Self::VisiblePassword,
+            Some("TextInputType.name") => Self::Name,
+            Some("TextInputType.address") => Self::Address,
+            Some("TextInputType.webSearch") => Self::WebSearch,
+            Some("TextInputType.twitter") => Self::Twitter,
+            Some("TextInputType.none") => Self::None,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// What the action button of an on-screen keyboard should do, i.e. `TextInputAction` in Dart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum TextInputAction {
+    #[default]
+    Unspecified,
+    None,
+    Done,
+    Go,
+    Search,
+    Send,
+    Next,
+    Previous,
+    Continue,
+    Join,
+    Route,
+    EmergencyCall,
+    Newline,
+}
+
+impl TextInputAction {
+    /// Parses the `inputAction` of a text input configuration, e.g. `"TextInputAction.done"`.
+    ///
+    /// Actions this doesn't know about are [`TextInputAction::Unspecified`].
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "TextInputAction.none" => Self::None,
+            "TextInputAction.done" => Self::Done,
+            "TextInputAction.go" => Self::Go,
+            "TextInputAction.search" => Self::Search,
+            "TextInputAction.send" => Self::Send,
+            "TextInputAction.next" => Self::Next,
+            "TextInputAction.previous" => Self::Previous,
+            "TextInputAction.continueAction" => Self::Continue,
+            "TextInputAction.join" => Self::Join,
+            "TextInputAction.route" => Self::Route,
+            "TextInputAction.emergencyCall" => Self::EmergencyCall,
+            "TextInputAction.newline" => Self::Newline,
+            _ => Self::Unspecified,
+        }
+    }
+}
+
+/// The parts of a text field's configuration that matter to an on-screen keyboard.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TextInputConfig {
+    pub input_type: TextInputType,
+    pub input_action: TextInputAction,
+    /// The text is a password, and shouldn't be shown or remembered.
+    pub obscure_text: bool,
+    pub autocorrect: bool,
+    pub enable_suggestions: bool,
+}
+
+impl TextInputConfig {
+    /// Parses the configuration that the framework sends with `TextInput.setClient`.
+    #[must_use]
+    pub fn from_json(config: &JsonValue) -> Self {
+        let flag = |key, default| {
+            config
+                .get(key)
+                .and_then(JsonValue::as_bool)
+                .unwrap_or(default)
+        };
+        Self {
+            input_type: config
+                .get("inputType")
+                .map(TextInputType::from_json)
+                .unwrap_or_default(),
+            input_action: config
+                .get("inputAction")
+                .and_then(JsonValue::as_str)
+                .map(TextInputAction::from_name)
+                .unwrap_or_default(),
+            obscure_text: flag("obscureText", false),
+            autocorrect: flag("autocorrect", true),
+            enable_suggestions: flag("enableSuggestions", true),
+        }
+    }
+}
+
+/// Raises and lowers an on-screen keyboard, e.g. through `zwp_input_method_v2` or a custom one.
+pub trait VirtualKeyboardHandler {
+    /// A text field wants the keyboard to be shown. This is also called when the focus moves to another text field
+    /// while the keyboard is shown, with that field's configuration.
+    fn show(&mut self, config: &TextInputConfig);
+
+    /// No text field wants the keyboard anymore.
+    fn hide(&mut self);
+}
+
+/// Tells a [`VirtualKeyboardHandler`] when to show and hide the keyboard, from the messages on [`TEXT_INPUT_CHANNEL`].
+///
+/// This only looks at the messages; responding to them is up to whatever handles text input.
+/// Text fields with [`TextInputType::None`] never show the keyboard.
+pub struct VirtualKeyboard<H: VirtualKeyboardHandler> {
+    handler: H,
+    config: Option<TextInputConfig>,
+    shown: bool,
+}
+
+impl<H: VirtualKeyboardHandler> fmt::Debug for VirtualKeyboard<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VirtualKeyboard")
+            .field("config", &self.config)
+            .field("shown", &self.shown)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H: VirtualKeyboardHandler> VirtualKeyboard<H> {
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler,
+            config: None,
+            shown: false,
+        }
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// The configuration of the text field that has focus, if any.
+    #[must_use]
+    pub fn config(&self) -> Option<&TextInputConfig> {
+        self.config.as_ref()
+    }
+
+    /// Whether the handler was last told to show the keyboard.
+    #[must_use]
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    /// Looks at a message on [`TEXT_INPUT_CHANNEL`].
+    pub fn observe(&mut self, message: &[u8]) {
+        let Some(call) = std::str::from_utf8(message).ok().and_then(JsonValue::parse) else {
+            return;
+        };
+        let args = call.get("args");
+        match call.get("method").and_then(JsonValue::as_str) {
+            Some("TextInput.setClient") => {
+                // [client id, configuration]
+                let config = args
+                    .and_then(JsonValue::as_array)
+                    .and_then(|args| args.get(1))
+                    .map(TextInputConfig::from_json)
+                    .unwrap_or_default();
+                let config = self.config.insert(config);
+                if self.shown {
+                    if config.input_type == TextInputType::None {
+                        self.shown = false;
+                        self.handler.hide();
+                    } else {
+                        self.handler.show(config);
+                    }
+                }
+            }
+            Some("TextInput.updateConfig") => {
+                if let Some(args) = args {
+                    self.config = Some(TextInputConfig::from_json(args));
+                }
+            }
+            Some("TextInput.show") => match &self.config {
+                Some(config) if config.input_type != TextInputType::None => {
+                    self.shown = true;
+                    self.handler.show(config);
+                }
+                _ => {}
+            },
+            Some("TextInput.hide") => self.hide(),
+            Some("TextInput.clearClient") => {
+                self.config = None;
+                self.hide();
+            }
+            _ => {}
+        }
+    }
+
+    fn hide(&mut self) {
+        if self.shown {
+            self.shown = false;
+            self.handler.hide();
+        }
+    }
+}
+
+impl<H: VirtualKeyboardHandler + ?Sized> VirtualKeyboardHandler for Box<H> {
+    fn show(&mut self, config: &TextInputConfig) {
+        (**self).show(config);
+    }
+
+    fn hide(&mut self) {
+        (**self).hide();
+    }
+}
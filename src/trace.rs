@@ -41,6 +41,7 @@ pub fn event_instant(name: &'static CStr) {
 /// A scope that logs a trace duration event to the timeline.
 /// In [`Self::new`], a duration begin event is logged.
 /// When it is dropped, a duration end event is logged.
+#[derive(Debug)]
 pub struct DurationScope {
     name: &'static CStr,
 }
@@ -0,0 +1,125 @@
+use std::ffi::CStr;
+
+use tracing::warn;
+
+use crate::{ChannelHandler, Engine, EngineContext, JsonValue, PlatformMessageResponse};
+
+/// The channel for the framework's `UndoManager`, which lets the platform's undo and redo commands reach an
+/// `UndoHistory` (e.g. of a text field). Messages use the `JSONMethodCodec`.
+pub const UNDO_MANAGER_CHANNEL: &CStr = c"flutter/undomanager";
+
+/// Whether the focused `UndoHistory` can currently undo or redo.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct UndoState {
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+/// i.e. `UndoDirection` in Dart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum UndoDirection {
+    Undo,
+    Redo,
+}
+
+impl UndoDirection {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Undo => "undo",
+            Self::Redo => "redo",
+        }
+    }
+}
+
+/// Updates the platform's undo and redo commands, e.g. the items in an "Edit" menu.
+pub trait UndoStateHandler {
+    /// Only called when the state actually changes.
+    fn set_undo_state(&mut self, state: UndoState);
+}
+
+impl<F: FnMut(UndoState)> UndoStateHandler for F {
+    fn set_undo_state(&mut self, state: UndoState) {
+        self(state);
+    }
+}
+
+/// Handles [`UNDO_MANAGER_CHANNEL`], keeping track of whether undo and redo are possible.
+/// Register it in a [`crate::ChannelRouter`], and call [`send_undo`] when the user undoes or redoes through the platform.
+#[derive(Debug, Default, Clone)]
+pub struct UndoManager<H> {
+    state: UndoState,
+    handler: H,
+}
+
+impl<H: UndoStateHandler> UndoManager<H> {
+    #[must_use]
+    pub fn new(handler: H) -> Self {
+        Self {
+            state: UndoState::default(),
+            handler,
+        }
+    }
+
+    /// The last state that the framework sent. Until it sends one, neither undo nor redo is possible.
+    #[must_use]
+    pub fn state(&self) -> UndoState {
+        self.state
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// The response to a method call, in the `JSONMethodCodec`.
+    fn handle(&mut self, message: &[u8]) -> String {
+        let Some(call) = std::str::from_utf8(message).ok().and_then(JsonValue::parse) else {
+            return r#"["malformed","malformed message",null]"#.to_owned();
+        };
+        if call.get("method").and_then(JsonValue::as_str) != Some("UndoManager.setUndoState") {
+            // not implemented
+            return String::new();
+        }
+
+        let args = call.get("args");
+        let flag = |key| {
+            args.and_then(|args| args.get(key))
+                .and_then(JsonValue::as_bool)
+        };
+        let (Some(can_undo), Some(can_redo)) = (flag("canUndo"), flag("canRedo")) else {
+            return r#"["malformed","missing canUndo or canRedo",null]"#.to_owned();
+        };
+
+        let state = UndoState { can_undo, can_redo };
+        if self.state != state {
+            self.state = state;
+            self.handler.set_undo_state(state);
+        }
+        "[null]".to_owned()
+    }
+}
+
+impl<H: UndoStateHandler> ChannelHandler for UndoManager<H> {
+    fn platform_message(
+        &mut self,
+        _cx: &mut EngineContext,
+        message: &[u8],
+        response: PlatformMessageResponse,
+    ) {
+        let reply = self.handle(message);
+        if let Err(err) = response.send(reply.as_bytes()) {
+            warn!("failed to respond to an undo manager message: {err}");
+        }
+    }
+}
+
+/// Undoes or redoes in the focused `UndoHistory`, e.g. when the user picks "Undo" from a menu.
+///
+/// Keyboard shortcuts like <kbd>Ctrl</kbd>+<kbd>Z</kbd> are already handled by the framework, so this is only needed for
+/// commands that don't come through as key events.
+pub fn send_undo(engine: &mut Engine, direction: UndoDirection) -> crate::Result<()> {
+    let message = format!(
+        r#"{{"method":"UndoManagerClient.handlePlatformUndo","args":["{}"]}}"#,
+        direction.name()
+    );
+    engine.send_platform_message(UNDO_MANAGER_CHANNEL, message.as_bytes(), |_| {})
+}
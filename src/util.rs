@@ -1,3 +1,7 @@
+use std::{alloc::Layout, cell::UnsafeCell, fmt::Write, ptr::NonNull, thread::ThreadId};
+
+use tracing::warn;
+
 use crate::sys;
 
 simple_enum! {
@@ -10,18 +14,55 @@ simple_enum! {
 }
 
 impl sys::FlutterEngineResult {
+    /// A result that isn't in `embedder.h` as of this crate is logged, and becomes [`Error::InternalInconsistency`].
     pub fn to_result(self) -> crate::Result<()> {
-        let result: EngineResult = self.try_into().expect("invalid FlutterEngineResult; flutter added a new variant but i thought that enum was exhaustive");
+        match EngineResult::try_from(self) {
+            Ok(result) => result.into(),
+            Err(err) => {
+                warn!("{err}; treating it as an internal inconsistency");
+                Err(Error::InternalInconsistency)
+            }
+        }
+    }
+}
+
+/// A value of an `embedder.h` enum that this crate doesn't know, most likely because the engine is newer than it.
+///
+/// This is the error of the `TryFrom` conversions from the raw enums to their wrappers,
+/// so that a new variant can be skipped or substituted instead of aborting.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct UnknownVariant<T> {
+    /// The name of the wrapper that has no variant for the value, e.g. `TextDirection`.
+    pub type_name: &'static str,
+    /// The raw value.
+    pub value: T,
+}
 
-        result.into()
+impl<T> UnknownVariant<T> {
+    pub(crate) fn new(type_name: &'static str, value: T) -> Self {
+        Self { type_name, value }
     }
 }
 
+impl<T: std::fmt::Debug> std::fmt::Display for UnknownVariant<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown {} from the engine: {:?}",
+            self.type_name, self.value
+        )
+    }
+}
+
+impl<T: std::fmt::Debug> std::error::Error for UnknownVariant<T> {}
+
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum Error {
     InvalidLibraryVersion,
     InvalidArguments,
     InternalInconsistency,
+    /// Caught before reaching the engine, which would have returned [`Error::InvalidArguments`].
+    InvalidWindowMetrics(crate::WindowMetricsError),
 }
 
 impl std::fmt::Display for Error {
@@ -30,16 +71,25 @@ impl std::fmt::Display for Error {
             Error::InvalidLibraryVersion => write!(f, "There has been a serious breakage in the Flutter embedder API. The version of the Flutter Engine that this library was compiled against is fundamentally incompatible with the version of the Flutter Engine that is present on the current system."),
             Error::InvalidArguments => write!(f, "Invalid arguments were passed to a function. You should check the documentation for the function you are calling to see what you might have done wrong."),
             Error::InternalInconsistency => write!(f, "Internal inconsistency; this is likely a bug in the Flutter Engine"),
+            Error::InvalidWindowMetrics(err) => write!(f, "Invalid window metrics: {err}"),
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+impl From<crate::WindowMetricsError> for Error {
+    fn from(error: crate::WindowMetricsError) -> Self {
+        Error::InvalidWindowMetrics(error)
+    }
+}
+
 impl From<Error> for std::io::Error {
     fn from(error: Error) -> std::io::Error {
         let kind = match error {
-            Error::InvalidArguments => std::io::ErrorKind::InvalidInput,
+            Error::InvalidArguments | Error::InvalidWindowMetrics(_) => {
+                std::io::ErrorKind::InvalidInput
+            }
             Error::InvalidLibraryVersion => std::io::ErrorKind::Unsupported,
             Error::InternalInconsistency => std::io::ErrorKind::Other,
         };
@@ -79,3 +129,158 @@ pub(crate) unsafe fn slice_from_raw_parts_with_invalid_empty<'a, T>(
         unsafe { std::slice::from_raw_parts(slice, len) }
     }
 }
+
+/// The integer behind a type made with `bitfield!`, so that the macro can work with its bits
+/// whether it wraps a bindgen bitfield enum or a plain integer.
+pub(crate) trait BitfieldRepr: Copy {
+    fn to_bits(self) -> u64;
+    fn from_bits(bits: u64) -> Self;
+}
+
+impl BitfieldRepr for i64 {
+    #[allow(clippy::cast_sign_loss)]
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn from_bits(bits: u64) -> Self {
+        bits as i64
+    }
+}
+
+macro_rules! sys_bitfield_repr {
+    ($($c_type:ty),* $(,)?) => {
+        $(
+            impl BitfieldRepr for $c_type {
+                fn to_bits(self) -> u64 {
+                    self.0.into()
+                }
+
+                #[allow(clippy::cast_possible_truncation)]
+                fn from_bits(bits: u64) -> Self {
+                    Self(bits as _)
+                }
+            }
+        )*
+    };
+}
+
+sys_bitfield_repr![
+    sys::FlutterAccessibilityFeature,
+    sys::FlutterSemanticsAction,
+    sys::FlutterSemanticsFlag,
+];
+
+/// The most allocations a [`UserDataPool`] keeps around for reuse.
+const USER_DATA_POOL_CAPACITY: usize = 32;
+
+/// A freelist for the user data of one-shot callbacks, which is allocated for every call and freed when the engine calls back.
+///
+/// Each engine owns one, which outlives every callback it's given to (they are all cancelled before it's dropped).
+/// It is only ever touched on the thread that created it, so it needs no lock;
+/// allocations that are made or freed on any other thread (e.g. the raster thread) go straight to the allocator.
+pub(crate) struct UserDataPool {
+    thread: ThreadId,
+    free: UnsafeCell<Vec<(Layout, NonNull<u8>)>>,
+}
+
+// SAFETY: the freelist is only accessed on `thread`, and the allocations in it don't hold any values.
+unsafe impl Send for UserDataPool {}
+unsafe impl Sync for UserDataPool {}
+
+/// User data allocated by a [`UserDataPool`]. The value comes first, so a pointer to it is a pointer to the allocation.
+#[repr(C)]
+struct Pooled<T> {
+    value: T,
+    pool: *const UserDataPool,
+}
+
+impl UserDataPool {
+    pub(crate) fn new() -> Self {
+        Self {
+            thread: std::thread::current().id(),
+            free: UnsafeCell::new(Vec::with_capacity(USER_DATA_POOL_CAPACITY)),
+        }
+    }
+
+    /// Calls `f` with the freelist, unless this isn't the thread that owns it.
+    fn with_free_list<R>(&self, f: impl FnOnce(&mut Vec<(Layout, NonNull<u8>)>) -> R) -> Option<R> {
+        // SAFETY: only this thread ever accesses it, and `f` can't reenter `alloc` or `take`
+        (std::thread::current().id() == self.thread).then(|| f(unsafe { &mut *self.free.get() }))
+    }
+
+    /// Use in place of `Box::into_raw(Box::new(value))`.
+    ///
+    /// `pool` may be null (for an engine that doesn't own its user data), in which case this is just an allocation.
+    pub(crate) fn alloc<T>(pool: *const Self, value: T) -> *mut T {
+        let layout = Layout::new::<Pooled<T>>();
+        let reused = unsafe { pool.as_ref() }.and_then(|pool| {
+            pool.with_free_list(|free| {
+                let index = free.iter().position(|&(free, _)| free == layout)?;
+                Some(free.swap_remove(index).1)
+            })?
+        });
+        let ptr = reused
+            .or_else(|| NonNull::new(unsafe { std::alloc::alloc(layout) }))
+            .unwrap_or_else(|| std::alloc::handle_alloc_error(layout))
+            .cast::<Pooled<T>>();
+        unsafe { ptr.write(Pooled { value, pool }) };
+        ptr.as_ptr().cast()
+    }
+
+    /// Use in place of `*Box::from_raw(ptr)`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from [`UserDataPool::alloc`], must not be used afterwards,
+    /// and the pool it was allocated from must still be alive.
+    pub(crate) unsafe fn take<T>(ptr: *mut T) -> T {
+        let ptr = ptr.cast::<Pooled<T>>();
+        let Pooled { value, pool } = unsafe { ptr.read() };
+        let layout = Layout::new::<Pooled<T>>();
+        let allocation = unsafe { NonNull::new_unchecked(ptr.cast::<u8>()) };
+        let pooled = unsafe { pool.as_ref() }.and_then(|pool| {
+            pool.with_free_list(|free| {
+                let pooled = free.len() < USER_DATA_POOL_CAPACITY;
+                if pooled {
+                    free.push((layout, allocation));
+                }
+                pooled
+            })
+        });
+        if pooled != Some(true) {
+            unsafe { std::alloc::dealloc(allocation.as_ptr(), layout) };
+        }
+        value
+    }
+}
+
+impl Drop for UserDataPool {
+    fn drop(&mut self) {
+        for (layout, ptr) in self.free.get_mut().drain(..) {
+            unsafe { std::alloc::dealloc(ptr.as_ptr(), layout) };
+        }
+    }
+}
+
+/// Escapes a string for inclusion in JSON.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
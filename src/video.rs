@@ -0,0 +1,322 @@
+//! Plays GStreamer video in a `Texture` widget.
+//!
+//! [`VideoTexture`] makes an `appsink` to end a pipeline with, and keeps the latest frame that reaches it:
+//!
+//! ```ignore
+//! let texture_id = 1;
+//! engine.register_external_texture(texture_id)?;
+//! let (video, sink) = VideoTexture::new(texture_id, true, move |texture_id| {
+//!     // wake the platform thread, which calls `video.mark_frame_available(engine)`
+//! });
+//! let playbin = gstreamer::ElementFactory::make("playbin")
+//!     .property("uri", "file:///video.mp4")
+//!     .property("video-sink", &sink)
+//!     .build()?;
+//! playbin.set_state(gstreamer::State::Playing)?;
+//!
+//! // in OpenGLRendererHandler::gl_external_texture_frame, on the raster thread
+//! let frame = video.latest_frame()?;
+//! match frame.dmabuf() {
+//!     Some(dmabuf) => { /* import it as an EGLImage, return OpenGLTexture::external_oes */ }
+//!     None => { /* upload frame.packed_pixels() with glTexImage2D(.., frame.format().gl_format(), ..) */ }
+//! }
+//! ```
+//!
+//! Frames are paced by the pipeline's clock; the sink keeps at most one frame, dropping late ones,
+//! and the engine is only told about a new frame once per frame it actually draws.
+
+use std::{
+    fmt,
+    os::fd::RawFd,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use gstreamer as gst;
+use gstreamer_allocators as gst_allocators;
+use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
+use tracing::warn;
+
+use crate::Engine;
+
+/// The pixel formats that a [`VideoTexture`] asks the pipeline for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VideoPixelFormat {
+    /// R, G, B, A bytes in memory.
+    Rgba,
+    /// B, G, R, A bytes in memory.
+    Bgra,
+}
+
+impl VideoPixelFormat {
+    fn from_gst(format: gst_video::VideoFormat) -> Option<Self> {
+        match format {
+            gst_video::VideoFormat::Rgba => Some(Self::Rgba),
+            gst_video::VideoFormat::Bgra => Some(Self::Bgra),
+            _ => None,
+        }
+    }
+
+    /// The format to upload pixels in this format with, e.g. with `glTexImage2D`.
+    /// `GL_BGRA_EXT` needs `GL_EXT_texture_format_BGRA8888` on GLES.
+    #[must_use]
+    pub fn gl_format(self) -> u32 {
+        match self {
+            // GL_RGBA
+            Self::Rgba => 0x1908,
+            // GL_BGRA_EXT
+            Self::Bgra => 0x80E1,
+        }
+    }
+
+    /// The DRM fourcc of this format, for importing a dma-buf as an `EGLImage`.
+    #[must_use]
+    pub fn drm_fourcc(self) -> u32 {
+        let fourcc = |code: &[u8; 4]| u32::from_le_bytes(*code);
+        match self {
+            // DRM formats are named by the order of the bits in a little-endian word
+            Self::Rgba => fourcc(b"AB24"),
+            Self::Bgra => fourcc(b"AR24"),
+        }
+    }
+}
+
+/// A decoded video frame, kept alive for as long as this is.
+pub struct VideoFrame {
+    buffer: gst::Buffer,
+    format: VideoPixelFormat,
+    width: u32,
+    height: u32,
+    stride: usize,
+    offset: usize,
+}
+
+impl fmt::Debug for VideoFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VideoFrame")
+            .field("format", &self.format)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("stride", &self.stride)
+            .field("offset", &self.offset)
+            .field("dmabuf", &self.dmabuf().is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A frame that lives in a dma-buf, to import without copying it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DmaBufPlane {
+    /// Owned by the [`VideoFrame`]; it's only valid for as long as the frame is kept.
+    pub fd: RawFd,
+    pub offset: usize,
+    pub stride: usize,
+    pub fourcc: u32,
+}
+
+impl VideoFrame {
+    fn from_sample(sample: &gst::Sample) -> Option<Self> {
+        let info = gst_video::VideoInfo::from_caps(sample.caps()?).ok()?;
+        let Some(format) = VideoPixelFormat::from_gst(info.format()) else {
+            warn!("video frame in unexpected format {:?}", info.format());
+            return None;
+        };
+        let buffer = sample.buffer_owned()?;
+        // decoders that pad their rows say so in the meta, which takes precedence over the caps
+        let (stride, offset) = match buffer.meta::<gst_video::VideoMeta>() {
+            Some(meta) => (meta.stride()[0], meta.offset()[0]),
+            None => (info.stride()[0], info.offset()[0]),
+        };
+        let Ok(stride) = usize::try_from(stride) else {
+            warn!("video frames flipped with a negative stride are not supported");
+            return None;
+        };
+        Some(Self {
+            buffer,
+            format,
+            width: info.width(),
+            height: info.height(),
+            stride,
+            offset,
+        })
+    }
+
+    #[must_use]
+    pub fn format(&self) -> VideoPixelFormat {
+        self.format
+    }
+
+    #[must_use]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[must_use]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of bytes between the start of each row, which may be more than 4 bytes per pixel.
+    #[must_use]
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// The dma-buf that the frame is in, if the pipeline produced one.
+    #[must_use]
+    pub fn dmabuf(&self) -> Option<DmaBufPlane> {
+        if self.buffer.n_memory() != 1 {
+            return None;
+        }
+        let memory = self
+            .buffer
+            .peek_memory(0)
+            .downcast_memory_ref::<gst_allocators::DmaBufMemory>()?;
+        Some(DmaBufPlane {
+            fd: memory.fd(),
+            offset: self.offset,
+            stride: self.stride,
+            fourcc: self.format.drm_fourcc(),
+        })
+    }
+
+    /// The pixels, with each row `stride` bytes apart. Dma-bufs are mapped, which may be slow.
+    #[must_use]
+    pub fn map_pixels(&self) -> Option<gst::buffer::MappedBuffer<gst::buffer::Readable>> {
+        self.buffer.clone().into_mapped_buffer_readable().ok()
+    }
+
+    /// The pixels without any row padding, ready to upload as a `width` by `height` texture.
+    #[must_use]
+    pub fn packed_pixels(&self) -> Option<Vec<u8>> {
+        let map = self.buffer.map_readable().ok()?;
+        let row = self.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row * self.height as usize);
+        for y in 0..self.height as usize {
+            let start = self.offset + y * self.stride;
+            pixels.extend_from_slice(map.get(start..start + row)?);
+        }
+        Some(pixels)
+    }
+}
+
+struct Shared {
+    frame: Mutex<Option<Arc<VideoFrame>>>,
+    /// A frame arrived that the engine hasn't been told about yet.
+    pending: AtomicBool,
+}
+
+/// Feeds the frames reaching an `appsink` into an external texture.
+///
+/// Cloning this gives another handle to the same texture, e.g. for the renderer.
+#[derive(Clone)]
+pub struct VideoTexture {
+    texture_id: i64,
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for VideoTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VideoTexture")
+            .field("texture_id", &self.texture_id)
+            .field("pending", &self.shared.pending.load(Ordering::Acquire))
+            .finish_non_exhaustive()
+    }
+}
+
+impl VideoTexture {
+    /// Makes an `appsink` for the external texture `texture_id`, which must be registered with the engine.
+    /// GStreamer must have been initialized, with `gstreamer::init`.
+    ///
+    /// The sink accepts RGBA and BGRA, in dma-bufs if `dmabuf` is set (and the pipeline can make them), or in system memory.
+    /// Put a `videoconvert` in front of it for anything else.
+    ///
+    /// `frame_available` is called on a streaming thread when a frame arrives and the engine hasn't been told yet.
+    /// It should get the platform thread to call [`VideoTexture::mark_frame_available`].
+    pub fn new(
+        texture_id: i64,
+        dmabuf: bool,
+        frame_available: impl Fn(i64) + Send + Sync + 'static,
+    ) -> (Self, gst_app::AppSink) {
+        let shared = Arc::new(Shared {
+            frame: Mutex::new(None),
+            pending: AtomicBool::new(false),
+        });
+
+        let formats = [gst_video::VideoFormat::Rgba, gst_video::VideoFormat::Bgra];
+        let mut caps = gst::Caps::new_empty();
+        {
+            let caps = caps.get_mut().expect("just created");
+            if dmabuf {
+                caps.append(
+                    gst_video::VideoCapsBuilder::new()
+                        .features([gst_allocators::CAPS_FEATURE_MEMORY_DMABUF])
+                        .format_list(formats)
+                        .build(),
+                );
+            }
+            caps.append(
+                gst_video::VideoCapsBuilder::new()
+                    .format_list(formats)
+                    .build(),
+            );
+        }
+
+        let sink = gst_app::AppSink::builder()
+            .caps(&caps)
+            .max_buffers(1)
+            .drop(true)
+            .build();
+
+        let callback_shared = shared.clone();
+        sink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |sink| {
+                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let Some(frame) = VideoFrame::from_sample(&sample) else {
+                        return Ok(gst::FlowSuccess::Ok);
+                    };
+                    *callback_shared
+                        .frame
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(frame));
+                    if !callback_shared.pending.swap(true, Ordering::AcqRel) {
+                        frame_available(texture_id);
+                    }
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        (Self { texture_id, shared }, sink)
+    }
+
+    #[must_use]
+    pub fn texture_id(&self) -> i64 {
+        self.texture_id
+    }
+
+    /// Tells the engine about the latest frame, if there's one it doesn't know about. Returns whether there was.
+    ///
+    /// This must be called on the platform thread.
+    pub fn mark_frame_available(&self, engine: &mut Engine) -> crate::Result<bool> {
+        if !self.shared.pending.swap(false, Ordering::AcqRel) {
+            return Ok(false);
+        }
+        engine.mark_external_texture_frame_available(self.texture_id)?;
+        Ok(true)
+    }
+
+    /// The most recent frame, for the renderer's external texture callback.
+    #[must_use]
+    pub fn latest_frame(&self) -> Option<Arc<VideoFrame>> {
+        self.shared
+            .frame
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
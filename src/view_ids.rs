@@ -0,0 +1,240 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::ViewId;
+#[cfg(embedder_add_view)]
+use crate::{Engine, WindowMetricsEvent};
+
+/// Where a view handed out by a [`ViewIdAllocator`] is in its lifecycle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ViewIdState {
+    /// Allocated, and maybe being added, but the engine hasn't confirmed it yet.
+    Adding,
+    /// The engine has added the view.
+    Added,
+    /// The view is being removed, and the engine hasn't confirmed it yet.
+    Removing,
+}
+
+/// Hands out view IDs for [`crate::Engine::add_view`], and takes them back once their views are removed.
+///
+/// The implicit view's ID is never handed out. An ID is only reused once the engine has confirmed
+/// that the view it belonged to is gone (or was never added), so that nothing still referring to
+/// the old view can be mistaken for the new one. The lowest free ID is reused first.
+///
+/// The engine confirms adding and removing views on its own threads, so cloning this gives another handle
+/// to the same allocator, which can be moved into the callbacks.
+#[derive(Debug, Clone, Default)]
+pub struct ViewIdAllocator {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The IDs above this have never been handed out.
+    highest: i64,
+    /// IDs that were handed out and given back.
+    free: BTreeSet<i64>,
+    views: BTreeMap<i64, ViewIdState>,
+}
+
+impl ViewIdAllocator {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves an ID that no other view has, in the [`ViewIdState::Adding`] state.
+    ///
+    /// Report how adding the view went with [`ViewIdAllocator::added`].
+    #[must_use]
+    pub fn allocate(&self) -> ViewId {
+        let mut state = self.state();
+        let id = state.free.pop_first().unwrap_or_else(|| {
+            state.highest += 1;
+            state.highest
+        });
+        state.views.insert(id, ViewIdState::Adding);
+        ViewId(id)
+    }
+
+    /// Records the result of adding a view, i.e. what the engine passed to the `add_view` callback.
+    /// If it wasn't added, the ID is free to be allocated again.
+    pub fn added(&self, view_id: ViewId, added: bool) {
+        let mut state = self.state();
+        if added {
+            state.views.insert(view_id.0, ViewIdState::Added);
+        } else {
+            state.release(view_id);
+        }
+    }
+
+    /// Records that a view is about to be removed.
+    pub fn removing(&self, view_id: ViewId) {
+        if let Some(view) = self.state().views.get_mut(&view_id.0) {
+            *view = ViewIdState::Removing;
+        }
+    }
+
+    /// Records the result of removing a view, i.e. what the engine passed to the `remove_view` callback.
+    /// If it was removed, the ID is free to be allocated again.
+    pub fn removed(&self, view_id: ViewId, removed: bool) {
+        let mut state = self.state();
+        if removed {
+            state.release(view_id);
+        } else if let Some(view) = state.views.get_mut(&view_id.0) {
+            *view = ViewIdState::Added;
+        }
+    }
+
+    /// Where a view is in its lifecycle, or `None` if its ID isn't allocated (including the implicit view).
+    #[must_use]
+    pub fn state_of(&self, view_id: ViewId) -> Option<ViewIdState> {
+        self.state().views.get(&view_id.0).copied()
+    }
+
+    /// The views that the engine has confirmed as added, and that aren't being removed.
+    #[must_use]
+    pub fn added_views(&self) -> Vec<ViewId> {
+        self.state()
+            .views
+            .iter()
+            .filter(|&(_, &view)| view == ViewIdState::Added)
+            .map(|(&id, _)| ViewId(id))
+            .collect()
+    }
+
+    /// Allocates an ID and adds a view with it, keeping track of the result.
+    ///
+    /// `callback` is called like with [`Engine::add_view`], after the allocator has been updated.
+    #[cfg(embedder_add_view)]
+    pub fn add_view(
+        &self,
+        engine: &mut Engine,
+        view_metrics: WindowMetricsEvent,
+        callback: impl FnOnce(ViewId, bool) + 'static,
+    ) -> crate::Result<ViewId> {
+        let view_id = self.allocate();
+        let allocator = self.clone();
+        let result = engine.add_view(view_id, view_metrics, move |added| {
+            allocator.added(view_id, added);
+            callback(view_id, added);
+        });
+        if let Err(err) = result {
+            self.added(view_id, false);
+            return Err(err);
+        }
+        Ok(view_id)
+    }
+
+    /// Removes a view, keeping track of the result.
+    ///
+    /// `callback` is called like with [`Engine::remove_view`], after the allocator has been updated.
+    #[cfg(embedder_add_view)]
+    pub fn remove_view(
+        &self,
+        engine: &mut Engine,
+        view_id: ViewId,
+        callback: impl FnOnce(bool) + Send + 'static,
+    ) -> crate::Result<()> {
+        self.removing(view_id);
+        let allocator = self.clone();
+        let result = engine.remove_view(view_id, move |removed| {
+            allocator.removed(view_id, removed);
+            callback(removed);
+        });
+        if result.is_err() {
+            self.removed(view_id, false);
+        }
+        result
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl State {
+    fn release(&mut self, view_id: ViewId) {
+        if self.views.remove(&view_id.0).is_some() {
+            self.free.insert(view_id.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_allocates_the_implicit_view() {
+        let allocator = ViewIdAllocator::new();
+        let ids = [allocator.allocate(), allocator.allocate()];
+        assert_eq!(ids, [ViewId(1), ViewId(2)]);
+        assert!(!ids.contains(&ViewId::IMPLICIT));
+        assert_eq!(allocator.state_of(ViewId::IMPLICIT), None);
+    }
+
+    #[test]
+    fn reuses_ids_only_once_removed() {
+        let allocator = ViewIdAllocator::new();
+        let view = allocator.allocate();
+        allocator.added(view, true);
+        assert_eq!(allocator.state_of(view), Some(ViewIdState::Added));
+
+        allocator.removing(view);
+        assert_eq!(allocator.state_of(view), Some(ViewIdState::Removing));
+        assert_ne!(allocator.allocate(), view);
+
+        allocator.removed(view, true);
+        assert_eq!(allocator.state_of(view), None);
+        assert_eq!(allocator.allocate(), view);
+    }
+
+    #[test]
+    fn reuses_the_lowest_free_id_first() {
+        let allocator = ViewIdAllocator::new();
+        let views = [(); 3].map(|()| allocator.allocate());
+        for view in views {
+            allocator.added(view, true);
+        }
+        allocator.removed(views[2], true);
+        allocator.removed(views[0], true);
+        assert_eq!(allocator.allocate(), views[0]);
+        assert_eq!(allocator.allocate(), views[2]);
+        assert_eq!(allocator.allocate(), ViewId(4));
+    }
+
+    #[test]
+    fn failed_additions_free_the_id() {
+        let allocator = ViewIdAllocator::new();
+        let view = allocator.allocate();
+        allocator.added(view, false);
+        assert_eq!(allocator.state_of(view), None);
+        assert_eq!(allocator.allocate(), view);
+    }
+
+    #[test]
+    fn failed_removals_keep_the_view() {
+        let allocator = ViewIdAllocator::new();
+        let view = allocator.allocate();
+        allocator.added(view, true);
+        allocator.removing(view);
+        assert!(allocator.added_views().is_empty());
+
+        allocator.removed(view, false);
+        assert_eq!(allocator.state_of(view), Some(ViewIdState::Added));
+        assert_eq!(allocator.added_views(), [view]);
+    }
+
+    #[test]
+    fn ignores_ids_it_never_handed_out() {
+        let allocator = ViewIdAllocator::new();
+        allocator.removed(ViewId(7), true);
+        allocator.removing(ViewId(7));
+        assert_eq!(allocator.state_of(ViewId(7)), None);
+        assert_eq!(allocator.allocate(), ViewId(1));
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use tracing::warn;
+
+use crate::{Clock, Engine, EngineClock, VsyncBaton};
+
+/// Hands vsync requests from the engine over to whatever drives presentation.
+///
+/// Call [`VsyncQueue::request`] from [`crate::EngineHandler::vsync`], which runs on an engine thread.
+/// Then, on the thread that called [`Engine::run`], call [`VsyncQueue::frame`] whenever the display is ready for a new frame:
+/// an output's frame event, a `wl_surface.frame` callback, a page flip, etc.
+///
+/// Cloning this gives another handle to the same queue.
+#[derive(Debug, Clone, Default)]
+pub struct VsyncQueue {
+    pending: Arc<Mutex<Option<VsyncBaton>>>,
+}
+
+impl VsyncQueue {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Option<VsyncBaton>> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queues a baton, to be returned to the engine on the next frame.
+    pub fn request(&self, baton: VsyncBaton) {
+        if let Some(previous) = self.pending().replace(baton) {
+            // the engine only ever waits for one vsync at a time
+            warn!(
+                "vsync requested while {previous:?} was still pending; it will never be returned"
+            );
+        }
+    }
+
+    /// Whether the engine is waiting for a frame.
+    ///
+    /// Use this to decide whether to ask the display for a frame callback at all.
+    #[must_use]
+    pub fn is_pending(&self) -> bool {
+        self.pending().is_some()
+    }
+
+    /// Returns the pending baton (if any) to the engine.
+    ///
+    /// `frame_start` is when the frame began (or will begin), in the engine's time base (see [`Engine::get_current_time`]),
+    /// e.g. from a [`crate::Clock`].
+    /// `refresh_interval` is the time until the next one, e.g. 16.6ms for a 60Hz display.
+    pub fn frame(
+        &self,
+        engine: &mut Engine,
+        frame_start: Duration,
+        refresh_interval: Duration,
+    ) -> crate::Result<()> {
+        let Some(baton) = self.pending().take() else {
+            return Ok(());
+        };
+        engine.on_vsync(baton, frame_start, frame_start + refresh_interval)
+    }
+}
+
+/// Collects requests for new frames from any number of places, and passes them on to [`Engine::schedule_frame`]
+/// at most once per vsync, and optionally no more often than a target frame rate.
+///
+/// Call [`FrameRequester::request`] from anywhere (it's cheap, and doesn't touch the engine).
+/// Then, on the thread that called [`Engine::run`], call [`FrameRequester::flush`] once per iteration of the event loop,
+/// and [`FrameRequester::vsync`] whenever a vsync is returned to the engine (e.g. next to [`VsyncQueue::frame`]).
+///
+/// The frame rate limit is measured with a [`Clock`], which is the [`EngineClock`] unless made with [`FrameRequester::with_clock`].
+///
+/// Cloning this gives another handle to the same requester.
+#[derive(Clone)]
+pub struct FrameRequester {
+    state: Arc<Mutex<FrameRequests>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl fmt::Debug for FrameRequester {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameRequester")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for FrameRequester {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Default)]
+struct FrameRequests {
+    requested: bool,
+    /// A frame was scheduled, and no vsync has happened since.
+    scheduled: bool,
+    min_interval: Option<Duration>,
+    last_scheduled: Option<Duration>,
+}
+
+impl FrameRequester {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_clock(EngineClock)
+    }
+
+    /// Measures the frame rate limit with `clock`, e.g. a [`crate::VirtualClock`] in tests.
+    #[must_use]
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        Self {
+            state: Arc::default(),
+            clock: Arc::new(clock),
+        }
+    }
+
+    /// Schedules frames no more often than `fps` per second, as measured by `clock` (e.g. the [`EngineClock`]).
+    #[must_use]
+    pub fn with_max_fps(fps: f64, clock: impl Clock + 'static) -> Self {
+        let requester = Self::with_clock(clock);
+        requester.set_max_fps(Some(fps));
+        requester
+    }
+
+    fn state(&self) -> MutexGuard<'_, FrameRequests> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Changes the target frame rate. `None` only limits frames to one per vsync.
+    pub fn set_max_fps(&self, fps: Option<f64>) {
+        self.state().min_interval = fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps));
+    }
+
+    /// Asks for a new frame.
+    pub fn request(&self) {
+        self.state().requested = true;
+    }
+
+    /// Whether a frame was requested, but not yet scheduled.
+    #[must_use]
+    pub fn is_requested(&self) -> bool {
+        self.state().requested
+    }
+
+    /// A vsync was returned to the engine, so the frame that was scheduled (if any) is underway.
+    pub fn vsync(&self) {
+        self.state().scheduled = false;
+    }
+
+    /// Schedules a frame, if one was requested and that's allowed right now. Returns whether it did.
+    ///
+    /// If the frame rate limit held it back, [`FrameRequester::next_deadline`] says when to try again.
+    pub fn flush(&self, engine: &mut Engine) -> crate::Result<bool> {
+        let now = self.clock.now();
+        let mut state = self.state();
+        if !state.requested || state.scheduled || state.deadline().is_some_and(|at| now < at) {
+            return Ok(false);
+        }
+        engine.schedule_frame()?;
+        state.requested = false;
+        state.scheduled = true;
+        state.last_scheduled = Some(now);
+        Ok(true)
+    }
+
+    /// When a requested frame that the frame rate limit is holding back can be scheduled,
+    /// in the requester's [`Clock`].
+    ///
+    /// `None` if there's nothing to wait for: no frame was requested, one is already scheduled,
+    /// or it can be scheduled right away.
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Duration> {
+        let state = self.state();
+        if !state.requested || state.scheduled {
+            return None;
+        }
+        state.deadline().filter(|&at| self.clock.now() < at)
+    }
+}
+
+impl FrameRequests {
+    fn deadline(&self) -> Option<Duration> {
+        Some(self.last_scheduled? + self.min_interval?)
+    }
+}
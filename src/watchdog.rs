@@ -0,0 +1,150 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use crate::{trace, Engine, FrameSubscription};
+
+/// Why a [`FrameWatchdog`] thinks the engine stopped drawing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum StallCause {
+    /// No view has any metrics, so the engine has nothing to draw into.
+    /// Send them with [`Engine::send_window_metrics_event`].
+    NoMetrics,
+    /// The engine asked for a vsync, and the baton was never returned with [`Engine::on_vsync`].
+    VsyncNotReturned,
+    /// The vsync was returned, but no frame came out of it: the UI thread (e.g. a long-running Dart call)
+    /// or the raster thread (e.g. a stuck GPU driver) isn't making progress.
+    ThreadStuck,
+    /// A frame was asked for, but the engine never asked for a vsync. The UI thread is likely busy.
+    VsyncNotRequested,
+}
+
+/// A stall reported by [`FrameWatchdog::check`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct StallDiagnosis {
+    pub cause: StallCause,
+    /// How long a frame has been waited for.
+    pub stalled_for: Duration,
+    /// When the last frame was drawn, in the engine's clock, if there was one at all.
+    pub last_frame: Option<Duration>,
+}
+
+/// Notices when the engine is expected to draw, but no frame has been drawn for a while.
+///
+/// Frames are recorded as they're drawn (see [`FrameWatchdog::attach`]). The rest of the picture comes from the embedder:
+/// - [`FrameWatchdog::frame_requested`] whenever a frame is asked for, e.g. next to [`Engine::schedule_frame`].
+/// - [`FrameWatchdog::vsync_requested`] from [`crate::EngineHandler::vsync`].
+/// - [`FrameWatchdog::vsync_returned`] after [`Engine::on_vsync`], e.g. next to [`crate::VsyncQueue::frame`].
+///
+/// Then call [`FrameWatchdog::check`] regularly, e.g. once a second. An idle app doesn't count as stalled,
+/// since it doesn't ask for frames.
+///
+/// Cloning this gives another handle to the same watchdog.
+#[derive(Debug, Clone)]
+pub struct FrameWatchdog {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    timeout: Duration,
+    last_frame: Option<Duration>,
+    /// When a frame was first expected since the last one was drawn.
+    waiting_since: Option<Duration>,
+    vsync_requested: bool,
+    vsync_returned: bool,
+    reported: bool,
+}
+
+impl FrameWatchdog {
+    /// Reports a stall when no frame is drawn within `timeout` of one being expected.
+    #[must_use]
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                timeout,
+                last_frame: None,
+                waiting_since: None,
+                vsync_requested: false,
+                vsync_returned: false,
+                reported: false,
+            })),
+        }
+    }
+
+    /// Records every frame the engine draws, until the subscription is dropped.
+    pub fn attach(&self, engine: &mut Engine) -> crate::Result<FrameSubscription> {
+        let watchdog = self.clone();
+        engine.on_frame(move || watchdog.frame_drawn(Engine::get_current_time()))
+    }
+
+    /// Records that a frame was drawn at `time`, in the engine's clock (see [`Engine::get_current_time`]).
+    pub fn frame_drawn(&self, time: Duration) {
+        let mut state = self.state();
+        state.last_frame = Some(time);
+        state.waiting_since = None;
+        state.vsync_requested = false;
+        state.vsync_returned = false;
+        state.reported = false;
+    }
+
+    /// Records that a frame was asked for. Can be called from any thread.
+    pub fn frame_requested(&self) {
+        self.state().expect_frame();
+    }
+
+    /// Records that the engine asked for a vsync. Can be called from any thread.
+    pub fn vsync_requested(&self) {
+        let mut state = self.state();
+        state.expect_frame();
+        state.vsync_requested = true;
+        state.vsync_returned = false;
+    }
+
+    /// Records that the vsync baton was returned to the engine.
+    pub fn vsync_returned(&self) {
+        self.state().vsync_returned = true;
+    }
+
+    /// Reports a stall, if a frame has been expected for longer than the timeout.
+    ///
+    /// Each stall is reported once, and logged to the timeline as `FrameWatchdog stall`.
+    #[must_use]
+    pub fn check(&self, engine: &Engine) -> Option<StallDiagnosis> {
+        let now = Engine::get_current_time();
+        let mut state = self.state();
+        let stalled_for = now.saturating_sub(state.waiting_since?);
+        if state.reported || stalled_for < state.timeout {
+            return None;
+        }
+        state.reported = true;
+
+        let cause = if engine.all_view_metrics().is_empty() {
+            StallCause::NoMetrics
+        } else if state.vsync_returned {
+            StallCause::ThreadStuck
+        } else if state.vsync_requested {
+            StallCause::VsyncNotReturned
+        } else {
+            StallCause::VsyncNotRequested
+        };
+        trace::event_instant(c"FrameWatchdog stall");
+        Some(StallDiagnosis {
+            cause,
+            stalled_for,
+            last_frame: state.last_frame,
+        })
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl State {
+    fn expect_frame(&mut self) {
+        self.waiting_since
+            .get_or_insert_with(Engine::get_current_time);
+    }
+}
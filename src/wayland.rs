@@ -0,0 +1,1099 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    thread::ThreadId,
+    time::Duration,
+};
+
+pub use ::smithay_client_toolkit as sctk;
+use sctk::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
+    delegate_registry, delegate_seat, delegate_shm, delegate_touch, delegate_xdg_shell,
+    delegate_xdg_window,
+    output::{OutputHandler, OutputState},
+    reexports::{
+        calloop::{
+            self,
+            channel::{self, Channel},
+            ping::{self, Ping},
+            timer::{TimeoutAction, Timer},
+            LoopHandle,
+        },
+        calloop_wayland_source::WaylandSource,
+        client::{
+            globals::{registry_queue_init, BindError, GlobalError},
+            protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface, wl_touch},
+            Connection, QueueHandle,
+        },
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        keyboard::{
+            KeyEvent as WlKeyEvent, KeyboardHandler, Keymap, Keysym, Modifiers, RawModifiers,
+        },
+        pointer::{PointerEvent as WlPointerEvent, PointerEventKind, PointerHandler},
+        touch::TouchHandler,
+        Capability, SeatHandler, SeatState,
+    },
+    shell::{
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        xdg::{
+            window::{Window, WindowConfigure, WindowDecorations, WindowHandler},
+            XdgShell,
+        },
+        WaylandSurface,
+    },
+    shm::{slot::SlotPool, CreatePoolError, Shm, ShmHandler},
+};
+use tracing::{error, warn};
+use xkbcommon::xkb;
+
+use crate::{
+    Engine, KeyEvent, KeyboardState, PointerButtons, PointerDeviceKind, PointerEvent, PointerState,
+    PressedKey, ScrollConfig, SoftwareRendererConfig, SoftwareRendererHandler, Task,
+    TaskRunnerDescription, TaskRunnerHandler, ViewId, VsyncBaton, VsyncQueue, WindowMetricsEvent,
+};
+
+/// What kind of surface a [`WaylandWindow`] is.
+#[derive(Debug)]
+pub enum SurfaceRole {
+    /// An ordinary window, using `xdg_toplevel`.
+    Toplevel { title: String, app_id: String },
+    /// A `wlr-layer-shell` surface, for panels, wallpapers, lock screens, etc.
+    ///
+    /// `size` is in logical pixels; a dimension of 0 means the surface is stretched between the anchors on that axis.
+    Layer {
+        layer: Layer,
+        namespace: String,
+        anchor: Anchor,
+        size: (u32, u32),
+        exclusive_zone: i32,
+    },
+}
+
+#[derive(Debug)]
+pub enum WaylandError {
+    Global(GlobalError),
+    Bind(BindError),
+    Pool(CreatePoolError),
+    EventLoop(calloop::Error),
+}
+
+impl fmt::Display for WaylandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaylandError::Global(err) => write!(f, "failed to list wayland globals: {err}"),
+            WaylandError::Bind(err) => write!(f, "failed to bind a wayland global: {err}"),
+            WaylandError::Pool(err) => write!(f, "failed to create a shm pool: {err}"),
+            WaylandError::EventLoop(err) => write!(f, "failed to insert an event source: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for WaylandError {}
+
+impl From<GlobalError> for WaylandError {
+    fn from(err: GlobalError) -> Self {
+        Self::Global(err)
+    }
+}
+
+impl From<BindError> for WaylandError {
+    fn from(err: BindError) -> Self {
+        Self::Bind(err)
+    }
+}
+
+impl From<CreatePoolError> for WaylandError {
+    fn from(err: CreatePoolError) -> Self {
+        Self::Pool(err)
+    }
+}
+
+impl<T> From<calloop::InsertError<T>> for WaylandError {
+    fn from(err: calloop::InsertError<T>) -> Self {
+        Self::EventLoop(err.error)
+    }
+}
+
+enum Surface {
+    Toplevel(Window),
+    Layer(LayerSurface),
+}
+
+impl Surface {
+    fn wl_surface(&self) -> &wl_surface::WlSurface {
+        match self {
+            Surface::Toplevel(window) => window.wl_surface(),
+            Surface::Layer(layer) => layer.wl_surface(),
+        }
+    }
+}
+
+struct SoftwareFrame {
+    pixels: Vec<u8>,
+    row_bytes: usize,
+    height: usize,
+}
+
+struct WaylandRenderer {
+    frame: Arc<Mutex<Option<SoftwareFrame>>>,
+    wake: Ping,
+}
+
+impl SoftwareRendererHandler for WaylandRenderer {
+    fn surface_present(&mut self, allocation: *const u8, row_bytes: usize, height: usize) -> bool {
+        // the engine reuses the allocation as soon as this returns
+        let pixels = unsafe { std::slice::from_raw_parts(allocation, row_bytes * height) };
+        *self.frame.lock().unwrap_or_else(PoisonError::into_inner) = Some(SoftwareFrame {
+            pixels: pixels.to_vec(),
+            row_bytes,
+            height,
+        });
+        self.wake.ping();
+        true
+    }
+}
+
+struct WaylandTaskRunner {
+    thread: ThreadId,
+    sender: channel::Sender<(Duration, Task)>,
+}
+
+impl TaskRunnerHandler for WaylandTaskRunner {
+    fn runs_task_on_current_thread(&self) -> bool {
+        std::thread::current().id() == self.thread
+    }
+
+    fn post_task(&self, target_time: Duration, task: Task) {
+        if self.sender.send((target_time, task)).is_err() {
+            warn!("a platform task was posted after the wayland event loop was dropped");
+        }
+    }
+}
+
+/// Lets the engine ask a [`WaylandWindow`] for vsync, from [`crate::EngineHandler::vsync`].
+#[derive(Clone)]
+pub struct VsyncRequester {
+    queue: VsyncQueue,
+    wake: Ping,
+}
+
+impl fmt::Debug for VsyncRequester {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VsyncRequester")
+            .field("queue", &self.queue)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VsyncRequester {
+    pub fn request(&self, baton: VsyncBaton) {
+        self.queue.request(baton);
+        self.wake.ping();
+    }
+}
+
+const MOUSE_DEVICE: i32 = 0;
+
+/// Runs the engine as an ordinary Wayland client, in a single `xdg_toplevel` or layer-shell surface.
+///
+/// This is the state of a `calloop` event loop, which becomes the engine's platform thread:
+///
+/// 1. Create the window with [`WaylandWindow::new`].
+/// 2. Start the engine with [`WaylandWindow::renderer`], and [`WaylandWindow::platform_task_runner`] as the platform task runner.
+///    Forward [`crate::EngineHandler::vsync`] to a [`WaylandWindow::vsync_requester`].
+/// 3. Hand the engine over with [`WaylandWindow::set_engine`].
+/// 4. Dispatch the event loop until [`WaylandWindow::is_closed`].
+///
+/// Frames are presented with `wl_shm`, so the engine must use the software renderer.
+/// Vsync comes from frame callbacks, and pointer, keyboard and touch input are forwarded to the implicit view.
+pub struct WaylandWindow {
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState,
+    shm: Shm,
+    pool: SlotPool,
+    surface: Surface,
+    qh: QueueHandle<Self>,
+    loop_handle: LoopHandle<'static, Self>,
+
+    engine: Option<Engine>,
+    frame: Arc<Mutex<Option<SoftwareFrame>>>,
+    vsync: VsyncQueue,
+    wake: Ping,
+    tasks: channel::Sender<(Duration, Task)>,
+
+    configured: bool,
+    closed: bool,
+    frame_callback_pending: bool,
+    size: (u32, u32),
+    scale: i32,
+    refresh_interval: Duration,
+
+    wl_pointer: Option<wl_pointer::WlPointer>,
+    wl_keyboard: Option<wl_keyboard::WlKeyboard>,
+    wl_touch: Option<wl_touch::WlTouch>,
+    pointer: PointerState,
+    keyboard: KeyboardState,
+    /// A copy of the seat's keymap, to translate held keys when the layout changes.
+    keymap: Option<xkb::Keymap>,
+    /// The active layout (XKB group).
+    layout: u32,
+    touches: HashMap<i32, PointerState>,
+}
+
+impl fmt::Debug for WaylandWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaylandWindow")
+            .field("engine", &self.engine)
+            .field("configured", &self.configured)
+            .field("closed", &self.closed)
+            .field("size", &self.size)
+            .field("scale", &self.scale)
+            .field("refresh_interval", &self.refresh_interval)
+            .field("pointer", &self.pointer)
+            .field("keyboard", &self.keyboard)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WaylandWindow {
+    pub fn new(
+        conn: &Connection,
+        loop_handle: LoopHandle<'static, Self>,
+        role: SurfaceRole,
+    ) -> Result<Self, WaylandError> {
+        let (globals, event_queue) = registry_queue_init(conn)?;
+        let qh = event_queue.handle();
+        WaylandSource::new(conn.clone(), event_queue).insert(loop_handle.clone())?;
+
+        let compositor = CompositorState::bind(&globals, &qh)?;
+        let shm = Shm::bind(&globals, &qh)?;
+        let wl_surface = compositor.create_surface(&qh);
+
+        let surface = match role {
+            SurfaceRole::Toplevel { title, app_id } => {
+                let xdg_shell = XdgShell::bind(&globals, &qh)?;
+                let window =
+                    xdg_shell.create_window(wl_surface, WindowDecorations::RequestServer, &qh);
+                window.set_title(title);
+                window.set_app_id(app_id);
+                Surface::Toplevel(window)
+            }
+            SurfaceRole::Layer {
+                layer,
+                namespace,
+                anchor,
+                size,
+                exclusive_zone,
+            } => {
+                let layer_shell = LayerShell::bind(&globals, &qh)?;
+                let layer =
+                    layer_shell.create_layer_surface(&qh, wl_surface, layer, Some(namespace), None);
+                layer.set_anchor(anchor);
+                layer.set_size(size.0, size.1);
+                layer.set_exclusive_zone(exclusive_zone);
+                layer.set_keyboard_interactivity(KeyboardInteractivity::OnDemand);
+                Surface::Layer(layer)
+            }
+        };
+        // the initial commit, without a buffer, to get the first configure
+        surface.wl_surface().commit();
+
+        let (wake, ping_source) = ping::make_ping()
+            .map_err(|err| WaylandError::EventLoop(calloop::Error::OtherError(Box::new(err))))?;
+        loop_handle.insert_source(ping_source, |(), (), window| window.wake())?;
+
+        let (tasks, task_channel): (_, Channel<(Duration, Task)>) = channel::channel();
+        loop_handle.insert_source(task_channel, |event, (), window| {
+            if let channel::Event::Msg((target_time, task)) = event {
+                window.schedule_task(target_time, task);
+            }
+        })?;
+
+        Ok(Self {
+            registry_state: RegistryState::new(&globals),
+            seat_state: SeatState::new(&globals, &qh),
+            output_state: OutputState::new(&globals, &qh),
+            pool: SlotPool::new(256 * 256 * 4, &shm)?,
+            shm,
+            surface,
+            qh,
+            loop_handle,
+
+            engine: None,
+            frame: Arc::default(),
+            vsync: VsyncQueue::new(),
+            wake,
+            tasks,
+
+            configured: false,
+            closed: false,
+            frame_callback_pending: false,
+            size: (800, 600),
+            scale: 1,
+            refresh_interval: Duration::from_nanos(1_000_000_000 / 60),
+
+            wl_pointer: None,
+            wl_keyboard: None,
+            wl_touch: None,
+            pointer: PointerState::new(ViewId::IMPLICIT, MOUSE_DEVICE, PointerDeviceKind::Mouse),
+            keyboard: KeyboardState::new(),
+            keymap: None,
+            layout: 0,
+            touches: HashMap::new(),
+        })
+    }
+
+    /// The renderer to start the engine with.
+    #[must_use]
+    pub fn renderer(&self) -> SoftwareRendererConfig {
+        SoftwareRendererConfig {
+            handler: Box::new(WaylandRenderer {
+                frame: self.frame.clone(),
+                wake: self.wake.clone(),
+            }),
+        }
+    }
+
+    /// A task runner that runs platform tasks on this event loop.
+    ///
+    /// This must be called on the thread that dispatches the event loop.
+    #[must_use]
+    pub fn platform_task_runner(&self) -> TaskRunnerDescription {
+        TaskRunnerDescription {
+            identifier: 1,
+            handler: Box::new(WaylandTaskRunner {
+                thread: std::thread::current().id(),
+                sender: self.tasks.clone(),
+            }),
+        }
+    }
+
+    #[must_use]
+    pub fn vsync_requester(&self) -> VsyncRequester {
+        VsyncRequester {
+            queue: self.vsync.clone(),
+            wake: self.wake.clone(),
+        }
+    }
+
+    /// Starts forwarding events to the engine. Events that arrive before this are dropped.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = Some(engine);
+        self.send_metrics();
+    }
+
+    /// Sets how far the mouse wheel scrolls. Touchpads scroll by the compositor's pixels, except for natural scrolling.
+    pub fn set_scroll_config(&mut self, config: &ScrollConfig) {
+        self.pointer.set_scroll_config(config);
+    }
+
+    pub fn engine(&mut self) -> Option<&mut Engine> {
+        self.engine.as_mut()
+    }
+
+    pub fn take_engine(&mut self) -> Option<Engine> {
+        self.engine.take()
+    }
+
+    /// Whether the compositor asked to close the surface.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn wl_surface(&self) -> &wl_surface::WlSurface {
+        self.surface.wl_surface()
+    }
+
+    fn schedule_task(&mut self, target_time: Duration, task: Task) {
+        let delay = target_time.saturating_sub(Engine::get_current_time());
+        let mut task = Some(task);
+        let result =
+            self.loop_handle
+                .insert_source(Timer::from_duration(delay), move |_, (), window| {
+                    if let Some(task) = task.take() {
+                        match &mut window.engine {
+                            Some(engine) => {
+                                if let Err(err) = engine.run_task(task) {
+                                    error!("failed to run platform task: {err:?}");
+                                }
+                            }
+                            None => {
+                                warn!("dropping a platform task that arrived before the engine")
+                            }
+                        }
+                    }
+                    TimeoutAction::Drop
+                });
+        if let Err(err) = result {
+            error!("failed to schedule platform task: {}", err.error);
+        }
+    }
+
+    /// Runs when the engine presented a frame, or wants a vsync.
+    fn wake(&mut self) {
+        if !self.configured {
+            // the first configure will wake us again
+            return;
+        }
+
+        let frame = self
+            .frame
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let mut commit = false;
+        if let Some(frame) = frame {
+            commit = self.attach(&frame);
+        }
+
+        if self.vsync.is_pending() && !self.frame_callback_pending {
+            let surface = self.wl_surface().clone();
+            surface.frame(&self.qh, surface.clone());
+            self.frame_callback_pending = true;
+            commit = true;
+        }
+
+        if commit {
+            self.wl_surface().commit();
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn attach(&mut self, frame: &SoftwareFrame) -> bool {
+        let (width, height, stride) = (
+            (frame.row_bytes / 4) as i32,
+            frame.height as i32,
+            frame.row_bytes as i32,
+        );
+        // the engine's native 32-bit format is BGRA in memory, which is ARGB8888 on little-endian
+        let (buffer, canvas) =
+            match self
+                .pool
+                .create_buffer(width, height, stride, wl_shm::Format::Argb8888)
+            {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    error!("failed to allocate a {width}x{height} shm buffer: {err}");
+                    return false;
+                }
+            };
+        canvas.copy_from_slice(&frame.pixels);
+
+        let surface = self.surface.wl_surface();
+        if let Err(err) = buffer.attach_to(surface) {
+            error!("failed to attach shm buffer: {err}");
+            return false;
+        }
+        surface.damage_buffer(0, 0, width, height);
+        true
+    }
+
+    fn send_metrics(&mut self) {
+        if !self.configured {
+            return;
+        }
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        #[allow(clippy::cast_sign_loss)]
+        let scale = self.scale as u32;
+        let result = engine.send_window_metrics_event(WindowMetricsEvent {
+            view_id: ViewId::IMPLICIT,
+            width: (self.size.0 * scale) as usize,
+            height: (self.size.1 * scale) as usize,
+            pixel_ratio: f64::from(self.scale),
+            left: 0,
+            top: 0,
+            physical_view_inset_top: 0.0,
+            physical_view_inset_right: 0.0,
+            physical_view_inset_bottom: 0.0,
+            physical_view_inset_left: 0.0,
+            display_id: 0,
+        });
+        if let Err(err) = result {
+            error!("failed to send window metrics: {err:?}");
+        }
+    }
+
+    fn configure(&mut self, width: Option<u32>, height: Option<u32>) {
+        self.size = (width.unwrap_or(self.size.0), height.unwrap_or(self.size.1));
+        let first = !self.configured;
+        self.configured = true;
+        self.send_metrics();
+        if first {
+            self.wake();
+        }
+    }
+
+    fn send_pointer_events(&mut self, events: &[PointerEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        if let Some(engine) = &mut self.engine {
+            if let Err(err) = engine.send_pointer_event(events) {
+                error!("failed to send pointer events: {err:?}");
+            }
+        }
+    }
+
+    fn send_key_event(&mut self, event: KeyEvent) {
+        if let Some(engine) = &mut self.engine {
+            if let Err(err) = engine.send_key_event(event, |_handled| {}) {
+                error!("failed to send key event: {err:?}");
+            }
+        }
+    }
+
+    fn key(&mut self, event: &WlKeyEvent, pressed: bool) {
+        let key_event = self.keyboard.key(
+            // wayland timestamps have an unspecified base, so use the engine's clock instead
+            Engine::get_current_time(),
+            event.raw_code,
+            event.keysym.raw(),
+            event.keysym.key_char(),
+            event.utf8.as_deref().and_then(|text| text.chars().next()),
+            pressed,
+        );
+        self.send_key_event(key_event);
+    }
+
+    /// Re-translates the held keys after the keymap or layout changed.
+    fn layout_changed(&mut self) {
+        let Some(keymap) = &self.keymap else {
+            return;
+        };
+        let layout = self.layout;
+        let events = self
+            .keyboard
+            .layout_changed(Engine::get_current_time(), |evdev_code| {
+                // xkb keycodes are evdev codes offset by 8
+                let keycode = xkb::Keycode::new(evdev_code + 8);
+                match keymap.key_get_syms_by_level(keycode, layout, 0) {
+                    [keysym, ..] => (keysym.raw(), keysym.key_char()),
+                    [] => (0, None),
+                }
+            });
+        for event in events {
+            self.send_key_event(event);
+        }
+    }
+
+    fn physical(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let scale = f64::from(self.scale);
+        (x * scale, y * scale)
+    }
+}
+
+impl CompositorHandler for WaylandWindow {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        if surface != self.wl_surface() {
+            return;
+        }
+        self.scale = new_factor;
+        surface.set_buffer_scale(new_factor);
+        self.send_metrics();
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+        self.frame_callback_pending = false;
+        if let Some(engine) = &mut self.engine {
+            if let Err(err) =
+                self.vsync
+                    .frame(engine, Engine::get_current_time(), self.refresh_interval)
+            {
+                error!("failed to return vsync baton: {err:?}");
+            }
+        }
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        let refresh_rate = self.output_state.info(output).and_then(|info| {
+            let mode = info.modes.iter().find(|mode| mode.current)?;
+            Some(mode.refresh_rate)
+        });
+        if let Some(millihertz) = refresh_rate.filter(|&rate| rate > 0) {
+            self.refresh_interval = Duration::from_secs_f64(1000.0 / f64::from(millihertz));
+        }
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for WaylandWindow {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl WindowHandler for WaylandWindow {
+    fn request_close(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _window: &Window) {
+        self.closed = true;
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _window: &Window,
+        configure: WindowConfigure,
+        _serial: u32,
+    ) {
+        let (width, height) = configure.new_size;
+        self.configure(width.map(Into::into), height.map(Into::into));
+    }
+}
+
+impl LayerShellHandler for WaylandWindow {
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _layer: &LayerSurface) {
+        self.closed = true;
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        let (width, height) = configure.new_size;
+        self.configure(
+            Some(width).filter(|&w| w > 0),
+            Some(height).filter(|&h| h > 0),
+        );
+    }
+}
+
+impl SeatHandler for WaylandWindow {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: wl_seat::WlSeat) {}
+
+    fn new_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        match capability {
+            Capability::Pointer if self.wl_pointer.is_none() => {
+                match self.seat_state.get_pointer(qh, &seat) {
+                    Ok(pointer) => self.wl_pointer = Some(pointer),
+                    Err(err) => error!("failed to get pointer: {err}"),
+                }
+            }
+            Capability::Keyboard if self.wl_keyboard.is_none() => {
+                let keyboard = self.seat_state.get_keyboard_with_repeat(
+                    qh,
+                    &seat,
+                    None,
+                    self.loop_handle.clone(),
+                    Box::new(|window, _keyboard, event| window.key(&event, true)),
+                );
+                match keyboard {
+                    Ok(keyboard) => self.wl_keyboard = Some(keyboard),
+                    Err(err) => error!("failed to get keyboard: {err}"),
+                }
+            }
+            Capability::Touch if self.wl_touch.is_none() => {
+                match self.seat_state.get_touch(qh, &seat) {
+                    Ok(touch) => self.wl_touch = Some(touch),
+                    Err(err) => error!("failed to get touch: {err}"),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        match capability {
+            Capability::Pointer => {
+                if let Some(pointer) = self.wl_pointer.take() {
+                    pointer.release();
+                }
+            }
+            Capability::Keyboard => {
+                if let Some(keyboard) = self.wl_keyboard.take() {
+                    keyboard.release();
+                }
+            }
+            Capability::Touch => {
+                if let Some(touch) = self.wl_touch.take() {
+                    touch.release();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn remove_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: wl_seat::WlSeat) {
+    }
+}
+
+impl PointerHandler for WaylandWindow {
+    fn pointer_frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _pointer: &wl_pointer::WlPointer,
+        events: &[WlPointerEvent],
+    ) {
+        let timestamp = Engine::get_current_time();
+        let mut flutter_events = Vec::new();
+        for event in events {
+            if &event.surface != self.wl_surface() {
+                continue;
+            }
+            let (x, y) = self.physical(event.position);
+            match &event.kind {
+                PointerEventKind::Enter { .. } => {
+                    flutter_events.extend(self.pointer.enter(ViewId::IMPLICIT, timestamp, x, y));
+                }
+                PointerEventKind::Leave { .. } => {
+                    flutter_events.extend(self.pointer.leave(timestamp));
+                }
+                PointerEventKind::Motion { .. } => {
+                    flutter_events.push(self.pointer.motion(timestamp, x, y));
+                }
+                PointerEventKind::Press { button, .. }
+                | PointerEventKind::Release { button, .. } => {
+                    let pressed = matches!(event.kind, PointerEventKind::Press { .. });
+                    if let Some(button) = PointerButtons::from_evdev(*button) {
+                        flutter_events.push(self.pointer.button(timestamp, button, pressed));
+                    }
+                }
+                PointerEventKind::Axis {
+                    horizontal,
+                    vertical,
+                    ..
+                } => {
+                    if horizontal.value120 != 0 || vertical.value120 != 0 {
+                        // wheel clicks, in 120ths
+                        flutter_events.push(self.pointer.scroll_clicks(
+                            timestamp,
+                            f64::from(horizontal.value120) / 120.0,
+                            f64::from(vertical.value120) / 120.0,
+                            f64::from(self.scale),
+                        ));
+                    } else if horizontal.absolute != 0.0 || vertical.absolute != 0.0 {
+                        let (dx, dy) = self.physical((horizontal.absolute, vertical.absolute));
+                        flutter_events.push(self.pointer.scroll(timestamp, dx, dy));
+                    }
+                }
+            }
+        }
+        self.send_pointer_events(&flutter_events);
+    }
+}
+
+impl KeyboardHandler for WaylandWindow {
+    fn enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _surface: &wl_surface::WlSurface,
+        _serial: u32,
+        raw: &[u32],
+        keysyms: &[Keysym],
+    ) {
+        // keys may have been pressed or released while another surface had focus
+        let pressed = raw
+            .iter()
+            .zip(keysyms)
+            .map(|(&evdev_code, keysym)| PressedKey {
+                evdev_code,
+                keysym: keysym.raw(),
+                character: keysym.key_char(),
+            })
+            .collect::<Vec<_>>();
+        let synced = self.keyboard.sync(Engine::get_current_time(), &pressed);
+        for event in synced {
+            self.send_key_event(event);
+        }
+    }
+
+    fn leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _surface: &wl_surface::WlSurface,
+        _serial: u32,
+    ) {
+        let released = self
+            .keyboard
+            .release_all(Engine::get_current_time())
+            .collect::<Vec<_>>();
+        for event in released {
+            self.send_key_event(event);
+        }
+    }
+
+    fn press_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        event: WlKeyEvent,
+    ) {
+        self.key(&event, true);
+    }
+
+    fn repeat_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        event: WlKeyEvent,
+    ) {
+        self.key(&event, true);
+    }
+
+    fn release_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        event: WlKeyEvent,
+    ) {
+        self.key(&event, false);
+    }
+
+    fn update_modifiers(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        _modifiers: Modifiers,
+        _raw_modifiers: RawModifiers,
+        layout: u32,
+    ) {
+        if layout != self.layout {
+            self.layout = layout;
+            self.layout_changed();
+        }
+    }
+
+    fn update_keymap(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        keymap: Keymap<'_>,
+    ) {
+        // sctk can't share its keymap, because xkbcommon's reference counting isn't thread-safe
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        self.keymap = xkb::Keymap::new_from_string(
+            &context,
+            keymap.as_string(),
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        );
+        if self.keymap.is_none() {
+            warn!("failed to compile the seat's keymap; held keys won't follow layout changes");
+        }
+        self.layout_changed();
+    }
+}
+
+impl TouchHandler for WaylandWindow {
+    fn down(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _serial: u32,
+        _time: u32,
+        surface: wl_surface::WlSurface,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        if &surface != self.wl_surface() {
+            return;
+        }
+        let timestamp = Engine::get_current_time();
+        let (x, y) = self.physical(position);
+        // keep touch devices apart from the mouse
+        let device = MOUSE_DEVICE.wrapping_add(1).wrapping_add(id);
+        let mut touch = PointerState::new(ViewId::IMPLICIT, device, PointerDeviceKind::Touch);
+        let mut events = touch
+            .enter(ViewId::IMPLICIT, timestamp, x, y)
+            .collect::<Vec<_>>();
+        events.push(touch.button(timestamp, PointerButtons::TouchContact, true));
+        self.touches.insert(id, touch);
+        self.send_pointer_events(&events);
+    }
+
+    fn up(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _serial: u32,
+        _time: u32,
+        id: i32,
+    ) {
+        let Some(mut touch) = self.touches.remove(&id) else {
+            return;
+        };
+        let timestamp = Engine::get_current_time();
+        let mut events = vec![touch.button(timestamp, PointerButtons::TouchContact, false)];
+        events.extend(touch.leave(timestamp));
+        self.send_pointer_events(&events);
+    }
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _time: u32,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        let (x, y) = self.physical(position);
+        let Some(touch) = self.touches.get_mut(&id) else {
+            return;
+        };
+        let event = touch.motion(Engine::get_current_time(), x, y);
+        self.send_pointer_events(&[event]);
+    }
+
+    fn shape(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _id: i32,
+        _major: f64,
+        _minor: f64,
+    ) {
+    }
+
+    fn orientation(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &wl_touch::WlTouch,
+        _id: i32,
+        _orientation: f64,
+    ) {
+    }
+
+    fn cancel(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _touch: &wl_touch::WlTouch) {
+        let timestamp = Engine::get_current_time();
+        let events = self
+            .touches
+            .drain()
+            .flat_map(|(_, mut touch)| touch.leave(timestamp).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        self.send_pointer_events(&events);
+    }
+}
+
+impl ShmHandler for WaylandWindow {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+impl ProvidesRegistryState for WaylandWindow {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(WaylandWindow);
+delegate_output!(WaylandWindow);
+delegate_shm!(WaylandWindow);
+delegate_seat!(WaylandWindow);
+delegate_pointer!(WaylandWindow);
+delegate_keyboard!(WaylandWindow);
+delegate_touch!(WaylandWindow);
+delegate_xdg_shell!(WaylandWindow);
+delegate_xdg_window!(WaylandWindow);
+delegate_layer!(WaylandWindow);
+delegate_registry!(WaylandWindow);
@@ -0,0 +1,1389 @@
+//! Renderer setup for any window that exposes [`raw_window_handle`] handles.
+//!
+//! This covers windowing crates that don't have a dedicated integration (winit, SDL, etc.):
+//! given the display and window handles, [`opengl_renderer_config`] and [`vulkan_renderer_config`]
+//! create the surface (and swapchain) the engine renders into.
+//!
+//! For an engine with more than one view, [`EglViewSurfaces`] and [`VulkanViewSurfaces`] keep a surface per view,
+//! each in a window of its own, and present them through a compositor:
+//!
+//! ```ignore
+//! let surfaces = unsafe { VulkanViewSurfaces::new(display, implicit_window) }?;
+//! let engine = Engine::run(surfaces.renderer(), ProjectArgs {
+//!     compositor: Some(surfaces.compositor()),
+//!     ..project_args()
+//! })?;
+//! unsafe { surfaces.add_window(view_id, display, window) }?;
+//! engine.add_view(view_id, metrics, |added| { /* ... */ })?;
+//! ```
+
+use std::fmt;
+
+use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
+
+pub use ::raw_window_handle;
+
+#[derive(Debug)]
+pub enum WindowHandleError {
+    /// The renderer can't be created for this kind of handle. The name of the handle is included.
+    Unsupported(&'static str),
+    /// A system library couldn't be loaded.
+    Load(String),
+    #[cfg(feature = "opengl")]
+    Egl(crate::EglError),
+    #[cfg(feature = "vulkan")]
+    Vulkan(ash::vk::Result),
+    /// No Vulkan device has a queue that can both render and present to the surface.
+    #[cfg(feature = "vulkan")]
+    NoSuitableDevice,
+}
+
+impl fmt::Display for WindowHandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowHandleError::Unsupported(handle) => {
+                write!(f, "unsupported window handle: {handle}")
+            }
+            WindowHandleError::Load(err) => write!(f, "failed to load library: {err}"),
+            #[cfg(feature = "opengl")]
+            WindowHandleError::Egl(err) => err.fmt(f),
+            #[cfg(feature = "vulkan")]
+            WindowHandleError::Vulkan(err) => write!(f, "Vulkan call failed: {err}"),
+            #[cfg(feature = "vulkan")]
+            WindowHandleError::NoSuitableDevice => write!(f, "no suitable Vulkan device"),
+        }
+    }
+}
+
+impl std::error::Error for WindowHandleError {}
+
+#[cfg(feature = "opengl")]
+impl From<crate::EglError> for WindowHandleError {
+    fn from(err: crate::EglError) -> Self {
+        Self::Egl(err)
+    }
+}
+
+#[cfg(feature = "vulkan")]
+impl From<ash::vk::Result> for WindowHandleError {
+    fn from(err: ash::vk::Result) -> Self {
+        Self::Vulkan(err)
+    }
+}
+
+#[cfg(feature = "opengl")]
+fn handle_name(window: RawWindowHandle) -> &'static str {
+    match window {
+        RawWindowHandle::Xlib(_) => "Xlib",
+        RawWindowHandle::Xcb(_) => "Xcb",
+        RawWindowHandle::Wayland(_) => "Wayland",
+        RawWindowHandle::Gbm(_) => "Gbm",
+        RawWindowHandle::Drm(_) => "Drm",
+        RawWindowHandle::Win32(_) => "Win32",
+        RawWindowHandle::AppKit(_) => "AppKit",
+        RawWindowHandle::UiKit(_) => "UiKit",
+        RawWindowHandle::AndroidNdk(_) => "AndroidNdk",
+        _ => "unknown",
+    }
+}
+
+#[cfg(feature = "opengl")]
+pub use opengl::{opengl_renderer_config, EglViewSurfaces};
+#[cfg(feature = "vulkan")]
+pub use vulkan::{vulkan_renderer_config, VulkanViewSurfaces};
+
+#[cfg(feature = "opengl")]
+mod opengl {
+    use std::{
+        collections::HashMap,
+        ffi::{c_char, c_int, c_ulong, c_void},
+        fmt,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+    };
+
+    use khronos_egl as egl;
+    use tracing::{error, warn};
+
+    use super::{handle_name, RawDisplayHandle, RawWindowHandle, WindowHandleError};
+    use crate::{
+        renderer::{EglContexts, EglNativeWindow},
+        BackingStore, BackingStoreConfig, Compositor, CompositorHandler, EglWindowContext,
+        FrameInfo, Layer, OpenGLBackingStore, OpenGLFramebuffer, OpenGLRendererConfig,
+        OpenGLRendererHandler, OpenGLTexture, PresentInfo, Region, Size, ViewId,
+    };
+
+    // EGL_KHR_platform_x11
+    const EGL_PLATFORM_X11_KHR: egl::Enum = 0x31D5;
+    const EGL_PLATFORM_X11_SCREEN_KHR: egl::Attrib = 0x31D6;
+    // EGL_KHR_platform_gbm
+    const EGL_PLATFORM_GBM_KHR: egl::Enum = 0x31D7;
+    // EGL_KHR_platform_wayland
+    const EGL_PLATFORM_WAYLAND_KHR: egl::Enum = 0x31D8;
+    // EGL_EXT_platform_xcb
+    const EGL_PLATFORM_XCB_EXT: egl::Enum = 0x31DC;
+    const EGL_PLATFORM_XCB_SCREEN_EXT: egl::Attrib = 0x31DE;
+
+    impl EglNativeWindow for () {}
+
+    type WlEglWindowCreate = unsafe extern "C" fn(*mut c_void, c_int, c_int) -> *mut c_void;
+    type WlEglWindowResize = unsafe extern "C" fn(*mut c_void, c_int, c_int, c_int, c_int);
+    type WlEglWindowDestroy = unsafe extern "C" fn(*mut c_void);
+
+    /// A `wl_egl_window` from `libwayland-egl`, which is loaded at runtime.
+    struct WlEglWindow {
+        window: *mut c_void,
+        resize: WlEglWindowResize,
+        destroy: WlEglWindowDestroy,
+        // the function pointers above point into this
+        _lib: libloading::Library,
+    }
+
+    impl WlEglWindow {
+        unsafe fn new(surface: *mut c_void) -> Result<Self, WindowHandleError> {
+            let load_err = |err: libloading::Error| WindowHandleError::Load(err.to_string());
+
+            let lib =
+                unsafe { libloading::Library::new("libwayland-egl.so.1") }.map_err(load_err)?;
+            let create = *unsafe { lib.get::<WlEglWindowCreate>(b"wl_egl_window_create\0") }
+                .map_err(load_err)?;
+            let resize = *unsafe { lib.get::<WlEglWindowResize>(b"wl_egl_window_resize\0") }
+                .map_err(load_err)?;
+            let destroy = *unsafe { lib.get::<WlEglWindowDestroy>(b"wl_egl_window_destroy\0") }
+                .map_err(load_err)?;
+
+            // the real size is set on the first frame, see `resize`
+            let window = unsafe { create(surface, 1, 1) };
+            if window.is_null() {
+                return Err(WindowHandleError::Load(
+                    "wl_egl_window_create returned NULL".to_string(),
+                ));
+            }
+
+            Ok(Self {
+                window,
+                resize,
+                destroy,
+                _lib: lib,
+            })
+        }
+    }
+
+    impl EglNativeWindow for WlEglWindow {
+        #[allow(clippy::cast_possible_wrap)]
+        fn resize(&self, width: u32, height: u32) {
+            unsafe { (self.resize)(self.window, width as c_int, height as c_int, 0, 0) };
+        }
+    }
+
+    impl Drop for WlEglWindow {
+        fn drop(&mut self) {
+            unsafe { (self.destroy)(self.window) };
+        }
+    }
+
+    /// What EGL needs to know about a window to create a surface for it.
+    struct NativeWindow {
+        platform: egl::Enum,
+        display: *mut c_void,
+        display_attribs: Vec<egl::Attrib>,
+        window: *mut c_void,
+        /// Kept alive for as long as the surface; `window` may point into it.
+        native: Box<dyn EglNativeWindow>,
+    }
+
+    impl EglNativeWindow for c_ulong {}
+    impl EglNativeWindow for u32 {}
+
+    /// # Safety
+    ///
+    /// Both handles must be valid.
+    unsafe fn native_window(
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+    ) -> Result<NativeWindow, WindowHandleError> {
+        let null = std::ptr::null_mut::<c_void>();
+        Ok(match (display, window) {
+            (RawDisplayHandle::Wayland(display), RawWindowHandle::Wayland(window)) => {
+                let native = unsafe { WlEglWindow::new(window.surface.as_ptr()) }?;
+                NativeWindow {
+                    platform: EGL_PLATFORM_WAYLAND_KHR,
+                    display: display.display.as_ptr(),
+                    display_attribs: vec![egl::ATTRIB_NONE],
+                    window: native.window,
+                    native: Box::new(native),
+                }
+            }
+            (RawDisplayHandle::Xlib(display), RawWindowHandle::Xlib(window)) => {
+                // the platform surface takes a pointer to the Window
+                let native: Box<c_ulong> = Box::new(window.window);
+                NativeWindow {
+                    platform: EGL_PLATFORM_X11_KHR,
+                    display: display.display.map_or(null, std::ptr::NonNull::as_ptr),
+                    display_attribs: vec![
+                        EGL_PLATFORM_X11_SCREEN_KHR,
+                        egl::Attrib::try_from(display.screen).unwrap_or(0),
+                        egl::ATTRIB_NONE,
+                    ],
+                    window: (&raw const *native).cast_mut().cast(),
+                    native,
+                }
+            }
+            (RawDisplayHandle::Xcb(display), RawWindowHandle::Xcb(window)) => {
+                let native: Box<u32> = Box::new(window.window.get());
+                NativeWindow {
+                    platform: EGL_PLATFORM_XCB_EXT,
+                    display: display.connection.map_or(null, std::ptr::NonNull::as_ptr),
+                    display_attribs: vec![
+                        EGL_PLATFORM_XCB_SCREEN_EXT,
+                        egl::Attrib::try_from(display.screen).unwrap_or(0),
+                        egl::ATTRIB_NONE,
+                    ],
+                    window: (&raw const *native).cast_mut().cast(),
+                    native,
+                }
+            }
+            (RawDisplayHandle::Gbm(display), RawWindowHandle::Gbm(window)) => NativeWindow {
+                platform: EGL_PLATFORM_GBM_KHR,
+                display: display.gbm_device.as_ptr(),
+                display_attribs: vec![egl::ATTRIB_NONE],
+                window: window.gbm_surface.as_ptr(),
+                native: Box::new(()),
+            },
+            (_, window) => return Err(WindowHandleError::Unsupported(handle_name(window))),
+        })
+    }
+
+    /// Creates an OpenGL ES renderer for the given window, with EGL.
+    ///
+    /// Wayland, X11 (both Xlib and XCB) and GBM handles are supported. `libEGL` (and `libwayland-egl`, for Wayland) are loaded at runtime.
+    ///
+    /// # Safety
+    ///
+    /// Both handles must be valid, and the window must outlive the returned renderer (i.e. the engine it's given to).
+    pub unsafe fn opengl_renderer_config(
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+    ) -> Result<OpenGLRendererConfig, WindowHandleError> {
+        let native = unsafe { native_window(display, window) }?;
+        let context = unsafe {
+            EglWindowContext::new(
+                native.platform,
+                native.display,
+                &native.display_attribs,
+                native.window,
+                native.native,
+            )
+        }?;
+
+        Ok(OpenGLRendererConfig {
+            // so that `fbo_callback` sees every frame's size, which resizes the `wl_egl_window`
+            fbo_reset_after_present: true,
+            handler: Box::new(context),
+        })
+    }
+
+    /// The surface of one view's window.
+    struct ViewSurface {
+        surface: egl::Surface,
+        size: Option<Size<u32>>,
+        // dropped after the surface
+        native: Box<dyn EglNativeWindow>,
+    }
+
+    struct EglViews {
+        contexts: EglContexts,
+        views: HashMap<ViewId, ViewSurface>,
+    }
+
+    // SAFETY: EGL handles can be used from any thread, and the native windows are only used behind the mutex.
+    unsafe impl Send for EglViews {}
+
+    impl EglViews {
+        fn remove(&mut self, view_id: ViewId) {
+            if let Some(view) = self.views.remove(&view_id) {
+                let _ = self
+                    .contexts
+                    .egl
+                    .destroy_surface(self.contexts.display, view.surface);
+                drop(view.native);
+            }
+        }
+    }
+
+    impl Drop for EglViews {
+        fn drop(&mut self) {
+            let _ = self
+                .contexts
+                .egl
+                .make_current(self.contexts.display, None, None, None);
+            let views = self.views.keys().copied().collect::<Vec<_>>();
+            for view_id in views {
+                self.remove(view_id);
+            }
+        }
+    }
+
+    /// The renderer that goes with [`EglViewSurfaces::compositor`].
+    ///
+    /// It renders without a surface; the compositor makes the surface of each view current as the engine renders it.
+    struct EglViewsRenderer {
+        views: Arc<Mutex<EglViews>>,
+    }
+
+    impl EglViewsRenderer {
+        fn views(&self) -> MutexGuard<'_, EglViews> {
+            self.views.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    impl OpenGLRendererHandler for EglViewsRenderer {
+        fn make_current(&mut self) -> bool {
+            let views = self.views();
+            views
+                .contexts
+                .make_surfaceless_current(views.contexts.context)
+        }
+
+        fn clear_current(&mut self) -> bool {
+            let views = self.views();
+            views
+                .contexts
+                .egl
+                .make_current(views.contexts.display, None, None, None)
+                .is_ok()
+        }
+
+        fn present(&mut self, present_info: PresentInfo) -> bool {
+            // only called when rendering to the window surface, which we don't have
+            let _ = present_info;
+            true
+        }
+
+        fn fbo_callback(&mut self, frame_info: FrameInfo) -> u32 {
+            let _ = frame_info;
+            0
+        }
+
+        fn make_resource_current(&mut self) -> bool {
+            let views = self.views();
+            views
+                .contexts
+                .make_surfaceless_current(views.contexts.resource_context)
+        }
+
+        fn gl_proc_resolver(&mut self, name: *const c_char) -> *mut c_void {
+            self.views().contexts.gl_proc_resolver(name)
+        }
+
+        fn gl_external_texture_frame(
+            &mut self,
+            texture_id: i64,
+            width: usize,
+            height: usize,
+        ) -> Option<OpenGLTexture> {
+            let _ = (texture_id, width, height);
+            None
+        }
+
+        fn populate_existing_damage(&mut self, fbo_id: isize) -> Region {
+            let _ = fbo_id;
+            Region {
+                regions: Vec::new(),
+            }
+        }
+    }
+
+    /// The engine's half of [`EglViewSurfaces`]. It runs on the raster thread.
+    struct EglViewsCompositor {
+        views: Arc<Mutex<EglViews>>,
+    }
+
+    impl CompositorHandler for EglViewsCompositor {
+        fn create_backing_store(&mut self, config: BackingStoreConfig) -> Option<BackingStore> {
+            let mut views = self.views.lock().unwrap_or_else(PoisonError::into_inner);
+            let views = &mut *views;
+            let Some(view) = views.views.get_mut(&config.view_id) else {
+                warn!("no window was added for view {:?}", config.view_id);
+                return None;
+            };
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let size = Size {
+                width: config.size.width.ceil() as u32,
+                height: config.size.height.ceil() as u32,
+            };
+            if view.size != Some(size) {
+                view.native.resize(size.width, size.height);
+                view.size = Some(size);
+            }
+            // the engine renders the layer right away, into the default framebuffer of whatever surface is current
+            views
+                .contexts
+                .egl
+                .make_current(
+                    views.contexts.display,
+                    Some(view.surface),
+                    Some(view.surface),
+                    Some(views.contexts.context),
+                )
+                .inspect_err(|err| error!("failed to make the surface of a view current: {err}"))
+                .ok()?;
+            Some(BackingStore::OpenGL(OpenGLBackingStore::Framebuffer(
+                OpenGLFramebuffer { format: 0, name: 0 },
+            )))
+        }
+
+        fn collect_backing_store(&mut self, backing_store: BackingStore) -> bool {
+            // the default framebuffer belongs to the surface
+            let _ = backing_store;
+            true
+        }
+
+        fn present_view(&mut self, view_id: ViewId, layers: &[Layer]) -> bool {
+            if layers.len() > 1 {
+                warn!(
+                    "view {view_id:?} has {} layers, but EglViewSurfaces only shows the first one; platform views are not supported",
+                    layers.len()
+                );
+            }
+            let views = self.views.lock().unwrap_or_else(PoisonError::into_inner);
+            let Some(view) = views.views.get(&view_id) else {
+                return false;
+            };
+            let contexts = &views.contexts;
+            contexts
+                .egl
+                .make_current(
+                    contexts.display,
+                    Some(view.surface),
+                    Some(view.surface),
+                    Some(contexts.context),
+                )
+                .and_then(|()| contexts.egl.swap_buffers(contexts.display, view.surface))
+                .inspect_err(|err| error!("failed to swap the buffers of view {view_id:?}: {err}"))
+                .is_ok()
+        }
+
+        fn view_removed(&mut self, view_id: ViewId) {
+            self.views
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(view_id);
+        }
+    }
+
+    /// Renders every view of the engine to a window of its own, with EGL.
+    ///
+    /// Each view has an EGL surface, which the engine renders into through the default framebuffer.
+    /// The surfaces share one context, and are resized along with the view's window metrics.
+    /// Pass [`EglViewSurfaces::renderer`] and [`EglViewSurfaces::compositor`] to the engine,
+    /// and [`EglViewSurfaces::add_window`] before adding each view with [`crate::Engine::add_view`].
+    ///
+    /// Cloning this gives another handle to the same surfaces.
+    /// Every view must consist of a single layer: platform views are not supported.
+    #[derive(Clone)]
+    pub struct EglViewSurfaces {
+        views: Arc<Mutex<EglViews>>,
+    }
+
+    impl fmt::Debug for EglViewSurfaces {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("EglViewSurfaces").finish_non_exhaustive()
+        }
+    }
+
+    impl EglViewSurfaces {
+        /// Creates the EGL display and context, and the surface of the implicit view in `window`.
+        ///
+        /// The same handles as with [`opengl_renderer_config`] are supported.
+        ///
+        /// # Safety
+        ///
+        /// Both handles must be valid, and the display must outlive this (and the engine it's given to).
+        /// The window must stay valid until its view is removed.
+        pub unsafe fn new(
+            display: RawDisplayHandle,
+            window: RawWindowHandle,
+        ) -> Result<Self, WindowHandleError> {
+            let native = unsafe { native_window(display, window) }?;
+            let contexts = unsafe {
+                EglContexts::new(native.platform, native.display, &native.display_attribs)
+            }?;
+            let surface = unsafe { contexts.create_window_surface(native.window) }?;
+            let views = HashMap::from([(
+                ViewId::IMPLICIT,
+                ViewSurface {
+                    surface,
+                    size: None,
+                    native: native.native,
+                },
+            )]);
+            Ok(Self {
+                views: Arc::new(Mutex::new(EglViews { contexts, views })),
+            })
+        }
+
+        /// The renderer to pass to the engine in [`crate::ProjectArgs::renderer`].
+        #[must_use]
+        pub fn renderer(&self) -> OpenGLRendererConfig {
+            OpenGLRendererConfig {
+                fbo_reset_after_present: false,
+                handler: Box::new(EglViewsRenderer {
+                    views: self.views.clone(),
+                }),
+            }
+        }
+
+        /// The compositor to pass to the engine in [`crate::ProjectArgs::compositor`].
+        #[must_use]
+        pub fn compositor(&self) -> Compositor {
+            Compositor {
+                // so that every frame makes the surface of its view current
+                avoid_backing_store_cache: true,
+                handler: Box::new(EglViewsCompositor {
+                    views: self.views.clone(),
+                }),
+            }
+        }
+
+        /// Creates the surface of `view_id` in `window`. Call this before adding the view.
+        ///
+        /// Adding a window for a view that already has one replaces it.
+        ///
+        /// # Safety
+        ///
+        /// `display` must be the display this was created with, and both handles must be valid.
+        /// The window must stay valid until its view is removed.
+        pub unsafe fn add_window(
+            &self,
+            view_id: ViewId,
+            display: RawDisplayHandle,
+            window: RawWindowHandle,
+        ) -> Result<(), WindowHandleError> {
+            let native = unsafe { native_window(display, window) }?;
+            let mut views = self.views.lock().unwrap_or_else(PoisonError::into_inner);
+            let surface = unsafe { views.contexts.create_window_surface(native.window) }?;
+            views.remove(view_id);
+            views.views.insert(
+                view_id,
+                ViewSurface {
+                    surface,
+                    size: None,
+                    native: native.native,
+                },
+            );
+            Ok(())
+        }
+
+        /// Destroys the surface of a view, whose window is about to be destroyed.
+        ///
+        /// Views removed with [`crate::Engine::remove_view`] are forgotten on their own.
+        pub fn remove_window(&self, view_id: ViewId) {
+            self.views
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(view_id);
+        }
+    }
+}
+
+#[cfg(feature = "vulkan")]
+mod vulkan {
+    use std::{
+        collections::HashMap,
+        ffi::{c_void, CStr, CString},
+        fmt,
+        sync::{Arc, Mutex, PoisonError},
+    };
+
+    use ash::vk::{self, Handle};
+    use tracing::{error, warn};
+
+    use super::{RawDisplayHandle, RawWindowHandle, WindowHandleError};
+    use crate::{
+        lock_vulkan_queue, renderer::locked_instance_proc_address, sys, BackingStore,
+        BackingStoreConfig, Compositor, CompositorHandler, FrameInfo, Layer, Size, ViewId,
+        VulkanBackingStore, VulkanImage, VulkanRendererConfig, VulkanRendererHandler,
+    };
+
+    const FORMAT: vk::Format = vk::Format::B8G8R8A8_UNORM;
+    const VERSION: u32 = vk::API_VERSION_1_1;
+
+    #[allow(clippy::cast_possible_truncation)] // dispatchable handles are pointers
+    fn raw_handle<H: Handle>(handle: H) -> *mut c_void {
+        handle.as_raw() as usize as *mut c_void
+    }
+
+    /// Creates a Vulkan renderer for the given window, presenting to a swapchain on its surface.
+    ///
+    /// This creates its own instance and device. The swapchain is created on the first frame, and recreated whenever the size changes.
+    /// The Vulkan loader is loaded at runtime.
+    ///
+    /// # Safety
+    ///
+    /// Both handles must be valid, and the window must outlive the returned renderer (i.e. the engine it's given to).
+    pub unsafe fn vulkan_renderer_config(
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+    ) -> Result<VulkanRendererConfig, WindowHandleError> {
+        let (device, surface) = unsafe { VulkanDevice::with_surface(display, window) }?;
+        let device = Arc::new(device);
+        let swapchain = unsafe { SurfaceSwapchain::new(&device, surface) }?;
+        Ok(device.renderer_config(Box::new(VulkanWindow {
+            device: device.clone(),
+            swapchain,
+        })))
+    }
+
+    /// The instance and device that the engine renders with, and that every surface is presented from.
+    struct VulkanDevice {
+        entry: ash::Entry,
+        instance: ash::Instance,
+        instance_extensions: Vec<CString>,
+        surface_fn: ash::khr::surface::Instance,
+        physical_device: vk::PhysicalDevice,
+        queue_family_index: u32,
+        device: Option<(ash::Device, ash::khr::swapchain::Device)>,
+        queue: vk::Queue,
+    }
+
+    impl VulkanDevice {
+        /// Creates an instance, a surface for `window`, and a device that can present to it.
+        ///
+        /// The surface belongs to the caller, and must be destroyed before the device is dropped.
+        unsafe fn with_surface(
+            display: RawDisplayHandle,
+            window: RawWindowHandle,
+        ) -> Result<(Self, vk::SurfaceKHR), WindowHandleError> {
+            let entry = unsafe { ash::Entry::load() }
+                .map_err(|err| WindowHandleError::Load(err.to_string()))?;
+
+            let instance_extensions = ash_window::enumerate_required_extensions(display)?;
+            let app_info = vk::ApplicationInfo::default()
+                .application_name(c"volito")
+                .api_version(VERSION);
+            let instance = unsafe {
+                entry.create_instance(
+                    &vk::InstanceCreateInfo::default()
+                        .application_info(&app_info)
+                        .enabled_extension_names(instance_extensions),
+                    None,
+                )
+            }?;
+
+            // on error, whatever was created so far is destroyed by dropping this
+            let mut device = Self {
+                surface_fn: ash::khr::surface::Instance::new(&entry, &instance),
+                entry,
+                instance,
+                instance_extensions: instance_extensions
+                    .iter()
+                    .map(|&name| unsafe { CStr::from_ptr(name) }.to_owned())
+                    .collect(),
+                physical_device: vk::PhysicalDevice::null(),
+                queue_family_index: 0,
+                device: None,
+                queue: vk::Queue::null(),
+            };
+            let surface = unsafe { device.create_surface(display, window) }?;
+            if let Err(err) = unsafe { device.create_device(surface) } {
+                unsafe { device.surface_fn.destroy_surface(surface, None) };
+                return Err(err);
+            }
+            Ok((device, surface))
+        }
+
+        unsafe fn create_surface(
+            &self,
+            display: RawDisplayHandle,
+            window: RawWindowHandle,
+        ) -> Result<vk::SurfaceKHR, vk::Result> {
+            unsafe {
+                ash_window::create_surface(&self.entry, &self.instance, display, window, None)
+            }
+        }
+
+        fn device(&self) -> &ash::Device {
+            &self.device.as_ref().unwrap().0
+        }
+
+        fn swapchain_fn(&self) -> &ash::khr::swapchain::Device {
+            &self.device.as_ref().unwrap().1
+        }
+
+        fn can_present_to(
+            &self,
+            physical_device: vk::PhysicalDevice,
+            queue_family_index: u32,
+            surface: vk::SurfaceKHR,
+        ) -> bool {
+            unsafe {
+                self.surface_fn.get_physical_device_surface_support(
+                    physical_device,
+                    queue_family_index,
+                    surface,
+                )
+            }
+            .unwrap_or(false)
+        }
+
+        unsafe fn create_device(
+            &mut self,
+            surface: vk::SurfaceKHR,
+        ) -> Result<(), WindowHandleError> {
+            let (physical_device, queue_family_index) =
+                unsafe { self.instance.enumerate_physical_devices()? }
+                    .into_iter()
+                    .find_map(|device| {
+                        unsafe {
+                            self.instance
+                                .get_physical_device_queue_family_properties(device)
+                        }
+                        .iter()
+                        .zip(0..)
+                        .find(|&(family, index)| {
+                            family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                                && self.can_present_to(device, index, surface)
+                        })
+                        .map(|(_, index)| (device, index))
+                    })
+                    .ok_or(WindowHandleError::NoSuitableDevice)?;
+            self.physical_device = physical_device;
+            self.queue_family_index = queue_family_index;
+
+            let device_extensions = [ash::khr::swapchain::NAME.as_ptr()];
+            let queue_info = [vk::DeviceQueueCreateInfo::default()
+                .queue_family_index(queue_family_index)
+                .queue_priorities(&[1.0])];
+            let device = unsafe {
+                self.instance.create_device(
+                    physical_device,
+                    &vk::DeviceCreateInfo::default()
+                        .queue_create_infos(&queue_info)
+                        .enabled_extension_names(&device_extensions),
+                    None,
+                )
+            }?;
+            let swapchain_fn = ash::khr::swapchain::Device::new(&self.instance, &device);
+            self.queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+            self.device = Some((device, swapchain_fn));
+            Ok(())
+        }
+
+        /// The renderer config for the engine to render with this device.
+        fn renderer_config(&self, handler: Box<dyn VulkanRendererHandler>) -> VulkanRendererConfig {
+            VulkanRendererConfig {
+                version: VERSION,
+                instance: raw_handle(self.instance.handle()),
+                physical_device: raw_handle(self.physical_device),
+                device: raw_handle(self.device().handle()),
+                queue_family_index: self.queue_family_index,
+                queue: raw_handle(self.queue),
+                enabled_instance_extensions: self.instance_extensions.clone(),
+                enabled_device_extensions: vec![CString::from(ash::khr::swapchain::NAME)],
+                handler,
+            }
+        }
+
+        /// Waits until the engine (and every present) is done with the device.
+        fn wait_idle(&self) -> Result<(), vk::Result> {
+            let _guard = lock_vulkan_queue();
+            unsafe { self.device().device_wait_idle() }
+        }
+    }
+
+    impl Drop for VulkanDevice {
+        fn drop(&mut self) {
+            unsafe {
+                if let Some((device, _)) = &self.device {
+                    let _ = self.wait_idle();
+                    device.destroy_device(None);
+                }
+                self.instance.destroy_instance(None);
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Swapchain {
+        handle: vk::SwapchainKHR,
+        images: Vec<vk::Image>,
+        extent: vk::Extent2D,
+        /// The image the engine is currently rendering into.
+        current: u32,
+    }
+
+    /// A surface, and the swapchain on it that the engine renders into.
+    struct SurfaceSwapchain {
+        surface: vk::SurfaceKHR,
+        swapchain: Swapchain,
+        command_pool: vk::CommandPool,
+        command_buffer: vk::CommandBuffer,
+        acquire_fence: vk::Fence,
+        submit_fence: vk::Fence,
+        present_semaphore: vk::Semaphore,
+    }
+
+    impl SurfaceSwapchain {
+        /// Takes ownership of `surface`, which is destroyed along with everything else if this fails.
+        /// The swapchain itself is created by the first [`SurfaceSwapchain::next_image`].
+        unsafe fn new(device: &VulkanDevice, surface: vk::SurfaceKHR) -> Result<Self, vk::Result> {
+            let mut swapchain = Self {
+                surface,
+                swapchain: Swapchain::default(),
+                command_pool: vk::CommandPool::null(),
+                command_buffer: vk::CommandBuffer::null(),
+                acquire_fence: vk::Fence::null(),
+                submit_fence: vk::Fence::null(),
+                present_semaphore: vk::Semaphore::null(),
+            };
+            if let Err(err) = unsafe { swapchain.create_sync(device) } {
+                unsafe { swapchain.destroy(device) };
+                return Err(err);
+            }
+            Ok(swapchain)
+        }
+
+        unsafe fn create_sync(&mut self, device: &VulkanDevice) -> Result<(), vk::Result> {
+            let queue_family_index = device.queue_family_index;
+            let device = device.device();
+            self.command_pool = unsafe {
+                device.create_command_pool(
+                    &vk::CommandPoolCreateInfo::default()
+                        .queue_family_index(queue_family_index)
+                        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+                    None,
+                )
+            }?;
+            self.command_buffer = unsafe {
+                device.allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::default()
+                        .command_pool(self.command_pool)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1),
+                )
+            }?[0];
+            self.acquire_fence =
+                unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }?;
+            self.submit_fence = unsafe {
+                device.create_fence(
+                    &vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED),
+                    None,
+                )
+            }?;
+            self.present_semaphore =
+                unsafe { device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None) }?;
+            Ok(())
+        }
+
+        /// (Re)creates the swapchain with the given size, clamped to what the surface supports.
+        unsafe fn create_swapchain(
+            &mut self,
+            device: &VulkanDevice,
+            width: u32,
+            height: u32,
+        ) -> Result<(), vk::Result> {
+            let caps = unsafe {
+                device
+                    .surface_fn
+                    .get_physical_device_surface_capabilities(device.physical_device, self.surface)
+            }?;
+
+            let extent = vk::Extent2D {
+                width: width.clamp(caps.min_image_extent.width, caps.max_image_extent.width),
+                height: height.clamp(caps.min_image_extent.height, caps.max_image_extent.height),
+            };
+            let mut image_count = caps.min_image_count + 1;
+            if caps.max_image_count != 0 {
+                image_count = image_count.min(caps.max_image_count);
+            }
+            let composite_alpha = [
+                vk::CompositeAlphaFlagsKHR::OPAQUE,
+                vk::CompositeAlphaFlagsKHR::INHERIT,
+                vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+                vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+            ]
+            .into_iter()
+            .find(|&flag| caps.supported_composite_alpha.contains(flag))
+            .unwrap_or(vk::CompositeAlphaFlagsKHR::OPAQUE);
+
+            // the engine may still be using the old images
+            device.wait_idle()?;
+
+            let old_swapchain = self.swapchain.handle;
+            let handle = unsafe {
+                device.swapchain_fn().create_swapchain(
+                    &vk::SwapchainCreateInfoKHR::default()
+                        .surface(self.surface)
+                        .min_image_count(image_count)
+                        .image_format(FORMAT)
+                        .image_color_space(vk::ColorSpaceKHR::SRGB_NONLINEAR)
+                        .image_extent(extent)
+                        .image_array_layers(1)
+                        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+                        .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+                        .pre_transform(caps.current_transform)
+                        .composite_alpha(composite_alpha)
+                        .present_mode(vk::PresentModeKHR::FIFO)
+                        .clipped(true)
+                        .old_swapchain(old_swapchain),
+                    None,
+                )
+            }?;
+            if old_swapchain != vk::SwapchainKHR::null() {
+                unsafe { device.swapchain_fn().destroy_swapchain(old_swapchain, None) };
+            }
+
+            let images = unsafe { device.swapchain_fn().get_swapchain_images(handle) }?;
+            self.swapchain = Swapchain {
+                handle,
+                images,
+                extent,
+                current: 0,
+            };
+            Ok(())
+        }
+
+        unsafe fn next_image(
+            &mut self,
+            device: &VulkanDevice,
+            width: u32,
+            height: u32,
+        ) -> Result<vk::Image, vk::Result> {
+            let extent = self.swapchain.extent;
+            if self.swapchain.handle == vk::SwapchainKHR::null()
+                || (extent.width, extent.height) != (width, height)
+            {
+                unsafe { self.create_swapchain(device, width, height) }?;
+            }
+
+            let (index, _suboptimal) = match unsafe {
+                device.swapchain_fn().acquire_next_image(
+                    self.swapchain.handle,
+                    u64::MAX,
+                    vk::Semaphore::null(),
+                    self.acquire_fence,
+                )
+            } {
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    unsafe { self.create_swapchain(device, width, height) }?;
+                    unsafe {
+                        device.swapchain_fn().acquire_next_image(
+                            self.swapchain.handle,
+                            u64::MAX,
+                            vk::Semaphore::null(),
+                            self.acquire_fence,
+                        )
+                    }?
+                }
+                result => result?,
+            };
+            unsafe {
+                device
+                    .device()
+                    .wait_for_fences(&[self.acquire_fence], true, u64::MAX)?;
+                device.device().reset_fences(&[self.acquire_fence])?;
+            }
+
+            self.swapchain.current = index;
+            Ok(self.swapchain.images[index as usize])
+        }
+
+        unsafe fn present(
+            &mut self,
+            device: &VulkanDevice,
+            image: vk::Image,
+        ) -> Result<(), vk::Result> {
+            let (queue, swapchain_fn) = (device.queue, device.swapchain_fn());
+            let device = device.device();
+
+            // the command buffer may still be in use by the previous present
+            unsafe {
+                device.wait_for_fences(&[self.submit_fence], true, u64::MAX)?;
+                device.reset_fences(&[self.submit_fence])?;
+                device.begin_command_buffer(
+                    self.command_buffer,
+                    &vk::CommandBufferBeginInfo::default()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )?;
+                device.cmd_pipeline_barrier(
+                    self.command_buffer,
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[vk::ImageMemoryBarrier::default()
+                        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                        .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                        .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+                        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .image(image)
+                        .subresource_range(vk::ImageSubresourceRange {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        })],
+                );
+                device.end_command_buffer(self.command_buffer)?;
+            }
+
+            let command_buffers = [self.command_buffer];
+            let signal_semaphores = [self.present_semaphore];
+            let swapchains = [self.swapchain.handle];
+            let image_indices = [self.swapchain.current];
+
+            let _guard = lock_vulkan_queue();
+            unsafe {
+                device.queue_submit(
+                    queue,
+                    &[vk::SubmitInfo::default()
+                        .command_buffers(&command_buffers)
+                        .signal_semaphores(&signal_semaphores)],
+                    self.submit_fence,
+                )?;
+                match swapchain_fn.queue_present(
+                    queue,
+                    &vk::PresentInfoKHR::default()
+                        .wait_semaphores(&signal_semaphores)
+                        .swapchains(&swapchains)
+                        .image_indices(&image_indices),
+                ) {
+                    // the next frame recreates the swapchain if the size changed, and otherwise this frame is just lost
+                    Ok(_) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(()),
+                    Err(err) => Err(err),
+                }
+            }
+        }
+
+        /// Destroys the swapchain and the surface, once the device is idle.
+        /// Anything that wasn't created yet is null, which Vulkan ignores.
+        unsafe fn destroy(&mut self, device: &VulkanDevice) {
+            let _ = device.wait_idle();
+            unsafe {
+                if self.swapchain.handle != vk::SwapchainKHR::null() {
+                    device
+                        .swapchain_fn()
+                        .destroy_swapchain(self.swapchain.handle, None);
+                }
+                let (surface_fn, device) = (&device.surface_fn, device.device());
+                device.destroy_semaphore(self.present_semaphore, None);
+                device.destroy_fence(self.submit_fence, None);
+                device.destroy_fence(self.acquire_fence, None);
+                device.destroy_command_pool(self.command_pool, None);
+                surface_fn.destroy_surface(self.surface, None);
+            }
+        }
+    }
+
+    struct VulkanWindow {
+        // dropped after the swapchain is destroyed
+        device: Arc<VulkanDevice>,
+        swapchain: SurfaceSwapchain,
+    }
+
+    impl VulkanRendererHandler for VulkanWindow {
+        fn get_instance_proc_address(
+            &mut self,
+            instance: sys::FlutterVulkanInstanceHandle,
+            name: &CStr,
+        ) -> *mut c_void {
+            unsafe {
+                locked_instance_proc_address(
+                    &self.device.entry,
+                    vk::Instance::from_raw(instance as u64),
+                    name,
+                )
+            }
+        }
+
+        fn get_next_image(&mut self, frame_info: FrameInfo) -> VulkanImage {
+            let size = frame_info.size();
+            let image = unsafe {
+                self.swapchain
+                    .next_image(&self.device, size.width, size.height)
+            }
+            .inspect_err(|err| error!("failed to acquire a swapchain image: {err}"))
+            // the engine has no way to skip a frame here; a null image makes it fail the frame
+            .unwrap_or(vk::Image::null());
+            VulkanImage {
+                image_handle: image.as_raw(),
+                #[allow(clippy::cast_sign_loss)]
+                format: FORMAT.as_raw() as u32,
+            }
+        }
+
+        fn present_image(&mut self, image: VulkanImage) -> bool {
+            unsafe {
+                self.swapchain
+                    .present(&self.device, vk::Image::from_raw(image.image_handle))
+            }
+            .inspect_err(|err| error!("failed to present a swapchain image: {err}"))
+            .is_ok()
+        }
+    }
+
+    impl Drop for VulkanWindow {
+        fn drop(&mut self) {
+            unsafe { self.swapchain.destroy(&self.device) };
+        }
+    }
+
+    /// The swapchain of one view's window.
+    struct ViewSwapchain {
+        swapchain: SurfaceSwapchain,
+        /// The image the engine is rendering into, from when it asks for a backing store until the view is presented.
+        acquired: Option<vk::Image>,
+    }
+
+    struct VulkanViews {
+        device: Arc<VulkanDevice>,
+        views: HashMap<ViewId, ViewSwapchain>,
+    }
+
+    impl VulkanViews {
+        fn remove(&mut self, view_id: ViewId) {
+            if let Some(mut view) = self.views.remove(&view_id) {
+                unsafe { view.swapchain.destroy(&self.device) };
+            }
+        }
+    }
+
+    impl Drop for VulkanViews {
+        fn drop(&mut self) {
+            let views = self.views.keys().copied().collect::<Vec<_>>();
+            for view_id in views {
+                self.remove(view_id);
+            }
+        }
+    }
+
+    /// The renderer handler that goes with [`VulkanViewSurfaces::compositor`].
+    ///
+    /// The engine never asks it for images; everything goes through the compositor.
+    struct VulkanViewsRenderer {
+        entry: ash::Entry,
+    }
+
+    impl VulkanRendererHandler for VulkanViewsRenderer {
+        fn get_instance_proc_address(
+            &mut self,
+            instance: sys::FlutterVulkanInstanceHandle,
+            name: &CStr,
+        ) -> *mut c_void {
+            unsafe {
+                locked_instance_proc_address(
+                    &self.entry,
+                    vk::Instance::from_raw(instance as u64),
+                    name,
+                )
+            }
+        }
+
+        fn get_next_image(&mut self, frame_info: FrameInfo) -> VulkanImage {
+            let _ = frame_info;
+            unreachable!("the engine only calls get_next_image without a compositor")
+        }
+
+        fn present_image(&mut self, image: VulkanImage) -> bool {
+            let _ = image;
+            unreachable!("the engine only calls present_image without a compositor")
+        }
+    }
+
+    /// The engine's half of [`VulkanViewSurfaces`]. It runs on the raster thread.
+    struct VulkanViewsCompositor {
+        views: Arc<Mutex<VulkanViews>>,
+    }
+
+    impl CompositorHandler for VulkanViewsCompositor {
+        fn create_backing_store(&mut self, config: BackingStoreConfig) -> Option<BackingStore> {
+            let mut views = self.views.lock().unwrap_or_else(PoisonError::into_inner);
+            let views = &mut *views;
+            let Some(view) = views.views.get_mut(&config.view_id) else {
+                warn!("no window was added for view {:?}", config.view_id);
+                return None;
+            };
+            if view.acquired.is_some() {
+                warn!(
+                    "view {:?} has more than one layer, but VulkanViewSurfaces only shows one; platform views are not supported",
+                    config.view_id
+                );
+                return None;
+            }
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let size = Size {
+                width: config.size.width.ceil() as u32,
+                height: config.size.height.ceil() as u32,
+            };
+            // the swapchain is recreated whenever the view's size changes
+            let image = unsafe {
+                view.swapchain
+                    .next_image(&views.device, size.width, size.height)
+            }
+            .inspect_err(|err| {
+                error!(
+                    "failed to acquire a swapchain image for view {:?}: {err}",
+                    config.view_id
+                );
+            })
+            .ok()?;
+            view.acquired = Some(image);
+            Some(BackingStore::Vulkan(VulkanBackingStore {
+                image: VulkanImage {
+                    image_handle: image.as_raw(),
+                    #[allow(clippy::cast_sign_loss)]
+                    format: FORMAT.as_raw() as u32,
+                },
+            }))
+        }
+
+        fn collect_backing_store(&mut self, backing_store: BackingStore) -> bool {
+            // the image belongs to the swapchain
+            let _ = backing_store;
+            true
+        }
+
+        fn present_view(&mut self, view_id: ViewId, layers: &[Layer]) -> bool {
+            let _ = layers;
+            let mut views = self.views.lock().unwrap_or_else(PoisonError::into_inner);
+            let views = &mut *views;
+            let Some(view) = views.views.get_mut(&view_id) else {
+                return false;
+            };
+            let Some(image) = view.acquired.take() else {
+                return false;
+            };
+            unsafe { view.swapchain.present(&views.device, image) }
+                .inspect_err(|err| {
+                    error!("failed to present a swapchain image for view {view_id:?}: {err}");
+                })
+                .is_ok()
+        }
+
+        fn view_removed(&mut self, view_id: ViewId) {
+            self.views
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(view_id);
+        }
+    }
+
+    /// Renders every view of the engine to a window of its own, with a Vulkan swapchain per view.
+    ///
+    /// Every swapchain is presented from one instance and device, which this creates.
+    /// A view's swapchain is recreated whenever its size changes along with the view's window metrics.
+    /// Pass [`VulkanViewSurfaces::renderer`] and [`VulkanViewSurfaces::compositor`] to the engine,
+    /// and [`VulkanViewSurfaces::add_window`] before adding each view with [`crate::Engine::add_view`].
+    ///
+    /// Cloning this gives another handle to the same swapchains.
+    /// Every view must consist of a single layer: platform views are not supported.
+    #[derive(Clone)]
+    pub struct VulkanViewSurfaces {
+        views: Arc<Mutex<VulkanViews>>,
+    }
+
+    impl fmt::Debug for VulkanViewSurfaces {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("VulkanViewSurfaces").finish_non_exhaustive()
+        }
+    }
+
+    impl VulkanViewSurfaces {
+        /// Creates the instance and device, and the surface of the implicit view in `window`.
+        ///
+        /// The device is picked so that it can present to this window; the windows of other views must be on the same display.
+        /// The Vulkan loader is loaded at runtime.
+        ///
+        /// # Safety
+        ///
+        /// Both handles must be valid, and the display must outlive this (and the engine it's given to).
+        /// The window must stay valid until its view is removed.
+        pub unsafe fn new(
+            display: RawDisplayHandle,
+            window: RawWindowHandle,
+        ) -> Result<Self, WindowHandleError> {
+            let (device, surface) = unsafe { VulkanDevice::with_surface(display, window) }?;
+            let device = Arc::new(device);
+            let swapchain = unsafe { SurfaceSwapchain::new(&device, surface) }?;
+            let views = HashMap::from([(
+                ViewId::IMPLICIT,
+                ViewSwapchain {
+                    swapchain,
+                    acquired: None,
+                },
+            )]);
+            Ok(Self {
+                views: Arc::new(Mutex::new(VulkanViews { device, views })),
+            })
+        }
+
+        /// The renderer to pass to the engine in [`crate::ProjectArgs::renderer`].
+        #[must_use]
+        pub fn renderer(&self) -> VulkanRendererConfig {
+            let views = self.views.lock().unwrap_or_else(PoisonError::into_inner);
+            views.device.renderer_config(Box::new(VulkanViewsRenderer {
+                entry: views.device.entry.clone(),
+            }))
+        }
+
+        /// The compositor to pass to the engine in [`crate::ProjectArgs::compositor`].
+        #[must_use]
+        pub fn compositor(&self) -> Compositor {
+            Compositor {
+                // so that every frame acquires an image from the swapchain of its view
+                avoid_backing_store_cache: true,
+                handler: Box::new(VulkanViewsCompositor {
+                    views: self.views.clone(),
+                }),
+            }
+        }
+
+        /// Creates the surface of `view_id` in `window`. Its swapchain is created on the view's first frame.
+        /// Call this before adding the view.
+        ///
+        /// Adding a window for a view that already has one replaces it.
+        /// Fails with [`WindowHandleError::NoSuitableDevice`] if the device can't present to the window.
+        ///
+        /// # Safety
+        ///
+        /// `display` must be the display this was created with, and both handles must be valid.
+        /// The window must stay valid until its view is removed.
+        pub unsafe fn add_window(
+            &self,
+            view_id: ViewId,
+            display: RawDisplayHandle,
+            window: RawWindowHandle,
+        ) -> Result<(), WindowHandleError> {
+            let mut views = self.views.lock().unwrap_or_else(PoisonError::into_inner);
+            let device = &views.device;
+            let surface = unsafe { device.create_surface(display, window) }?;
+            if !device.can_present_to(device.physical_device, device.queue_family_index, surface) {
+                unsafe { device.surface_fn.destroy_surface(surface, None) };
+                return Err(WindowHandleError::NoSuitableDevice);
+            }
+            let swapchain = unsafe { SurfaceSwapchain::new(device, surface) }?;
+            views.remove(view_id);
+            views.views.insert(
+                view_id,
+                ViewSwapchain {
+                    swapchain,
+                    acquired: None,
+                },
+            );
+            Ok(())
+        }
+
+        /// Destroys the swapchain and surface of a view, whose window is about to be destroyed.
+        ///
+        /// Views removed with [`crate::Engine::remove_view`] are forgotten on their own.
+        pub fn remove_window(&self, view_id: ViewId) {
+            self.views
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(view_id);
+        }
+    }
+}
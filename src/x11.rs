@@ -0,0 +1,584 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use khronos_egl as egl;
+use tracing::error;
+use x11rb::{
+    connection::Connection,
+    errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError},
+    protocol::{
+        randr::ConnectionExt as _,
+        xinput::{self, ConnectionExt as _},
+        xproto::{
+            AtomEnum, ClientMessageEvent, ConfigureNotifyEvent, ConnectionExt as _,
+            CreateWindowAux, EventMask, ModMask, PropMode, WindowClass,
+        },
+        Event,
+    },
+    wrapper::ConnectionExt as _,
+    xcb_ffi::XCBConnection,
+    COPY_DEPTH_FROM_PARENT,
+};
+
+use crate::{
+    renderer::EglNativeWindow, Display, EglError, EglWindowContext, Engine, KeyEvent,
+    KeyboardState, PointerButtons, PointerDeviceKind, PointerState, PressedKey, ScrollConfig,
+    ViewId, WindowMetricsEvent,
+};
+
+#[derive(Debug)]
+pub enum X11Error {
+    Connect(ConnectError),
+    Protocol(ReplyOrIdError),
+    /// The server doesn't support `XInput` 2.2.
+    MissingXInput,
+    Egl(EglError),
+}
+
+impl fmt::Display for X11Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            X11Error::Connect(err) => write!(f, "failed to connect to the X server: {err}"),
+            X11Error::Protocol(err) => write!(f, "X11 request failed: {err}"),
+            X11Error::MissingXInput => write!(f, "the X server doesn't support XInput 2.2"),
+            X11Error::Egl(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for X11Error {}
+
+impl From<ConnectError> for X11Error {
+    fn from(err: ConnectError) -> Self {
+        Self::Connect(err)
+    }
+}
+
+impl From<ConnectionError> for X11Error {
+    fn from(err: ConnectionError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+impl From<ReplyError> for X11Error {
+    fn from(err: ReplyError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+impl From<ReplyOrIdError> for X11Error {
+    fn from(err: ReplyOrIdError) -> Self {
+        Self::Protocol(err)
+    }
+}
+
+impl From<EglError> for X11Error {
+    fn from(err: EglError) -> Self {
+        Self::Egl(err)
+    }
+}
+
+// https://registry.khronos.org/EGL/extensions/EXT/EGL_EXT_platform_xcb.txt
+const EGL_PLATFORM_XCB_EXT: egl::Enum = 0x31DC;
+const EGL_PLATFORM_XCB_SCREEN_EXT: egl::Attrib = 0x31DE;
+
+// legacy scroll buttons; XInput 2 still sends these (flagged as emulated) for every scroll
+const SCROLL_UP: u32 = 4;
+const SCROLL_DOWN: u32 = 5;
+const SCROLL_LEFT: u32 = 6;
+const SCROLL_RIGHT: u32 = 7;
+
+const MOUSE_DEVICE: i32 = 0;
+
+fn fp1616(value: xinput::Fp1616) -> f64 {
+    f64::from(value) / 65536.0
+}
+
+/// A button number from the core protocol (as used by `XInput` 2) as Flutter's button.
+fn pointer_button(button: u32) -> Option<PointerButtons> {
+    match button {
+        1 => Some(PointerButtons::MousePrimary),
+        2 => Some(PointerButtons::MouseMiddle),
+        3 => Some(PointerButtons::MouseSecondary),
+        8 => Some(PointerButtons::MouseBack),
+        9 => Some(PointerButtons::MouseForward),
+        _ => None,
+    }
+}
+
+struct KeyboardMapping {
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: Vec<u32>,
+}
+
+impl KeyboardMapping {
+    fn get(conn: &XCBConnection) -> Result<Self, X11Error> {
+        let setup = conn.setup();
+        let (min_keycode, max_keycode) = (setup.min_keycode, setup.max_keycode);
+        let reply = conn
+            .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+            .reply()?;
+        Ok(Self {
+            min_keycode,
+            keysyms_per_keycode: reply.keysyms_per_keycode,
+            keysyms: reply.keysyms,
+        })
+    }
+
+    fn syms(&self, keycode: u32) -> &[u32] {
+        let Some(index) = keycode.checked_sub(u32::from(self.min_keycode)) else {
+            return &[];
+        };
+        let per_keycode = usize::from(self.keysyms_per_keycode);
+        let start = index as usize * per_keycode;
+        self.keysyms
+            .get(start..start + per_keycode)
+            .unwrap_or_default()
+    }
+
+    /// The keysym in the given column of the core keyboard mapping; 0 is unshifted and 1 is shifted.
+    fn keysym(&self, keycode: u32, column: usize) -> u32 {
+        let syms = self.syms(keycode);
+        match syms.get(column).copied() {
+            // a single keysym in the list is used for both columns
+            Some(0) | None if column > 0 => syms.first().copied().unwrap_or(0),
+            sym => sym.unwrap_or(0),
+        }
+    }
+
+    /// The keysym of a key in an XKB group (i.e. layout), unshifted or shifted.
+    ///
+    /// The core mapping only has columns for two groups, so the others wrap around, like XKB does by default.
+    /// Keys that are the same in the second group (like modifiers) only have the first group's columns.
+    fn group_keysym(&self, keycode: u32, group: u8, shifted: bool) -> u32 {
+        let column = usize::from(shifted);
+        if group % 2 == 1 {
+            match self.syms(keycode).get(2 + column).copied() {
+                Some(0) | None => {}
+                Some(sym) => return sym,
+            }
+        }
+        self.keysym(keycode, column)
+    }
+}
+
+fn keysym_char(keysym: u32) -> Option<char> {
+    xkeysym::Keysym::new(keysym).key_char()
+}
+
+/// A top-level X11 window, with input from `XInput` 2.
+///
+/// Create one, start the engine with [`X11Window::gl_context`] as the renderer handler, and then:
+///
+/// - Send the initial [`X11Window::send_metrics`] and [`X11Window::displays`].
+/// - Feed it the events from [`X11Window::connection`], through [`X11Window::poll_events`] or [`X11Window::handle_event`].
+///
+/// Keys are translated with the core keyboard mapping, in the effective XKB group of each event; there is no input method support.
+pub struct X11Window {
+    conn: Arc<XCBConnection>,
+    screen_num: usize,
+    window: u32,
+    wm_protocols: u32,
+    wm_delete_window: u32,
+    keyboard_mapping: KeyboardMapping,
+    /// The XKB group that keys were last translated in.
+    group: u8,
+
+    size: (u16, u16),
+    pixel_ratio: f64,
+    closed: bool,
+
+    pointer: PointerState,
+    keyboard: KeyboardState,
+}
+
+impl fmt::Debug for X11Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X11Window")
+            .field("screen_num", &self.screen_num)
+            .field("window", &self.window)
+            .field("size", &self.size)
+            .field("pixel_ratio", &self.pixel_ratio)
+            .field("closed", &self.closed)
+            .field("pointer", &self.pointer)
+            .field("keyboard", &self.keyboard)
+            .finish_non_exhaustive()
+    }
+}
+
+impl X11Window {
+    pub fn new(title: &str, width: u16, height: u16) -> Result<Self, X11Error> {
+        let (conn, screen_num) = XCBConnection::connect(None)?;
+
+        let xinput = conn.xinput_xi_query_version(2, 2)?.reply()?;
+        if (xinput.major_version, xinput.minor_version) < (2, 2) {
+            return Err(X11Error::MissingXInput);
+        }
+
+        let screen = &conn.setup().roots[screen_num];
+        let window = conn.generate_id()?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            screen.root,
+            0,
+            0,
+            width,
+            height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            // input comes from XInput 2 instead
+            &CreateWindowAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            title.as_bytes(),
+        )?;
+
+        let wm_protocols = conn.intern_atom(false, b"WM_PROTOCOLS")?.reply()?.atom;
+        let wm_delete_window = conn.intern_atom(false, b"WM_DELETE_WINDOW")?.reply()?.atom;
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            wm_protocols,
+            AtomEnum::ATOM,
+            &[wm_delete_window],
+        )?;
+
+        conn.xinput_xi_select_events(
+            window,
+            &[xinput::EventMask {
+                deviceid: xinput::Device::ALL_MASTER.into(),
+                mask: vec![
+                    xinput::XIEventMask::KEY_PRESS
+                        | xinput::XIEventMask::KEY_RELEASE
+                        | xinput::XIEventMask::BUTTON_PRESS
+                        | xinput::XIEventMask::BUTTON_RELEASE
+                        | xinput::XIEventMask::MOTION
+                        | xinput::XIEventMask::ENTER
+                        | xinput::XIEventMask::LEAVE
+                        | xinput::XIEventMask::FOCUS_IN
+                        | xinput::XIEventMask::FOCUS_OUT,
+                ],
+            }],
+        )?;
+
+        conn.map_window(window)?;
+        conn.flush()?;
+
+        let keyboard_mapping = KeyboardMapping::get(&conn)?;
+
+        Ok(Self {
+            conn: Arc::new(conn),
+            screen_num,
+            window,
+            wm_protocols,
+            wm_delete_window,
+            keyboard_mapping,
+            group: 0,
+
+            size: (width, height),
+            pixel_ratio: 1.0,
+            closed: false,
+
+            pointer: PointerState::new(ViewId::IMPLICIT, MOUSE_DEVICE, PointerDeviceKind::Mouse),
+            keyboard: KeyboardState::new(),
+        })
+    }
+
+    /// The connection to the X server. Use its file descriptor to wait for events.
+    #[must_use]
+    pub fn connection(&self) -> &XCBConnection {
+        &self.conn
+    }
+
+    #[must_use]
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// Whether the window manager asked to close the window.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Sets the pixel ratio sent to the engine. X11 has no per-window scale, so this is 1 unless set.
+    pub fn set_pixel_ratio(&mut self, engine: &mut Engine, pixel_ratio: f64) -> crate::Result<()> {
+        self.pixel_ratio = pixel_ratio;
+        self.send_metrics(engine)
+    }
+
+    /// Sets how far the mouse wheel scrolls. X11 reports wheel clicks as buttons, so there are no pixel deltas.
+    pub fn set_scroll_config(&mut self, config: &ScrollConfig) {
+        self.pointer.set_scroll_config(config);
+    }
+
+    /// EGL contexts rendering to this window, for the engine's OpenGL renderer.
+    ///
+    /// This uses `EGL_EXT_platform_xcb`.
+    pub fn gl_context(&self) -> Result<EglWindowContext, X11Error> {
+        // the platform surface takes a pointer to the xcb_window_t, only for the duration of the call
+        let mut native_window = self.window;
+        let context = unsafe {
+            EglWindowContext::new(
+                EGL_PLATFORM_XCB_EXT,
+                self.conn.get_raw_xcb_connection(),
+                &[
+                    EGL_PLATFORM_XCB_SCREEN_EXT,
+                    self.screen_num,
+                    egl::ATTRIB_NONE,
+                ],
+                (&raw mut native_window).cast(),
+                // keeps the connection alive for as long as EGL uses it
+                Box::new(self.conn.clone()),
+            )
+        }?;
+        Ok(context)
+    }
+
+    /// The monitors of the screen this window is on, from `RandR`, for [`Engine::notify_display_update`].
+    pub fn displays(&self) -> Result<Vec<Display>, X11Error> {
+        let resources = self
+            .conn
+            .randr_get_screen_resources_current(self.window)?
+            .reply()?;
+
+        let mut displays = Vec::new();
+        for (display_id, &crtc) in (0..).zip(&resources.crtcs) {
+            let info = self
+                .conn
+                .randr_get_crtc_info(crtc, resources.config_timestamp)?
+                .reply()?;
+            if info.mode == 0 {
+                // disabled
+                continue;
+            }
+            let refresh_rate = resources
+                .modes
+                .iter()
+                .find(|mode| mode.id == info.mode)
+                .filter(|mode| mode.htotal != 0 && mode.vtotal != 0)
+                .map_or(0.0, |mode| {
+                    f64::from(mode.dot_clock) / (f64::from(mode.htotal) * f64::from(mode.vtotal))
+                });
+            displays.push(Display {
+                display_id,
+                single_display: false,
+                refresh_rate,
+                width: usize::from(info.width),
+                height: usize::from(info.height),
+                device_pixel_ratio: self.pixel_ratio,
+            });
+        }
+        if let [display] = displays.as_mut_slice() {
+            display.single_display = true;
+        }
+        Ok(displays)
+    }
+
+    pub fn send_metrics(&self, engine: &mut Engine) -> crate::Result<()> {
+        engine.send_window_metrics_event(WindowMetricsEvent {
+            view_id: ViewId::IMPLICIT,
+            width: usize::from(self.size.0),
+            height: usize::from(self.size.1),
+            pixel_ratio: self.pixel_ratio,
+            left: 0,
+            top: 0,
+            physical_view_inset_top: 0.0,
+            physical_view_inset_right: 0.0,
+            physical_view_inset_bottom: 0.0,
+            physical_view_inset_left: 0.0,
+            display_id: 0,
+        })
+    }
+
+    /// Handles all events that are already queued, without blocking.
+    pub fn poll_events(&mut self, engine: &mut Engine) -> Result<(), X11Error> {
+        while let Some(event) = self.conn.poll_for_event()? {
+            self.handle_event(engine, &event)?;
+        }
+        Ok(())
+    }
+
+    /// Forwards an event to the engine, if it's for this window.
+    pub fn handle_event(&mut self, engine: &mut Engine, event: &Event) -> Result<(), X11Error> {
+        // X11 timestamps are in milliseconds since the server started, so use the engine's clock instead
+        let timestamp = Engine::get_current_time();
+        let mut pointer_events = Vec::new();
+
+        match event {
+            Event::ConfigureNotify(ConfigureNotifyEvent {
+                window,
+                width,
+                height,
+                ..
+            }) if *window == self.window && self.size != (*width, *height) => {
+                self.size = (*width, *height);
+                if let Err(err) = self.send_metrics(engine) {
+                    error!("failed to send window metrics: {err:?}");
+                }
+            }
+            Event::ClientMessage(ClientMessageEvent {
+                window,
+                type_,
+                data,
+                ..
+            }) if *window == self.window
+                && *type_ == self.wm_protocols
+                && data.as_data32()[0] == self.wm_delete_window =>
+            {
+                self.closed = true;
+            }
+            Event::MappingNotify(_) => {
+                self.keyboard_mapping = KeyboardMapping::get(&self.conn)?;
+                self.layout_changed(engine, timestamp);
+            }
+
+            Event::XinputEnter(event) if event.event == self.window => {
+                pointer_events.extend(self.pointer.enter(
+                    ViewId::IMPLICIT,
+                    timestamp,
+                    fp1616(event.event_x),
+                    fp1616(event.event_y),
+                ));
+            }
+            Event::XinputLeave(event) if event.event == self.window => {
+                pointer_events.extend(self.pointer.leave(timestamp));
+            }
+            Event::XinputMotion(event) if event.event == self.window => {
+                pointer_events.push(self.pointer.motion(
+                    timestamp,
+                    fp1616(event.event_x),
+                    fp1616(event.event_y),
+                ));
+            }
+            Event::XinputButtonPress(event) | Event::XinputButtonRelease(event)
+                if event.event == self.window =>
+            {
+                let pressed = event.event_type == xinput::BUTTON_PRESS_EVENT;
+                let scroll = match event.detail {
+                    SCROLL_UP => Some((0.0, -1.0)),
+                    SCROLL_DOWN => Some((0.0, 1.0)),
+                    SCROLL_LEFT => Some((-1.0, 0.0)),
+                    SCROLL_RIGHT => Some((1.0, 0.0)),
+                    _ => None,
+                };
+                if let Some((dx, dy)) = scroll {
+                    if pressed {
+                        pointer_events.push(self.pointer.scroll_clicks(
+                            timestamp,
+                            dx,
+                            dy,
+                            self.pixel_ratio,
+                        ));
+                    }
+                } else if let Some(button) = pointer_button(event.detail) {
+                    pointer_events.push(self.pointer.button(timestamp, button, pressed));
+                }
+            }
+
+            Event::XinputKeyPress(event) | Event::XinputKeyRelease(event)
+                if event.event == self.window =>
+            {
+                let pressed = event.event_type == xinput::KEY_PRESS_EVENT;
+                if event.group.effective != self.group {
+                    // switching layouts doesn't send an event of its own, but every key event has the group
+                    self.group = event.group.effective;
+                    self.layout_changed(engine, timestamp);
+                }
+                let unshifted = self
+                    .keyboard_mapping
+                    .group_keysym(event.detail, self.group, false);
+                let mods = event.mods.effective;
+                let shift = mods & u32::from(ModMask::SHIFT) != 0;
+                let caps = mods & u32::from(ModMask::LOCK) != 0;
+                let shifted_char = keysym_char(self.keyboard_mapping.group_keysym(
+                    event.detail,
+                    self.group,
+                    true,
+                ));
+                let text = match keysym_char(unshifted) {
+                    Some(c) if c.is_alphabetic() && (shift != caps) => shifted_char,
+                    Some(_) if shift => shifted_char,
+                    c => c,
+                };
+                // X11 keycodes are evdev codes offset by 8
+                let key_event = self.keyboard.key(
+                    timestamp,
+                    event.detail.saturating_sub(8),
+                    unshifted,
+                    keysym_char(unshifted),
+                    text,
+                    pressed,
+                );
+                Self::send_key_event(engine, key_event);
+            }
+            Event::XinputFocusIn(event) if event.event == self.window => {
+                // keys may have been pressed or released while another window had focus
+                self.group = event.group.effective;
+                let keymap = self.conn.query_keymap()?.reply()?.keys;
+                let pressed = (8..=u8::MAX)
+                    .filter(|&keycode| keymap[usize::from(keycode / 8)] & (1 << (keycode % 8)) != 0)
+                    .map(|keycode| {
+                        let keysym =
+                            self.keyboard_mapping
+                                .group_keysym(keycode.into(), self.group, false);
+                        PressedKey {
+                            evdev_code: u32::from(keycode) - 8,
+                            keysym,
+                            character: keysym_char(keysym),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                for key_event in self.keyboard.sync(timestamp, &pressed) {
+                    Self::send_key_event(engine, key_event);
+                }
+            }
+            Event::XinputFocusOut(event) if event.event == self.window => {
+                for key_event in self.keyboard.release_all(timestamp) {
+                    Self::send_key_event(engine, key_event);
+                }
+            }
+            _ => {}
+        }
+
+        if !pointer_events.is_empty() {
+            if let Err(err) = engine.send_pointer_event(&pointer_events) {
+                error!("failed to send pointer events: {err:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-translates the held keys after the mapping or group changed.
+    fn layout_changed(&mut self, engine: &mut Engine, timestamp: Duration) {
+        let (mapping, group) = (&self.keyboard_mapping, self.group);
+        let events = self.keyboard.layout_changed(timestamp, |evdev_code| {
+            let keysym = mapping.group_keysym(evdev_code + 8, group, false);
+            (keysym, keysym_char(keysym))
+        });
+        for key_event in events {
+            Self::send_key_event(engine, key_event);
+        }
+    }
+
+    fn send_key_event(engine: &mut Engine, event: KeyEvent) {
+        if let Err(err) = engine.send_key_event(event, |_handled| {}) {
+            error!("failed to send key event: {err:?}");
+        }
+    }
+}
+
+impl Drop for X11Window {
+    fn drop(&mut self) {
+        let _ = self.conn.destroy_window(self.window);
+        let _ = self.conn.flush();
+    }
+}
+
+impl EglNativeWindow for Arc<XCBConnection> {}